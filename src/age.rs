@@ -0,0 +1,176 @@
+//! Durations written by humans, like "60d" or "6m", and showing ages of commits.
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use eyre::{eyre, Report};
+use serde::Deserialize;
+
+const MINUTE: u64 = 60;
+const HOUR: u64 = 60 * MINUTE;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+const MONTH: u64 = 30 * DAY;
+const YEAR: u64 = 365 * DAY;
+
+/// Duration parsed from a number and a unit: "h" (hours), "d" (days), "w" (weeks),
+/// "m" (months, of 30 days) or "y" (years).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(try_from = "String")]
+pub struct HumanDuration(pub Duration);
+
+impl HumanDuration {
+    pub fn as_secs(&self) -> i64 {
+        self.0.as_secs() as i64
+    }
+}
+
+impl FromStr for HumanDuration {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Report> {
+        let s = s.trim();
+        let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (number, unit) = s.split_at(split);
+        let number: u64 = number
+            .parse()
+            .map_err(|_| eyre!("Invalid duration \"{s}\", expected something like \"60d\""))?;
+        let unit = match unit.trim() {
+            "h" => HOUR,
+            "d" => DAY,
+            "w" => WEEK,
+            "m" => MONTH,
+            "y" => YEAR,
+            _ => {
+                return Err(eyre!(
+                    "Invalid unit in duration \"{s}\", expected one of h, d, w, m, y"
+                ))
+            }
+        };
+        Ok(Self(Duration::from_secs(number * unit)))
+    }
+}
+
+impl TryFrom<String> for HumanDuration {
+    type Error = Report;
+
+    fn try_from(s: String) -> Result<Self, Report> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for HumanDuration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let secs = self.0.as_secs();
+        let (n, unit) = [
+            (YEAR, "y"),
+            (MONTH, "m"),
+            (WEEK, "w"),
+            (DAY, "d"),
+            (HOUR, "h"),
+        ]
+        .into_iter()
+        .find(|(u, _)| secs >= *u && secs.is_multiple_of(*u))
+        .map(|(u, name)| (secs / u, name))
+        .unwrap_or((secs / HOUR, "h"));
+        write!(f, "{n}{unit}")
+    }
+}
+
+/// Current time in seconds since Unix epoch.
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Check if the time (seconds since Unix epoch) is further in the past than `duration`.
+pub fn is_older_than(time: i64, duration: HumanDuration, now: i64) -> bool {
+    now - time > duration.as_secs()
+}
+
+/// How recent a commit is, for coloring its age.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Freshness {
+    /// Less than a week old.
+    Fresh,
+    /// Less than three months old.
+    Aging,
+    Stale,
+}
+
+impl Freshness {
+    pub fn of_age(age: i64) -> Self {
+        let age = age.max(0) as u64;
+        if age < WEEK {
+            Self::Fresh
+        } else if age < 3 * MONTH {
+            Self::Aging
+        } else {
+            Self::Stale
+        }
+    }
+}
+
+/// Short form of an age in seconds, in its largest unit, like "3d" or "2mo".
+pub fn format_age(age: i64) -> String {
+    let age = age.max(0) as u64;
+    [
+        (YEAR, "y"),
+        (MONTH, "mo"),
+        (WEEK, "w"),
+        (DAY, "d"),
+        (HOUR, "h"),
+        (MINUTE, "min"),
+    ]
+    .into_iter()
+    .find(|(u, _)| age >= *u)
+    .map(|(u, name)| format!("{}{name}", age / u))
+    .unwrap_or_else(|| "now".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(
+            "60d".parse::<HumanDuration>().unwrap().as_secs(),
+            60 * 86400
+        );
+        assert_eq!("2w".parse::<HumanDuration>().unwrap().as_secs(), 14 * 86400);
+        assert_eq!(
+            "6m".parse::<HumanDuration>().unwrap().as_secs(),
+            180 * 86400
+        );
+        assert_eq!(
+            " 12h ".parse::<HumanDuration>().unwrap().as_secs(),
+            12 * 3600
+        );
+        assert!("60".parse::<HumanDuration>().is_err());
+        assert!("d".parse::<HumanDuration>().is_err());
+        assert!("3x".parse::<HumanDuration>().is_err());
+    }
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!("60d".parse::<HumanDuration>().unwrap().to_string(), "2m");
+        assert_eq!("14d".parse::<HumanDuration>().unwrap().to_string(), "2w");
+        assert_eq!("36h".parse::<HumanDuration>().unwrap().to_string(), "36h");
+    }
+
+    #[test]
+    fn formats_ages() {
+        assert_eq!(format_age(30), "now");
+        assert_eq!(format_age(3 * 3600 + 5), "3h");
+        assert_eq!(format_age(10 * 86400), "1w");
+        assert_eq!(format_age(100 * 86400), "3mo");
+        assert_eq!(format_age(800 * 86400), "2y");
+        assert_eq!(Freshness::of_age(6 * 86400), Freshness::Fresh);
+        assert_eq!(Freshness::of_age(89 * 86400), Freshness::Aging);
+        assert_eq!(Freshness::of_age(90 * 86400), Freshness::Stale);
+    }
+}
@@ -0,0 +1,320 @@
+//! Ways of talking to Git, behind the `Backend` trait.
+//!
+//! libgit2 is the default. With the "gix" feature, branches can be listed and deleted
+//! locally with gitoxide instead. Gitoxide cannot push yet, so upstream deletions still go
+//! through libgit2. The "git" backend runs the `git` program for deletions.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use eyre::{eyre, Result};
+use git2::{PushOptions, Remote, RemoteCallbacks, Repository};
+
+use crate::branch::{self, BranchInfo, Upstream};
+use crate::delete::{self, Rejections};
+use crate::hooks::run_pre_push_hook;
+
+mod git_cli;
+#[cfg(feature = "gix")]
+mod gix;
+
+pub use self::git_cli::GitCliBackend;
+#[cfg(feature = "gix")]
+pub use self::gix::GixBackend;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BackendKind {
+    #[default]
+    Libgit2,
+    Gix,
+    GitCli,
+}
+
+impl fmt::Display for BackendKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Self::Libgit2 => "libgit2",
+            Self::Gix => "gix",
+            Self::GitCli => "git",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for BackendKind {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "libgit2" => Ok(Self::Libgit2),
+            "gix" => Ok(Self::Gix),
+            "git" => Ok(Self::GitCli),
+            _ => Err(eyre!(
+                "Unknown backend \"{s}\", expected libgit2, gix or git"
+            )),
+        }
+    }
+}
+
+/// When to sign the pushes, like the push.gpgSign setting of Git.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignedPush {
+    /// Sign only if the server supports it.
+    IfAsked,
+    Always,
+}
+
+impl SignedPush {
+    /// Read push.gpgSign from Git config.
+    pub fn from_config(config: &git2::Config) -> Option<Self> {
+        let value = config.get_string("push.gpgSign").ok()?;
+        if value.eq_ignore_ascii_case("if-asked") {
+            return Some(Self::IfAsked);
+        }
+        git2::Config::parse_bool(value)
+            .unwrap_or(false)
+            .then_some(Self::Always)
+    }
+
+    fn as_arg(&self) -> &'static str {
+        match self {
+            Self::IfAsked => "--signed=if-asked",
+            Self::Always => "--signed=true",
+        }
+    }
+}
+
+/// Options for the pushes which delete upstream branches.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PushSettings {
+    /// Skip the pre-push hook of the repository, like `git push --no-verify`.
+    pub no_verify: bool,
+    /// libgit2 cannot sign pushes, so signed pushes are done with the `git` program.
+    pub signed: Option<SignedPush>,
+}
+
+pub trait Backend {
+    fn list_local_branches(&self) -> Result<Vec<BranchInfo>>;
+    fn delete_local_branch(&mut self, name: &str) -> Result<()>;
+    /// Delete the branch on the remote server, and our remote-tracking branch of it.
+    fn delete_upstream_branch(&mut self, upstream: &Upstream) -> Result<()> {
+        self.delete_upstream_branches(&[upstream])
+    }
+    /// Delete branches of one remote with a single push, which fails as a whole, and our
+    /// remote-tracking branches of them.
+    fn delete_upstream_branches(&mut self, upstreams: &[&Upstream]) -> Result<()>;
+}
+
+pub struct Libgit2Backend<'r> {
+    repo: &'r Repository,
+    remotes: HashMap<String, Remote<'r>>,
+    push_options: PushOptions<'r>,
+    /// Recorded by the callbacks of the pushes.
+    rejections: Rejections,
+    settings: PushSettings,
+}
+
+impl<'r> Libgit2Backend<'r> {
+    /// Create the backend, with the callbacks for authenticating pushes, which record the refs
+    /// that the server refuses in `rejections`.
+    pub fn new(
+        repo: &'r Repository,
+        callbacks: RemoteCallbacks<'r>,
+        rejections: Rejections,
+        settings: PushSettings,
+    ) -> Self {
+        let mut push_options = PushOptions::new();
+        push_options.remote_callbacks(callbacks);
+        Self {
+            repo,
+            remotes: HashMap::new(),
+            push_options,
+            rejections,
+            settings,
+        }
+    }
+}
+
+/// Run the pre-push hook as `git push` would, failing if it refuses the deletions.
+fn check_pre_push_hook(
+    repo: &Repository,
+    remote: &Remote,
+    name: &str,
+    upstreams: &[&Upstream],
+) -> Result<()> {
+    let url = delete::get_push_url(remote).unwrap_or_default();
+    let Some(status) = run_pre_push_hook(repo, name, url, upstreams)? else {
+        return Ok(());
+    };
+    if !status.success() {
+        return Err(eyre!("The pre-push hook refused the deletion ({status})"));
+    }
+    Ok(())
+}
+
+impl Backend for Libgit2Backend<'_> {
+    fn list_local_branches(&self) -> Result<Vec<BranchInfo>> {
+        Ok(branch::list_local_branches(self.repo)?)
+    }
+
+    fn delete_local_branch(&mut self, name: &str) -> Result<()> {
+        Ok(delete::delete_local_branch(self.repo, name)?)
+    }
+
+    fn delete_upstream_branches(&mut self, upstreams: &[&Upstream]) -> Result<()> {
+        let Some(first) = upstreams.first() else {
+            return Ok(());
+        };
+        if self.settings.signed.is_some() {
+            return git_cli::push_deletion(self.repo, upstreams, self.settings);
+        }
+        let name = &first.remote;
+        if !self.remotes.contains_key(name) {
+            let remote = self.repo.find_remote(name)?;
+            self.remotes.insert(name.clone(), remote);
+        }
+        let remote = self
+            .remotes
+            .get_mut(name)
+            .expect("Remote was just inserted");
+        if !self.settings.no_verify {
+            check_pre_push_hook(self.repo, remote, name, upstreams)?;
+        }
+        delete::delete_upstream_branches(
+            self.repo,
+            remote,
+            upstreams,
+            &mut self.push_options,
+            &self.rejections,
+        )
+    }
+}
+
+/// Create the backend of the given kind. The callbacks are used for authenticating pushes, and
+/// record the refs which the server refuses in `rejections`.
+pub fn open_backend<'r>(
+    repo: &'r Repository,
+    kind: BackendKind,
+    callbacks: RemoteCallbacks<'r>,
+    rejections: Rejections,
+    settings: PushSettings,
+) -> Result<Box<dyn Backend + 'r>> {
+    let libgit2 = Libgit2Backend::new(repo, callbacks, rejections, settings);
+    match kind {
+        BackendKind::Libgit2 => Ok(Box::new(libgit2)),
+        BackendKind::GitCli => Ok(Box::new(GitCliBackend::new(repo, libgit2))),
+        #[cfg(feature = "gix")]
+        BackendKind::Gix => Ok(Box::new(GixBackend::open(repo.path(), libgit2)?)),
+        #[cfg(not(feature = "gix"))]
+        BackendKind::Gix => Err(eyre!(
+            "This build does not include the gix backend, rebuild with \"--features gix\""
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{commit, init_bare_repo, init_repo};
+
+    #[test]
+    fn parses_kind() {
+        assert_eq!("gix".parse::<BackendKind>().unwrap(), BackendKind::Gix);
+        assert_eq!(BackendKind::Libgit2.to_string(), "libgit2");
+        assert_eq!("git".parse::<BackendKind>().unwrap(), BackendKind::GitCli);
+        assert!("cli".parse::<BackendKind>().is_err());
+    }
+
+    #[test]
+    fn reads_push_signing() {
+        let (_dir, repo) = init_repo();
+        let mut config = repo.config().unwrap();
+        assert_eq!(SignedPush::from_config(&config), None);
+        let cases = [
+            ("if-asked", Some(SignedPush::IfAsked)),
+            ("yes", Some(SignedPush::Always)),
+            ("false", None),
+        ];
+        for (value, expected) in cases {
+            config.set_str("push.gpgSign", value).unwrap();
+            let snapshot = config.snapshot().unwrap();
+            assert_eq!(SignedPush::from_config(&snapshot), expected, "{value}");
+        }
+    }
+
+    #[test]
+    fn signed_push_uses_git() {
+        let (_server_dir, server) = init_bare_repo();
+        commit(&server, "refs/heads/feature", None);
+        let (_dir, repo) = init_repo();
+        repo.remote("origin", server.path().to_str().unwrap())
+            .unwrap();
+        let settings = PushSettings {
+            signed: Some(SignedPush::Always),
+            ..PushSettings::default()
+        };
+        let mut backend = Libgit2Backend::new(
+            &repo,
+            RemoteCallbacks::new(),
+            Rejections::default(),
+            settings,
+        );
+        let upstream = Upstream {
+            remote: "origin".into(),
+            name: "feature".into(),
+        };
+        // The local server does not accept push certificates, which only git tells
+        let e = backend.delete_upstream_branch(&upstream).unwrap_err();
+        assert!(e.to_string().contains("signed push"), "{e}");
+    }
+
+    #[test]
+    fn libgit2_lists_and_deletes() {
+        let (_dir, repo) = init_repo();
+        commit(&repo, "refs/heads/feature", None);
+        let mut backend = Libgit2Backend::new(
+            &repo,
+            RemoteCallbacks::new(),
+            Rejections::default(),
+            PushSettings::default(),
+        );
+        assert_eq!(backend.list_local_branches().unwrap()[0].name, "feature");
+        backend.delete_local_branch("feature").unwrap();
+        assert!(backend.list_local_branches().unwrap().is_empty());
+        let upstream = Upstream {
+            remote: "nowhere".into(),
+            name: "feature".into(),
+        };
+        assert!(backend.delete_upstream_branch(&upstream).is_err());
+    }
+
+    #[test]
+    fn libgit2_deletes_in_one_push() {
+        let (_server_dir, server) = init_bare_repo();
+        commit(&server, "refs/heads/a", None);
+        commit(&server, "refs/heads/b", None);
+        commit(&server, "refs/heads/c", None);
+        let (_dir, repo) = init_repo();
+        repo.remote("origin", server.path().to_str().unwrap())
+            .unwrap();
+        let mut backend = Libgit2Backend::new(
+            &repo,
+            RemoteCallbacks::new(),
+            Rejections::default(),
+            PushSettings::default(),
+        );
+        let upstream = |name: &str| Upstream {
+            remote: "origin".into(),
+            name: name.into(),
+        };
+        let (a, b) = (upstream("a"), upstream("b"));
+        backend.delete_upstream_branches(&[&a, &b]).unwrap();
+        let names: Vec<String> = server
+            .references()
+            .unwrap()
+            .filter_map(|r| r.ok()?.shorthand().map(str::to_string))
+            .collect();
+        assert_eq!(names, ["c"]);
+    }
+}
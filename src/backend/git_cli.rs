@@ -0,0 +1,186 @@
+use std::process::{Command, Stdio};
+
+use eyre::{eyre, Result};
+use git2::Repository;
+
+use super::{Backend, Libgit2Backend, PushSettings};
+use crate::branch::{BranchInfo, Upstream};
+use crate::delete::delete_tracking_branch;
+use crate::gitenv::set_git_env;
+
+/// Backend running the `git` program for deletions, so that the user's whole Git setup
+/// (credential helpers, SSH config, hooks) applies. Listing is still done with libgit2.
+/// Git runs the pre-push hook by itself.
+pub struct GitCliBackend<'r> {
+    repo: &'r Repository,
+    lister: Libgit2Backend<'r>,
+}
+
+impl<'r> GitCliBackend<'r> {
+    pub fn new(repo: &'r Repository, lister: Libgit2Backend<'r>) -> Self {
+        Self { repo, lister }
+    }
+}
+
+/// Run git with the given arguments. Its stderr is returned as error if it fails.
+fn run_git(repo: &Repository, args: &[&str]) -> Result<()> {
+    query_git(repo, args).map(|_| ())
+}
+
+/// Run git with the given arguments, returning its stdout. Its stderr is returned as error if
+/// it fails.
+fn query_git(repo: &Repository, args: &[&str]) -> Result<String> {
+    let mut cmd = Command::new("git");
+    set_git_env(&mut cmd, repo);
+    let output = cmd
+        .current_dir(repo.workdir().unwrap_or_else(|| repo.path()))
+        .args(args)
+        // Let git ask for credentials or the signing key passphrase if it needs to
+        .stdin(Stdio::inherit())
+        .output()
+        .map_err(|e| eyre!("Failed to run git: {e}"))?;
+    if output.status.success() {
+        return Ok(String::from_utf8_lossy(&output.stdout).into_owned());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Err(eyre!("{}", stderr.trim()))
+}
+
+/// Delete the upstream branches, all of a remote, with one `git push`. Git refuses the whole
+/// push if any of them is gone from the remote already, like when a teammate deleted it, so
+/// it is then pushed again without those.
+pub(super) fn push_deletion(
+    repo: &Repository,
+    upstreams: &[&Upstream],
+    settings: PushSettings,
+) -> Result<()> {
+    let Err(e) = run_push_deletion(repo, upstreams, settings) else {
+        return Ok(());
+    };
+    // The message telling which is gone may be translated, so the remote is asked
+    let Some(first) = upstreams.first() else {
+        return Err(e);
+    };
+    let mut args = vec!["ls-remote", "--heads", first.remote.as_str()];
+    args.extend(upstreams.iter().map(|u| u.name.as_str()));
+    let Ok(heads) = query_git(repo, &args) else {
+        return Err(e);
+    };
+    let remaining: Vec<&Upstream> = upstreams
+        .iter()
+        .copied()
+        .filter(|u| {
+            let refname = format!("refs/heads/{}", u.name);
+            heads
+                .lines()
+                .any(|l| l.split('\t').nth(1) == Some(refname.as_str()))
+        })
+        .collect();
+    if remaining.len() == upstreams.len() {
+        return Err(e);
+    }
+    run_push_deletion(repo, &remaining, settings)?;
+    for upstream in upstreams.iter().filter(|u| !remaining.contains(u)) {
+        delete_tracking_branch(repo, upstream)?;
+    }
+    Ok(())
+}
+
+fn run_push_deletion(
+    repo: &Repository,
+    upstreams: &[&Upstream],
+    settings: PushSettings,
+) -> Result<()> {
+    let Some(first) = upstreams.first() else {
+        return Ok(());
+    };
+    let mut args = vec!["push"];
+    if settings.no_verify {
+        args.push("--no-verify");
+    }
+    if let Some(signed) = settings.signed {
+        args.push(signed.as_arg());
+    }
+    args.extend([first.remote.as_str(), "--delete"]);
+    args.extend(upstreams.iter().map(|u| u.name.as_str()));
+    run_git(repo, &args)
+}
+
+impl Backend for GitCliBackend<'_> {
+    fn list_local_branches(&self) -> Result<Vec<BranchInfo>> {
+        self.lister.list_local_branches()
+    }
+
+    fn delete_local_branch(&mut self, name: &str) -> Result<()> {
+        run_git(self.repo, &["branch", "-D", "--", name])
+    }
+
+    fn delete_upstream_branches(&mut self, upstreams: &[&Upstream]) -> Result<()> {
+        push_deletion(self.repo, upstreams, self.lister.settings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use git2::{BranchType, RemoteCallbacks};
+
+    use super::*;
+    use crate::backend::PushSettings;
+    use crate::delete::Rejections;
+    use crate::testutil::{commit, init_bare_repo, init_repo};
+
+    #[test]
+    fn deletes_with_git() {
+        let (_server_dir, server) = init_bare_repo();
+        commit(&server, "refs/heads/feature", None);
+        let (_dir, repo) = init_repo();
+        let url = server.path().to_str().unwrap();
+        let mut remote = repo.remote("origin", url).unwrap();
+        remote
+            .fetch(&["refs/heads/*:refs/remotes/origin/*"], None, None)
+            .unwrap();
+        let tip = repo.refname_to_id("refs/remotes/origin/feature").unwrap();
+        repo.branch("feature", &repo.find_commit(tip).unwrap(), false)
+            .unwrap();
+        let lister = Libgit2Backend::new(
+            &repo,
+            RemoteCallbacks::new(),
+            Rejections::default(),
+            PushSettings::default(),
+        );
+        let mut backend = GitCliBackend::new(&repo, lister);
+        backend.delete_local_branch("feature").unwrap();
+        assert!(repo.find_branch("feature", BranchType::Local).is_err());
+        let upstream = Upstream {
+            remote: "origin".into(),
+            name: "feature".into(),
+        };
+        backend.delete_upstream_branch(&upstream).unwrap();
+        assert!(server.find_branch("feature", BranchType::Local).is_err());
+        assert!(repo
+            .find_branch("origin/feature", BranchType::Remote)
+            .is_err());
+        let e = backend.delete_local_branch("feature").unwrap_err();
+        assert!(e.to_string().contains("feature"));
+    }
+
+    #[test]
+    fn skips_upstreams_gone_already() {
+        let (_server_dir, server) = init_bare_repo();
+        commit(&server, "refs/heads/feature", None);
+        let (_dir, repo) = init_repo();
+        let tip = commit(&repo, "refs/heads/main", None);
+        repo.remote("origin", server.path().to_str().unwrap())
+            .unwrap();
+        let upstream = |name: &str| Upstream {
+            remote: "origin".into(),
+            name: name.into(),
+        };
+        let (gone, feature) = (upstream("gone"), upstream("feature"));
+        repo.reference("refs/remotes/origin/gone", tip, false, "")
+            .unwrap();
+        push_deletion(&repo, &[&gone, &feature], PushSettings::default()).unwrap();
+        assert!(server.find_branch("feature", BranchType::Local).is_err());
+        assert!(repo.find_branch("origin/gone", BranchType::Remote).is_err());
+    }
+}
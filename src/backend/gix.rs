@@ -0,0 +1,174 @@
+use std::fs;
+use std::path::Path;
+
+use eyre::{eyre, Result};
+use gix::bstr::ByteSlice;
+use gix::remote::Direction;
+
+use super::{Backend, Libgit2Backend};
+use crate::branch::{get_worktree_branches, BranchInfo, Upstream};
+
+/// Backend using gitoxide for local operations, and libgit2 for pushing.
+pub struct GixBackend<'r> {
+    repo: gix::Repository,
+    pusher: Libgit2Backend<'r>,
+}
+
+impl<'r> GixBackend<'r> {
+    pub fn open(path: &Path, pusher: Libgit2Backend<'r>) -> Result<Self> {
+        let repo = gix::open(path)?;
+        Ok(Self { repo, pusher })
+    }
+
+    fn get_upstream(&self, full_name: &gix::refs::FullNameRef) -> Option<Upstream> {
+        let short_name = full_name.shorten();
+        // Check that the remote-tracking branch exists
+        let tracking = self
+            .repo
+            .branch_remote_tracking_ref_name(full_name, Direction::Fetch)?
+            .ok()?;
+        self.repo.try_find_reference(tracking.as_ref()).ok()??;
+        let remote = self.repo.branch_remote_name(short_name, Direction::Fetch)?;
+        let merge = self
+            .repo
+            .branch_remote_ref_name(full_name, Direction::Fetch)?
+            .ok()?;
+        let name = merge
+            .as_bstr()
+            .strip_prefix(b"refs/heads/")?
+            .to_str()
+            .ok()?;
+        Some(Upstream {
+            remote: remote.as_bstr().to_str().ok()?.to_string(),
+            name: name.to_string(),
+        })
+    }
+
+    fn is_upstream_gone(&self, full_name: &gix::refs::FullNameRef) -> bool {
+        let Some(Ok(tracking)) = self
+            .repo
+            .branch_remote_tracking_ref_name(full_name, Direction::Fetch)
+        else {
+            return false;
+        };
+        matches!(self.repo.try_find_reference(tracking.as_ref()), Ok(None))
+    }
+
+    /// Remove the "branch.<name>" section from the repository config, like `git branch -D` does.
+    fn remove_branch_config(&self, name: &str) -> Result<()> {
+        let path = self.repo.common_dir().join("config");
+        let mut file =
+            gix::config::File::from_path_no_includes(path.clone(), gix::config::Source::Local)?;
+        if file.remove_section("branch", Some(name.into())).is_some() {
+            let mut content = Vec::new();
+            file.write_to(&mut content)?;
+            fs::write(path, content)?;
+        }
+        Ok(())
+    }
+}
+
+impl Backend for GixBackend<'_> {
+    fn list_local_branches(&self) -> Result<Vec<BranchInfo>> {
+        let head_name = self.repo.head_name()?;
+        let mut worktree_branches = get_worktree_branches(self.pusher.repo);
+        let platform = self.repo.references()?;
+        let mut branches = Vec::new();
+        for reference in platform.local_branches()? {
+            let mut reference = reference.map_err(|e| eyre!(e))?;
+            // Branches whose names are not UTF-8 are not supported
+            let Ok(name) = reference.name().shorten().to_str().map(ToString::to_string) else {
+                continue;
+            };
+            let full_name = reference.name().to_owned();
+            let Ok(commit) = reference.peel_to_commit() else {
+                continue;
+            };
+            let signature = commit.author()?;
+            let author = signature.name.to_string();
+            let author_email = signature.email.to_string();
+            branches.push(BranchInfo {
+                tip: git2::Oid::from_bytes(commit.id.as_bytes())?,
+                is_head: head_name.as_ref() == Some(&full_name),
+                worktree: worktree_branches.remove(&name),
+                upstream: self.get_upstream(full_name.as_ref()),
+                upstream_gone: self.is_upstream_gone(full_name.as_ref()),
+                author,
+                author_email,
+                author_time: signature.time()?.seconds,
+                time: commit.time()?.seconds,
+                name,
+            });
+        }
+        Ok(branches)
+    }
+
+    fn delete_local_branch(&mut self, name: &str) -> Result<()> {
+        let reference = self.repo.find_reference(&format!("refs/heads/{name}"))?;
+        reference.delete()?;
+        self.remove_branch_config(name)
+    }
+
+    fn delete_upstream_branches(&mut self, upstreams: &[&Upstream]) -> Result<()> {
+        self.pusher.delete_upstream_branches(upstreams)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use git2::RemoteCallbacks;
+
+    use super::*;
+    use crate::backend::PushSettings;
+    use crate::delete::Rejections;
+    use crate::testutil::{commit, init_repo};
+
+    #[test]
+    fn lists_and_deletes() {
+        let (_dir, repo) = init_repo();
+        let base = commit(&repo, "refs/heads/main", None);
+        repo.set_head("refs/heads/main").unwrap();
+        let tip = commit(&repo, "refs/heads/feature", Some(base));
+        repo.remote("origin", "https://example.com/repo.git")
+            .unwrap();
+        repo.reference("refs/remotes/origin/feat", tip, false, "")
+            .unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("branch.feature.remote", "origin").unwrap();
+        config
+            .set_str("branch.feature.merge", "refs/heads/feat")
+            .unwrap();
+        let pusher = Libgit2Backend::new(
+            &repo,
+            RemoteCallbacks::new(),
+            Rejections::default(),
+            PushSettings::default(),
+        );
+        let mut backend = GixBackend::open(repo.path(), pusher).unwrap();
+        let branches = backend.list_local_branches().unwrap();
+        let feature = branches.iter().find(|b| b.name == "feature").unwrap();
+        assert_eq!(feature.tip, tip);
+        assert_eq!(feature.author, "Tester");
+        assert!(!feature.is_head);
+        assert_eq!(
+            feature.upstream.as_ref().unwrap().to_string(),
+            "origin/feat"
+        );
+        assert!(!feature.upstream_gone);
+        assert!(branches.iter().find(|b| b.name == "main").unwrap().is_head);
+        repo.find_reference("refs/remotes/origin/feat")
+            .unwrap()
+            .delete()
+            .unwrap();
+        let branches = backend.list_local_branches().unwrap();
+        let feature = branches.iter().find(|b| b.name == "feature").unwrap();
+        assert!(feature.upstream.is_none() && feature.upstream_gone);
+        backend.delete_local_branch("feature").unwrap();
+        assert!(repo
+            .find_branch("feature", git2::BranchType::Local)
+            .is_err());
+        // libgit2 caches the config, so read the file
+        let config = fs::read_to_string(repo.path().join("config")).unwrap();
+        assert!(!config.contains("[branch \"feature\"]"));
+    }
+}
@@ -0,0 +1,694 @@
+//! Discovering local branches, with the metadata we show to the user.
+
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use eyre::eyre;
+use git2::{Branch, BranchType, ErrorCode, Oid, Repository};
+
+use crate::gitenv::get_common_dir;
+use crate::pattern;
+
+/// Branches which are never offered for deletion, besides the detected default branch.
+pub const EXCLUDES: &[&str] = &["master", "main", "develop", "development"];
+
+/// Files in the top folder of the repository, committed to share with the whole team the globs
+/// of the branches to protect, one per line.
+pub const PROTECTED_FILES: &[&str] = &[".protected-branches", ".git-del-branches"];
+
+/// Environment variable with more globs of branches to protect, separated by commas or spaces,
+/// for CI jobs and containers.
+pub const PROTECT_ENV: &str = "GIT_DEL_BRANCHES_PROTECT";
+
+/// The remote branch which a local branch is tracking.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Upstream {
+    /// Name of the remote, like "origin".
+    pub remote: String,
+    /// Name of the branch on the remote, without "refs/heads/".
+    pub name: String,
+}
+
+impl fmt::Display for Upstream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.remote, self.name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BranchInfo {
+    pub name: String,
+    /// The commit the branch points to.
+    pub tip: Oid,
+    /// Whether the branch is checked out.
+    pub is_head: bool,
+    /// Path of the other worktree where the branch is checked out, if any.
+    /// Git refuses to delete such branch, like the current one.
+    pub worktree: Option<PathBuf>,
+    pub upstream: Option<Upstream>,
+    /// Whether an upstream is configured but its remote-tracking branch is missing,
+    /// usually because the remote branch was deleted. Then `upstream` is `None`.
+    pub upstream_gone: bool,
+    /// Author name of the tip commit.
+    pub author: String,
+    /// Author email of the tip commit.
+    pub author_email: String,
+    /// Time of the tip commit, in seconds since Unix epoch. It is the committer date, which
+    /// changes on rebase and amend, unless `use_dates` chose the author date.
+    pub time: i64,
+    /// Author date of the tip commit, in seconds since Unix epoch.
+    pub author_time: i64,
+}
+
+impl BranchInfo {
+    /// Collect info of a local branch. Branches whose names are not UTF-8 are not supported.
+    pub fn from_branch(repo: &Repository, branch: &Branch) -> Option<Self> {
+        let name = branch.name().ok()??.to_string();
+        let commit = branch.get().peel_to_commit().ok()?;
+        let signature = commit.author();
+        let author = signature.name().unwrap_or_default().to_string();
+        let author_email = signature.email().unwrap_or_default().to_string();
+        Some(Self {
+            tip: commit.id(),
+            is_head: branch.is_head(),
+            worktree: None,
+            upstream: get_upstream(repo, &name),
+            upstream_gone: is_upstream_gone(repo, &name),
+            author,
+            author_email,
+            time: commit.time().seconds(),
+            author_time: signature.when().seconds(),
+            name,
+        })
+    }
+
+    /// Author of the tip commit, in the given format.
+    pub fn format_author(&self, format: AuthorFormat) -> String {
+        match format {
+            AuthorFormat::Name => self.author.clone(),
+            AuthorFormat::Email => self.author_email.clone(),
+            AuthorFormat::User => match self.author_email.split_once('@') {
+                Some((user, _)) => format!("{user}@"),
+                None => self.author_email.clone(),
+            },
+            AuthorFormat::Both => format!("{} <{}>", self.author, self.author_email),
+        }
+    }
+}
+
+/// How to show the author of a branch, for telling apart people with the same name.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum AuthorFormat {
+    #[default]
+    Name,
+    Email,
+    /// The part of the email before "@", like "quan@".
+    User,
+    /// Name and email, like `git log` shows them.
+    Both,
+}
+
+impl fmt::Display for AuthorFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Self::Name => "name",
+            Self::Email => "email",
+            Self::User => "user",
+            Self::Both => "both",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for AuthorFormat {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> eyre::Result<Self> {
+        match s {
+            "name" => Ok(Self::Name),
+            "email" => Ok(Self::Email),
+            "user" => Ok(Self::User),
+            "both" => Ok(Self::Both),
+            _ => Err(eyre!(
+                "Unknown author format \"{s}\", expected name, email, user or both"
+            )),
+        }
+    }
+}
+
+/// Which date of the tip commit tells the age of a branch.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DateKind {
+    /// When the commit was last rewritten, e.g. by a rebase.
+    #[default]
+    Committer,
+    /// When the changes were first committed.
+    Author,
+}
+
+impl fmt::Display for DateKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Self::Committer => "committer",
+            Self::Author => "author",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for DateKind {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> eyre::Result<Self> {
+        match s {
+            "committer" => Ok(Self::Committer),
+            "author" => Ok(Self::Author),
+            _ => Err(eyre!("Unknown date \"{s}\", expected committer or author")),
+        }
+    }
+}
+
+/// Set the `time` of the branches to the chosen date of their tips.
+pub fn use_dates(branches: &mut [BranchInfo], kind: DateKind) {
+    if kind == DateKind::Author {
+        for branch in branches {
+            branch.time = branch.author_time;
+        }
+    }
+}
+
+/// Find the upstream of a local branch, if it is a remote branch which we have fetched.
+fn get_upstream(repo: &Repository, name: &str) -> Option<Upstream> {
+    let refname = format!("refs/heads/{name}");
+    // Check that the remote-tracking branch exists
+    let tracking = repo.branch_upstream_name(&refname).ok()?;
+    repo.find_reference(tracking.as_str()?).ok()?;
+    let remote = repo
+        .branch_upstream_remote(&refname)
+        .ok()?
+        .as_str()?
+        .to_string();
+    let config = repo.config().ok()?;
+    let merge = config.get_string(&format!("branch.{name}.merge")).ok()?;
+    let remote_name = merge.strip_prefix("refs/heads/")?.to_string();
+    Some(Upstream {
+        remote,
+        name: remote_name,
+    })
+}
+
+/// The branch on the server which the local branch is for: its upstream, even if it is
+/// gone, else the branch of the same name on the default remote, `remote.pushDefault` or
+/// "origin".
+pub fn get_server_branch(repo: &Repository, branch: &BranchInfo) -> Option<Upstream> {
+    if let Some(upstream) = &branch.upstream {
+        return Some(upstream.clone());
+    }
+    let config = repo.config().ok()?;
+    let configured = |key: &str| config.get_string(&format!("branch.{}.{key}", branch.name));
+    if let (Ok(remote), Ok(merge)) = (configured("remote"), configured("merge")) {
+        if let Some(name) = merge.strip_prefix("refs/heads/") {
+            if remote != "." {
+                return Some(Upstream {
+                    remote,
+                    name: name.to_string(),
+                });
+            }
+        }
+    }
+    let remote = config
+        .get_string("remote.pushDefault")
+        .unwrap_or_else(|_| "origin".into());
+    repo.find_remote(&remote).ok()?;
+    Some(Upstream {
+        remote,
+        name: branch.name.clone(),
+    })
+}
+
+/// What HEAD points to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HeadState {
+    /// On a local branch, which therefore cannot be deleted.
+    Branch(String),
+    /// At a commit, with no branch checked out.
+    Detached(Oid),
+    /// On a branch which has no commit yet.
+    Unborn,
+}
+
+pub fn get_head_state(repo: &Repository) -> Result<HeadState, git2::Error> {
+    let head = match repo.head() {
+        Ok(r) => r,
+        Err(e) if e.code() == ErrorCode::UnbornBranch => return Ok(HeadState::Unborn),
+        Err(e) => return Err(e),
+    };
+    if !head.is_branch() {
+        return Ok(HeadState::Detached(head.peel_to_commit()?.id()));
+    }
+    let name = head.shorthand().unwrap_or_default().to_string();
+    Ok(HeadState::Branch(name))
+}
+
+/// Find the real default branches of the repository: the one which `origin/HEAD` points to,
+/// and the one in init.defaultBranch setting.
+pub fn get_default_branch_names(repo: &Repository) -> Vec<String> {
+    let mut names = Vec::new();
+    if let Ok(head) = repo.find_reference("refs/remotes/origin/HEAD") {
+        if let Some(name) = head
+            .symbolic_target()
+            .and_then(|t| t.strip_prefix("refs/remotes/origin/"))
+        {
+            names.push(name.to_string());
+        }
+    }
+    if let Ok(name) = repo
+        .config()
+        .and_then(|c| c.get_string("init.defaultBranch"))
+    {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+/// Names of the branches which are never offered for deletion: the default branches,
+/// `EXCLUDES`, and the globs of `PROTECTED_FILES` and `PROTECT_ENV`.
+pub fn get_protected_names(repo: &Repository) -> Vec<String> {
+    let mut names = get_default_branch_names(repo);
+    let mut patterns = get_committed_patterns(repo);
+    patterns.extend(get_env_patterns());
+    for name in EXCLUDES
+        .iter()
+        .copied()
+        .chain(patterns.iter().map(String::as_str))
+    {
+        if !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
+
+/// The globs of `PROTECTED_FILES`, from the working tree, or from the tree of HEAD in a bare
+/// repository. Blank lines and those starting with "#" are skipped.
+pub fn get_committed_patterns(repo: &Repository) -> Vec<String> {
+    let mut patterns = Vec::new();
+    for file in PROTECTED_FILES {
+        let content = match repo.workdir() {
+            Some(folder) => fs::read_to_string(folder.join(file)).ok(),
+            None => read_head_file(repo, file),
+        };
+        patterns.extend(content.iter().flat_map(|c| pattern::parse_patterns(c)));
+    }
+    patterns
+}
+
+/// The globs of `PROTECT_ENV`.
+pub fn get_env_patterns() -> Vec<String> {
+    split_patterns(&env::var(PROTECT_ENV).unwrap_or_default())
+}
+
+fn split_patterns(list: &str) -> Vec<String> {
+    list.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|p| !p.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+fn read_head_file(repo: &Repository, path: &str) -> Option<String> {
+    let tree = repo.head().ok()?.peel_to_tree().ok()?;
+    let blob = tree.get_path(Path::new(path)).ok()?.to_object(repo).ok()?;
+    String::from_utf8(blob.as_blob()?.content().to_vec()).ok()
+}
+
+/// Whether the branch tracks a remote branch which we don't have anymore, shown as "gone"
+/// by `git branch -vv`.
+fn is_upstream_gone(repo: &Repository, name: &str) -> bool {
+    let Ok(tracking) = repo.branch_upstream_name(&format!("refs/heads/{name}")) else {
+        return false;
+    };
+    tracking
+        .as_str()
+        .is_some_and(|t| repo.find_reference(t).is_err())
+}
+
+/// Whether the name is one of the protected names, or matches one of their globs.
+pub fn is_protected(protected: &[String], name: &str) -> bool {
+    pattern::matches_any(protected, name)
+}
+
+/// Find the local branch to switch to when leaving the current one: the first existing
+/// of the protected names, the default branches coming first.
+pub fn find_default_branch(repo: &Repository) -> Option<String> {
+    get_protected_names(repo)
+        .into_iter()
+        .find(|n| repo.find_branch(n, BranchType::Local).is_ok())
+}
+
+/// When the branch was created, in seconds since Unix epoch, from the oldest entry of its
+/// reflog. `None` if it has no reflog, or if that entry was expired, as it then tells a later
+/// update, not the creation.
+pub fn get_creation_time(repo: &Repository, name: &str) -> Option<i64> {
+    let reflog = repo.reflog(&format!("refs/heads/{name}")).ok()?;
+    // Newest first
+    let first = reflog.iter().next_back()?;
+    first
+        .id_old()
+        .is_zero()
+        .then(|| first.committer().when().seconds())
+}
+
+/// Whether the tip of the branch was authored by the owner of `email`. Emails are compared
+/// regardless of case, as mail servers do.
+pub fn is_authored_by(branch: &BranchInfo, email: &str) -> bool {
+    branch.author_email.eq_ignore_ascii_case(email)
+}
+
+/// Whether the branch can be offered for deletion.
+pub fn is_deletable(branch: &BranchInfo, protected: &[String]) -> bool {
+    !branch.is_head && branch.worktree.is_none() && !is_protected(protected, &branch.name)
+}
+
+/// Find the branches checked out in the other worktrees of the repository, the main one
+/// included, with the paths of these worktrees. Broken worktrees are skipped.
+pub fn get_worktree_branches(repo: &Repository) -> HashMap<String, PathBuf> {
+    let mut branches = HashMap::new();
+    let Ok(main) = Repository::open(get_common_dir(repo)) else {
+        return branches;
+    };
+    let mut worktrees = Vec::new();
+    if let Ok(names) = main.worktrees() {
+        for name in names.iter().flatten() {
+            let Ok(worktree) = main.find_worktree(name) else {
+                continue;
+            };
+            if worktree.validate().is_err() {
+                continue;
+            }
+            if let Ok(wt_repo) = Repository::open_from_worktree(&worktree) {
+                worktrees.push(wt_repo);
+            }
+        }
+    }
+    worktrees.push(main);
+    let ours = repo.workdir().and_then(|p| p.canonicalize().ok());
+    for wt_repo in worktrees {
+        let Some(workdir) = wt_repo.workdir().and_then(|p| p.canonicalize().ok()) else {
+            continue;
+        };
+        if ours.as_ref() == Some(&workdir) {
+            continue;
+        }
+        if let Ok(HeadState::Branch(name)) = get_head_state(&wt_repo) {
+            branches.insert(name, workdir);
+        }
+    }
+    branches
+}
+
+pub fn list_local_branches(repo: &Repository) -> Result<Vec<BranchInfo>, git2::Error> {
+    let mut worktree_branches = get_worktree_branches(repo);
+    let branches = repo
+        .branches(Some(BranchType::Local))?
+        .flatten()
+        .filter_map(|(b, _type)| BranchInfo::from_branch(repo, &b))
+        .map(|mut b| {
+            b.worktree = worktree_branches.remove(&b.name);
+            b
+        })
+        .collect();
+    Ok(branches)
+}
+
+/// Find the local branches which `list_local_branches` skips, with why: those whose names
+/// are not UTF-8, shown lossily, and those which do not point to a commit.
+pub fn find_unlisted_branches(repo: &Repository) -> Vec<(String, &'static str)> {
+    let Ok(branches) = repo.branches(Some(BranchType::Local)) else {
+        return Vec::new();
+    };
+    branches
+        .flatten()
+        .filter_map(|(b, _type)| {
+            let name = String::from_utf8_lossy(b.name_bytes().ok()?).into_owned();
+            if b.name().ok().flatten().is_none() {
+                return Some((name, "its name is not UTF-8"));
+            }
+            match b.get().peel_to_commit() {
+                Ok(_) => None,
+                Err(_) => Some((name, "it does not point to a commit")),
+            }
+        })
+        .collect()
+}
+
+pub fn list_deletable_branches(repo: &Repository) -> Result<Vec<BranchInfo>, git2::Error> {
+    let protected = get_protected_names(repo);
+    let mut branches = list_local_branches(repo)?;
+    branches.retain(|b| is_deletable(b, &protected));
+    Ok(branches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::age::now;
+    use crate::testutil::{commit, commit_at, init_repo};
+    use tempfile::TempDir;
+
+    #[test]
+    fn finds_unlisted_branches() {
+        let (_dir, repo) = init_repo();
+        let base = commit(&repo, "refs/heads/main", None);
+        let blob = repo.blob(b"not a commit").unwrap();
+        repo.reference("refs/heads/blob", blob, true, "").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+            let name = std::ffi::OsStr::from_bytes(b"caf\xe9");
+            let path = repo.path().join("refs/heads").join(name);
+            std::fs::write(path, format!("{base}\n")).unwrap();
+        }
+        let mut unlisted = find_unlisted_branches(&repo);
+        unlisted.sort();
+        let mut expected = vec![("blob".to_string(), "it does not point to a commit")];
+        if cfg!(unix) {
+            expected.push(("caf\u{fffd}".into(), "its name is not UTF-8"));
+        }
+        assert_eq!(unlisted, expected);
+    }
+
+    #[test]
+    fn lists_metadata() {
+        let (_dir, repo) = init_repo();
+        let base = commit(&repo, "refs/heads/main", None);
+        let tip = commit_at(&repo, "refs/heads/feature", Some(base), 1_700_000_000);
+        let branches = list_local_branches(&repo).unwrap();
+        let feature = branches.iter().find(|b| b.name == "feature").unwrap();
+        assert_eq!(feature.tip, tip);
+        assert_eq!(feature.author, "Tester");
+        assert_eq!(feature.format_author(AuthorFormat::User), "tester@");
+        assert_eq!(
+            feature.format_author(AuthorFormat::Both),
+            "Tester <tester@example.com>"
+        );
+        assert_eq!(feature.time, 1_700_000_000);
+        assert_eq!(feature.author_time, 1_700_000_000);
+        assert!(feature.upstream.is_none());
+    }
+
+    #[test]
+    fn finds_creation_time() {
+        let (_dir, repo) = init_repo();
+        let base = commit(&repo, "refs/heads/main", None);
+        repo.branch("feature", &repo.find_commit(base).unwrap(), false)
+            .unwrap();
+        commit(&repo, "refs/heads/feature", Some(base));
+        let created = get_creation_time(&repo, "feature").unwrap();
+        assert!((now() - created).abs() < 60);
+        let mut reflog = repo.reflog("refs/heads/feature").unwrap();
+        reflog.remove(reflog.len() - 1, false).unwrap();
+        reflog.write().unwrap();
+        assert_eq!(get_creation_time(&repo, "feature"), None);
+        assert_eq!(get_creation_time(&repo, "missing"), None);
+    }
+
+    #[test]
+    fn tells_author() {
+        let (_dir, repo) = init_repo();
+        commit(&repo, "refs/heads/feature", None);
+        let branches = list_local_branches(&repo).unwrap();
+        assert!(is_authored_by(&branches[0], "Tester@Example.com"));
+        assert!(!is_authored_by(&branches[0], "other@example.com"));
+    }
+
+    #[test]
+    fn uses_chosen_dates() {
+        let (_dir, repo) = init_repo();
+        commit_at(&repo, "refs/heads/feature", None, 1_700_000_000);
+        let mut branches = list_local_branches(&repo).unwrap();
+        // As if it was rebased later
+        branches[0].time = 1_800_000_000;
+        use_dates(&mut branches, DateKind::Committer);
+        assert_eq!(branches[0].time, 1_800_000_000);
+        use_dates(&mut branches, DateKind::Author);
+        assert_eq!(branches[0].time, 1_700_000_000);
+        assert_eq!("author".parse::<DateKind>().unwrap(), DateKind::Author);
+    }
+
+    #[test]
+    fn excludes_head_and_protected() {
+        let (_dir, repo) = init_repo();
+        let base = commit(&repo, "refs/heads/main", None);
+        repo.set_head("refs/heads/main").unwrap();
+        commit(&repo, "refs/heads/develop", Some(base));
+        commit(&repo, "refs/heads/current", Some(base));
+        commit(&repo, "refs/heads/old", Some(base));
+        repo.set_head("refs/heads/current").unwrap();
+        let names: Vec<_> = list_deletable_branches(&repo)
+            .unwrap()
+            .into_iter()
+            .map(|b| b.name)
+            .collect();
+        assert_eq!(names, ["old"]);
+        assert_eq!(find_default_branch(&repo).as_deref(), Some("main"));
+    }
+
+    #[test]
+    fn protects_committed_patterns() {
+        let (dir, repo) = init_repo();
+        let content = "# Supported releases\nrelease/*\n\n  hotfix  \n";
+        std::fs::write(dir.path().join(".protected-branches"), content).unwrap();
+        let protected = get_protected_names(&repo);
+        assert!(protected.ends_with(&["release/*".to_string(), "hotfix".to_string()]));
+        assert!(is_protected(&protected, "release/1.2"));
+        assert!(is_protected(&protected, "hotfix"));
+        assert!(!is_protected(&protected, "hotfix/login"));
+        assert_eq!(
+            split_patterns("release/*, hotfix  qa"),
+            ["release/*", "hotfix", "qa"]
+        );
+    }
+
+    #[test]
+    fn protects_default_branch() {
+        let (_dir, repo) = init_repo();
+        let base = commit(&repo, "refs/heads/trunk", None);
+        commit(&repo, "refs/heads/stable", Some(base));
+        commit(&repo, "refs/heads/feature", Some(base));
+        repo.reference("refs/remotes/origin/trunk", base, false, "")
+            .unwrap();
+        repo.reference_symbolic(
+            "refs/remotes/origin/HEAD",
+            "refs/remotes/origin/trunk",
+            false,
+            "",
+        )
+        .unwrap();
+        repo.config()
+            .unwrap()
+            .set_str("init.defaultBranch", "stable")
+            .unwrap();
+        assert_eq!(get_default_branch_names(&repo), ["trunk", "stable"]);
+        let names: Vec<_> = list_deletable_branches(&repo)
+            .unwrap()
+            .into_iter()
+            .map(|b| b.name)
+            .collect();
+        assert_eq!(names, ["feature"]);
+        assert_eq!(find_default_branch(&repo).as_deref(), Some("trunk"));
+    }
+
+    #[test]
+    fn tells_head_state() {
+        let (_dir, repo) = init_repo();
+        assert_eq!(get_head_state(&repo).unwrap(), HeadState::Unborn);
+        let base = commit(&repo, "refs/heads/main", None);
+        repo.set_head("refs/heads/main").unwrap();
+        assert_eq!(
+            get_head_state(&repo).unwrap(),
+            HeadState::Branch("main".into())
+        );
+        repo.set_head_detached(base).unwrap();
+        assert_eq!(get_head_state(&repo).unwrap(), HeadState::Detached(base));
+        // No branch is checked out, so only the protected ones are excluded
+        commit(&repo, "refs/heads/feature", Some(base));
+        let names: Vec<_> = list_deletable_branches(&repo)
+            .unwrap()
+            .into_iter()
+            .map(|b| b.name)
+            .collect();
+        assert_eq!(names, ["feature"]);
+    }
+
+    #[test]
+    fn finds_upstream() {
+        let (_dir, repo) = init_repo();
+        let base = commit(&repo, "refs/heads/feature", None);
+        repo.remote("origin", "https://example.com/repo.git")
+            .unwrap();
+        repo.reference("refs/remotes/origin/feat", base, false, "")
+            .unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("branch.feature.remote", "origin").unwrap();
+        config
+            .set_str("branch.feature.merge", "refs/heads/feat")
+            .unwrap();
+        let branches = list_local_branches(&repo).unwrap();
+        let upstream = branches[0].upstream.as_ref().unwrap();
+        assert_eq!(upstream.remote, "origin");
+        assert_eq!(upstream.name, "feat");
+        assert_eq!(upstream.to_string(), "origin/feat");
+        assert!(!branches[0].upstream_gone);
+        repo.find_reference("refs/remotes/origin/feat")
+            .unwrap()
+            .delete()
+            .unwrap();
+        let branches = list_local_branches(&repo).unwrap();
+        assert!(branches[0].upstream.is_none());
+        assert!(branches[0].upstream_gone);
+        let server = get_server_branch(&repo, &branches[0]).unwrap();
+        assert_eq!(server.to_string(), "origin/feat");
+        config.remove("branch.feature.merge").unwrap();
+        let server = get_server_branch(&repo, &branches[0]).unwrap();
+        assert_eq!(server.to_string(), "origin/feature");
+    }
+
+    #[test]
+    fn excludes_branches_of_other_worktrees() {
+        let (_dir, repo) = init_repo();
+        let base = commit(&repo, "refs/heads/main", None);
+        repo.set_head("refs/heads/main").unwrap();
+        commit(&repo, "refs/heads/linked", Some(base));
+        commit(&repo, "refs/heads/old", Some(base));
+        let wt_dir = TempDir::new().unwrap();
+        let wt_path = wt_dir.path().join("linked");
+        let linked = repo.find_reference("refs/heads/linked").unwrap();
+        let mut opts = git2::WorktreeAddOptions::new();
+        opts.reference(Some(&linked));
+        let worktree = repo.worktree("linked", &wt_path, Some(&opts)).unwrap();
+
+        let branches = list_local_branches(&repo).unwrap();
+        let linked = branches.iter().find(|b| b.name == "linked").unwrap();
+        assert_eq!(linked.worktree, Some(wt_path.canonicalize().unwrap()));
+        let names: Vec<_> = list_deletable_branches(&repo)
+            .unwrap()
+            .into_iter()
+            .map(|b| b.name)
+            .collect();
+        assert_eq!(names, ["old"]);
+
+        // From the linked worktree, the branch of the main one is busy instead
+        let wt_repo = Repository::open_from_worktree(&worktree).unwrap();
+        let branches = list_local_branches(&wt_repo).unwrap();
+        let main = branches.iter().find(|b| b.name == "main").unwrap();
+        assert!(main.worktree.is_some());
+        let linked = branches.iter().find(|b| b.name == "linked").unwrap();
+        assert!(linked.is_head && linked.worktree.is_none());
+    }
+}
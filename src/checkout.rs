@@ -0,0 +1,46 @@
+//! Switching the working tree to another branch, so that the current one can be deleted.
+
+use git2::build::CheckoutBuilder;
+use git2::{BranchType, Repository, StatusOptions};
+
+/// Whether the working tree and index have no changes. Untracked files don't count,
+/// like with `git switch`.
+pub fn is_worktree_clean(repo: &Repository) -> Result<bool, git2::Error> {
+    let mut opts = StatusOptions::new();
+    opts.include_untracked(false).include_ignored(false);
+    Ok(repo.statuses(Some(&mut opts))?.is_empty())
+}
+
+/// Check out the local branch, refusing to overwrite any change.
+pub fn switch_to_branch(repo: &Repository, name: &str) -> Result<(), git2::Error> {
+    let branch = repo.find_branch(name, BranchType::Local)?;
+    let commit = branch.get().peel_to_commit()?;
+    repo.checkout_tree(commit.as_object(), Some(CheckoutBuilder::new().safe()))?;
+    repo.set_head(&format!("refs/heads/{name}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::testutil::{commit, init_repo};
+
+    #[test]
+    fn switches_when_clean() {
+        let (dir, repo) = init_repo();
+        let base = commit(&repo, "refs/heads/main", None);
+        commit(&repo, "refs/heads/feature", Some(base));
+        repo.set_head("refs/heads/feature").unwrap();
+        fs::write(dir.path().join("new.txt"), "x").unwrap();
+        assert!(is_worktree_clean(&repo).unwrap());
+        let mut index = repo.index().unwrap();
+        index.add_path("new.txt".as_ref()).unwrap();
+        index.write().unwrap();
+        assert!(!is_worktree_clean(&repo).unwrap());
+        index.clear().unwrap();
+        index.write().unwrap();
+        switch_to_branch(&repo, "main").unwrap();
+        assert_eq!(repo.head().unwrap().shorthand(), Some("main"));
+    }
+}
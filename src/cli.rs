@@ -0,0 +1,409 @@
+use std::env;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+
+use clap::builder::BoolishValueParser;
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Args, CommandFactory, FromArgMatches, Parser, Subcommand};
+use clap_complete::Shell;
+use git_del_branches::age::HumanDuration;
+use git_del_branches::backend::BackendKind;
+use git_del_branches::branch::{AuthorFormat, DateKind};
+use git_del_branches::report::ReportFormat;
+
+#[derive(Parser)]
+#[command(author, version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// Git folder of the repository, instead of finding it from the current folder, like GIT_DIR
+    #[arg(long, global = true, value_name = "PATH")]
+    pub git_dir: Option<PathBuf>,
+    /// Ignore the settings of this tool in the Git config files, and the GIT_DEL_BRANCHES_*
+    /// variables of the options, to behave the same for everyone. Those of `git -c`, the
+    /// protected branches and the settings of Git itself still apply
+    #[arg(
+        long,
+        global = true,
+        env = "GIT_DEL_BRANCHES_NO_CONFIG",
+        value_parser = BoolishValueParser::new()
+    )]
+    pub no_config: bool,
+    /// Options for the default `delete` command
+    #[command(flatten)]
+    pub delete: DeleteArgs,
+}
+
+/// Environment variables which options are read from, when not given.
+pub const OPTION_VARS: &[&str] = &[
+    "GIT_DEL_BRANCHES_YES",
+    "GIT_DEL_BRANCHES_EXPLAIN",
+    "GIT_DEL_BRANCHES_BASE",
+    "GIT_DEL_BRANCHES_REMOTE",
+];
+
+/// Parse the command line. The options of the default command cannot be given with another
+/// command, but the global ones can, on either side of it, like with `git`. The variables of
+/// `OPTION_VARS` count below the command line: one which conflicts with the options given, like
+/// GIT_DEL_BRANCHES_YES with --preselect, is left out, and so are all of them with --no-config.
+pub fn parse() -> Cli {
+    let vars: Vec<_> = OPTION_VARS
+        .iter()
+        .filter_map(|v| Some((*v, env::var_os(v)?)))
+        .collect();
+    // Parsed without them first, for the conflicts to be those of the command line alone. The
+    // command reads the variables when it is made, so it is made again with each one
+    for (var, _) in &vars {
+        env::remove_var(var);
+    }
+    let mut cmd = Cli::command();
+    let mut matches = cmd.get_matches_mut();
+    if !matches.get_flag("no_config") {
+        for (var, value) in vars {
+            env::set_var(var, value);
+            let mut with_var = Cli::command();
+            match with_var.try_get_matches_from_mut(env::args_os()) {
+                Ok(found) => (cmd, matches) = (with_var, found),
+                Err(e) if e.kind() == ErrorKind::ArgumentConflict => env::remove_var(var),
+                Err(e) => e.exit(),
+            }
+        }
+    }
+    if let Some((name, _)) = matches.subcommand() {
+        if let Some(arg) = find_delete_arg(&cmd, &matches) {
+            cmd.error(
+                ErrorKind::ArgumentConflict,
+                format!("the subcommand '{name}' cannot be used with '{arg}'"),
+            )
+            .exit();
+        }
+    }
+    Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.format(&mut cmd).exit())
+}
+
+/// Find an option of the default command which was given on the command line.
+fn find_delete_arg(cmd: &clap::Command, matches: &ArgMatches) -> Option<String> {
+    cmd.get_arguments()
+        .filter(|a| !a.is_global_set())
+        .find(|a| matches.value_source(a.get_id().as_str()) == Some(ValueSource::CommandLine))
+        .map(|a| a.to_string())
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Select branches and delete them (default)
+    Delete(DeleteArgs),
+    /// Print the branches which can be deleted, one per line
+    List(ListArgs),
+    /// Bring back branches which were deleted by this tool
+    Restore(RestoreArgs),
+    /// Remove the deletions older than the retention period from the journal of restore
+    Purge(PurgeArgs),
+    /// Select tags and delete them
+    Tags(TagsArgs),
+    /// Print statistics of the local branches: by author, age, merge status and upstream
+    Stats(StatsArgs),
+    /// Print a report of the stale branches, by author, to share in a wiki or a meeting
+    Report(ReportArgs),
+    /// Manage the tokens of hosting APIs, kept in the keychain of the OS
+    Auth {
+        #[command(subcommand)]
+        action: AuthAction,
+    },
+    /// Show or change settings
+    Config {
+        #[command(subcommand)]
+        action: Option<ConfigAction>,
+    },
+    /// Print shell completion script
+    Completions { shell: Shell },
+    /// Replace this program with the latest release from GitHub
+    SelfUpdate {
+        /// Only check if there is a newer version
+        #[arg(long)]
+        check: bool,
+    },
+}
+
+#[derive(Args, Default)]
+pub struct DeleteArgs {
+    /// Only offer the branches picked by the rules in this policy file (TOML)
+    #[arg(long, value_name = "FILE")]
+    pub policy: Option<PathBuf>,
+    /// Delete all branches picked by the policy, without asking. For running from cron
+    #[arg(long)]
+    pub auto: bool,
+    /// Only offer the branches meeting the criteria of this saved profile, in its order
+    #[arg(long, value_name = "NAME")]
+    pub profile: Option<String>,
+    /// Delete all the offered branches without asking, keeping their upstream branches unless
+    /// the policy says otherwise
+    #[arg(
+        long,
+        env = "GIT_DEL_BRANCHES_YES",
+        value_parser = BoolishValueParser::new(),
+        conflicts_with = "include_protected"
+    )]
+    pub yes: bool,
+    /// Start with the branches named in this file selected, one per line, like a file written
+    /// with w in the picker by whoever reviewed the cleanup. With --yes, only those are deleted
+    #[arg(long, value_name = "FILE", conflicts_with = "auto")]
+    pub select_from: Option<PathBuf>,
+    /// Delete exactly the branches of this spec file (TOML, or JSON), with their upstream
+    /// branches as it says, after showing the plan. Nothing is deleted if any of them cannot be
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["auto", "policy", "profile", "explain", "switch", "select_from", "preselect"]
+    )]
+    pub apply: Option<PathBuf>,
+    /// Start with the branches matching this glob selected, like "feature/*", to review them
+    /// before confirming. Can be repeated
+    #[arg(long, value_name = "GLOB", conflicts_with_all = ["auto", "yes"])]
+    pub preselect: Vec<String>,
+    /// Never offer the branches matching the globs of this file, one per line, like a list of
+    /// branches not to delete kept by the release team. Lines starting with "#" are skipped
+    #[arg(long, value_name = "FILE", conflicts_with = "apply")]
+    pub exclude_file: Option<PathBuf>,
+    /// With --auto or --yes, also delete the branches whose pull request is still open, which
+    /// are skipped otherwise
+    #[arg(long)]
+    pub force_open_pr: bool,
+    /// Also offer the current branch. If it is picked, the default branch is checked out first
+    #[arg(long)]
+    pub switch: bool,
+    /// Only offer the branches which are merged into the base
+    #[arg(long)]
+    pub merged: bool,
+    /// Only offer the branches whose last commit is yours, by the user.email setting
+    #[arg(long)]
+    pub mine: bool,
+    /// Never offer the branches with commits newer than this, like "3d", whatever the other
+    /// filters or the policy say. "0h" turns off the default of del-branches.keepActive
+    #[arg(long, value_name = "DURATION")]
+    pub keep_active: Option<HumanDuration>,
+    /// Branch to compare with for merge status, like origin/develop (default: the default branch)
+    #[arg(long, value_name = "REF", env = "GIT_DEL_BRANCHES_BASE")]
+    pub base: Option<String>,
+    /// Write the commands restoring the deleted branches, with plain Git, to this shell script
+    #[arg(long, value_name = "FILE")]
+    pub undo_script: Option<PathBuf>,
+    /// Print every branch with what got it offered, or why it is left out, and stop there,
+    /// like a dry run
+    #[arg(long, env = "GIT_DEL_BRANCHES_EXPLAIN", value_parser = BoolishValueParser::new())]
+    pub explain: bool,
+    /// Print what happens on stdout as it does, one JSON object per line, for the tools
+    /// wrapping this one. The summary then goes to stderr
+    #[arg(long, conflicts_with = "explain")]
+    pub events: bool,
+    /// Show how long ago the branches were created, from their reflogs, and offer sorting by
+    /// it, as a rebase renews the dates of the commits
+    #[arg(long)]
+    pub created: bool,
+    /// Show an estimate of the space which each branch takes on its own, which deleting it
+    /// would eventually free, and offer sorting by it
+    #[arg(long)]
+    pub sizes: bool,
+    /// Look up the status of the CI checks of the branch tips on the hosting service, shown with
+    /// ✅, ❌ or 🕒
+    #[arg(long)]
+    pub ci_status: bool,
+    /// Look up the issues which the branches are named after, like PROJ-123 in Jira, or #567
+    /// on GitHub or Gitea, and select those whose issue is resolved, marked with 🎫
+    #[arg(long)]
+    pub issues: bool,
+    /// Also offer the protected branches, like the default branch, marked with 🔒
+    #[arg(long, conflicts_with = "auto")]
+    pub include_protected: bool,
+    /// How to show the authors: name (default), email, user (email before @) or both
+    #[arg(long, value_name = "FORMAT")]
+    pub author_format: Option<AuthorFormat>,
+    /// Date of the last commit to count ages from: committer (default), which changes on rebase,
+    /// or author
+    #[arg(long, value_name = "KIND")]
+    pub date: Option<DateKind>,
+    /// Git implementation to list and delete branches with: libgit2 (default), gix or git
+    #[arg(long, value_name = "NAME")]
+    pub backend: Option<BackendKind>,
+    /// Delete with the git program, using your full Git setup. Same as `--backend git`
+    #[arg(long, conflicts_with = "backend")]
+    pub use_git_cli: bool,
+    /// Do not run the pre-push hook of the repository when deleting upstream branches
+    #[arg(long)]
+    pub no_verify: bool,
+    /// Sign the pushes deleting upstream branches (default: the push.gpgSign setting)
+    #[arg(long)]
+    pub signed: bool,
+    /// After deleting, run `git gc --prune=now` to free the space of the deleted branches
+    #[arg(long)]
+    pub gc: bool,
+    /// After deleting, remove the reflog entries which keep their commits from being collected
+    #[arg(long)]
+    pub expire_reflogs: bool,
+    /// In a partial clone, fetch the objects needed for diff stats, previews and squash merge
+    /// detection, which are otherwise skipped
+    #[arg(long)]
+    pub fetch_blobs: bool,
+    /// In a shallow clone, fetch as much history as needed to tell whether the branches are merged
+    #[arg(long)]
+    pub deepen: bool,
+    /// Then do the same in each initialized submodule
+    #[arg(long)]
+    pub recurse_submodules: bool,
+    /// Delete the upstream branches N at a time, with one push for each remote, instead of
+    /// one push per branch. For servers which time out, or refuse pushes with many refs
+    #[arg(long, value_name = "N")]
+    pub batch_size: Option<NonZeroUsize>,
+    /// Retry the deletions of the upstream branches which failed in the previous runs, from the
+    /// journal, without picking anything. Those which are gone already are skipped
+    #[arg(long, conflicts_with_all = ["auto", "yes", "explain", "policy", "profile", "select_from", "preselect", "apply"])]
+    pub resume: bool,
+    /// Clean up even if another cleanup of the repository seems in progress, like when the
+    /// previous one crashed and left its lock behind
+    #[arg(long)]
+    pub force_lock: bool,
+    /// Delete the upstream branches on mirror remotes (remote.<name>.mirror) without asking.
+    /// Otherwise they are kept by --auto and --yes
+    #[arg(long)]
+    pub allow_mirror: bool,
+    /// Pick and delete branches of the remote at this URL, which needs no clone. Only their
+    /// names and tips are known
+    #[arg(
+        long,
+        value_name = "URL",
+        conflicts_with_all = [
+            "auto",
+            "yes",
+            "explain",
+            "events",
+            "recurse_submodules",
+            "select_from",
+            "preselect",
+            "apply"
+        ]
+    )]
+    pub url: Option<String>,
+}
+
+#[derive(Args, Default)]
+pub struct ListArgs {
+    /// Only print the branches whose last commit is yours, by the user.email setting
+    #[arg(long)]
+    pub mine: bool,
+    /// Print a table of the branches, with their tips, upstreams, merge status, authors, dates
+    /// and pull requests
+    #[arg(long)]
+    pub long: bool,
+    /// With --long, the branch to compare with for merge status (default: the default branch)
+    #[arg(long, value_name = "REF", requires = "long")]
+    pub base: Option<String>,
+}
+
+#[derive(Args, Default)]
+pub struct TagsArgs {
+    /// Only offer the tags matching this glob, like "nightly-*". Can be repeated
+    #[arg(long, value_name = "GLOB")]
+    pub pattern: Vec<String>,
+    /// Only offer the tags older than this, like "6m" or "1y"
+    #[arg(long, value_name = "DURATION")]
+    pub older_than: Option<HumanDuration>,
+    /// Also delete the picked tags on this remote
+    #[arg(long, value_name = "NAME", env = "GIT_DEL_BRANCHES_REMOTE")]
+    pub remote: Option<String>,
+}
+
+#[derive(Args, Default)]
+pub struct StatsArgs {
+    /// Branch to compare with for merge status (default: the default branch)
+    #[arg(long, value_name = "REF", env = "GIT_DEL_BRANCHES_BASE")]
+    pub base: Option<String>,
+    /// How to show the authors: name (default), email, user (email before @) or both
+    #[arg(long, value_name = "FORMAT")]
+    pub author_format: Option<AuthorFormat>,
+    /// Date of the last commit to count ages from: committer (default) or author
+    #[arg(long, value_name = "KIND")]
+    pub date: Option<DateKind>,
+}
+
+#[derive(Args)]
+pub struct RestoreArgs {
+    /// Restore from the log shared with the team (see del-branches.sharedLog), with the branches
+    /// deleted by anyone
+    #[arg(long)]
+    pub shared: bool,
+}
+
+#[derive(Args, Default)]
+pub struct PurgeArgs {
+    /// Remove the deletions older than this, like "6m" (default: del-branches.retention)
+    #[arg(long, value_name = "DURATION")]
+    pub older_than: Option<HumanDuration>,
+}
+
+#[derive(Args)]
+pub struct ReportArgs {
+    /// Format of the report: markdown (default) or html
+    #[arg(long, value_name = "FORMAT", default_value_t)]
+    pub format: ReportFormat,
+    /// Report the branches whose last commit is older than this, like "6m" or "1y"
+    #[arg(long, value_name = "DURATION", default_value = "3m")]
+    pub older_than: HumanDuration,
+    /// Branch to compare with for merge status (default: the default branch)
+    #[arg(long, value_name = "REF", env = "GIT_DEL_BRANCHES_BASE")]
+    pub base: Option<String>,
+    /// How to show the authors: name (default), email, user (email before @) or both
+    #[arg(long, value_name = "FORMAT")]
+    pub author_format: Option<AuthorFormat>,
+    /// Date of the last commit to count ages from: committer (default) or author
+    #[arg(long, value_name = "KIND")]
+    pub date: Option<DateKind>,
+    /// Print how many stale branches each author has and the age of the oldest, instead of
+    /// the report
+    #[arg(long)]
+    pub by_author: bool,
+    /// Write the report of each author in a file of this folder, to send to them
+    #[arg(long, value_name = "DIR", conflicts_with = "by_author")]
+    pub output_dir: Option<PathBuf>,
+}
+
+#[derive(Subcommand)]
+pub enum AuthAction {
+    /// Log in to GitHub in the browser, and store the token it gives
+    Login {
+        #[arg(default_value = "github.com")]
+        host: String,
+        /// Only ask for access to public repositories
+        #[arg(long)]
+        public: bool,
+    },
+    /// Ask for a token of the API of a host, like github.com, and store it
+    Token { host: String },
+    /// Tell whether a token is stored for the host
+    Status { host: String },
+    /// Remove the stored token of the host
+    Logout { host: String },
+}
+
+#[derive(Subcommand)]
+pub enum ConfigAction {
+    /// List all settings with their current values (default)
+    List,
+    /// Print the settings as they are resolved, with where each value comes from
+    Show,
+    /// Print the value of a setting
+    Get { name: String },
+    /// Change a setting, in the repository config unless --global is given
+    Set {
+        name: String,
+        value: String,
+        #[arg(long)]
+        global: bool,
+    },
+    /// Remove a setting, from the repository config unless --global is given
+    Unset {
+        name: String,
+        #[arg(long)]
+        global: bool,
+    },
+}
@@ -0,0 +1,88 @@
+use color_eyre::Result;
+use console::{style, Emoji};
+use eyre::eyre;
+use git2::Config;
+use inquire::{Password, PasswordDisplayMode};
+
+use git_del_branches::config::{get_settings, GITHUB_CLIENT_ID};
+use git_del_branches::gitenv::open_repository;
+use git_del_branches::keychain::{delete_secret, load_secret, store_secret};
+
+use super::get_answer;
+use crate::cli::AuthAction;
+use crate::github::{request_device_code, wait_for_token, PUBLIC_SCOPE, SCOPE};
+
+/// Ask for the token of the host. Returns `None` if user cancelled.
+pub fn ask_token(host: &str, help: Option<&str>) -> Result<Option<String>> {
+    let message = format!("Token for {host}:");
+    let mut prompt = Password::new(&message)
+        .with_display_mode(PasswordDisplayMode::Masked)
+        .without_confirmation();
+    if let Some(help) = help {
+        prompt = prompt.with_help_message(help);
+    }
+    let token = get_answer(prompt.prompt())?;
+    Ok(token
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty()))
+}
+
+pub fn store_token(host: &str, token: &str) -> Result<()> {
+    store_secret(host, token)?;
+    let msg = format!("Stored the token for {host} in the keychain.");
+    eprintln!("{} {}", Emoji("🔑", "*"), style(msg).green());
+    Ok(())
+}
+
+/// Client ID of the OAuth app to authorize on the GitHub at `host`, which is to be set, as
+/// there is none of our own.
+fn get_client_id(host: &str, no_config: bool) -> Result<String> {
+    // Like the other settings, when run in a repository
+    let config = match (open_repository(), no_config) {
+        (Ok(repo), _) => get_settings(&repo, no_config)?,
+        (Err(_), false) => Config::open_default()?,
+        (Err(_), true) => Config::new()?,
+    };
+    config.get_string(GITHUB_CLIENT_ID).map_err(|_| {
+        eyre!(
+            "Set {GITHUB_CLIENT_ID} to the client ID of an OAuth app of {host}, with the device flow enabled"
+        )
+    })
+}
+
+/// Get a token from the GitHub at `host` with the device flow, and store it in the keychain.
+fn login(host: &str, public: bool, no_config: bool) -> Result<()> {
+    let client_id = get_client_id(host, no_config)?;
+    let scope = if public { PUBLIC_SCOPE } else { SCOPE };
+    let code = request_device_code(host, &client_id, scope)?;
+    eprintln!(
+        "Open {} and enter the code {}",
+        style(&code.verification_uri).underlined(),
+        style(&code.user_code).bold()
+    );
+    eprintln!("{}", style("Waiting for the authorization...").dim());
+    let token = wait_for_token(host, &client_id, &code)?;
+    eprintln!("Logged in to {host}.");
+    store_token(host, &token)?;
+    Ok(())
+}
+
+pub fn run(action: AuthAction, no_config: bool) -> Result<()> {
+    match action {
+        AuthAction::Login { host, public } => login(&host, public, no_config)?,
+        AuthAction::Token { host } => {
+            if let Some(token) = ask_token(&host, None)? {
+                store_token(&host, &token)?;
+            }
+        }
+        AuthAction::Status { host } => match load_secret(&host) {
+            Some(_) => println!("A token for {host} is stored in the keychain."),
+            None => println!("No token for {host} in the keychain."),
+        },
+        AuthAction::Logout { host } => {
+            delete_secret(&host)?;
+            eprintln!("Removed the token for {host} from the keychain.");
+        }
+    }
+    Ok(())
+}
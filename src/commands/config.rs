@@ -0,0 +1,191 @@
+use std::env;
+
+use color_eyre::Result;
+use console::style;
+use git2::{Config, ConfigEntry, ConfigLevel, Repository};
+
+use git_del_branches::branch::{
+    get_committed_patterns, get_default_branch_names, get_env_patterns, EXCLUDES, PROTECTED_FILES,
+    PROTECT_ENV,
+};
+use git_del_branches::config::{get_full_key, BASE, ICON, PROFILE_PREFIX, SETTINGS};
+use git_del_branches::gitenv::{get_common_dir, open_repository};
+use git_del_branches::profile::{list_profiles, load_profile};
+
+use crate::cli::{ConfigAction, OPTION_VARS};
+
+fn open_writable_config(global: bool) -> Result<Config> {
+    let config = if global {
+        // The global file may not exist yet
+        let path = match Config::find_global() {
+            Ok(path) => path,
+            Err(_) => dirs::home_dir().unwrap_or_default().join(".gitconfig"),
+        };
+        Config::open(&path)?
+    } else {
+        open_repository()?
+            .config()?
+            .open_level(ConfigLevel::Local)?
+    };
+    Ok(config)
+}
+
+/// Where a value of Git config comes from, like "global config /home/me/.gitconfig".
+fn describe_level(repo: &Repository, level: ConfigLevel) -> String {
+    let (name, path) = match level {
+        ConfigLevel::App => return "git -c".to_string(),
+        ConfigLevel::Local => ("local", Some(get_common_dir(repo).join("config"))),
+        ConfigLevel::Global => ("global", Config::find_global().ok()),
+        ConfigLevel::XDG => ("XDG", Config::find_xdg().ok()),
+        ConfigLevel::System => ("system", Config::find_system().ok()),
+        ConfigLevel::ProgramData | ConfigLevel::Highest => ("Git", None),
+    };
+    match path {
+        Some(path) => format!("{name} config {}", path.display()),
+        None => format!("{name} config"),
+    }
+}
+
+/// Print the value of Git config, with where it comes from, and whether `--no-config` ignores it.
+fn print_entry(repo: &Repository, entry: &ConfigEntry, no_config: bool) {
+    let (Some(key), Some(value)) = (entry.name(), entry.value()) else {
+        return;
+    };
+    let source = describe_level(repo, entry.level());
+    match no_config && entry.level() != ConfigLevel::App {
+        true => {
+            println!("{key} =");
+            let msg = format!("default, --no-config ignores \"{value}\" of {source}");
+            println!("  {}", style(msg).dim());
+        }
+        false => {
+            println!("{key} = {value}");
+            println!("  {}", style(source).dim());
+        }
+    }
+}
+
+/// Print each setting as the tool resolves it, then the options read from the environment and
+/// the protected branches, each with where it comes from.
+fn show(repo: &Repository, no_config: bool) -> Result<()> {
+    let config = repo.config()?;
+    println!(
+        "{}",
+        style("The options given on the command line win over all of these.").dim()
+    );
+    for setting in SETTINGS {
+        let env_var = (setting.key == BASE).then_some("GIT_DEL_BRANCHES_BASE");
+        if let Some((var, value)) = env_var.and_then(|v| Some((v, env::var(v).ok()?))) {
+            println!("{} = {value}", setting.key);
+            println!("  {}", style(format!("{var}, over Git config")).dim());
+        } else if setting.key == ICON {
+            let mut entries = config.multivar(ICON, None)?;
+            let mut found = false;
+            while let Some(entry) = entries.next() {
+                print_entry(repo, entry?, no_config);
+                found = true;
+            }
+            if !found {
+                println!("{ICON} =");
+                println!("  {}", style("default").dim());
+            }
+        } else {
+            match config.get_entry(setting.key) {
+                Ok(entry) => print_entry(repo, &entry, no_config),
+                Err(_) => {
+                    println!("{} =", setting.key);
+                    println!("  {}", style("default").dim());
+                }
+            }
+        }
+    }
+    let mut entries = config.entries(Some("^del-branches\\.(profile|host)\\."))?;
+    while let Some(entry) = entries.next() {
+        print_entry(repo, entry?, no_config);
+    }
+    for var in OPTION_VARS {
+        if let Ok(value) = env::var(var) {
+            println!("{var} = {value}");
+            println!(
+                "  {}",
+                style("environment, when the option is not given").dim()
+            );
+        }
+    }
+
+    println!();
+    println!("Protected branches:");
+    let sources = [
+        (get_default_branch_names(repo), "default branch".to_string()),
+        (
+            EXCLUDES.iter().map(|n| n.to_string()).collect(),
+            "built in".to_string(),
+        ),
+        (
+            get_committed_patterns(repo),
+            format!("committed in {}", PROTECTED_FILES.join(" or ")),
+        ),
+        (get_env_patterns(), PROTECT_ENV.to_string()),
+    ];
+    for (names, source) in sources {
+        for name in names {
+            println!("  {name} {}", style(format!("({source})")).dim());
+        }
+    }
+    Ok(())
+}
+
+pub fn run(action: Option<ConfigAction>, no_config: bool) -> Result<()> {
+    match action.unwrap_or(ConfigAction::List) {
+        ConfigAction::List => {
+            let config = open_repository()?.config()?;
+            for setting in SETTINGS {
+                let value = config.get_string(setting.key).ok();
+                println!("{} = {}", setting.key, value.as_deref().unwrap_or(""));
+                println!("  {}", style(setting.description).dim());
+            }
+            for name in list_profiles(&config)? {
+                match load_profile(&config, &name) {
+                    Ok(profile) => {
+                        println!("{PROFILE_PREFIX}{name}.* = {profile}");
+                        println!("  {}", style("Saved filter profile").dim());
+                    }
+                    Err(e) => {
+                        println!("{PROFILE_PREFIX}{name}.* =");
+                        println!("  {}", style(e).red());
+                    }
+                }
+            }
+            let mut entries = config.entries(Some("^del-branches\\.host\\."))?;
+            while let Some(entry) = entries.next() {
+                let entry = entry?;
+                let (Some(key), Some(value)) = (entry.name(), entry.value()) else {
+                    continue;
+                };
+                println!("{key} = {value}");
+                println!("  {}", style("Setting for the remotes on one host").dim());
+            }
+        }
+        ConfigAction::Show => {
+            let repo = open_repository()?;
+            show(&repo, no_config)?;
+        }
+        ConfigAction::Get { name } => {
+            let config = open_repository()?.config()?;
+            if let Ok(value) = config.get_string(&get_full_key(&name)) {
+                println!("{value}");
+            }
+        }
+        ConfigAction::Set {
+            name,
+            value,
+            global,
+        } => {
+            open_writable_config(global)?.set_str(&get_full_key(&name), &value)?;
+        }
+        ConfigAction::Unset { name, global } => {
+            open_writable_config(global)?.remove(&get_full_key(&name))?;
+        }
+    }
+    Ok(())
+}
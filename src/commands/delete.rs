@@ -0,0 +1,2684 @@
+use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::process::{self, ExitCode};
+use std::rc::Rc;
+
+use color_eyre::Result;
+use console::{measure_text_width, style, Emoji, Style};
+use eyre::{eyre, Context};
+use git2::{
+    BranchType, CertificateCheckStatus, Config, Cred, CredentialType, FetchOptions, Oid,
+    PushOptions, RemoteCallbacks, Repository,
+};
+use git2_credentials::CredentialHandler;
+use inquire::{Confirm, MultiSelect};
+use serde_json::json;
+
+use git_del_branches::age::{format_age, is_older_than, now, HumanDuration};
+use git_del_branches::backend::{open_backend, Backend, BackendKind, PushSettings, SignedPush};
+use git_del_branches::branch::{
+    find_default_branch, find_unlisted_branches, get_creation_time, get_head_state,
+    get_protected_names, get_server_branch, get_worktree_branches, is_authored_by, is_protected,
+    list_local_branches, use_dates, BranchInfo, HeadState, Upstream,
+};
+use git_del_branches::checkout::{is_worktree_clean, switch_to_branch};
+use git_del_branches::config::{
+    get_settings, BACKEND, BASE, KEEP_ACTIVE, KEYS_CONFIRM, KEYS_DETAILS, KEYS_PREVIEW,
+    KEYS_SEARCH, KEYS_SORT, KEYS_TOGGLE, POLICY, POST_DELETE_HOOK, PRE_DELETE_HOOK,
+    PROTECT_COMMAND, RETENTION, SHARED_LOG, UNDO_SCRIPT, WEBHOOK_URL,
+};
+use git_del_branches::credential::GitCredentials;
+use git_del_branches::delete::{
+    delete_tracking_branch, find_remote_default_branch, is_mirror, list_server_branches,
+    PushRejected, Rejections,
+};
+use git_del_branches::depend::{find_dependents, find_stacks, Dependence, Stack};
+use git_del_branches::diffstat::{format_time, get_diff_stat, get_log_patch};
+use git_del_branches::gitenv::{open_repository, open_submodules, reopen, set_git_env};
+use git_del_branches::hooks::{run_hook, run_protect_command};
+use git_del_branches::hosting::tracker::{find_issue_key, IssueKey, IssueState};
+use git_del_branches::hosting::{
+    parse_remote_url, CiStatus, ProtectedRef, PullRequest, ReviewState,
+};
+use git_del_branches::icon::{find_icon, load_icons, Icon};
+use git_del_branches::journal::{self, build_undo_script, Entry, UpstreamState};
+use git_del_branches::lock::{get_holder, Lock};
+use git_del_branches::merged::{
+    check_contained_in, count_unique_commits, is_risky, resolve_commit, MergeKind, PatchIndex,
+};
+use git_del_branches::notify;
+use git_del_branches::partial::{fetch_missing_objects, is_partial_clone};
+use git_del_branches::pattern::{matches, matches_any, read_patterns};
+use git_del_branches::policy::{Decision, Policy};
+use git_del_branches::profile::{load_profile, SortOrder};
+use git_del_branches::shallow::deepen_until_known;
+use git_del_branches::sharedlog::{self, LOG_REF};
+use git_del_branches::space::{
+    estimate_unreachable, expire_reflogs, find_unreachable_commits, format_size, BranchSizes,
+    Reclaimable,
+};
+use git_del_branches::spec::Spec;
+use git_del_branches::ssh::{diagnose, find_key_files, probe_agent, SshAttempts};
+use git_del_branches::stash::count_stashes_by_branch;
+use git_del_branches::tls::{use_ca_file, TlsSettings, SSL_CA_INFO};
+use git_del_branches::urlremote::{RemoteBranch, UrlRemote};
+use git_del_branches::{selection, session};
+
+use super::hostings::Hostings;
+use super::purge::get_retention;
+use super::{get_answer, get_author_format, get_date_kind, get_user_email, style_age};
+use crate::cli::DeleteArgs;
+use crate::events::Events;
+use crate::picker::{Fields, KeyMap, Picker, Value};
+use crate::update;
+
+/// Exit code when some branches could not be deleted.
+const EXIT_PARTIAL_FAILURE: u8 = 2;
+
+/// Names of the orders of the picker, which profiles can start with.
+const OLDEST_FIRST: &str = "oldest first";
+const NEWEST_FIRST: &str = "newest first";
+const BY_AUTHOR: &str = "by author, oldest first";
+const CREATED_FIRST: &str = "by creation, oldest first";
+const LARGEST_FIRST: &str = "by size, largest first";
+
+/// Branch, displayed for the picker.
+struct Item<'a> {
+    branch: BranchInfo,
+    protected: bool,
+    /// Seconds since the tip was committed.
+    age: i64,
+    /// Seconds since the branch was created, if its reflog tells.
+    created: Option<i64>,
+    /// Name of the base branch, if the branch is contained in it.
+    contained_in: Option<&'a str>,
+    /// Name of the base branch, if the clone is too shallow to tell whether the branch
+    /// is contained in it.
+    unknown_in: Option<&'a str>,
+    /// Whether it was squash or rebase merged into the base branch.
+    merge_kind: Option<MergeKind>,
+    /// Whether it was not found merged into the base branch, including when the shallow
+    /// clone cannot tell.
+    unmerged: bool,
+    /// Number of stashes created on the branch.
+    stashes: usize,
+    /// The latest pull request from it, if its hosting tells.
+    pull_request: Option<PullRequest>,
+    /// Web page of that pull request.
+    pull_request_url: Option<String>,
+    /// Whether its hosting was asked for its pull request, so that not having one is told.
+    reviewed: bool,
+    /// Status of the CI checks of its tip, if they were looked up.
+    ci_status: Option<CiStatus>,
+    /// The issue it is named after, if any.
+    issue: Option<IssueKey>,
+    /// State of that issue, if it was looked up.
+    issue_state: Option<IssueState>,
+    /// Whether deleting it may lose work, which is then shown in red.
+    risky: bool,
+    /// Author of the tip commit, in the chosen format.
+    author: String,
+    /// Number of its commits which are not on the base, if there is one.
+    unique: Option<usize>,
+    /// Bytes which only it takes, if they were estimated.
+    size: Option<u64>,
+    /// Icon of its category, by the prefix of its name.
+    icon: Option<&'a Icon>,
+    widths: Widths,
+}
+
+/// Widths of the columns of the picker, which are not shown when 0.
+#[derive(Clone, Copy)]
+struct Widths {
+    icon: usize,
+    name: usize,
+    author: usize,
+    /// Of the remotes of the upstream branches, shown if the repository has several.
+    remote: usize,
+    contained: usize,
+    unique: usize,
+    size: usize,
+    pull_request: usize,
+    created: usize,
+}
+
+impl fmt::Display for Item<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Widths {
+            icon: icon_width,
+            name: name_width,
+            author: author_width,
+            remote: remote_width,
+            contained: contained_width,
+            unique: unique_width,
+            size: size_width,
+            pull_request: pr_width,
+            created: created_width,
+        } = self.widths;
+        if icon_width > 0 {
+            let glyph = self.icon.map_or("", |i| i.glyph.as_str());
+            let pad = icon_width.saturating_sub(measure_text_width(glyph));
+            write!(f, "{glyph}{:pad$} ", "")?;
+        }
+        let name = format!("{:<name_width$}", self.branch.name);
+        match (self.risky, self.icon.and_then(|i| i.color.as_deref())) {
+            (true, _) => write!(f, "{}", style(name).red().bold())?,
+            (false, Some(color)) => write!(f, "{}", Style::from_dotted_str(color).apply_to(name))?,
+            (false, None) => write!(f, "{name}")?,
+        }
+        write!(f, " {}", style_age(self.age))?;
+        if created_width > 0 {
+            let created = self.created.map_or("?".into(), format_age);
+            write!(f, " {}", style(format!("{created:>created_width$}")).dim())?;
+        }
+        let author = format!("{:<author_width$}", self.author);
+        write!(f, " {}", style(author).dim())?;
+        if remote_width > 0 {
+            let remote = self
+                .branch
+                .upstream
+                .as_ref()
+                .map_or("", |u| u.remote.as_str());
+            write!(f, " {}", style(format!("{remote:<remote_width$}")).cyan())?;
+        }
+        match (self.contained_in, self.unknown_in) {
+            (Some(b), _) => write!(f, " {:<contained_width$}", format!("in {b}"))?,
+            (None, Some(b)) => {
+                let unknown = format!("{:<contained_width$}", format!("in {b}?"));
+                write!(f, " {}", style(unknown).dim())?;
+            }
+            (None, None) => write!(f, " {:<contained_width$}", "")?,
+        }
+        if let (Some(unique), true) = (self.unique, unique_width > 0) {
+            let label = format!("{:<unique_width$}", format!("{unique} unique"));
+            match unique {
+                0 => write!(f, " {}", style(label).dim())?,
+                _ => write!(f, " {label}")?,
+            }
+        }
+        if size_width > 0 {
+            let size = self.size.map_or("?".into(), format_size);
+            write!(f, " {}", style(format!("{size:>size_width$}")).dim())?;
+        }
+        match (self.reviewed, pr_width) {
+            (_, 0) => {}
+            (true, _) => write!(
+                f,
+                " {}",
+                style_pull_request(self.pull_request.as_ref(), pr_width)
+            )?,
+            (false, _) => write!(f, " {:<pr_width$}", "")?,
+        }
+        if self.protected {
+            write!(f, " {}", Emoji("🔒", "[protected]"))?;
+        }
+        if self.branch.upstream.is_some() {
+            write!(f, " {}", Emoji("🔭", "[upstream]"))?;
+        } else if self.branch.upstream_gone {
+            write!(f, " {}", Emoji("👻", "[gone]"))?;
+        }
+        match self.merge_kind {
+            Some(MergeKind::Squashed) => write!(f, " {}", Emoji("🔀", "[squash-merged]"))?,
+            Some(MergeKind::Rebased) => write!(f, " {}", Emoji("🔁", "[rebase-merged]"))?,
+            None => {}
+        }
+        if self.stashes > 0 {
+            write!(f, " {}", Emoji("📦", "[stashed]"))?;
+        }
+        match self.ci_status {
+            Some(CiStatus::Passed) => write!(f, " {}", Emoji("✅", "[ci-passed]"))?,
+            Some(CiStatus::Failed) => write!(f, " {}", Emoji("❌", "[ci-failed]"))?,
+            Some(CiStatus::Pending) => write!(f, " {}", Emoji("🕒", "[ci-pending]"))?,
+            None => {}
+        }
+        if self.issue_state == Some(IssueState::Resolved) {
+            write!(f, " {}", Emoji("🎫", "[issue-resolved]"))?;
+        }
+        Ok(())
+    }
+}
+
+/// Number and state of the pull request, or "no PR" for a branch without any.
+fn get_pull_request_label(pr: Option<&PullRequest>) -> String {
+    match pr {
+        Some(pr) => format!("PR #{} {}", pr.number, pr.state),
+        None => "no PR".into(),
+    }
+}
+
+/// Label of the pull request, padded to the width, and colored like the signs of what can
+/// be deleted.
+pub fn style_pull_request(pr: Option<&PullRequest>, width: usize) -> String {
+    let label = style(format!("{:<width$}", get_pull_request_label(pr)));
+    match pr.map(|pr| pr.state) {
+        Some(ReviewState::Open) => label.yellow(),
+        Some(ReviewState::Merged) => label.green(),
+        Some(ReviewState::Closed) | None => label.dim(),
+    }
+    .to_string()
+}
+
+/// Tell that some branches may be merged below the cut of the shallow clone.
+fn warn_shallow(count: usize, base: &str) {
+    let msg = format!(
+        "This clone is shallow, so whether {count} branches are merged into {base} is unknown. Pass --deepen to fetch the history needed."
+    );
+    eprintln!("{}", style(msg).dim());
+}
+
+/// Fields which the picker's search can filter by, like `author:alice`, `age>6m` or
+/// `is:gone`.
+fn get_fields(item: &Item) -> Fields {
+    let branch = &item.branch;
+    let mut flags = Vec::new();
+    if item.contained_in.is_some() || item.merge_kind.is_some() {
+        flags.push("merged");
+    } else if item.unmerged && item.unknown_in.is_none() {
+        flags.push("unmerged");
+    }
+    match item.merge_kind {
+        Some(MergeKind::Squashed) => flags.push("squashed"),
+        Some(MergeKind::Rebased) => flags.push("rebased"),
+        None => {}
+    }
+    if item.risky {
+        flags.push("risky");
+    }
+    if item.protected {
+        flags.push("protected");
+    }
+    if branch.upstream.is_some() {
+        flags.push("upstream");
+    } else if branch.upstream_gone {
+        flags.push("gone");
+    }
+    if item.stashes > 0 {
+        flags.push("stashed");
+    }
+    if item.unique == Some(0) {
+        flags.push("empty");
+    }
+    match item.ci_status {
+        Some(CiStatus::Passed) => flags.push("ci-passed"),
+        Some(CiStatus::Failed) => flags.push("ci-failed"),
+        Some(CiStatus::Pending) => flags.push("ci-pending"),
+        None => {}
+    }
+    match item.issue_state {
+        Some(IssueState::Resolved) => flags.push("issue-resolved"),
+        Some(IssueState::Open) => flags.push("issue-open"),
+        None => {}
+    }
+    let pr_state = match (&item.pull_request, item.reviewed) {
+        (Some(pr), _) => pr.state.to_string(),
+        (None, true) => "none".into(),
+        (None, false) => String::new(),
+    };
+    vec![
+        ("name", Value::Text(branch.name.clone())),
+        (
+            "author",
+            Value::Text(format!("{} <{}>", branch.author, branch.author_email)),
+        ),
+        ("age", Value::Age(item.age)),
+        (
+            "created",
+            item.created.map_or(Value::UnknownAge, Value::Age),
+        ),
+        ("is", Value::Flags(flags)),
+        (
+            "remote",
+            Value::Text(
+                branch
+                    .upstream
+                    .as_ref()
+                    .map_or(String::new(), |u| u.remote.clone()),
+            ),
+        ),
+        ("pr", Value::Text(pr_state)),
+        (
+            "issue",
+            Value::Text(item.issue.as_ref().map_or(String::new(), |k| k.to_string())),
+        ),
+    ]
+}
+
+/// The branches of the file of --select-from which are offered, telling about the others.
+fn select_reviewed(items: &[Item], path: &Path, names: Vec<String>) -> Vec<String> {
+    let (offered, missing): (Vec<String>, Vec<String>) = names
+        .into_iter()
+        .partition(|n| items.iter().any(|i| &i.branch.name == n));
+    let msg = format!(
+        "Selected the {} branches of {}.",
+        offered.len(),
+        path.display()
+    );
+    eprintln!("{}", style(msg).dim());
+    if !missing.is_empty() {
+        let msg = format!(
+            "{} of them are not offered, being gone or left out: {}",
+            missing.len(),
+            missing.join(", ")
+        );
+        eprintln!("{}", style(msg).yellow());
+    }
+    offered
+}
+
+/// The unprotected branches matching the globs of --preselect.
+fn select_matching(items: &[Item], patterns: &[String]) -> Vec<String> {
+    if patterns.is_empty() {
+        return Vec::new();
+    }
+    let names: Vec<String> = items
+        .iter()
+        .filter(|i| !i.protected && matches_any(patterns, &i.branch.name))
+        .map(|i| i.branch.name.clone())
+        .collect();
+    let msg = format!(
+        "Selected the {} branches matching {}.",
+        names.len(),
+        patterns.join(", ")
+    );
+    eprintln!("{}", style(msg).dim());
+    names
+}
+
+/// Everything known about the branch, for the details of the picker, one "Label  value" per
+/// line.
+fn get_details(repo: &Repository, item: &Item, base: Option<&str>) -> Vec<String> {
+    let branch = &item.branch;
+    let mut lines = vec![("Branch", branch.name.clone())];
+    if let Ok(commit) = repo.find_commit(branch.tip) {
+        let summary = commit.summary().unwrap_or_default().to_string();
+        lines.push(("Commit", format!("{} {summary}", branch.tip)));
+        let author = commit.author();
+        lines.push((
+            "Author",
+            format!(
+                "{} <{}>",
+                author.name().unwrap_or_default(),
+                author.email().unwrap_or_default()
+            ),
+        ));
+        let (authored, committed) = (author.when(), commit.committer().when());
+        let ago = |seconds: i64| format_age(now() - seconds);
+        lines.push((
+            "Authored",
+            format!(
+                "{} ({} ago)",
+                format_time(authored),
+                ago(authored.seconds())
+            ),
+        ));
+        lines.push((
+            "Committed",
+            format!(
+                "{} ({} ago)",
+                format_time(committed),
+                ago(committed.seconds())
+            ),
+        ));
+    }
+    if let Some(created) = item.created {
+        lines.push(("Created", format!("{} ago", format_age(created))));
+    }
+    let upstream = match &branch.upstream {
+        Some(upstream) => {
+            let tracking = repo
+                .find_branch(&upstream.to_string(), BranchType::Remote)
+                .ok()
+                .and_then(|b| b.get().target());
+            match tracking.and_then(|id| repo.graph_ahead_behind(branch.tip, id).ok()) {
+                Some((ahead, behind)) => format!("{upstream}, {ahead} ahead, {behind} behind"),
+                None => upstream.to_string(),
+            }
+        }
+        None if branch.upstream_gone => "gone from the remote".into(),
+        None => "none".into(),
+    };
+    lines.push(("Upstream", upstream));
+    if let Some(base) = base {
+        let merged = match (item.contained_in, item.merge_kind, item.unknown_in) {
+            (Some(_), _, _) => format!("merged into {base}"),
+            (None, Some(MergeKind::Squashed), _) => format!("squash-merged into {base}"),
+            (None, Some(MergeKind::Rebased), _) => format!("rebase-merged into {base}"),
+            (None, None, Some(_)) => "unknown, the clone is too shallow to tell".into(),
+            (None, None, None) => format!("not merged into {base}"),
+        };
+        lines.push(("Merged", merged));
+    }
+    if let Some(unique) = item.unique {
+        lines.push(("Unique", format!("{unique} commits")));
+    }
+    if let Some(size) = item.size {
+        lines.push(("Size", format_size(size)));
+    }
+    if item.reviewed {
+        let pr = match &item.pull_request {
+            Some(pr) => format!("#{} {}: {}", pr.number, pr.state, pr.title),
+            None => "none".into(),
+        };
+        lines.push(("Pull request", pr));
+    }
+    if let Some(url) = &item.pull_request_url {
+        lines.push(("", url.clone()));
+    }
+    let ci = match item.ci_status {
+        Some(CiStatus::Passed) => Some("passed"),
+        Some(CiStatus::Failed) => Some("failed"),
+        Some(CiStatus::Pending) => Some("pending"),
+        None => None,
+    };
+    if let Some(ci) = ci {
+        lines.push(("CI", ci.to_string()));
+    }
+    if let Some(key) = &item.issue {
+        let state = item.issue_state.map_or(String::new(), |s| format!(" {s}"));
+        lines.push(("Issue", format!("{key}{state}")));
+    }
+    if item.stashes > 0 {
+        lines.push(("Stashes", item.stashes.to_string()));
+    }
+    if let Some(path) = &branch.worktree {
+        lines.push(("Worktree", path.display().to_string()));
+    }
+    let mut flags = Vec::new();
+    if item.protected {
+        flags.push("protected");
+    }
+    if item.risky {
+        flags.push("risky to delete, it may lose work");
+    }
+    if !flags.is_empty() {
+        lines.push(("", flags.join(", ")));
+    }
+    let mut lines: Vec<String> = lines
+        .into_iter()
+        .map(|(label, value)| format!("{label:<14}{value}"))
+        .collect();
+    let description = repo
+        .config()
+        .and_then(|c| c.get_string(&format!("branch.{}.description", branch.name)));
+    if let Ok(description) = description {
+        lines.push(String::new());
+        lines.extend(description.lines().map(str::to_string));
+    }
+    lines
+}
+
+/// Meanings of the signs in the picker.
+fn get_legend(base: Option<&str>, created: bool, sizes: bool) -> Vec<(String, String)> {
+    let base = base.unwrap_or("base");
+    let mut legend = vec![
+        (
+            format!("in {base}"),
+            format!("merged into {base}, which it is compared with"),
+        ),
+        (
+            style(format!("in {base}?")).dim().to_string(),
+            "unknown, the shallow clone lacks the history to tell".into(),
+        ),
+        (
+            style("name").red().bold().to_string(),
+            "risky: not merged, has commits which were not pushed, or an open pull request".into(),
+        ),
+        (
+            style("age").green().to_string(),
+            "of the last commit: green under a week, yellow under 3 months, else red".into(),
+        ),
+    ];
+    legend.push((
+        "5 unique".into(),
+        format!("commits which are not on {base}, dimmed when there are none"),
+    ));
+    if created {
+        legend.push((
+            style("age").dim().to_string(),
+            "since the branch was created, by its reflog, ? if it was expired".into(),
+        ));
+    }
+    if sizes {
+        legend.push((
+            style("1.5 MiB").dim().to_string(),
+            "what deleting it would free, from its commits which no protected branch has".into(),
+        ));
+    }
+    let signs = [
+        (
+            Emoji("🔒", "[protected]"),
+            "protected, like the default branch, or by the server",
+        ),
+        (Emoji("🔭", "[upstream]"), "has an upstream branch"),
+        (
+            Emoji("👻", "[gone]"),
+            "its upstream branch is gone from the remote",
+        ),
+        (
+            Emoji("🔀", "[squash-merged]"),
+            "squash-merged into the base",
+        ),
+        (
+            Emoji("🔁", "[rebase-merged]"),
+            "rebase-merged into the base",
+        ),
+        (
+            Emoji("📦", "[stashed]"),
+            "has stashes which were created on it",
+        ),
+        (
+            Emoji("✅", "[ci-passed]"),
+            "the CI checks of its tip passed",
+        ),
+        (
+            Emoji("❌", "[ci-failed]"),
+            "some CI checks of its tip failed",
+        ),
+        (
+            Emoji("🕒", "[ci-pending]"),
+            "some CI checks of its tip still run",
+        ),
+        (
+            Emoji("🎫", "[issue-resolved]"),
+            "the issue it is named after is resolved",
+        ),
+    ];
+    legend.extend(signs.iter().map(|(e, m)| (e.to_string(), m.to_string())));
+    legend.push((
+        style("PR #1 merged").green().to_string(),
+        "its latest pull request: open, merged or closed".into(),
+    ));
+    legend.push((
+        style("no PR").dim().to_string(),
+        "the hosting has no pull request from it".into(),
+    ));
+    legend
+}
+
+/// Warn about the branches to delete which have stashes, which will be hard to make sense of
+/// without their branch.
+fn warn_stashes(names: &[&str], stashes: &HashMap<String, usize>) {
+    for name in names {
+        let count = stashes.get(*name).copied().unwrap_or(0);
+        if count > 0 {
+            let msg = format!("{name} has {count} stashes created on it, see `git stash list`.");
+            eprintln!("{} {}", Emoji("⚠️", "!"), style(msg).yellow());
+        }
+    }
+}
+
+/// Warn about the branches to delete which other local branches track, unless those are
+/// deleted too.
+fn warn_dependents(
+    repo: &Repository,
+    deleting: &[BranchInfo],
+    locals: &[BranchInfo],
+    base: Option<Oid>,
+) {
+    let names: Vec<&str> = deleting.iter().map(|b| b.name.as_str()).collect();
+    for branch in deleting {
+        let dependents = find_dependents(repo, branch, locals, base).unwrap_or_default();
+        // The branches made from it are told about with its stack
+        let tracking: Vec<&str> = dependents
+            .iter()
+            .filter(|d| d.dependence == Dependence::Tracks)
+            .map(|d| d.name.as_str())
+            .filter(|n| !names.contains(n))
+            .collect();
+        if !tracking.is_empty() {
+            let msg = format!(
+                "{} is the upstream of {}.",
+                branch.name,
+                tracking.join(", ")
+            );
+            eprintln!("{} {}", Emoji("⚠️", "!"), style(msg).yellow());
+        }
+    }
+}
+
+/// Put the branches of each stack together, from its top down. A stack is then deleted from
+/// its top, so that a failure leaves its bottom whole.
+fn order_by_stack<T>(items: &mut [T], stacks: &[Stack], name: impl Fn(&T) -> &str) {
+    items.sort_by_key(|i| {
+        let name = name(i);
+        stacks.iter().enumerate().find_map(|(s, stack)| {
+            let pos = stack.branches.iter().position(|b| b == name)?;
+            Some((s, Reverse(pos)))
+        })
+    });
+}
+
+/// Warn about the stacks which are only partly picked, leaving some of their branches.
+fn warn_partial_stacks(names: &[&str], stacks: &[Stack]) {
+    for stack in stacks {
+        let kept: Vec<&str> = stack
+            .branches
+            .iter()
+            .map(String::as_str)
+            .filter(|b| !names.contains(b))
+            .collect();
+        if !kept.is_empty() && kept.len() < stack.branches.len() {
+            let msg = format!(
+                "Only part of the stack {stack} is picked, {} will stay.",
+                kept.join(", ")
+            );
+            eprintln!("{} {}", Emoji("⚠️", "!"), style(msg).yellow());
+        }
+    }
+}
+
+/// Add to the protected names the local branches whose branches on the server are protected
+/// by its hosting, like by the branch policies of Azure Repos.
+fn add_server_protected(
+    repo: &Repository,
+    hostings: &mut Hostings,
+    branches: &[BranchInfo],
+    protected: &mut Vec<String>,
+) {
+    let mut by_remote: HashMap<String, Vec<ProtectedRef>> = HashMap::new();
+    for branch in branches {
+        if is_protected(protected, &branch.name) {
+            continue;
+        }
+        let Some(server) = get_server_branch(repo, branch) else {
+            continue;
+        };
+        if !by_remote.contains_key(&server.remote) {
+            let refs = hostings.find_protected(&server.remote);
+            by_remote.insert(server.remote.clone(), refs);
+        }
+        if by_remote[&server.remote]
+            .iter()
+            .any(|r| r.matches(&server.name))
+        {
+            protected.push(branch.name.clone());
+        }
+    }
+}
+
+/// Add the branches which the protection command protects, by exiting with non-zero status.
+fn add_command_protected(
+    repo: &Repository,
+    settings: &Config,
+    branches: &[BranchInfo],
+    protected: &mut Vec<String>,
+) -> Result<()> {
+    let Ok(command) = settings.get_string(PROTECT_COMMAND) else {
+        return Ok(());
+    };
+    let mut count = 0;
+    for branch in branches.iter().filter(|b| !b.is_head) {
+        if is_protected(protected, &branch.name) {
+            continue;
+        }
+        let status = run_protect_command(repo, &command, branch)
+            .wrap_err_with(|| format!("Cannot run {PROTECT_COMMAND}"))?;
+        if !status.success() {
+            protected.push(branch.name.clone());
+            count += 1;
+        }
+    }
+    if count > 0 {
+        let msg = format!("{count} branches are protected by {PROTECT_COMMAND}.");
+        eprintln!("{}", style(msg).dim());
+    }
+    Ok(())
+}
+
+/// Why each branch is offered or not, for --explain.
+struct Explanation {
+    /// The branches, in the order they were listed, with what picked each one, or why it
+    /// was left out.
+    entries: Vec<(String, Result<Vec<String>, String>)>,
+}
+
+impl Explanation {
+    fn new(branches: &[BranchInfo]) -> Self {
+        Self {
+            entries: branches
+                .iter()
+                .map(|b| (b.name.clone(), Ok(Vec::new())))
+                .collect(),
+        }
+    }
+
+    fn pick(&mut self, name: &str, reason: String) {
+        if let Some((_, Ok(reasons))) = self.entries.iter_mut().find(|(n, _)| n == name) {
+            reasons.push(reason);
+        }
+    }
+
+    /// Record why the branch was left out, unless it already was.
+    fn leave_out(&mut self, name: &str, reason: String) {
+        if let Some((_, result)) = self.entries.iter_mut().find(|(n, _)| n == name) {
+            if result.is_ok() {
+                *result = Err(reason);
+            }
+        }
+    }
+
+    /// Record why the branches which are not in `kept` anymore were left out, and what
+    /// picked the others, if it's worth telling.
+    fn update(&mut self, kept: &[BranchInfo], picked: Option<&str>, left_out: &str) {
+        for (name, result) in &mut self.entries {
+            match (result, kept.iter().any(|b| &b.name == name)) {
+                (Ok(reasons), true) => reasons.extend(picked.map(String::from)),
+                (result @ Ok(_), false) => *result = Err(left_out.to_string()),
+                (Err(_), _) => {}
+            }
+        }
+    }
+
+    fn print(&self, unlisted: &[(String, &str)]) {
+        let width = self
+            .entries
+            .iter()
+            .map(|(n, _)| n.chars().count())
+            .chain(unlisted.iter().map(|(n, _)| n.chars().count()))
+            .max()
+            .unwrap_or(0);
+        for (name, result) in &self.entries {
+            match result {
+                Ok(reasons) if reasons.is_empty() => {
+                    println!("{name:<width$} {}", style("offered").green())
+                }
+                Ok(reasons) => println!(
+                    "{name:<width$} {}: {}",
+                    style("offered").green(),
+                    reasons.join("; ")
+                ),
+                Err(reason) => println!(
+                    "{name:<width$} {}",
+                    style(format!("left out: {reason}")).dim()
+                ),
+            }
+        }
+        for (name, reason) in unlisted {
+            println!(
+                "{name:<width$} {}",
+                style(format!("left out: {reason}")).dim()
+            );
+        }
+    }
+}
+
+/// The globs of --exclude-file, if it is given.
+fn get_excluded_patterns(args: &DeleteArgs) -> Result<Vec<String>> {
+    match &args.exclude_file {
+        Some(path) => read_patterns(path),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Why the branch is never offered, if it is not.
+fn get_exclusion(
+    branch: &BranchInfo,
+    protected: &[String],
+    switch_target: Option<&str>,
+    args: &DeleteArgs,
+) -> Option<String> {
+    if branch.is_head {
+        return match (switch_target, args.switch) {
+            (Some(_), _) => None,
+            (None, true) if is_protected(protected, &branch.name) => {
+                Some("the current branch, and protected".into())
+            }
+            (None, true) => Some("the current branch, which cannot be switched away from".into()),
+            (None, false) => Some("the current branch, pass --switch to offer it".into()),
+        };
+    }
+    if let Some(path) = &branch.worktree {
+        return Some(format!("checked out in {}", path.display()));
+    }
+    if is_protected(protected, &branch.name) && !args.include_protected {
+        return Some("protected, pass --include-protected to offer it".into());
+    }
+    None
+}
+
+/// Leave out the branches whose pull request is still open, as their work is in review.
+fn skip_open_pull_requests(hostings: &mut Hostings, branches: &mut Vec<BranchInfo>) {
+    let pull_requests = hostings.find_pull_requests(branches);
+    branches.retain(|b| match pull_requests.get(&b.name).and_then(Option::as_ref) {
+        Some(pr) if pr.state == ReviewState::Open => {
+            let msg = format!(
+                "Skipping {}, its pull request #{} is open. Pass --force-open-pr to delete it anyway.",
+                b.name, pr.number
+            );
+            eprintln!("{} {}", Emoji("⚠️", "!"), style(msg).yellow());
+            false
+        }
+        _ => true,
+    });
+}
+
+/// Show the branches to delete, with the size of their work not in the base.
+/// The branches of a stack come under a header with the whole stack.
+fn show_list_of_branches(repo: &Repository, items: &[Item], base: Option<Oid>, stacks: &[Stack]) {
+    let mut lines = Vec::new();
+    let mut current_stack = None;
+    for i in items {
+        let b = &i.branch;
+        let stack = stacks.iter().find(|s| s.contains(&b.name));
+        if let Some(stack) = stack.filter(|s| Some(*s) != current_stack) {
+            lines.push(format!(" {}", style(format!("Stack {stack}:")).dim()));
+        }
+        current_stack = stack;
+        let indent = if stack.is_some() { "   " } else { " " };
+        let name = if i.risky {
+            style(&b.name).red().bold().to_string()
+        } else {
+            b.name.clone()
+        };
+        let mut line = match (&b.upstream, i.widths.remote) {
+            (Some(upstream), 0) => format!("{indent}{name} ({upstream})"),
+            (Some(upstream), _) => format!(
+                "{indent}{name} ({}/{})",
+                style(&upstream.remote).cyan(),
+                upstream.name
+            ),
+            (None, _) => format!("{indent}{name}"),
+        };
+        if let Some(stat) = base.and_then(|id| get_diff_stat(repo, b.tip, id).ok()) {
+            line.push_str(&format!(" {}", style(stat).dim()));
+        }
+        if i.reviewed {
+            let pr = i.pull_request.as_ref();
+            line.push_str(&format!(" {}", style_pull_request(pr, 0)));
+            if let Some(pr) = pr {
+                line.push_str(&format!(" {}", style(&pr.title).dim()));
+            }
+        }
+        if let (Some(key), Some(state)) = (&i.issue, i.issue_state) {
+            line.push_str(&format!(" {}", style(format!("{key} {state}")).dim()));
+        }
+        lines.push(line);
+    }
+    eprintln!("{}", lines.join("\n"));
+}
+
+/// Tell which servers the upstream branches are deleted on, if they are on several remotes.
+fn tell_servers(repo: &Repository, items: &[Item]) {
+    let mut remotes: Vec<(&str, usize)> = Vec::new();
+    for upstream in items.iter().filter_map(|i| i.branch.upstream.as_ref()) {
+        match remotes.iter_mut().find(|(r, _)| *r == upstream.remote) {
+            Some((_, count)) => *count += 1,
+            None => remotes.push((&upstream.remote, 1)),
+        }
+    }
+    if remotes.len() < 2 {
+        return;
+    }
+    let servers: Vec<String> = remotes
+        .iter()
+        .map(|(remote, count)| {
+            // Like "example.com/me/app", without the credentials which the URL may have
+            let url = repo.find_remote(remote).ok().and_then(|r| {
+                let url = r.pushurl().or(r.url())?;
+                Some(
+                    parse_remote_url(url)
+                        .map_or(url.to_string(), |u| format!("{}/{}", u.host, u.path)),
+                )
+            });
+            match url {
+                Some(url) => format!("{count} on {remote} ({url})"),
+                None => format!("{count} on {remote}"),
+            }
+        })
+        .collect();
+    let msg = format!(
+        "The upstream branches are deleted on {} remotes: {}.",
+        remotes.len(),
+        servers.join(", ")
+    );
+    eprintln!("{}", style(msg).dim());
+}
+
+pub fn get_remote_callbacks(repo: &Repository) -> Result<RemoteCallbacks<'static>> {
+    get_push_callbacks(repo, Rejections::default())
+}
+
+/// Like `get_remote_callbacks`, recording the refs which the server refuses to update in
+/// `rejections`, as libgit2 tells them only there.
+fn get_push_callbacks(
+    repo: &Repository,
+    rejections: Rejections,
+) -> Result<RemoteCallbacks<'static>> {
+    let mut remote_callback = RemoteCallbacks::new();
+    let git_config = repo.config()?;
+    let mut credential_handler = CredentialHandler::new(git_config);
+    // Asked first, for the helpers which only Git can run, like the Windows Credential Manager
+    let git_credentials = Rc::new(RefCell::new(GitCredentials::new(repo)));
+    let filler = Rc::clone(&git_credentials);
+    let mut attempts = SshAttempts::default();
+    let mut reported = false;
+    remote_callback.credentials(move |url, username, allowed| {
+        if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) {
+            if let Some((user, password)) = filler.borrow_mut().fill(url, username) {
+                let msg = format!(
+                    "Try authenticating as \"{user}\" for {url}, with Git's credentials..."
+                );
+                eprintln!("  {}", style(msg).dim());
+                return Cred::userpass_plaintext(&user, &password);
+            }
+        }
+        let msg = if let Some(name) = username {
+            format!(
+                "Try authenticating with \"{}\" username for {}...",
+                name, url
+            )
+        } else {
+            format!("Try authenticating for {}, without username...", url)
+        };
+        eprintln!("  {}", style(msg).dim());
+        if allowed.contains(CredentialType::SSH_KEY) {
+            attempts.record(url, username);
+        }
+        let result = credential_handler.try_next_credential(url, username, allowed);
+        if result.is_err() && attempts.offers > 0 {
+            // Explained once, the next branches fail the same way
+            if !reported {
+                report_ssh_failure(&attempts);
+                reported = true;
+            }
+            return Err(git2::Error::from_str(
+                "the server refused all the SSH credentials, see above",
+            ));
+        }
+        result
+    });
+    let tls = TlsSettings::read(&repo.config()?);
+    if let Some(path) = &tls.ca_info {
+        if let Err(e) = use_ca_file(path) {
+            let msg = format!(
+                "Cannot trust the CAs of {SSL_CA_INFO} ({}): {}. Pass --use-git-cli if pushes fail.",
+                path.display(),
+                e.message()
+            );
+            eprintln!("{} {}", Emoji("⚠️", "!"), style(msg).yellow());
+        }
+    }
+    if let Some(setting) = tls.no_verify {
+        let mut warned = false;
+        remote_callback.certificate_check(move |_, host| {
+            if !warned {
+                let msg = format!(
+                    "Not verifying the TLS certificate of {host}, as {setting} says! Anyone on the network could read and change what is pushed."
+                );
+                eprintln!("{} {}", Emoji("🔓", "!!"), style(msg).red().bold());
+                warned = true;
+            }
+            Ok(CertificateCheckStatus::CertificateOk)
+        });
+    }
+    remote_callback.push_update_reference(move |refname, status| {
+        rejections.record(refname, status);
+        if status.is_none() {
+            git_credentials.borrow_mut().approve();
+        }
+        Ok(())
+    });
+    Ok(remote_callback)
+}
+
+/// Tell what was tried to authenticate over SSH, and how it could be fixed.
+fn report_ssh_failure(attempts: &SshAttempts) {
+    let ssh_dir = dirs::home_dir().unwrap_or_default().join(".ssh");
+    let diagnosis = diagnose(attempts, probe_agent(), &find_key_files(&ssh_dir));
+    let msg = "Cannot authenticate over SSH, nothing which was offered is accepted:";
+    eprintln!("{} {}", Emoji("⚠️", "!"), style(msg).yellow());
+    for fact in &diagnosis.facts {
+        eprintln!("  {fact}");
+    }
+    for hint in diagnosis
+        .hints
+        .iter()
+        .map(String::as_str)
+        .chain(["Or pass --use-git-cli, to push with the ssh program and all its config"])
+    {
+        eprintln!("  {}", style(format!("- {hint}")).dim());
+    }
+}
+
+/// Message of the error, without the class and code of libgit2 errors.
+fn get_error_detail(e: &eyre::Report) -> String {
+    match e.downcast_ref::<git2::Error>() {
+        Some(e) => e.message().to_string(),
+        None => e.to_string(),
+    }
+}
+
+pub fn warn_failure(e: &eyre::Report, msg: &str) {
+    eprintln!("  {}", style(get_error_detail(e)).dim());
+    eprintln!("{} {}", Emoji("⚠️", "!"), style(msg).yellow());
+}
+
+/// Run the pre-delete hook, telling if the branch may be deleted.
+fn allowed_by_hook(
+    repo: &Repository,
+    command: Option<&str>,
+    branch: &BranchInfo,
+    delete_upstream: bool,
+) -> bool {
+    let Some(command) = command else {
+        return true;
+    };
+    match run_hook(repo, command, branch, delete_upstream, None) {
+        Ok(status) if status.success() => true,
+        Ok(status) => {
+            let msg = format!("Pre-delete hook vetoed {} ({status})", branch.name);
+            eprintln!("{} {}", Emoji("⚠️", "!"), style(msg).yellow());
+            false
+        }
+        Err(e) => {
+            eprintln!("  {}", style(e).dim());
+            let msg = format!("Failed to run pre-delete hook, skipping {}", branch.name);
+            eprintln!("{} {}", Emoji("⚠️", "!"), style(msg).yellow());
+            false
+        }
+    }
+}
+
+fn run_post_hook(
+    repo: &Repository,
+    command: Option<&str>,
+    branch: &BranchInfo,
+    delete_upstream: bool,
+    state: UpstreamState,
+) {
+    let Some(command) = command else {
+        return;
+    };
+    match run_hook(repo, command, branch, delete_upstream, Some(state)) {
+        Ok(status) if status.success() => {}
+        Ok(status) => {
+            let msg = format!("Post-delete hook failed for {} ({status})", branch.name);
+            eprintln!("{} {}", Emoji("⚠️", "!"), style(msg).yellow());
+        }
+        Err(e) => {
+            eprintln!("  {}", style(e).dim());
+            eprintln!(
+                "{} {}",
+                Emoji("⚠️", "!"),
+                style("Failed to run post-delete hook").yellow()
+            );
+        }
+    }
+}
+
+/// Get the policy file given in command line or in config.
+/// A path from config is relative to the top of the working tree.
+fn get_policy_path(
+    repo: &Repository,
+    settings: &Config,
+    args: &DeleteArgs,
+) -> Result<Option<PathBuf>> {
+    if let Some(path) = &args.policy {
+        return Ok(Some(path.clone()));
+    }
+    let path = match settings.get_path(POLICY) {
+        Ok(p) => p,
+        Err(_) => return Ok(None),
+    };
+    Ok(Some(repo.workdir().unwrap_or(repo.path()).join(path)))
+}
+
+/// Get the age under which branches are kept, given in command line or in config. `None`
+/// when it is zero.
+fn get_keep_active(settings: &Config, args: &DeleteArgs) -> Result<Option<HumanDuration>> {
+    let duration = match args.keep_active {
+        Some(duration) => duration,
+        None => match settings.get_string(KEEP_ACTIVE) {
+            Ok(s) => s.parse()?,
+            Err(_) => return Ok(None),
+        },
+    };
+    Ok((duration.as_secs() > 0).then_some(duration))
+}
+
+/// Get the backend given in command line or in config.
+fn get_backend_kind(settings: &Config, args: &DeleteArgs) -> Result<BackendKind> {
+    if args.use_git_cli {
+        return Ok(BackendKind::GitCli);
+    }
+    if let Some(kind) = args.backend {
+        return Ok(kind);
+    }
+    match settings.get_string(BACKEND) {
+        Ok(s) => s.parse(),
+        Err(_) => Ok(BackendKind::default()),
+    }
+}
+
+/// Read the picker keys which user remapped in the settings.
+pub fn get_key_map(settings: &Config) -> Result<KeyMap> {
+    let mut keys = KeyMap::default();
+    let fields = [
+        (KEYS_TOGGLE, &mut keys.toggle),
+        (KEYS_CONFIRM, &mut keys.confirm),
+        (KEYS_SEARCH, &mut keys.search),
+        (KEYS_SORT, &mut keys.sort),
+        (KEYS_PREVIEW, &mut keys.preview),
+        (KEYS_DETAILS, &mut keys.details),
+    ];
+    for (setting, key) in fields {
+        if let Ok(value) = settings.get_string(setting) {
+            *key = value.parse().map_err(|e| eyre!("Invalid {setting}: {e}"))?;
+        }
+    }
+    Ok(keys)
+}
+
+/// Get the base to compare branches with: given in command line or config, else the default
+/// branch. Returns its name and commit.
+pub fn get_base(
+    repo: &Repository,
+    settings: &Config,
+    base: Option<&str>,
+) -> Result<Option<(String, Oid)>> {
+    let name = match base {
+        Some(name) => name.to_string(),
+        None => match settings.get_string(BASE) {
+            Ok(name) => name,
+            Err(_) => {
+                return Ok(find_default_branch(repo)
+                    .and_then(|name| resolve_commit(repo, &name).ok().map(|id| (name, id))))
+            }
+        },
+    };
+    let id = resolve_commit(repo, &name)
+        .map_err(|e| eyre!("Cannot find the base \"{name}\": {}", e.message()))?;
+    Ok(Some((name, id)))
+}
+
+/// Fetch the history which the shallow clone lacks, to tell whether the branches are merged
+/// into the base.
+fn deepen_history(repo: &Repository, settings: &Config, args: &DeleteArgs) -> Result<()> {
+    let Some((base_name, _)) = get_base(repo, settings, args.base.as_deref())? else {
+        return Ok(());
+    };
+    let tips: Vec<Oid> = list_local_branches(repo)?.iter().map(|b| b.tip).collect();
+    deepen_until_known(repo.path(), &tips, &base_name, |depth, count| {
+        let msg = format!(
+            "Deepening the shallow clone by {depth} commits, to tell whether {count} branches are merged into {base_name}..."
+        );
+        eprintln!("{}", style(msg).dim());
+    })
+}
+
+/// Find the branch to switch to, for allowing the deletion of the current one.
+fn get_switch_target(
+    repo: &Repository,
+    protected: &[String],
+    current: &str,
+) -> Result<Option<String>> {
+    if is_protected(protected, current) {
+        return Ok(None);
+    }
+    let warn = |msg: String| eprintln!("{} {}", Emoji("⚠️", "!"), style(msg).yellow());
+    if !is_worktree_clean(repo)? {
+        warn(format!(
+            "The working tree has changes, so {current} cannot be switched away from and is not offered."
+        ));
+        return Ok(None);
+    }
+    let target = find_default_branch(repo);
+    let Some(target) = target else {
+        warn(format!(
+            "There is no default branch to switch to, so {current} is not offered."
+        ));
+        return Ok(None);
+    };
+    // Git doesn't let a branch be checked out in two worktrees
+    if let Some(path) = get_worktree_branches(repo).get(&target) {
+        warn(format!(
+            "{target} is checked out in {}, so {current} cannot be switched away from and is not offered.",
+            path.display()
+        ));
+        return Ok(None);
+    }
+    Ok(Some(target))
+}
+
+/// Check out the target branch if the current branch is going to be deleted.
+fn switch_if_needed(
+    repo: &Repository,
+    branches: &[BranchInfo],
+    target: Option<&str>,
+) -> Result<()> {
+    let Some(target) = target.filter(|_| branches.iter().any(|b| b.is_head)) else {
+        return Ok(());
+    };
+    switch_to_branch(repo, target)?;
+    eprintln!("Switched to branch {target}.");
+    Ok(())
+}
+
+/// Result of deleting the selected branches.
+#[derive(Default)]
+struct Outcome {
+    deleted: Vec<Entry>,
+    /// Branches which we failed to delete.
+    failed: Vec<String>,
+    /// Branches which the pre-delete hook refused.
+    vetoed: Vec<String>,
+    /// Branches which were deleted already when we came to them.
+    gone: Vec<String>,
+}
+
+impl Outcome {
+    fn has_failure(&self) -> bool {
+        !self.failed.is_empty()
+            || self
+                .deleted
+                .iter()
+                .any(|e| e.upstream_state == UpstreamState::Failed)
+    }
+
+    fn exit_code(&self) -> ExitCode {
+        if self.has_failure() {
+            ExitCode::from(EXIT_PARTIAL_FAILURE)
+        } else {
+            ExitCode::SUCCESS
+        }
+    }
+}
+
+/// How the upstream branches of the deleted branches are deleted.
+struct UpstreamDeletion<'a> {
+    enabled: bool,
+    /// Those which are kept anyway, see `get_kept_upstreams`.
+    kept: &'a [Upstream],
+    /// How many are deleted by each push.
+    batch_size: usize,
+}
+
+fn delete_branches(
+    repo: &Repository,
+    settings: &Config,
+    backend: &mut dyn Backend,
+    hostings: &mut Hostings,
+    branches: Vec<BranchInfo>,
+    upstreams: &UpstreamDeletion,
+    events: &Events,
+) -> Result<Outcome> {
+    let pre_hook = settings.get_string(PRE_DELETE_HOOK).ok();
+    let post_hook = settings.get_string(POST_DELETE_HOOK).ok();
+    let mut outcome = Outcome::default();
+    let selected: Vec<&str> = branches.iter().map(|b| b.name.as_str()).collect();
+    events.emit(
+        "selected",
+        json!({ "branches": selected, "delete_upstream": upstreams.enabled }),
+    );
+    let chunk_count = branches.len().div_ceil(upstreams.batch_size);
+    // The local branches of a chunk are deleted first, then their upstream branches together
+    for (i, chunk) in branches.chunks(upstreams.batch_size).enumerate() {
+        let mut deleted: Vec<(&BranchInfo, Option<&Upstream>)> = Vec::new();
+        for branch in chunk {
+            let upstream = branch
+                .upstream
+                .as_ref()
+                .filter(|u| upstreams.enabled && !upstreams.kept.contains(u));
+            if !allowed_by_hook(repo, pre_hook.as_deref(), branch, upstream.is_some()) {
+                let fields = json!({ "name": branch.name, "reason": "vetoed" });
+                events.emit("failed", fields);
+                outcome.vetoed.push(branch.name.clone());
+                continue;
+            }
+            events.emit(
+                "deleting",
+                json!({ "name": branch.name, "upstream": upstream.map(|u| u.to_string()) }),
+            );
+            if let Err(e) = backend.delete_local_branch(&branch.name) {
+                // Like by another run, or by a tool while we were asking which to delete
+                if repo.find_branch(&branch.name, BranchType::Local).is_err() {
+                    let msg = format!("{} is already gone", branch.name);
+                    eprintln!("{}", style(msg).dim());
+                    events.emit("gone", json!({ "name": branch.name }));
+                    outcome.gone.push(branch.name.clone());
+                    continue;
+                }
+                warn_failure(&e, &format!("Failed to delete branch {}", branch.name));
+                let fields = json!({
+                    "name": branch.name,
+                    "reason": "error",
+                    "message": get_error_detail(&e),
+                });
+                events.emit("failed", fields);
+                outcome.failed.push(branch.name.clone());
+                continue;
+            }
+            deleted.push((branch, upstream));
+        }
+        let to_push: Vec<&Upstream> = deleted.iter().filter_map(|(_, u)| *u).collect();
+        let chunk = (upstreams.batch_size > 1).then_some((i + 1, chunk_count));
+        let failed = delete_upstreams(backend, &to_push, chunk);
+        for (branch, upstream) in deleted {
+            let upstream_state = match upstream {
+                None => UpstreamState::Kept,
+                Some(upstream) => match failed.iter().find(|(u, _)| *u == upstream) {
+                    None => UpstreamState::Deleted,
+                    Some((_, e)) => {
+                        delete_with_hosting(repo, hostings, upstream, e, chunk.is_some())
+                    }
+                },
+            };
+            let entry = Entry::new(branch, now(), upstream_state);
+            // As soon as it's done, for getting it back from the scrollback whatever happens next
+            events.print(&describe_deletion(&entry));
+            events.emit("deleted", notify::describe_entry(&entry));
+            journal::append(repo, &entry)?;
+            run_post_hook(
+                repo,
+                post_hook.as_deref(),
+                branch,
+                upstream.is_some(),
+                upstream_state,
+            );
+            outcome.deleted.push(entry);
+        }
+    }
+    Ok(outcome)
+}
+
+/// Delete the upstream branches, with one push per remote, telling the progress if they are
+/// deleted in chunks, with the number of the chunk and the count of them. Returns those whose
+/// push failed, with its error.
+fn delete_upstreams<'u>(
+    backend: &mut dyn Backend,
+    upstreams: &[&'u Upstream],
+    chunk: Option<(usize, usize)>,
+) -> Vec<(&'u Upstream, eyre::Report)> {
+    let mut remotes: Vec<&str> = Vec::new();
+    for upstream in upstreams {
+        if !remotes.contains(&upstream.remote.as_str()) {
+            remotes.push(&upstream.remote);
+        }
+    }
+    let mut failed = Vec::new();
+    for remote in remotes {
+        let batch: Vec<&Upstream> = upstreams
+            .iter()
+            .copied()
+            .filter(|u| u.remote == remote)
+            .collect();
+        if let Some((number, count)) = chunk {
+            let msg = format!(
+                "Deleting {} upstream branches on {remote} (chunk {number} of {count})...",
+                batch.len()
+            );
+            eprintln!("{}", style(msg).dim());
+        }
+        if let Err(e) = backend.delete_upstream_branches(&batch) {
+            if let Some((number, _)) = chunk {
+                let msg = format!("Failed to delete the upstream branches of chunk {number}");
+                warn_failure(&e, &msg);
+            }
+            // Each is then tried with the hosting API. If the server refused only some of them,
+            // the others are deleted.
+            let detail = get_error_detail(&e);
+            let rejected = e.downcast_ref::<PushRejected>();
+            failed.extend(batch.into_iter().filter_map(|u| {
+                match rejected.map(|r| r.find_reason(&u.name)) {
+                    Some(None) => None,
+                    Some(Some(reason)) => Some((u, eyre!("{reason}"))),
+                    None => Some((u, eyre!("{detail}"))),
+                }
+            }));
+        }
+    }
+    failed
+}
+
+/// Delete the upstream branch with the API of its hosting, after the push was refused. The
+/// failure of the push is told if there is no hosting, unless it was already.
+fn delete_with_hosting(
+    repo: &Repository,
+    hostings: &mut Hostings,
+    upstream: &Upstream,
+    push_error: &eyre::Report,
+    reported: bool,
+) -> UpstreamState {
+    let failure = format!("Failed to delete upstream branch {upstream}");
+    let Some(hosting) = hostings.get_for_deleting(&upstream.remote) else {
+        if !reported {
+            warn_failure(push_error, &failure);
+        }
+        return UpstreamState::Failed;
+    };
+    let msg = format!(
+        "The push failed ({}), deleting {upstream} with the API of {} instead...",
+        get_error_detail(push_error),
+        hosting.kind().title()
+    );
+    eprintln!("  {}", style(msg).dim());
+    let deleted = hosting
+        .delete_branch(&upstream.name)
+        .and_then(|()| Ok(delete_tracking_branch(repo, upstream)?));
+    match deleted {
+        Ok(()) => UpstreamState::Deleted,
+        Err(e) => {
+            warn_failure(&e, &failure);
+            UpstreamState::Failed
+        }
+    }
+}
+
+fn get_undo_script_path(
+    repo: &Repository,
+    settings: &Config,
+    args: &DeleteArgs,
+) -> Result<Option<PathBuf>> {
+    if let Some(path) = &args.undo_script {
+        return Ok(Some(path.clone()));
+    }
+    let path = match settings.get_path(UNDO_SCRIPT) {
+        Ok(p) => p,
+        Err(_) => return Ok(None),
+    };
+    Ok(Some(repo.workdir().unwrap_or(repo.path()).join(path)))
+}
+
+/// Write the script restoring the deleted branches, if one is asked for.
+fn write_undo_script(
+    repo: &Repository,
+    settings: &Config,
+    outcome: &Outcome,
+    args: &DeleteArgs,
+) -> Result<()> {
+    let Some(path) = get_undo_script_path(repo, settings, args)? else {
+        return Ok(());
+    };
+    if outcome.deleted.is_empty() {
+        return Ok(());
+    }
+    fs::write(&path, build_undo_script(&outcome.deleted))
+        .wrap_err_with(|| format!("Cannot write {}", path.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755))?;
+    }
+    let msg = format!(
+        "Wrote the commands to restore the deleted branches to {}.",
+        path.display()
+    );
+    eprintln!("{}", style(msg).dim());
+    Ok(())
+}
+
+/// Remove the deletions older than the retention period from the journal, if one is set.
+fn purge_journal(repo: &Repository, settings: &Config) -> Result<()> {
+    let Some(retention) = get_retention(settings)? else {
+        return Ok(());
+    };
+    let removed = journal::purge(repo, retention, now())?;
+    if removed > 0 {
+        let msg = format!(
+            "Removed {removed} deletions older than {retention} from the journal (see {RETENTION})."
+        );
+        eprintln!("{}", style(msg).dim());
+    }
+    Ok(())
+}
+
+/// Record the deletions in the log shared with the team, and push it, if
+/// `del-branches.sharedLog` names its remote.
+fn share_deletions(repo: &Repository, settings: &Config, outcome: &Outcome) -> Result<()> {
+    let Ok(remote) = settings.get_string(SHARED_LOG) else {
+        return Ok(());
+    };
+    if outcome.deleted.is_empty() {
+        return Ok(());
+    }
+    let mut fetch_options = FetchOptions::new();
+    fetch_options.remote_callbacks(get_remote_callbacks(repo)?);
+    // Still recorded, for the next run to push
+    let fetched = sharedlog::fetch(repo, &remote, &mut fetch_options);
+    sharedlog::record(repo, &outcome.deleted)?;
+    let mut push_options = PushOptions::new();
+    push_options.remote_callbacks(get_remote_callbacks(repo)?);
+    match fetched.and_then(|()| sharedlog::push(repo, &remote, &mut push_options)) {
+        Ok(()) => {
+            let msg = format!("Recorded the deletions in {LOG_REF} on {remote}.");
+            eprintln!("{}", style(msg).dim());
+        }
+        Err(e) => {
+            let msg = format!(
+                "Cannot share the deletions on {remote}: {}. They are pushed by the next run.",
+                e.message()
+            );
+            eprintln!("{} {}", Emoji("⚠️", "!"), style(msg).yellow());
+        }
+    }
+    Ok(())
+}
+
+/// Post the summary of the run to the webhook of the team, if one is set and anything was done.
+fn notify_team(repo: &Repository, settings: &Config, outcome: &Outcome) {
+    let Ok(url) = settings.get_string(WEBHOOK_URL) else {
+        return;
+    };
+    if outcome.deleted.is_empty() && outcome.failed.is_empty() {
+        return;
+    }
+    let message = notify::build_message(repo, &outcome.deleted, &outcome.failed, &outcome.vetoed);
+    if let Err(e) = notify::post(&url, &message) {
+        eprintln!("{} {}", Emoji("⚠️", "!"), style(e).yellow());
+    }
+}
+
+/// Line telling the deletion, with the tip like `git branch -d` does, so that
+/// `git branch <name> <sha>` can bring it back.
+fn describe_deletion(entry: &Entry) -> String {
+    let short_sha = &entry.tip.to_string()[..7];
+    match &entry.upstream {
+        Some(upstream) if entry.upstream_state != UpstreamState::Kept => format!(
+            "deleted {} (was {short_sha}, upstream {upstream} {})",
+            entry.name, entry.upstream_state
+        ),
+        _ => format!("deleted {} (was {short_sha})", entry.name),
+    }
+}
+
+/// Print what else happened and the counts, on stdout, for the log of unattended runs,
+/// after the deletions which were told as they were done.
+fn print_summary(outcome: &Outcome, events: &Events) {
+    for name in &outcome.failed {
+        events.print(&format!("failed {name}"));
+    }
+    for name in &outcome.vetoed {
+        events.print(&format!("vetoed {name}"));
+    }
+    let gone = match outcome.gone.len() {
+        0 => String::new(),
+        count => format!(", {count} already gone"),
+    };
+    events.print(&format!(
+        "Deleted {} branches, {} failed, {} vetoed{gone}.",
+        outcome.deleted.len(),
+        outcome.failed.len(),
+        outcome.vetoed.len()
+    ));
+}
+
+/// Check the mirror remotes on which upstream branches would be deleted, as a `git push`
+/// there mirrors all the refs, which may bring them back or clash. Returns those on which the
+/// upstream branches are to be kept, `None` if cancelled.
+fn check_mirrors(
+    repo: &Repository,
+    branches: &[BranchInfo],
+    delete_upstream: bool,
+    args: &DeleteArgs,
+) -> Result<Option<Vec<String>>> {
+    if !delete_upstream || args.allow_mirror {
+        return Ok(Some(Vec::new()));
+    }
+    let mut mirrors: Vec<String> = Vec::new();
+    for upstream in branches.iter().filter_map(|b| b.upstream.as_ref()) {
+        if !mirrors.contains(&upstream.remote) && is_mirror(repo, &upstream.remote) {
+            mirrors.push(upstream.remote.clone());
+        }
+    }
+    if mirrors.is_empty() {
+        return Ok(Some(mirrors));
+    }
+    let verb = if mirrors.len() == 1 {
+        "is a mirror"
+    } else {
+        "are mirrors"
+    };
+    let msg = format!(
+        "{} {verb} (remote.<name>.mirror): a git push there mirrors all the refs, which may bring back the deleted branches or clash with the deletions.",
+        mirrors.join(", ")
+    );
+    eprintln!("{} {}", Emoji("⚠️", "!"), style(msg).yellow());
+    if args.auto || args.yes {
+        let msg = "Keeping the upstream branches there. Pass --allow-mirror to delete them.";
+        eprintln!("{}", style(msg).dim());
+        return Ok(Some(mirrors));
+    }
+    let prompt =
+        Confirm::new("Delete the upstream branches on the mirrors anyway").with_default(false);
+    Ok(get_answer(prompt.prompt())?.map(|delete| match delete {
+        true => Vec::new(),
+        false => mirrors,
+    }))
+}
+
+/// The upstream branches which are not to be deleted with their local branches: those on the
+/// mirrors, unless confirmed, and always the default branches of the remotes, which servers
+/// sometimes let be deleted. `None` if cancelled.
+fn get_kept_upstreams(
+    repo: &Repository,
+    branches: &[BranchInfo],
+    delete_upstream: bool,
+    args: &DeleteArgs,
+) -> Result<Option<Vec<Upstream>>> {
+    let Some(mirrors) = check_mirrors(repo, branches, delete_upstream, args)? else {
+        return Ok(None);
+    };
+    let mut kept = Vec::new();
+    if !delete_upstream {
+        return Ok(Some(kept));
+    }
+    let mut defaults: HashMap<&str, Option<String>> = HashMap::new();
+    for upstream in branches.iter().filter_map(|b| b.upstream.as_ref()) {
+        let remote = upstream.remote.as_str();
+        if mirrors.iter().any(|m| m == remote) {
+            kept.push(upstream.clone());
+            continue;
+        }
+        if !defaults.contains_key(remote) {
+            let callbacks = get_remote_callbacks(repo)?;
+            defaults.insert(remote, find_remote_default_branch(repo, remote, callbacks));
+        }
+        if defaults[remote].as_ref() == Some(&upstream.name) {
+            let msg = format!(
+                "Not deleting {upstream}, the default branch of {remote}, which the server would be left without."
+            );
+            eprintln!("{} {}", Emoji("⚠️", "!"), style(msg).yellow());
+            kept.push(upstream.clone());
+        }
+    }
+    Ok(Some(kept))
+}
+
+/// Tell the wrapping tools that the run is over, with its counts.
+fn emit_done(outcome: &Outcome, events: &Events) {
+    events.emit(
+        "done",
+        json!({
+            "deleted": outcome.deleted.len(),
+            "failed": outcome.failed.len(),
+            "vetoed": outcome.vetoed.len(),
+            "gone": outcome.gone.len(),
+            "partial_failure": outcome.has_failure(),
+        }),
+    );
+}
+
+/// Tell how much space the deletions made reclaimable. Also expire the reflogs keeping it
+/// and run `git gc`, if asked.
+fn reclaim_space(repo: &Repository, outcome: &Outcome, args: &DeleteArgs) -> Result<()> {
+    let tips: Vec<Oid> = outcome.deleted.iter().map(|e| e.tip).collect();
+    if tips.is_empty() {
+        return Ok(());
+    }
+    if args.expire_reflogs {
+        let names: Vec<String> = outcome.deleted.iter().map(|e| e.name.clone()).collect();
+        let commits = find_unreachable_commits(repo, &tips)?;
+        let removed = expire_reflogs(repo, &names, &commits)?;
+        let msg = format!(
+            "Removed the reflogs of the deleted branches, and {removed} entries of HEAD's reflog."
+        );
+        eprintln!("{}", style(msg).dim());
+    }
+    // The sizes of the objects which a partial clone lacks are unknown
+    let estimate = match is_partial_clone(repo) {
+        true => Ok(Reclaimable::default()),
+        false => estimate_unreachable(repo, &tips),
+    };
+    match estimate {
+        Ok(space) if space.commits > 0 => {
+            let msg = match (args.gc, args.expire_reflogs) {
+                (true, true) => format!("About {space} are unreachable now. Running git gc..."),
+                (true, false) => format!("About {space} are unreachable now. Running git gc, which keeps the objects still in reflogs..."),
+                (false, true) => format!("About {space} can be freed by git gc. Pass --gc to run it now."),
+                (false, false) => format!("About {space} can be freed by git gc, once they expire from the reflogs. Pass --gc to run it now."),
+            };
+            eprintln!("{}", style(msg).dim());
+        }
+        Ok(_) => {}
+        Err(e) => eprintln!(
+            "{}",
+            style(format!(
+                "Cannot estimate the space to free: {}",
+                e.message()
+            ))
+            .dim()
+        ),
+    }
+    if args.gc {
+        let mut cmd = process::Command::new("git");
+        set_git_env(&mut cmd, repo);
+        let status = cmd.args(["gc", "--prune=now"]).status()?;
+        if !status.success() {
+            return Err(eyre!("git gc failed ({status})"));
+        }
+    }
+    Ok(())
+}
+
+/// Delete all the offered branches, without asking anything.
+fn run_auto(
+    repo: &Repository,
+    backend: &mut dyn Backend,
+    hostings: &mut Hostings,
+    branches: Vec<BranchInfo>,
+    delete_upstream: impl Fn(&BranchInfo) -> bool,
+    switch_target: Option<&str>,
+    args: &DeleteArgs,
+) -> Result<ExitCode> {
+    let settings = hostings.settings();
+    let names: Vec<&str> = branches.iter().map(|b| b.name.as_str()).collect();
+    warn_stashes(&names, &count_stashes_by_branch(repo)?);
+    let with_upstream: Vec<BranchInfo> = branches
+        .iter()
+        .filter(|b| delete_upstream(b))
+        .cloned()
+        .collect();
+    let enabled = !with_upstream.is_empty();
+    let mut kept_upstreams =
+        get_kept_upstreams(repo, &with_upstream, enabled, args)?.unwrap_or_default();
+    kept_upstreams.extend(
+        branches
+            .iter()
+            .filter(|b| !delete_upstream(b))
+            .filter_map(|b| b.upstream.clone()),
+    );
+    switch_if_needed(repo, &branches, switch_target)?;
+    let events = Events::new(args.events);
+    let upstreams = UpstreamDeletion {
+        enabled,
+        kept: &kept_upstreams,
+        batch_size: args.batch_size.map_or(1, NonZeroUsize::get),
+    };
+    let outcome = delete_branches(
+        repo, settings, backend, hostings, branches, &upstreams, &events,
+    )?;
+    print_summary(&outcome, &events);
+    write_undo_script(repo, settings, &outcome, args)?;
+    purge_journal(repo, settings)?;
+    share_deletions(repo, settings, &outcome)?;
+    notify_team(repo, settings, &outcome);
+    reclaim_space(repo, &outcome, args)?;
+    emit_done(&outcome, &events);
+    Ok(outcome.exit_code())
+}
+
+/// Delete the branches of the spec file, with their upstream branches as it says, after
+/// showing the plan and, unless --yes, asking. Nothing is deleted if any cannot be.
+fn apply_spec(
+    repo: &Repository,
+    settings: &Config,
+    backend: &mut dyn Backend,
+    hostings: &mut Hostings,
+    path: &Path,
+    args: &DeleteArgs,
+) -> Result<ExitCode> {
+    let spec = Spec::load(path)?;
+    let branches = backend.list_local_branches()?;
+    let mut protected = get_protected_names(repo);
+    add_command_protected(repo, settings, &branches, &mut protected)?;
+    let plan = match spec.plan(&branches, &protected) {
+        Ok(plan) => plan,
+        Err(problems) => {
+            for problem in &problems {
+                eprintln!("{} {}", Emoji("⚠️", "!"), style(problem).yellow());
+            }
+            return Err(eyre!(
+                "Cannot apply {}, as {} of its branches cannot be deleted as it says. Nothing was deleted.",
+                path.display(),
+                problems.len()
+            ));
+        }
+    };
+    for name in &plan.gone {
+        eprintln!("{}", style(format!("{name} is already gone")).dim());
+    }
+    if plan.deletions.is_empty() {
+        eprintln!("No branches to delete.");
+        return Ok(ExitCode::SUCCESS);
+    }
+    let msg = format!("To delete, as {} says:", path.display());
+    eprintln!("{}", style(msg).blue());
+    for (branch, upstream) in &plan.deletions {
+        match branch.upstream.as_ref().filter(|_| *upstream) {
+            Some(u) => eprintln!("  {} and {}", branch.name, u),
+            None => eprintln!("  {}", branch.name),
+        }
+    }
+    if !args.yes {
+        let prompt = Confirm::new("Apply this plan").with_default(false);
+        let Some(true) = get_answer(prompt.prompt())? else {
+            return Ok(ExitCode::SUCCESS);
+        };
+    }
+    let with_upstream: Vec<String> = plan
+        .deletions
+        .iter()
+        .filter(|(_, upstream)| *upstream)
+        .map(|(b, _)| b.name.clone())
+        .collect();
+    let branches = plan.deletions.into_iter().map(|(b, _)| b).collect();
+    run_auto(
+        repo,
+        backend,
+        hostings,
+        branches,
+        |b| with_upstream.contains(&b.name),
+        None,
+        args,
+    )
+}
+
+/// Retry the deletions of the upstream branches which failed, from the journal. Those which
+/// the server does not have anymore are recorded as deleted.
+fn resume(
+    repo: &Repository,
+    backend: &mut dyn Backend,
+    hostings: &mut Hostings,
+) -> Result<ExitCode> {
+    let entries = journal::list_failed_upstreams(repo)?;
+    if entries.is_empty() {
+        eprintln!("No failed upstream deletions to retry.");
+        return Ok(ExitCode::SUCCESS);
+    }
+    let mut on_servers: HashMap<String, Option<Vec<String>>> = HashMap::new();
+    let mut failed = 0;
+    for entry in &entries {
+        let Some(upstream) = &entry.upstream else {
+            continue;
+        };
+        if !on_servers.contains_key(&upstream.remote) {
+            let callbacks = get_remote_callbacks(repo)?;
+            // If the server cannot tell, the deletion is retried anyway
+            let names = list_server_branches(repo, &upstream.remote, callbacks).ok();
+            on_servers.insert(upstream.remote.clone(), names);
+        }
+        let gone = on_servers[&upstream.remote]
+            .as_ref()
+            .is_some_and(|names| !names.contains(&upstream.name));
+        let upstream_state = if gone {
+            delete_tracking_branch(repo, upstream)?;
+            println!("{upstream} is gone already");
+            UpstreamState::Deleted
+        } else {
+            let state = match backend.delete_upstream_branch(upstream) {
+                Ok(()) => UpstreamState::Deleted,
+                Err(e) => delete_with_hosting(repo, hostings, upstream, &e, false),
+            };
+            if state == UpstreamState::Deleted {
+                println!("deleted {upstream} (was {})", &entry.tip.to_string()[..7]);
+            }
+            state
+        };
+        match upstream_state {
+            UpstreamState::Deleted => {
+                let retried = Entry {
+                    time: now(),
+                    upstream_state,
+                    ..entry.clone()
+                };
+                journal::append(repo, &retried)?;
+            }
+            _ => failed += 1,
+        }
+    }
+    println!(
+        "Retried {} upstream deletions, {failed} failed.",
+        entries.len()
+    );
+    match failed {
+        0 => Ok(ExitCode::SUCCESS),
+        _ => Ok(ExitCode::from(EXIT_PARTIAL_FAILURE)),
+    }
+}
+
+/// Pick branches of the remote at the URL, which has no clone, and delete them there.
+fn run_on_url(url: &str, excluded: &[String]) -> Result<ExitCode> {
+    let remote = UrlRemote::new(url)?;
+    let (branches, protected) = remote.list_branches(get_remote_callbacks(remote.repo())?)?;
+    let deletable: Vec<&RemoteBranch> = branches
+        .iter()
+        .filter(|b| !is_protected(&protected, &b.name) && !matches_any(excluded, &b.name))
+        .collect();
+    if deletable.is_empty() {
+        eprintln!("No branches on {url} to delete.");
+        return Ok(ExitCode::SUCCESS);
+    }
+    let items: Vec<String> = deletable
+        .iter()
+        .map(|b| format!("{} ({})", b.name, &b.tip.to_string()[..7]))
+        .collect();
+    let msg = format!("Select branches to delete on {url}");
+    let prompt = MultiSelect::new(&msg, items);
+    let Some(picked) = get_answer(prompt.raw_prompt())? else {
+        return Ok(ExitCode::SUCCESS);
+    };
+    if picked.is_empty() {
+        return Ok(ExitCode::SUCCESS);
+    }
+    let msg = format!("Delete these {} branches on {url}", picked.len());
+    if get_answer(Confirm::new(&msg).with_default(false).prompt())? != Some(true) {
+        return Ok(ExitCode::SUCCESS);
+    }
+    let mut push_options = PushOptions::new();
+    push_options.remote_callbacks(get_remote_callbacks(remote.repo())?);
+    let mut failed = 0;
+    for option in &picked {
+        let branch = deletable[option.index];
+        let short_sha = &branch.tip.to_string()[..7];
+        match remote.delete_branch(&branch.name, &mut push_options) {
+            Ok(()) => println!("deleted {} (was {short_sha})", branch.name),
+            Err(e) => {
+                warn_failure(
+                    &e.into(),
+                    &format!("Failed to delete branch {}", branch.name),
+                );
+                failed += 1;
+            }
+        }
+    }
+    println!(
+        "Deleted {} branches, {failed} failed.",
+        picked.len() - failed
+    );
+    match failed {
+        0 => Ok(ExitCode::SUCCESS),
+        _ => Ok(ExitCode::from(EXIT_PARTIAL_FAILURE)),
+    }
+}
+
+pub fn run(args: &DeleteArgs, no_config: bool) -> Result<ExitCode> {
+    if let Some(url) = &args.url {
+        return run_on_url(url, &get_excluded_patterns(args)?);
+    }
+    let repo = open_repository()?;
+    if !args.auto {
+        update::notify_new_version(&get_settings(&repo, no_config)?);
+    }
+    let code = run_in(&repo, args, no_config)?;
+    if !args.recurse_submodules {
+        return Ok(code);
+    }
+    let mut failed = code != ExitCode::SUCCESS;
+    for (path, sub_repo) in open_submodules(&repo)? {
+        eprintln!();
+        eprintln!(
+            "{} {}",
+            style("Submodule").bold(),
+            style(path.display()).cyan().bold()
+        );
+        match run_in(&sub_repo, args, no_config) {
+            Ok(code) => failed |= code != ExitCode::SUCCESS,
+            Err(e) => {
+                let msg = format!("Failed to clean up submodule {}", path.display());
+                warn_failure(&e, &msg);
+                failed = true;
+            }
+        }
+    }
+    match failed {
+        true => Ok(ExitCode::from(EXIT_PARTIAL_FAILURE)),
+        false => Ok(ExitCode::SUCCESS),
+    }
+}
+
+/// Take the lock of the repository, so that no other cleanup runs at the same time.
+fn take_lock(repo: &Repository, force: bool) -> Result<Lock> {
+    let lock = match force {
+        true => Lock::force(repo)?,
+        false => Lock::acquire(repo)?,
+    };
+    if let Some(lock) = lock {
+        return Ok(lock);
+    }
+    let by = match get_holder(repo) {
+        Some(holder) => {
+            let on = holder.host.map(|h| format!(" on {h}")).unwrap_or_default();
+            format!(
+                " (process {}{on}, started {} ago)",
+                holder.pid,
+                format_age(now() - holder.time)
+            )
+        }
+        None => String::new(),
+    };
+    Err(eyre!(
+        "Another cleanup is in progress in this repository{by}. If it is not, like after a crash, pass --force-lock."
+    ))
+}
+
+/// Run the whole flow in one repository.
+fn run_in(repo: &Repository, args: &DeleteArgs, no_config: bool) -> Result<ExitCode> {
+    let _lock = take_lock(repo, args.force_lock)?;
+    let settings = get_settings(repo, no_config)?;
+    let deepened;
+    let repo = match args.deepen && repo.is_shallow() {
+        true => {
+            deepen_history(repo, &settings, args)?;
+            deepened = reopen(repo)?;
+            &deepened
+        }
+        false => repo,
+    };
+    let head = get_head_state(repo)?;
+    if let (HeadState::Detached(id), false) = (&head, args.auto) {
+        let msg = format!(
+            "HEAD is detached at {}. No branch is checked out, so all but the protected branches are offered.",
+            &id.to_string()[..7]
+        );
+        eprintln!("{}", style(msg).dim());
+    }
+    let kind = get_backend_kind(&settings, args)?;
+    let signed = match args.signed {
+        true => Some(SignedPush::Always),
+        false => SignedPush::from_config(&repo.config()?),
+    };
+    let push_settings = PushSettings {
+        no_verify: args.no_verify,
+        signed,
+    };
+    let rejections = Rejections::default();
+    let callbacks = get_push_callbacks(repo, rejections.clone())?;
+    let mut backend = open_backend(repo, kind, callbacks, rejections, push_settings)?;
+    let mut hostings = Hostings::new(repo, &settings, !args.auto);
+    if args.resume {
+        return resume(repo, backend.as_mut(), &mut hostings);
+    }
+    if let Some(path) = &args.apply {
+        return apply_spec(repo, &settings, backend.as_mut(), &mut hostings, path, args);
+    }
+    let mut protected = get_protected_names(repo);
+    let switch_target = match (&head, args.switch) {
+        (HeadState::Branch(current), true) => get_switch_target(repo, &protected, current)?,
+        _ => None,
+    };
+    let base = get_base(repo, &settings, args.base.as_deref())?;
+    let mut branches = backend.list_local_branches()?;
+    add_server_protected(repo, &mut hostings, &branches, &mut protected);
+    add_command_protected(repo, &settings, &branches, &mut protected)?;
+    use_dates(&mut branches, get_date_kind(&settings, args.date)?);
+    // All of them, to tell which ones depend on those to delete
+    let locals = branches.clone();
+    let base_id = base.as_ref().map(|(_, id)| *id);
+    for b in branches
+        .iter()
+        .filter(|b| !is_protected(&protected, &b.name))
+    {
+        if let Some(path) = &b.worktree {
+            let msg = format!(
+                "{} is checked out in {}, remove that worktree to delete it.",
+                b.name,
+                path.display()
+            );
+            eprintln!("{}", style(msg).dim());
+        }
+    }
+    let mut explanation = Explanation::new(&branches);
+    branches.retain(|b| {
+        let exclusion = get_exclusion(b, &protected, switch_target.as_deref(), args);
+        let kept = exclusion.is_none();
+        if let Some(reason) = exclusion {
+            explanation.leave_out(&b.name, reason);
+        }
+        kept
+    });
+    let excluded = get_excluded_patterns(args)?;
+    branches.retain(|b| match excluded.iter().find(|p| matches(p, &b.name)) {
+        Some(p) => {
+            explanation.leave_out(&b.name, format!("excluded by \"{p}\" of --exclude-file"));
+            false
+        }
+        None => true,
+    });
+    let profile = match &args.profile {
+        Some(name) => Some(load_profile(&settings, name)?),
+        None => None,
+    };
+    let email = match args.mine || profile.as_ref().is_some_and(|p| p.mine) {
+        true => Some(get_user_email(repo)?),
+        false => None,
+    };
+    if let Some(email) = &email {
+        let count = branches.len();
+        branches.retain(|b| is_authored_by(b, email));
+        explanation.update(
+            &branches,
+            Some("last commit by you"),
+            &format!("its last commit is not by {email}"),
+        );
+        if branches.is_empty() && count > 0 {
+            eprintln!("No branches have their last commit by {email}.");
+        }
+    }
+    if args.merged || profile.as_ref().is_some_and(|p| p.merged) {
+        let Some((base_name, base_id)) = &base else {
+            return Err(eyre!(
+                "No default branch found for --merged, give one with --base"
+            ));
+        };
+        let count = branches.len();
+        let mut unknown = 0;
+        branches.retain(|b| match check_contained_in(repo, b.tip, *base_id) {
+            Ok(Some(merged)) => merged,
+            Ok(None) => {
+                unknown += 1;
+                let reason = format!(
+                    "whether it is merged into {base_name} is unknown, the clone is shallow"
+                );
+                explanation.leave_out(&b.name, reason);
+                false
+            }
+            Err(_) => false,
+        });
+        explanation.update(
+            &branches,
+            Some(&format!("merged into {base_name}")),
+            &format!("not merged into {base_name}"),
+        );
+        if unknown > 0 {
+            warn_shallow(unknown, base_name);
+        }
+        if branches.is_empty() && count > 0 {
+            eprintln!("No branches are merged into {base_name}.");
+        }
+    }
+    if let Some(profile) = &profile {
+        let count = branches.len();
+        let now = now();
+        branches.retain(|b| profile.matches(b, email.as_deref(), now));
+        explanation.update(
+            &branches,
+            Some(&format!("matches the profile {} ({profile})", profile.name)),
+            &format!("does not match the profile {} ({profile})", profile.name),
+        );
+        if branches.is_empty() && count > 0 {
+            eprintln!("No branches match the profile {}.", profile.name);
+        }
+    }
+    let policy = match get_policy_path(repo, &settings, args)? {
+        Some(path) => {
+            let policy = Policy::load(&path)?;
+            let count = branches.len();
+            let now = now();
+            let mut selected = Vec::new();
+            for b in branches {
+                match policy.decide(repo, &b, now)? {
+                    Decision::Picked(i) => {
+                        let reason = format!("rule {} of the policy ({})", i + 1, policy.rules[i]);
+                        explanation.pick(&b.name, reason);
+                        selected.push(b);
+                    }
+                    Decision::Excluded(p) => {
+                        let reason = format!("excluded by \"{p}\" in the policy");
+                        explanation.leave_out(&b.name, reason);
+                    }
+                    Decision::Unmatched => {
+                        let reason = "matches no rule of the policy".into();
+                        explanation.leave_out(&b.name, reason);
+                    }
+                }
+            }
+            branches = selected;
+            if branches.is_empty() && count > 0 {
+                eprintln!("No branches match the policy in {}.", path.display());
+            }
+            Some(policy)
+        }
+        None if args.auto => {
+            return Err(eyre!(
+                "--auto requires a policy, via --policy or the {POLICY} setting"
+            ))
+        }
+        None => None,
+    };
+    // Whatever picked them, the branches being worked on are kept
+    if let Some(active) = get_keep_active(&settings, args)? {
+        let count = branches.len();
+        let now = now();
+        branches.retain(|b| is_older_than(b.time, active, now));
+        explanation.update(
+            &branches,
+            None,
+            &format!("still active, with commits in the last {active} (see --keep-active)"),
+        );
+        let kept = count - branches.len();
+        if kept > 0 {
+            let msg = format!(
+                "Not offering the {kept} branches with commits in the last {active}, which are still active (see --keep-active)."
+            );
+            eprintln!("{}", style(msg).dim());
+        }
+    }
+    let reviewed = match args.select_from.as_deref() {
+        Some(path) => Some((path, selection::read(path)?)),
+        None => None,
+    };
+    if let (Some((_, names)), true) = (&reviewed, args.yes) {
+        branches.retain(|b| names.contains(&b.name));
+        explanation.update(&branches, None, "not in the file of --select-from");
+    }
+    if args.explain {
+        if (args.auto || args.yes) && !args.force_open_pr {
+            skip_open_pull_requests(&mut hostings, &mut branches);
+            let reason = "its pull request is open, see --force-open-pr";
+            explanation.update(&branches, None, reason);
+        }
+        explanation.print(&find_unlisted_branches(repo));
+        return Ok(ExitCode::SUCCESS);
+    }
+    let events = Events::new(args.events);
+    for branch in &branches {
+        let fields = json!({
+            "name": branch.name,
+            "sha": branch.tip.to_string(),
+            "upstream": branch.upstream.as_ref().map(|u| u.to_string()),
+            "upstream_gone": branch.upstream_gone,
+            "time": branch.time,
+        });
+        events.emit("branch_listed", fields);
+    }
+    // --auto requires a policy, checked above
+    if args.auto || (args.yes && !branches.is_empty()) {
+        if !args.force_open_pr {
+            skip_open_pull_requests(&mut hostings, &mut branches);
+        }
+        let stacks = find_stacks(repo, &locals, base_id).unwrap_or_default();
+        order_by_stack(&mut branches, &stacks, |b| &b.name);
+        let names: Vec<&str> = branches.iter().map(|b| b.name.as_str()).collect();
+        warn_partial_stacks(&names, &stacks);
+        warn_dependents(repo, &branches, &locals, base_id);
+        return run_auto(
+            repo,
+            backend.as_mut(),
+            &mut hostings,
+            branches,
+            |_| policy.as_ref().is_some_and(|p| p.delete_upstream),
+            switch_target.as_deref(),
+            args,
+        );
+    }
+    if branches.is_empty() {
+        if policy.is_none() && profile.is_none() {
+            eprintln!("No branches eligible to delete.");
+            match &head {
+                HeadState::Branch(name) if !is_protected(&protected, name) => {
+                    let msg = format!(
+                        "You cannot delete {name}, the branch you are on. Switch to another one, or pass --switch."
+                    );
+                    eprintln!("{}", style(msg).yellow());
+                }
+                _ => {}
+            }
+        }
+        emit_done(&Outcome::default(), &events);
+        return Ok(ExitCode::SUCCESS);
+    }
+    // Diffs need the file contents, which libgit2 cannot fetch in a partial clone
+    let has_contents = match (is_partial_clone(repo), &base) {
+        (false, _) => true,
+        (true, Some((base_name, base_id))) if args.fetch_blobs => {
+            let msg = format!(
+                "Fetching the objects which the partial clone lacks, to compare {} branches with {base_name}...",
+                branches.len()
+            );
+            eprintln!("{}", style(msg).dim());
+            let tips: Vec<Oid> = branches.iter().map(|b| b.tip).collect();
+            fetch_missing_objects(repo, *base_id, &tips)?;
+            true
+        }
+        (true, _) => {
+            if !args.fetch_blobs {
+                let msg = "This is a partial clone, so the diff stats, the previews and the detection of squash and rebase merges are skipped. Pass --fetch-blobs to fetch what they need.";
+                eprintln!("{}", style(msg).dim());
+            }
+            false
+        }
+    };
+    let mut patch_index = base
+        .as_ref()
+        .filter(|_| has_contents)
+        .map(|(_, id)| PatchIndex::new(repo, *id));
+    let name_width = branches
+        .iter()
+        .map(|b| b.name.chars().count())
+        .max()
+        .unwrap_or(0);
+    let author_format = get_author_format(&settings, args.author_format)?;
+    let authors: Vec<String> = branches
+        .iter()
+        .map(|b| b.format_author(author_format))
+        .collect();
+    let author_width = authors.iter().map(|a| a.chars().count()).max().unwrap_or(0);
+    // With one remote, all deletions go to the same server
+    let remote_width = match repo.remotes()?.len() > 1 {
+        true => branches
+            .iter()
+            .filter_map(|b| b.upstream.as_ref())
+            .map(|u| u.remote.chars().count())
+            .max()
+            .unwrap_or(0),
+        false => 0,
+    };
+    // "in " and maybe "?"
+    let contained_width = base
+        .as_ref()
+        .map_or(0, |(name, _)| name.chars().count() + 4);
+    let unique: Vec<Option<usize>> = branches
+        .iter()
+        .map(|b| base_id.and_then(|id| count_unique_commits(repo, b.tip, id).ok()))
+        .collect();
+    let unique_width = unique
+        .iter()
+        .flatten()
+        .map(|n| format!("{n} unique").len())
+        .max()
+        .unwrap_or(0);
+    let sizes: Vec<Option<u64>> = match (args.sizes, has_contents) {
+        (true, true) => {
+            let mut kept: Vec<Oid> = locals
+                .iter()
+                .filter(|b| is_protected(&protected, &b.name))
+                .map(|b| b.tip)
+                .collect();
+            kept.extend(base_id);
+            let estimator = BranchSizes::new(repo, &kept)?;
+            branches
+                .iter()
+                .map(|b| estimator.estimate(b.tip).ok())
+                .collect()
+        }
+        _ => vec![None; branches.len()],
+    };
+    let size_width = match args.sizes {
+        true => sizes
+            .iter()
+            .map(|s| s.map_or(1, |s| format_size(s).len()))
+            .max()
+            .unwrap_or(0),
+        false => 0,
+    };
+    let icons = load_icons(&settings)?;
+    let icon_width = branches
+        .iter()
+        .filter_map(|b| find_icon(&icons, &b.name))
+        .map(|i| measure_text_width(&i.glyph))
+        .max()
+        .unwrap_or(0);
+    let now = now();
+    let stashes = count_stashes_by_branch(repo)?;
+    let mut pull_requests = hostings.find_pull_requests(&branches);
+    let pr_width = pull_requests
+        .values()
+        .map(|pr| get_pull_request_label(pr.as_ref()).chars().count())
+        .max()
+        .unwrap_or(0);
+    let mut ci_statuses = match args.ci_status {
+        true => hostings.find_ci_statuses(&branches),
+        false => HashMap::new(),
+    };
+    let mut issue_states = match args.issues {
+        true => hostings.find_issue_states(&branches),
+        false => HashMap::new(),
+    };
+    let items: Vec<Item> = branches
+        .into_iter()
+        .zip(authors)
+        .zip(unique)
+        .zip(sizes)
+        .map(|(((branch, author), unique), size)| {
+            let contained = base.as_ref().map(|(name, id)| {
+                let contained = check_contained_in(repo, branch.tip, *id).unwrap_or(Some(false));
+                (name.as_str(), contained)
+            });
+            let contained_in = contained.and_then(|(name, c)| (c == Some(true)).then_some(name));
+            // A contained branch is merged anyway
+            let merge_kind = match (&mut patch_index, contained_in) {
+                (Some(index), None) => index.find_merge_kind(branch.tip).ok().flatten(),
+                _ => None,
+            };
+            let unknown_in = contained
+                .and_then(|(name, c)| (c.is_none() && merge_kind.is_none()).then_some(name));
+            // Without a base, whether it is merged is unknown
+            let unmerged = base.is_some() && contained_in.is_none() && merge_kind.is_none();
+            Item {
+                protected: is_protected(&protected, &branch.name),
+                risky: is_risky(
+                    repo,
+                    &branch.name,
+                    branch.tip,
+                    unmerged,
+                    pull_requests
+                        .get(&branch.name)
+                        .and_then(Option::as_ref)
+                        .is_some_and(|pr| pr.state == ReviewState::Open),
+                ),
+                unmerged,
+                age: now - branch.time,
+                created: get_creation_time(repo, &branch.name).map(|t| now - t),
+                contained_in,
+                unknown_in,
+                merge_kind,
+                stashes: stashes.get(&branch.name).copied().unwrap_or(0),
+                reviewed: pull_requests.contains_key(&branch.name),
+                pull_request_url: pull_requests
+                    .get(&branch.name)
+                    .and_then(|pr| pr.as_ref())
+                    .and_then(|pr| hostings.get_pull_request_url(&branch, pr.number)),
+                pull_request: pull_requests.remove(&branch.name).flatten(),
+                ci_status: ci_statuses.remove(&branch.name).flatten(),
+                issue: find_issue_key(&branch.name),
+                issue_state: issue_states.remove(&branch.name).flatten(),
+                author,
+                unique,
+                size,
+                icon: find_icon(&icons, &branch.name),
+                widths: Widths {
+                    icon: icon_width,
+                    name: name_width,
+                    author: author_width,
+                    remote: remote_width,
+                    contained: contained_width,
+                    unique: unique_width,
+                    size: size_width,
+                    pull_request: pr_width,
+                    created: if args.created { 5 } else { 0 },
+                },
+                branch,
+            }
+        })
+        .collect();
+    let unknown = items.iter().filter(|i| i.unknown_in.is_some()).count();
+    if let Some(base_name) = items.iter().find_map(|i| i.unknown_in) {
+        warn_shallow(unknown, base_name);
+    }
+    // Offer to pick again the branches of a run which did not finish, unless others are asked for
+    let chosen = reviewed.is_some() || !args.preselect.is_empty();
+    let mut saved = match chosen {
+        true => Vec::new(),
+        false => session::load(repo)?,
+    };
+    saved.retain(|n| items.iter().any(|i| &i.branch.name == n));
+    if !saved.is_empty() {
+        let msg = format!(
+            "Select again the {} branches picked last time, which were not deleted",
+            saved.len()
+        );
+        let Some(again) = get_answer(Confirm::new(&msg).with_default(true).prompt())? else {
+            emit_done(&Outcome::default(), &events);
+            return Ok(ExitCode::SUCCESS);
+        };
+        if !again {
+            saved.clear();
+            session::clear(repo)?;
+        }
+    }
+    // Else the branches whose review or issue is over, for cleaning up after a release
+    let preselected: Vec<String> = match (chosen, saved.is_empty()) {
+        (true, _) => {
+            let mut names = match reviewed {
+                Some((path, names)) => select_reviewed(&items, path, names),
+                None => Vec::new(),
+            };
+            names.extend(select_matching(&items, &args.preselect));
+            names
+        }
+        (false, false) => saved,
+        (false, true) => {
+            let done: Vec<String> = items
+                .iter()
+                .filter(|i| !i.protected)
+                .filter(|i| {
+                    i.pull_request
+                        .as_ref()
+                        .is_some_and(|pr| pr.state != ReviewState::Open)
+                        || i.issue_state == Some(IssueState::Resolved)
+                })
+                .map(|i| i.branch.name.clone())
+                .collect();
+            if !done.is_empty() {
+                let reason = match args.issues {
+                    true => "pull requests are merged or closed, or whose issues are resolved",
+                    false => "pull requests are merged or closed",
+                };
+                let msg = format!("Selected the {} branches whose {reason}.", done.len());
+                eprintln!("{}", style(msg).dim());
+            }
+            done
+        }
+    };
+    let stat_base = base_id.filter(|_| has_contents);
+    let mut stats = HashMap::new();
+    let mut picker = Picker::new("Select branches to delete", items)
+        .with_keys(get_key_map(&settings)?)
+        .with_export()
+        .with_legend(get_legend(
+            base.as_ref().map(|(name, _)| name.as_str()),
+            args.created,
+            args.sizes,
+        ))
+        .with_sort(OLDEST_FIRST, |a: &Item, b: &Item| {
+            a.branch.time.cmp(&b.branch.time)
+        })
+        .with_sort(NEWEST_FIRST, |a: &Item, b: &Item| {
+            b.branch.time.cmp(&a.branch.time)
+        })
+        .with_grouping(
+            BY_AUTHOR,
+            |i: &Item| i.author.clone(),
+            |a: &Item, b: &Item| a.branch.time.cmp(&b.branch.time),
+        )
+        .with_selected(|i: &Item| preselected.contains(&i.branch.name))
+        .with_formatter(|i: &Item| i.branch.name.clone())
+        .with_fields(get_fields)
+        .with_status(|i: &Item| {
+            let stat = stats
+                .entry(i.branch.tip)
+                .or_insert_with(|| get_diff_stat(repo, i.branch.tip, stat_base?).ok());
+            Some(format!("{}: {}", i.branch.name, stat.as_ref()?))
+        });
+    if args.created {
+        picker = picker.with_sort(CREATED_FIRST, |a: &Item, b: &Item| {
+            b.created.cmp(&a.created)
+        });
+    }
+    if args.sizes {
+        picker = picker.with_sort(LARGEST_FIRST, |a: &Item, b: &Item| b.size.cmp(&a.size));
+    }
+    if let Some(sort) = profile.and_then(|p| p.sort) {
+        picker = picker.with_initial_sort(match sort {
+            SortOrder::Oldest => OLDEST_FIRST,
+            SortOrder::Newest => NEWEST_FIRST,
+            SortOrder::Author => BY_AUTHOR,
+        });
+    }
+    let base_name = base.as_ref().map(|(name, _)| name.as_str());
+    picker = picker.with_details(|i: &Item| get_details(repo, i, base_name));
+    if has_contents {
+        picker = picker.with_preview(|i: &Item| {
+            get_log_patch(repo, i.branch.tip, base_id)
+                .unwrap_or_else(|e| vec![format!("Cannot show the changes: {}", e.message())])
+        });
+    }
+    let Some(mut ans_items) = picker.prompt()? else {
+        emit_done(&Outcome::default(), &events);
+        return Ok(ExitCode::SUCCESS);
+    };
+    // Kept until the deletion succeeds, so that cancelling below or failing can be resumed
+    let names: Vec<String> = ans_items.iter().map(|i| i.branch.name.clone()).collect();
+    session::save(repo, &names)?;
+    let picked_protected: Vec<&str> = ans_items
+        .iter()
+        .filter(|i| i.protected)
+        .map(|i| i.branch.name.as_str())
+        .collect();
+    if !picked_protected.is_empty() {
+        let verb = if picked_protected.len() == 1 {
+            "is"
+        } else {
+            "are"
+        };
+        let msg = format!(
+            "{} {verb} protected. Delete anyway",
+            picked_protected.join(", ")
+        );
+        let Some(force) = get_answer(Confirm::new(&msg).with_default(false).prompt())? else {
+            emit_done(&Outcome::default(), &events);
+            return Ok(ExitCode::SUCCESS);
+        };
+        if !force {
+            ans_items.retain(|i| !i.protected);
+        }
+    }
+    let prompt =
+        Confirm::new("Do you want to delete the upstream branches also").with_default(false);
+    let Some(ans_up) = get_answer(prompt.prompt())? else {
+        emit_done(&Outcome::default(), &events);
+        return Ok(ExitCode::SUCCESS);
+    };
+    let msg = if ans_up {
+        "To delete these branches and their upstream:"
+    } else {
+        "To delete these branches:"
+    };
+    let stacks = find_stacks(repo, &locals, base_id).unwrap_or_default();
+    order_by_stack(&mut ans_items, &stacks, |i| &i.branch.name);
+    eprintln!("{}", style(msg).blue());
+    show_list_of_branches(repo, &ans_items, stat_base, &stacks);
+    if ans_up {
+        tell_servers(repo, &ans_items);
+    }
+    let names: Vec<&str> = ans_items.iter().map(|i| i.branch.name.as_str()).collect();
+    warn_stashes(&names, &stashes);
+    warn_partial_stacks(&names, &stacks);
+    let selected: Vec<BranchInfo> = ans_items.into_iter().map(|i| i.branch).collect();
+    warn_dependents(repo, &selected, &locals, base_id);
+    let Some(kept_upstreams) = get_kept_upstreams(repo, &selected, ans_up, args)? else {
+        emit_done(&Outcome::default(), &events);
+        return Ok(ExitCode::SUCCESS);
+    };
+    switch_if_needed(repo, &selected, switch_target.as_deref())?;
+    let upstreams = UpstreamDeletion {
+        enabled: ans_up,
+        kept: &kept_upstreams,
+        batch_size: args.batch_size.map_or(1, NonZeroUsize::get),
+    };
+    let outcome = delete_branches(
+        repo,
+        &settings,
+        backend.as_mut(),
+        &mut hostings,
+        selected,
+        &upstreams,
+        &events,
+    )?;
+    if !outcome.has_failure() {
+        session::clear(repo)?;
+    }
+    write_undo_script(repo, &settings, &outcome, args)?;
+    purge_journal(repo, &settings)?;
+    share_deletions(repo, &settings, &outcome)?;
+    notify_team(repo, &settings, &outcome);
+    eprintln!("{} {}", Emoji("🎉", "v"), style("Done!").bright().green());
+    reclaim_space(repo, &outcome, args)?;
+    emit_done(&outcome, &events);
+    Ok(outcome.exit_code())
+}
@@ -0,0 +1,426 @@
+//! The hosting APIs of the remotes, and the issue trackers, connected when first needed, and
+//! asked through the cache of the repository.
+
+use std::collections::HashMap;
+
+use color_eyre::Result;
+use console::{style, Emoji};
+use git2::{Config, Repository};
+use inquire::Confirm;
+
+use git_del_branches::age::now;
+use git_del_branches::branch::{get_server_branch, BranchInfo};
+use git_del_branches::delete::get_push_url;
+use git_del_branches::hosting::cache::Cache;
+use git_del_branches::hosting::tracker::{
+    self, find_issue_key, IssueKey, IssueState, Tracker, TrackerSite,
+};
+use git_del_branches::hosting::{
+    connect, find_site, CiStatus, Hosting, ProtectedRef, PullRequest, Site,
+};
+use git_del_branches::keychain::find_token;
+
+use super::auth::{ask_token, store_token};
+use super::get_answer;
+
+/// Get the token for the API of the host: from the environment variable or the keychain,
+/// else asked, if we may prompt, and then offered to be stored for the next runs.
+pub fn get_token(host: &str, env_var: &str, prompt: bool) -> Result<Option<String>> {
+    if let Some(token) = find_token(host, env_var) {
+        return Ok(Some(token));
+    }
+    if !prompt {
+        return Ok(None);
+    }
+    let help = format!("Or set {env_var}. Esc to go on without it");
+    let Some(token) = ask_token(host, Some(&help))? else {
+        return Ok(None);
+    };
+    let prompt = Confirm::new("Store it in the keychain, for the next runs?").with_default(true);
+    if get_answer(prompt.prompt())?.unwrap_or(false) {
+        if let Err(e) = store_token(host, &token) {
+            eprintln!("{} {}", Emoji("⚠️", "!"), style(e).yellow());
+        }
+    }
+    Ok(Some(token))
+}
+
+fn warn(msg: &str) {
+    eprintln!("{} {}", Emoji("⚠️", "!"), style(msg).yellow());
+}
+
+/// A branch to look up, with its branch on the server.
+struct Pending<'b> {
+    branch: &'b BranchInfo,
+    server_name: String,
+}
+
+pub struct Hostings<'r> {
+    repo: &'r Repository,
+    settings: &'r Config,
+    /// Whether we may ask for the tokens needed for deleting.
+    interactive: bool,
+    /// The site of the hosting of each remote, if it is one we know.
+    sites: HashMap<String, Option<Site>>,
+    by_remote: HashMap<String, Option<Box<dyn Hosting>>>,
+    /// The issue trackers, by their keys.
+    trackers: HashMap<String, Option<Box<dyn Tracker>>>,
+    /// The tokens found or asked for, by host and environment variable, so that each is asked
+    /// at most once.
+    tokens: HashMap<(String, String), Option<String>>,
+    cache: Cache,
+}
+
+impl<'r> Hostings<'r> {
+    pub fn new(repo: &'r Repository, settings: &'r Config, interactive: bool) -> Self {
+        Self {
+            repo,
+            settings,
+            interactive,
+            sites: HashMap::new(),
+            by_remote: HashMap::new(),
+            trackers: HashMap::new(),
+            tokens: HashMap::new(),
+            cache: Cache::load(repo, now()),
+        }
+    }
+
+    /// The settings which the sites of the remotes are found with.
+    pub fn settings(&self) -> &'r Config {
+        self.settings
+    }
+
+    /// The API of the hosting of the remote, if it is one we know and we have a token for.
+    /// The token is not asked for, so that looking things up never waits for one.
+    pub fn get(&mut self, remote: &str) -> Option<&dyn Hosting> {
+        self.get_hosting(remote, false)
+    }
+
+    /// The API of the hosting of the remote, for deleting with: its token is asked for if it
+    /// is not found, when we may and the hosting is set rather than guessed from the URL.
+    pub fn get_for_deleting(&mut self, remote: &str) -> Option<&dyn Hosting> {
+        self.get_hosting(remote, self.interactive)
+    }
+
+    fn get_hosting(&mut self, remote: &str, prompt: bool) -> Option<&dyn Hosting> {
+        // A guessed hosting may not be the one which the token would be typed for
+        let prompt = prompt && self.get_site(remote).is_some_and(|s| !s.guessed);
+        // Connected without a token before, but it may be asked for now
+        let unconnected = matches!(self.by_remote.get(remote), Some(None));
+        if !self.by_remote.contains_key(remote) || (prompt && unconnected) {
+            let hosting = match self.get_site(remote).cloned() {
+                Some(site) => self.connect(&site, prompt).unwrap_or_else(|e| {
+                    warn(&e.to_string());
+                    None
+                }),
+                None => None,
+            };
+            self.by_remote.insert(remote.to_string(), hosting);
+        }
+        self.by_remote.get(remote)?.as_deref()
+    }
+
+    fn get_site(&mut self, remote: &str) -> Option<&Site> {
+        if !self.sites.contains_key(remote) {
+            let site = self.find_site(remote).unwrap_or_else(|e| {
+                warn(&e.to_string());
+                None
+            });
+            self.sites.insert(remote.to_string(), site);
+        }
+        self.sites.get(remote)?.as_ref()
+    }
+
+    /// Web page of the pull request of this number from the branch, if its hosting has them.
+    pub fn get_pull_request_url(&mut self, branch: &BranchInfo, number: u64) -> Option<String> {
+        let server = get_server_branch(self.repo, branch)?;
+        self.get_site(&server.remote)?.get_pull_request_url(number)
+    }
+
+    /// Key of the site of the remote in the cache, if its hosting has pull requests. Others,
+    /// like Gerrit, are not connected to, so that their token is only asked for when needed
+    /// for deleting.
+    fn get_review_site(&mut self, remote: &str) -> Option<String> {
+        self.get_site(remote)
+            .filter(|s| s.kind.has_pull_requests())
+            .map(Site::get_key)
+    }
+
+    fn find_site(&self, remote: &str) -> Result<Option<Site>> {
+        let remote = self.repo.find_remote(remote)?;
+        let Some(url) = get_push_url(&remote) else {
+            return Ok(None);
+        };
+        find_site(self.settings, url)
+    }
+
+    fn get_token(&mut self, host: &str, env_var: &str, prompt: bool) -> Result<Option<String>> {
+        let key = (host.to_string(), env_var.to_string());
+        if let Some(token) = self.tokens.get(&key) {
+            return Ok(token.clone());
+        }
+        let token = get_token(host, env_var, prompt)?;
+        // Not found without asking, so that it can still be asked for deleting
+        if token.is_some() || prompt {
+            self.tokens.insert(key, token.clone());
+        }
+        Ok(token)
+    }
+
+    fn connect(&mut self, site: &Site, prompt: bool) -> Result<Option<Box<dyn Hosting>>> {
+        let token = self.get_token(&site.host, &site.token_env, prompt)?;
+        Ok(token.map(|t| connect(site, &t)))
+    }
+
+    /// Where the issue is tracked: in Jira for its keys, else in the issues of the repository
+    /// of the branch on the server, if it is on GitHub or Gitea.
+    fn find_tracker_site(&mut self, branch: &BranchInfo, key: &IssueKey) -> Option<TrackerSite> {
+        if let IssueKey::Jira(_) = key {
+            return TrackerSite::find_jira(self.settings);
+        }
+        let server = get_server_branch(self.repo, branch)?;
+        TrackerSite::from_site(self.get_site(&server.remote)?)
+    }
+
+    fn get_tracker(&mut self, site: &TrackerSite) -> Option<&dyn Tracker> {
+        let key = site.get_key();
+        if !self.trackers.contains_key(&key) {
+            let token = self
+                .get_token(&site.get_host(), site.token_env(), false)
+                .unwrap_or_else(|e| {
+                    warn(&e.to_string());
+                    None
+                });
+            let tracker = token.map(|t| tracker::connect(site, &t));
+            self.trackers.insert(key.clone(), tracker);
+        }
+        self.trackers.get(&key)?.as_deref()
+    }
+
+    /// Branches which the hosting of the remote protects, empty if it does not tell.
+    pub fn find_protected(&mut self, remote: &str) -> Vec<ProtectedRef> {
+        let Some(site) = self.get_review_site(remote) else {
+            return Vec::new();
+        };
+        if let Some(protected) = self.cache.get_protected(&site) {
+            return protected;
+        }
+        let Some(hosting) = self.get(remote) else {
+            return Vec::new();
+        };
+        match hosting.list_protected() {
+            Ok(protected) => {
+                self.cache.put_protected(&site, protected.clone());
+                protected
+            }
+            Err(e) => {
+                let title = hosting.kind().title();
+                warn(&format!("Cannot tell which branches {title} protects: {e}"));
+                Vec::new()
+            }
+        }
+    }
+
+    /// Group the branches by the remote of their branch on the server, for those whose
+    /// hosting has pull requests, in the order of the branches.
+    fn group_by_remote<'b>(
+        &mut self,
+        branches: &'b [BranchInfo],
+    ) -> Vec<(String, String, Vec<Pending<'b>>)> {
+        let mut groups: Vec<(String, String, Vec<Pending>)> = Vec::new();
+        for branch in branches {
+            let Some(server) = get_server_branch(self.repo, branch) else {
+                continue;
+            };
+            let Some(site) = self.get_review_site(&server.remote) else {
+                continue;
+            };
+            let pending = Pending {
+                branch,
+                server_name: server.name,
+            };
+            match groups.iter_mut().find(|(r, _, _)| *r == server.remote) {
+                Some((_, _, group)) => group.push(pending),
+                None => groups.push((server.remote, site, vec![pending])),
+            }
+        }
+        groups
+    }
+
+    /// The latest pull requests from the branches, by their names, `None` for those known to
+    /// have none. Those of a remote are asked together, when they are not in the cache.
+    pub fn find_pull_requests(
+        &mut self,
+        branches: &[BranchInfo],
+    ) -> HashMap<String, Option<PullRequest>> {
+        let mut found = HashMap::new();
+        for (remote, site, mut group) in self.group_by_remote(branches) {
+            group.retain(|p| {
+                let cached = self
+                    .cache
+                    .get_pull_request(&site, &p.server_name, p.branch.tip);
+                match cached {
+                    Some(pr) => {
+                        found.insert(p.branch.name.clone(), pr);
+                        false
+                    }
+                    None => true,
+                }
+            });
+            if group.is_empty() {
+                continue;
+            }
+            let Some(hosting) = self.get(&remote) else {
+                continue;
+            };
+            let msg = format!(
+                "Looking up the pull requests of {} branches on {}...",
+                group.len(),
+                hosting.kind().title()
+            );
+            eprintln!("{}", style(msg).dim());
+            let names: Vec<&str> = group.iter().map(|p| p.server_name.as_str()).collect();
+            match hosting.find_pull_requests(&names) {
+                Ok(prs) => {
+                    for p in group {
+                        let pr = prs.get(&p.server_name).cloned();
+                        self.cache.put_pull_request(
+                            &site,
+                            &p.server_name,
+                            p.branch.tip,
+                            pr.clone(),
+                        );
+                        found.insert(p.branch.name.clone(), pr);
+                    }
+                }
+                Err(e) => warn(&format!(
+                    "Cannot look up the pull requests on {remote}: {e}"
+                )),
+            }
+        }
+        found
+    }
+
+    /// Status of the CI checks of the tips of the branches, by their names, `None` for those
+    /// known to have no checks. After an error, the other tips of the remote are not asked
+    /// about.
+    pub fn find_ci_statuses(
+        &mut self,
+        branches: &[BranchInfo],
+    ) -> HashMap<String, Option<CiStatus>> {
+        let mut found = HashMap::new();
+        for (remote, site, mut group) in self.group_by_remote(branches) {
+            group.retain(|p| match self.cache.get_ci_status(&site, p.branch.tip) {
+                Some(status) => {
+                    found.insert(p.branch.name.clone(), status);
+                    false
+                }
+                None => true,
+            });
+            if group.is_empty() {
+                continue;
+            }
+            let Some(hosting) = self.get(&remote) else {
+                continue;
+            };
+            let msg = format!(
+                "Looking up the CI status of {} branches on {}...",
+                group.len(),
+                hosting.kind().title()
+            );
+            eprintln!("{}", style(msg).dim());
+            let mut statuses: Vec<(&BranchInfo, Option<CiStatus>)> = Vec::new();
+            for p in &group {
+                // Branches may share their tip
+                let known = statuses.iter().find(|(b, _)| b.tip == p.branch.tip);
+                if let Some(&(_, status)) = known {
+                    statuses.push((p.branch, status));
+                    continue;
+                }
+                match hosting.find_ci_status(&p.branch.tip.to_string()) {
+                    Ok(status) => statuses.push((p.branch, status)),
+                    Err(e) => {
+                        warn(&format!("Cannot look up the CI status on {remote}: {e}"));
+                        break;
+                    }
+                }
+            }
+            for (branch, status) in statuses {
+                self.cache.put_ci_status(&site, branch.tip, status);
+                found.insert(branch.name.clone(), status);
+            }
+        }
+        found
+    }
+
+    /// State of the issues which the branches are named after, by their names, `None` for
+    /// those whose tracker has no such issue. After an error, the other issues of the tracker
+    /// are not asked about.
+    pub fn find_issue_states(
+        &mut self,
+        branches: &[BranchInfo],
+    ) -> HashMap<String, Option<IssueState>> {
+        let mut found = HashMap::new();
+        let mut groups: Vec<(TrackerSite, Vec<(&BranchInfo, IssueKey)>)> = Vec::new();
+        for branch in branches {
+            let Some(key) = find_issue_key(&branch.name) else {
+                continue;
+            };
+            let Some(site) = self.find_tracker_site(branch, &key) else {
+                continue;
+            };
+            if let Some(state) = self
+                .cache
+                .get_issue_state(&site.get_key(), &key.to_string())
+            {
+                found.insert(branch.name.clone(), state);
+                continue;
+            }
+            match groups.iter_mut().find(|(s, _)| *s == site) {
+                Some((_, group)) => group.push((branch, key)),
+                None => groups.push((site, vec![(branch, key)])),
+            }
+        }
+        for (site, group) in groups {
+            let Some(tracker) = self.get_tracker(&site) else {
+                continue;
+            };
+            let msg = format!(
+                "Looking up the issues of {} branches on {}...",
+                group.len(),
+                tracker.title()
+            );
+            eprintln!("{}", style(msg).dim());
+            let mut states: Vec<(&BranchInfo, IssueKey, Option<IssueState>)> = Vec::new();
+            for (branch, key) in group {
+                // Branches may be named after the same issue
+                let known = states.iter().find(|(_, k, _)| *k == key);
+                if let Some(&(_, _, state)) = known {
+                    states.push((branch, key, state));
+                    continue;
+                }
+                match tracker.find_issue_state(&key) {
+                    Ok(state) => states.push((branch, key, state)),
+                    Err(e) => {
+                        warn(&format!("Cannot look up the issues: {e}"));
+                        break;
+                    }
+                }
+            }
+            for (branch, key, state) in states {
+                self.cache
+                    .put_issue_state(&site.get_key(), &key.to_string(), state);
+                found.insert(branch.name.clone(), state);
+            }
+        }
+        found
+    }
+}
+
+impl Drop for Hostings<'_> {
+    fn drop(&mut self) {
+        if let Err(e) = self.cache.save() {
+            warn(&format!("Cannot save the cache of the hosting APIs: {e}"));
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use color_eyre::Result;
+use console::{style, Style};
+use git2::{Config, Repository};
+
+use git_del_branches::age::{format_age, now, Freshness};
+use git_del_branches::branch::{
+    get_creation_time, is_authored_by, list_deletable_branches, use_dates, BranchInfo,
+};
+use git_del_branches::config::get_settings;
+use git_del_branches::gitenv::open_repository;
+use git_del_branches::merged::{check_contained_in, count_ahead_behind, MergeKind, PatchIndex};
+use git_del_branches::partial::is_partial_clone;
+
+use super::delete::{get_base, style_pull_request};
+use super::hostings::Hostings;
+use super::{get_author_format, get_date_kind, get_user_email};
+use crate::cli::ListArgs;
+
+pub fn run(args: &ListArgs, no_config: bool) -> Result<()> {
+    let repo = open_repository()?;
+    let mut branches = list_deletable_branches(&repo)?;
+    if args.mine {
+        let email = get_user_email(&repo)?;
+        branches.retain(|b| is_authored_by(b, &email));
+    }
+    if args.long {
+        return print_table(&repo, &get_settings(&repo, no_config)?, branches, args);
+    }
+    for branch in branches {
+        println!("{}", branch.name);
+    }
+    Ok(())
+}
+
+/// Text of a cell of the table, which is padded before it is styled.
+struct Cell {
+    text: String,
+    style: Style,
+}
+
+impl Cell {
+    fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            style: Style::new(),
+        }
+    }
+
+    fn styled(text: impl Into<String>, style: Style) -> Self {
+        Self {
+            text: text.into(),
+            style,
+        }
+    }
+}
+
+/// The upstream, or "gone" if its branch was deleted from the remote.
+fn get_upstream_cell(branch: &BranchInfo) -> Cell {
+    match (&branch.upstream, branch.upstream_gone) {
+        (Some(upstream), _) => Cell::new(upstream.to_string()),
+        (None, true) => Cell::styled("gone", Style::new().yellow()),
+        (None, false) => Cell::styled("-", Style::new().dim()),
+    }
+}
+
+/// Age of a commit, colored by how stale it is.
+fn get_age_cell(age: i64) -> Cell {
+    let style = match Freshness::of_age(age) {
+        Freshness::Fresh => Style::new().green(),
+        Freshness::Aging => Style::new().yellow(),
+        Freshness::Stale => Style::new().red(),
+    };
+    Cell::styled(format_age(age), style)
+}
+
+/// Like "+2 -1" for 2 commits ahead of the upstream and 1 behind, "=" when in sync.
+fn get_ahead_behind_cell(repo: &Repository, branch: &BranchInfo) -> Cell {
+    match count_ahead_behind(repo, &branch.name, branch.tip) {
+        Ok(Some((0, 0))) => Cell::styled("=", Style::new().dim()),
+        Ok(Some((ahead, 0))) => Cell::styled(format!("+{ahead}"), Style::new().yellow()),
+        Ok(Some((0, behind))) => Cell::new(format!("-{behind}")),
+        Ok(Some((ahead, behind))) => {
+            Cell::styled(format!("+{ahead} -{behind}"), Style::new().yellow())
+        }
+        Ok(None) | Err(_) => Cell::new(""),
+    }
+}
+
+/// Print the branches with all we know about them, like `git branch -vv` does with less.
+fn print_table(
+    repo: &Repository,
+    settings: &Config,
+    mut branches: Vec<BranchInfo>,
+    args: &ListArgs,
+) -> Result<()> {
+    use_dates(&mut branches, get_date_kind(settings, None)?);
+    let base = get_base(repo, settings, args.base.as_deref())?;
+    let author_format = get_author_format(settings, None)?;
+    let mut patch_index = base
+        .as_ref()
+        .filter(|_| !is_partial_clone(repo))
+        .map(|(_, id)| PatchIndex::new(repo, *id));
+    let mut hostings = Hostings::new(repo, settings, false);
+    let mut pull_requests = hostings.find_pull_requests(&branches);
+    let now = now();
+    let headers = [
+        "BRANCH",
+        "SHA",
+        "UPSTREAM",
+        "AHEAD/BEHIND",
+        "MERGED",
+        "AUTHOR",
+        "LAST",
+        "CREATED",
+    ];
+    let rows: Vec<Vec<Cell>> = branches
+        .iter()
+        .map(|b| {
+            let merged = match &base {
+                Some((name, id)) => match check_contained_in(repo, b.tip, *id) {
+                    Ok(Some(true)) => Cell::styled(format!("in {name}"), Style::new().green()),
+                    _ => match patch_index
+                        .as_mut()
+                        .and_then(|i| i.find_merge_kind(b.tip).ok())
+                    {
+                        Some(Some(MergeKind::Squashed)) => {
+                            Cell::styled("squashed", Style::new().green())
+                        }
+                        Some(Some(MergeKind::Rebased)) => {
+                            Cell::styled("rebased", Style::new().green())
+                        }
+                        _ => Cell::new("unmerged"),
+                    },
+                },
+                None => Cell::styled("?", Style::new().dim()),
+            };
+            let created =
+                get_creation_time(repo, &b.name).map_or("?".into(), |t| format_age(now - t));
+            vec![
+                Cell::new(b.name.clone()),
+                Cell::styled(b.tip.to_string()[..7].to_string(), Style::new().dim()),
+                get_upstream_cell(b),
+                get_ahead_behind_cell(repo, b),
+                merged,
+                Cell::styled(b.format_author(author_format), Style::new().dim()),
+                get_age_cell(now - b.time),
+                Cell::styled(created, Style::new().dim()),
+            ]
+        })
+        .collect();
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.len()).collect();
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.text.chars().count());
+        }
+    }
+    // The last column is not padded
+    let has_pull_requests = pull_requests.values().any(Option::is_some);
+    if let (Some(last), false) = (widths.last_mut(), has_pull_requests) {
+        *last = 0;
+    }
+    let mut header: Vec<String> = headers
+        .iter()
+        .zip(&widths)
+        .map(|(h, width)| format!("{h:<width$}"))
+        .collect();
+    if has_pull_requests {
+        header.push("PULL REQUEST".into());
+    }
+    println!("{}", style(header.join("  ")).bold());
+    for (branch, row) in branches.iter().zip(rows) {
+        let mut line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| {
+                cell.style
+                    .apply_to(format!("{:<width$}", cell.text))
+                    .to_string()
+            })
+            .collect();
+        if has_pull_requests {
+            let pr = pull_requests.remove(&branch.name).flatten();
+            line.push(style_pull_request(pr.as_ref(), 0));
+        }
+        println!("{}", line.join("  "));
+    }
+    Ok(())
+}
@@ -0,0 +1,77 @@
+use color_eyre::Result;
+use console::{style, StyledObject};
+use eyre::eyre;
+use git2::{Config, Repository};
+use inquire::error::InquireError;
+use inquire::ui::{RenderConfig, Styled};
+
+use git_del_branches::age::{format_age, Freshness};
+use git_del_branches::branch::{AuthorFormat, DateKind};
+use git_del_branches::config::{AUTHOR_FORMAT, DATE};
+
+pub mod auth;
+pub mod config;
+pub mod delete;
+pub mod hostings;
+pub mod list;
+pub mod purge;
+pub mod report;
+pub mod restore;
+pub mod stats;
+pub mod tags;
+
+pub fn get_render_config() -> RenderConfig {
+    RenderConfig {
+        scroll_down_prefix: Styled::new("▼"),
+        scroll_up_prefix: Styled::new("▲"),
+        ..RenderConfig::default()
+    }
+}
+
+/// Get the answer of a prompt, or `None` if user cancelled it.
+pub fn get_answer<T>(result: Result<T, InquireError>) -> Result<Option<T>> {
+    match result {
+        Ok(ans) => Ok(Some(ans)),
+        Err(InquireError::OperationCanceled) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Age of a commit for the pickers, colored by how stale it is.
+pub fn style_age(age: i64) -> StyledObject<String> {
+    let text = format!("{:>5}", format_age(age));
+    match Freshness::of_age(age) {
+        Freshness::Fresh => style(text).green(),
+        Freshness::Aging => style(text).yellow(),
+        Freshness::Stale => style(text).red(),
+    }
+}
+
+/// Get the author format from the command line, else from the settings.
+pub fn get_author_format(settings: &Config, arg: Option<AuthorFormat>) -> Result<AuthorFormat> {
+    if let Some(format) = arg {
+        return Ok(format);
+    }
+    match settings.get_string(AUTHOR_FORMAT) {
+        Ok(s) => s.parse(),
+        Err(_) => Ok(AuthorFormat::default()),
+    }
+}
+
+/// Get the email of the user, for `--mine`.
+pub fn get_user_email(repo: &Repository) -> Result<String> {
+    repo.config()?
+        .get_string("user.email")
+        .map_err(|_| eyre!("--mine requires user.email to be set in Git config"))
+}
+
+/// Get which date to count ages from, from the command line, else from the settings.
+pub fn get_date_kind(settings: &Config, arg: Option<DateKind>) -> Result<DateKind> {
+    if let Some(kind) = arg {
+        return Ok(kind);
+    }
+    match settings.get_string(DATE) {
+        Ok(s) => s.parse(),
+        Err(_) => Ok(DateKind::default()),
+    }
+}
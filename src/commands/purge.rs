@@ -0,0 +1,31 @@
+use color_eyre::Result;
+use eyre::eyre;
+use git2::Config;
+
+use git_del_branches::age::{now, HumanDuration};
+use git_del_branches::config::{get_settings, RETENTION};
+use git_del_branches::gitenv::open_repository;
+use git_del_branches::journal;
+
+use crate::cli::PurgeArgs;
+
+/// Get the retention period of the journal from the settings, if one is set.
+pub fn get_retention(settings: &Config) -> Result<Option<HumanDuration>> {
+    match settings.get_string(RETENTION) {
+        Ok(s) => Ok(Some(s.parse()?)),
+        Err(_) => Ok(None),
+    }
+}
+
+pub fn run(args: &PurgeArgs, no_config: bool) -> Result<()> {
+    let repo = open_repository()?;
+    let older_than = match args.older_than {
+        Some(duration) => duration,
+        None => get_retention(&get_settings(&repo, no_config)?)?.ok_or_else(|| {
+            eyre!("No retention period, give one with --older-than or set {RETENTION}")
+        })?,
+    };
+    let removed = journal::purge(&repo, older_than, now())?;
+    eprintln!("Removed {removed} deletions older than {older_than} from the journal.");
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use std::fs;
+
+use color_eyre::Result;
+use console::style;
+use eyre::Context;
+
+use git_del_branches::age::now;
+use git_del_branches::branch::{get_protected_names, is_protected, list_local_branches, use_dates};
+use git_del_branches::config::get_settings;
+use git_del_branches::gitenv::open_repository;
+use git_del_branches::icon::load_icons;
+use git_del_branches::notify::get_repository_name;
+use git_del_branches::report::{get_file_name, Report};
+
+use super::delete::get_base;
+use super::{get_author_format, get_date_kind};
+use crate::cli::ReportArgs;
+
+pub fn run(args: &ReportArgs, no_config: bool) -> Result<()> {
+    let repo = open_repository()?;
+    let settings = get_settings(&repo, no_config)?;
+    let protected = get_protected_names(&repo);
+    let mut branches = list_local_branches(&repo)?;
+    branches.retain(|b| !is_protected(&protected, &b.name));
+    use_dates(&mut branches, get_date_kind(&settings, args.date)?);
+    let base = get_base(&repo, &settings, args.base.as_deref())?;
+    let author_format = get_author_format(&settings, args.author_format)?;
+    let report = Report::collect(
+        &repo,
+        get_repository_name(&repo),
+        &branches,
+        base.as_ref().map(|(name, id)| (name.as_str(), *id)),
+        args.older_than,
+        author_format,
+        now(),
+    )?
+    .with_icons(&load_icons(&settings)?);
+    if report.count() == 0 {
+        let msg = format!(
+            "No branches have gone without commits for {}.",
+            args.older_than
+        );
+        eprintln!("{}", style(msg).dim());
+    }
+    if args.by_author {
+        for line in report.summarize() {
+            println!("{line}");
+        }
+        return Ok(());
+    }
+    let Some(dir) = &args.output_dir else {
+        print!("{}", report.render(args.format));
+        return Ok(());
+    };
+    fs::create_dir_all(dir).wrap_err_with(|| format!("Cannot create {}", dir.display()))?;
+    for ((author, report), line) in report.split().into_iter().zip(report.summarize()) {
+        let path = dir.join(get_file_name(&author, args.format));
+        fs::write(&path, report.render(args.format))
+            .wrap_err_with(|| format!("Cannot write {}", path.display()))?;
+        println!("{line}: {}", path.display());
+    }
+    Ok(())
+}
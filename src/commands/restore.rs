@@ -0,0 +1,86 @@
+use std::fmt;
+
+use color_eyre::Result;
+use console::{style, Emoji};
+use eyre::eyre;
+use git2::{FetchOptions, Repository};
+use inquire::MultiSelect;
+
+use git_del_branches::config::{get_settings, SHARED_LOG};
+use git_del_branches::gitenv::open_repository;
+use git_del_branches::journal::{self, Entry};
+use git_del_branches::sharedlog;
+
+use super::delete::get_remote_callbacks;
+use super::get_answer;
+use crate::cli::RestoreArgs;
+
+/// Journal entry, displayed for the picker, with who deleted it if it is from the shared log.
+struct Item {
+    entry: Entry,
+    user: Option<String>,
+}
+
+impl fmt::Display for Item {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let short_sha = &self.entry.tip.to_string()[..7];
+        write!(f, "{} ({short_sha})", self.entry.name)?;
+        if let Some(user) = &self.user {
+            write!(f, " {}", style(format!("deleted by {user}")).dim())?;
+        }
+        Ok(())
+    }
+}
+
+/// Fetch the shared log and its deleted branches, and list those which can be restored.
+fn list_shared(repo: &Repository, no_config: bool) -> Result<Vec<Item>> {
+    let remote = get_settings(repo, no_config)?
+        .get_string(SHARED_LOG)
+        .map_err(|_| eyre!("No shared log, set {SHARED_LOG} to the remote which has it"))?;
+    let mut fetch_options = FetchOptions::new();
+    fetch_options.remote_callbacks(get_remote_callbacks(repo)?);
+    sharedlog::fetch(repo, &remote, &mut fetch_options)?;
+    Ok(sharedlog::list_restorable(repo)?
+        .into_iter()
+        .map(|e| Item {
+            entry: e.entry,
+            user: Some(e.user),
+        })
+        .collect())
+}
+
+pub fn run(args: &RestoreArgs, no_config: bool) -> Result<()> {
+    let repo = open_repository()?;
+    let items = match args.shared {
+        true => list_shared(&repo, no_config)?,
+        false => journal::list_restorable(&repo)?
+            .into_iter()
+            .map(|entry| Item { entry, user: None })
+            .collect(),
+    };
+    if items.is_empty() {
+        eprintln!("No deleted branches to restore.");
+        return Ok(());
+    }
+    let Some(selected) =
+        get_answer(MultiSelect::new("Select branches to restore", items).prompt())?
+    else {
+        return Ok(());
+    };
+    for Item { entry, .. } in selected {
+        match journal::restore(&repo, &entry) {
+            Ok(()) => eprintln!(
+                " {} {}",
+                entry.name,
+                style(&entry.tip.to_string()[..7]).dim()
+            ),
+            Err(e) => {
+                eprintln!("  {}", style(e.message()).dim());
+                let msg = format!("Failed to restore branch {}", entry.name);
+                eprintln!("{} {}", Emoji("⚠️", "!"), style(msg).yellow());
+            }
+        }
+    }
+    eprintln!("{} {}", Emoji("🎉", "v"), style("Done!").bright().green());
+    Ok(())
+}
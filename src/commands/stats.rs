@@ -0,0 +1,67 @@
+use color_eyre::Result;
+use console::style;
+
+use git_del_branches::age::now;
+use git_del_branches::branch::{list_local_branches, use_dates};
+use git_del_branches::config::get_settings;
+use git_del_branches::gitenv::open_repository;
+use git_del_branches::stats::{self, AGE_BUCKETS};
+
+use super::delete::get_base;
+use super::{get_author_format, get_date_kind};
+use crate::cli::StatsArgs;
+
+fn print_section(title: &str, rows: &[(&str, usize)]) {
+    println!("{}", style(title).bold());
+    let width = rows
+        .iter()
+        .map(|(n, _)| n.chars().count())
+        .max()
+        .unwrap_or(0);
+    for (name, count) in rows {
+        println!("  {name:<width$}  {count:>5}");
+    }
+}
+
+pub fn run(args: &StatsArgs, no_config: bool) -> Result<()> {
+    let repo = open_repository()?;
+    let settings = get_settings(&repo, no_config)?;
+    let mut branches = list_local_branches(&repo)?;
+    use_dates(&mut branches, get_date_kind(&settings, args.date)?);
+    let base = get_base(&repo, &settings, args.base.as_deref())?;
+    let author_format = get_author_format(&settings, args.author_format)?;
+    let base_id = base.as_ref().map(|(_, id)| *id);
+    let stats = stats::collect(&repo, &branches, base_id, author_format, now())?;
+    println!("{} local branches", stats.total);
+    if let Some((name, _)) = &base {
+        println!();
+        let mut rows = vec![("merged", stats.merged), ("unmerged", stats.unmerged)];
+        // Only shallow clones have unknowns
+        if stats.merge_unknown > 0 {
+            rows.push(("unknown", stats.merge_unknown));
+        }
+        print_section(&format!("Merged into {name}"), &rows);
+    }
+    println!();
+    let rows = [
+        ("tracking", stats.with_upstream),
+        ("gone", stats.upstream_gone),
+        ("none", stats.without_upstream),
+    ];
+    print_section("Upstream", &rows);
+    println!();
+    let rows: Vec<(&str, usize)> = AGE_BUCKETS
+        .iter()
+        .zip(&stats.by_age)
+        .map(|((_, name), count)| (*name, *count))
+        .collect();
+    print_section("Age of last commit", &rows);
+    println!();
+    let rows: Vec<(&str, usize)> = stats
+        .by_author
+        .iter()
+        .map(|(name, count)| (name.as_str(), *count))
+        .collect();
+    print_section("Author of last commit", &rows);
+    Ok(())
+}
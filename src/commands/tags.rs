@@ -0,0 +1,136 @@
+use std::fmt;
+use std::process::ExitCode;
+
+use color_eyre::Result;
+use console::{style, Emoji};
+use git2::PushOptions;
+
+use git_del_branches::age::{is_older_than, now};
+use git_del_branches::config::get_settings;
+use git_del_branches::gitenv::open_repository;
+use git_del_branches::pattern::matches_any;
+use git_del_branches::tag::{delete_local_tag, delete_remote_tag, list_tags, TagInfo};
+
+use super::delete::{get_key_map, get_remote_callbacks, warn_failure};
+use super::style_age;
+use crate::cli::TagsArgs;
+use crate::picker::{Picker, Value};
+
+/// Exit code when some tags could not be deleted.
+const EXIT_PARTIAL_FAILURE: u8 = 2;
+
+/// Tag, displayed for the picker.
+struct Item {
+    tag: TagInfo,
+    /// Seconds since the tag was made.
+    age: i64,
+    name_width: usize,
+}
+
+impl fmt::Display for Item {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name_width = self.name_width;
+        write!(f, "{:<name_width$} {}", self.tag.name, style_age(self.age))?;
+        if self.tag.annotated {
+            write!(f, " {}", Emoji("📝", "[annotated]"))?;
+        }
+        Ok(())
+    }
+}
+
+pub fn run(args: &TagsArgs, no_config: bool) -> Result<ExitCode> {
+    let repo = open_repository()?;
+    let now = now();
+    let mut tags = list_tags(&repo)?;
+    if !args.pattern.is_empty() {
+        tags.retain(|t| matches_any(&args.pattern, &t.name));
+    }
+    if let Some(duration) = args.older_than {
+        tags.retain(|t| is_older_than(t.time, duration, now));
+    }
+    if tags.is_empty() {
+        eprintln!("No tags to delete.");
+        return Ok(ExitCode::SUCCESS);
+    }
+    // Fail early if the remote is wrong
+    let mut remote = match &args.remote {
+        Some(name) => Some(repo.find_remote(name)?),
+        None => None,
+    };
+    let name_width = tags
+        .iter()
+        .map(|t| t.name.chars().count())
+        .max()
+        .unwrap_or(0);
+    let items: Vec<Item> = tags
+        .into_iter()
+        .map(|tag| Item {
+            age: now - tag.time,
+            tag,
+            name_width,
+        })
+        .collect();
+    let picker = Picker::new("Select tags to delete", items)
+        .with_keys(get_key_map(&get_settings(&repo, no_config)?)?)
+        .with_legend(vec![
+            (
+                style("age").green().to_string(),
+                "of the tag: green under a week, yellow under 3 months, else red".into(),
+            ),
+            (
+                Emoji("📝", "[annotated]").to_string(),
+                "annotated tag, with a message".into(),
+            ),
+        ])
+        .with_fields(|i: &Item| {
+            let flags = if i.tag.annotated {
+                vec!["annotated"]
+            } else {
+                Vec::new()
+            };
+            vec![
+                ("name", Value::Text(i.tag.name.clone())),
+                ("age", Value::Age(i.age)),
+                ("is", Value::Flags(flags)),
+            ]
+        })
+        .with_sort("oldest first", |a: &Item, b: &Item| {
+            a.tag.time.cmp(&b.tag.time)
+        })
+        .with_sort("newest first", |a: &Item, b: &Item| {
+            b.tag.time.cmp(&a.tag.time)
+        })
+        .with_formatter(|i: &Item| i.tag.name.clone());
+    let Some(selected) = picker.prompt()? else {
+        return Ok(ExitCode::SUCCESS);
+    };
+    let mut push_options = PushOptions::new();
+    push_options.remote_callbacks(get_remote_callbacks(&repo)?);
+    let mut failed = 0;
+    for item in selected {
+        let tag = &item.tag;
+        let short_sha = &tag.id.to_string()[..7];
+        if let Err(e) = delete_local_tag(&repo, &tag.name) {
+            warn_failure(&e.into(), &format!("Failed to delete tag {}", tag.name));
+            failed += 1;
+            continue;
+        }
+        eprintln!("Deleted tag {} (was {short_sha})", tag.name);
+        if let Some(remote) = remote.as_mut() {
+            let remote_name = remote.name().unwrap_or_default().to_string();
+            match delete_remote_tag(remote, &tag.name, &mut push_options) {
+                Ok(()) => eprintln!("  Deleted it on {remote_name}"),
+                Err(e) => {
+                    let msg = format!("Failed to delete tag {} on {remote_name}", tag.name);
+                    warn_failure(&e.into(), &msg);
+                    failed += 1;
+                }
+            }
+        }
+    }
+    if failed > 0 {
+        return Ok(ExitCode::from(EXIT_PARTIAL_FAILURE));
+    }
+    eprintln!("{} {}", Emoji("🎉", "v"), style("Done!").bright().green());
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,221 @@
+//! Settings of git-del-branches, stored in Git config under the "del-branches" section.
+
+use git2::{Config, ConfigLevel, Repository};
+
+/// Git config section of our settings.
+pub const SECTION: &str = "del-branches";
+
+pub const UPDATE_CHECK: &str = "del-branches.updateCheck";
+pub const PRE_DELETE_HOOK: &str = "del-branches.preDeleteHook";
+pub const POST_DELETE_HOOK: &str = "del-branches.postDeleteHook";
+pub const PROTECT_COMMAND: &str = "del-branches.protectCommand";
+pub const WEBHOOK_URL: &str = "del-branches.webhookUrl";
+pub const UNDO_SCRIPT: &str = "del-branches.undoScript";
+pub const RETENTION: &str = "del-branches.retention";
+pub const SHARED_LOG: &str = "del-branches.sharedLog";
+pub const POLICY: &str = "del-branches.policy";
+pub const BACKEND: &str = "del-branches.backend";
+pub const BASE: &str = "del-branches.base";
+pub const AUTHOR_FORMAT: &str = "del-branches.authorFormat";
+pub const DATE: &str = "del-branches.date";
+pub const KEEP_ACTIVE: &str = "del-branches.keepActive";
+pub const ICON: &str = "del-branches.icon";
+pub const HOSTING: &str = "del-branches.hosting";
+pub const HOSTING_URL: &str = "del-branches.hostingUrl";
+pub const HOSTING_USER: &str = "del-branches.hostingUser";
+pub const JIRA_URL: &str = "del-branches.jiraUrl";
+pub const JIRA_USER: &str = "del-branches.jiraUser";
+pub const GITHUB_CLIENT_ID: &str = "del-branches.githubClientId";
+pub const KEYS_TOGGLE: &str = "del-branches.keys.toggle";
+pub const KEYS_CONFIRM: &str = "del-branches.keys.confirm";
+pub const KEYS_SEARCH: &str = "del-branches.keys.search";
+pub const KEYS_SORT: &str = "del-branches.keys.sort";
+pub const KEYS_PREVIEW: &str = "del-branches.keys.preview";
+pub const KEYS_DETAILS: &str = "del-branches.keys.details";
+/// Start of the keys of the saved filter profiles, like "del-branches.profile.weekly.merged".
+pub const PROFILE_PREFIX: &str = "del-branches.profile.";
+/// Start of the keys of the settings for the remotes on a host, like
+/// "del-branches.host.git.example.com.hosting", which win over those for all hosts.
+pub const HOST_PREFIX: &str = "del-branches.host.";
+
+pub struct Setting {
+    pub key: &'static str,
+    pub description: &'static str,
+}
+
+/// All settings we read, for listing by the `config` command.
+pub const SETTINGS: &[Setting] = &[
+    Setting {
+        key: UPDATE_CHECK,
+        description: "Check for a new release on startup (true/false)",
+    },
+    Setting {
+        key: PRE_DELETE_HOOK,
+        description:
+            "Shell command run before deleting each branch, non-zero exit skips the branch",
+    },
+    Setting {
+        key: POST_DELETE_HOOK,
+        description: "Shell command run after deleting each branch",
+    },
+    Setting {
+        key: PROTECT_COMMAND,
+        description: "Shell command run for each branch which could be offered, non-zero exit protects the branch",
+    },
+    Setting {
+        key: WEBHOOK_URL,
+        description: "URL which the summary of each cleanup is posted to as JSON, like a Slack incoming webhook",
+    },
+    Setting {
+        key: UNDO_SCRIPT,
+        description: "File which each cleanup writes the commands restoring the deleted branches to, relative to the repository",
+    },
+    Setting {
+        key: RETENTION,
+        description: "How long the journal keeps the deleted branches for restore, like \"6m\", purged after each cleanup",
+    },
+    Setting {
+        key: SHARED_LOG,
+        description: "Remote to push the log of deletions to, in refs/del-branches/log, for the team to see and restore them",
+    },
+    Setting {
+        key: POLICY,
+        description: "Path to the policy file restricting which branches are offered",
+    },
+    Setting {
+        key: BASE,
+        description: "Branch to compare with for merge status, instead of the default branch",
+    },
+    Setting {
+        key: AUTHOR_FORMAT,
+        description: "How to show the authors: name, email, user (email before @) or both",
+    },
+    Setting {
+        key: DATE,
+        description: "Date of the last commit which ages are counted from (committer/author)",
+    },
+    Setting {
+        key: KEEP_ACTIVE,
+        description: "Never offer the branches with commits newer than this, like \"3d\", unless --keep-active says otherwise",
+    },
+    Setting {
+        key: ICON,
+        description: "Glyph shown before the branches whose name starts with a prefix, and maybe their color, like \"hotfix/ 🔥 red\", one value per prefix",
+    },
+    Setting {
+        key: BACKEND,
+        description: "Git implementation for listing and deleting branches (libgit2/gix/git)",
+    },
+    Setting {
+        key: HOSTING,
+        description: "Hosting service of the remotes, whose REST API tells about pull requests and deletes branches (gerrit, azure, gitea, forgejo, github or gitlab)",
+    },
+    Setting {
+        key: HOSTING_URL,
+        description: "Base URL of the REST API of the hosting service, if not found from the remote URL",
+    },
+    Setting {
+        key: HOSTING_USER,
+        description: "Username for the REST API of the hosting service, if not the one of the remote URL",
+    },
+    Setting {
+        key: JIRA_URL,
+        description: "Base URL of the Jira which the keys in branch names, like PROJ-123, are asked to with --issues",
+    },
+    Setting {
+        key: JIRA_USER,
+        description: "Email of the Jira Cloud user of the API token, unset for a personal access token of a Jira server",
+    },
+    Setting {
+        key: GITHUB_CLIENT_ID,
+        description: "Client ID of the GitHub OAuth app which `auth login` asks a token for",
+    },
+    Setting {
+        key: KEYS_TOGGLE,
+        description: "Key to select a branch in the picker, like \"x\" or \"ctrl-t\" (space)",
+    },
+    Setting {
+        key: KEYS_CONFIRM,
+        description: "Key to confirm the selection in the picker (enter)",
+    },
+    Setting {
+        key: KEYS_SEARCH,
+        description: "Key to start filtering the picker by name (/)",
+    },
+    Setting {
+        key: KEYS_SORT,
+        description: "Key to change the order of the picker (s)",
+    },
+    Setting {
+        key: KEYS_PREVIEW,
+        description: "Key to review the changes of the highlighted branch (p)",
+    },
+    Setting {
+        key: KEYS_DETAILS,
+        description: "Key to show all that is known about the highlighted branch (tab)",
+    },
+];
+
+/// The Git config to read our settings from: that of the repository, or with `no_config`, for
+/// `--no-config`, only the `git -c` overrides, which count like the command line options, so
+/// that the tool behaves the same for everyone. Not for the settings of Git, like
+/// `user.email`, which are always read.
+pub fn get_settings(repo: &Repository, no_config: bool) -> Result<Config, git2::Error> {
+    let config = repo.config()?;
+    match no_config {
+        true => config
+            .open_level(ConfigLevel::App)
+            .or_else(|_| Config::new()),
+        false => Ok(config),
+    }
+}
+
+/// Expand a short setting name, like "updateCheck", "keys.sort", "profile.weekly.sort" or
+/// "host.example.com.url", to the full Git config key.
+pub fn get_full_key(name: &str) -> String {
+    let full = format!("{SECTION}.{name}");
+    if !name.contains('.')
+        || SETTINGS.iter().any(|s| s.key == full)
+        || full.starts_with(PROFILE_PREFIX)
+        || full.starts_with(HOST_PREFIX)
+    {
+        full
+    } else {
+        name.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gitenv::apply_config_overrides;
+    use crate::testutil::init_repo;
+
+    #[test]
+    fn expands_short_name() {
+        assert_eq!(get_full_key("updateCheck"), UPDATE_CHECK);
+        assert_eq!(get_full_key("del-branches.updateCheck"), UPDATE_CHECK);
+        assert_eq!(get_full_key("keys.sort"), KEYS_SORT);
+        assert_eq!(
+            get_full_key("profile.weekly.sort"),
+            "del-branches.profile.weekly.sort"
+        );
+        assert_eq!(
+            get_full_key("host.git.example.com.hosting"),
+            "del-branches.host.git.example.com.hosting"
+        );
+        assert_eq!(get_full_key("push.gpgSign"), "push.gpgSign");
+    }
+
+    #[test]
+    fn ignores_settings() {
+        let (_dir, repo) = init_repo();
+        repo.config().unwrap().set_str(BASE, "develop").unwrap();
+        let base = |no_config| get_settings(&repo, no_config).unwrap().get_string(BASE);
+        assert_eq!(base(false).unwrap(), "develop");
+        assert!(base(true).is_err());
+        let overrides = [(BASE.to_string(), "trunk".to_string())];
+        let _file = apply_config_overrides(&repo, &overrides).unwrap();
+        assert_eq!(base(true).unwrap(), "trunk");
+    }
+}
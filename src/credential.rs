@@ -0,0 +1,124 @@
+//! Credentials from `git credential`, which runs the credential helpers the way Git does.
+//! Git for Windows keeps HTTPS credentials in the Windows Credential Manager, via a helper
+//! which libgit2 can neither find nor run.
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use git2::Repository;
+
+use crate::gitenv::set_git_dirs;
+
+/// Credentials for pushes, asked from Git once. If they are refused, and libgit2 asks again,
+/// Git is told to forget them.
+pub struct GitCredentials {
+    git_dir: PathBuf,
+    work_tree: Option<PathBuf>,
+    /// The lines describing the credentials Git gave, to give back to `git credential
+    /// approve` or `reject`.
+    given: Option<String>,
+    asked: bool,
+}
+
+impl GitCredentials {
+    pub fn new(repo: &Repository) -> Self {
+        Self {
+            git_dir: repo.path().to_path_buf(),
+            work_tree: repo.workdir().map(PathBuf::from),
+            given: None,
+            asked: false,
+        }
+    }
+
+    /// Run `git credential <action>`, giving it the description. Returns its output, or
+    /// `None` if it fails.
+    fn run(&self, action: &str, description: &str) -> Option<String> {
+        let mut cmd = Command::new("git");
+        set_git_dirs(&mut cmd, &self.git_dir, self.work_tree.as_deref());
+        let mut child = cmd
+            .args(["credential", action])
+            // We ask on the terminal ourselves if Git has nothing
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+        child.stdin.take()?.write_all(description.as_bytes()).ok()?;
+        let output = child.wait_with_output().ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        String::from_utf8(output.stdout).ok()
+    }
+
+    /// Get a username and password for the URL, from the helpers of the Git setup. Returns
+    /// `None` if they have none, or when asked again, as the ones given before were refused.
+    pub fn fill(&mut self, url: &str, username: Option<&str>) -> Option<(String, String)> {
+        if let Some(refused) = self.given.take() {
+            self.run("reject", &refused);
+        }
+        if self.asked {
+            return None;
+        }
+        self.asked = true;
+        let mut description = format!("url={url}\n");
+        if let Some(name) = username {
+            description.push_str(&format!("username={name}\n"));
+        }
+        description.push('\n');
+        let output = self.run("fill", &description)?;
+        let value = |key: &str| {
+            output
+                .lines()
+                .find_map(|l| l.strip_prefix(key)?.strip_prefix('='))
+                .map(str::to_string)
+        };
+        let (username, password) = (value("username")?, value("password")?);
+        self.given = Some(format!("{output}\n"));
+        Some((username, password))
+    }
+
+    /// Tell Git that the credentials it gave worked, for its helpers to store them.
+    pub fn approve(&mut self) {
+        if let Some(given) = self.given.take() {
+            self.run("approve", &given);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::init_repo;
+
+    #[test]
+    fn fills_from_helper() {
+        let (dir, repo) = init_repo();
+        let log = dir.path().join("helper.log");
+        let helper = format!(
+            "!f() {{ echo \"$1\" >> '{}'; test \"$1\" = get && echo username=bot && echo password=secret; }}; f",
+            log.display()
+        );
+        repo.config()
+            .unwrap()
+            .set_str("credential.helper", &helper)
+            .unwrap();
+        let mut credentials = GitCredentials::new(&repo);
+        let url = "https://example.com/repo.git";
+        assert_eq!(
+            credentials.fill(url, None),
+            Some(("bot".to_string(), "secret".to_string()))
+        );
+        // Asked again, so they were refused
+        assert_eq!(credentials.fill(url, None), None);
+        let mut credentials = GitCredentials::new(&repo);
+        assert!(credentials.fill(url, Some("bot")).is_some());
+        credentials.approve();
+        assert_eq!(
+            std::fs::read_to_string(&log).unwrap(),
+            "get\nerase\nget\nstore\n"
+        );
+    }
+}
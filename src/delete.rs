@@ -0,0 +1,417 @@
+//! Deleting branches, locally and on the remote.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use eyre::Result;
+use git2::{BranchType, ConfigLevel, Direction, PushOptions, Remote, RemoteCallbacks, Repository};
+
+use crate::branch::Upstream;
+
+pub fn delete_local_branch(repo: &Repository, name: &str) -> Result<(), git2::Error> {
+    let mut branch = repo.find_branch(name, BranchType::Local)?;
+    // Not Branch::delete(), which removes the branch config via repo.config(). Its writes go to
+    // the file of `git -c` overrides when there is one, and the deletion fails.
+    branch.get_mut().delete()?;
+    remove_branch_config(repo, name)
+}
+
+/// Remove the "branch.<name>.*" settings from the repository config.
+pub fn remove_branch_config(repo: &Repository, name: &str) -> Result<(), git2::Error> {
+    let mut config = repo.config()?.open_level(ConfigLevel::Local)?;
+    let prefix = format!("branch.{name}.");
+    let mut keys: Vec<String> = Vec::new();
+    config.entries(None)?.for_each(|entry| {
+        let Some(key) = entry.name() else {
+            return;
+        };
+        // The branch name may contain dots, but the variable name doesn't
+        let is_ours = key.strip_prefix(&prefix).is_some_and(|v| !v.contains('.'));
+        if is_ours && !keys.iter().any(|k| k == key) {
+            keys.push(key.to_string());
+        }
+    })?;
+    for key in keys {
+        config.remove_multivar(&key, ".*")?;
+    }
+    Ok(())
+}
+
+/// Refspec which deletes the branch when pushed.
+pub fn get_deletion_refspec(name: &str) -> String {
+    format!(":refs/heads/{name}")
+}
+
+/// URL which the pushes to the remote go to. libgit2 has already rewritten it with the
+/// `url.<base>.insteadOf` and `pushInsteadOf` rules when looking up the remote.
+pub fn get_push_url<'r>(remote: &'r Remote) -> Option<&'r str> {
+    remote.pushurl().or(remote.url())
+}
+
+/// Whether the remote is set as a mirror, with `remote.<name>.mirror`: `git push` then pushes
+/// all the refs there, and deletes those which we don't have.
+pub fn is_mirror(repo: &Repository, remote: &str) -> bool {
+    repo.config()
+        .and_then(|c| c.get_bool(&format!("remote.{remote}.mirror")))
+        .unwrap_or(false)
+}
+
+/// Name of the branch which HEAD of the remote points to, its default branch. It is asked to
+/// the server, as our `refs/remotes/<remote>/HEAD` may be stale or missing, else taken from it.
+pub fn find_remote_default_branch(
+    repo: &Repository,
+    remote: &str,
+    callbacks: RemoteCallbacks,
+) -> Option<String> {
+    let asked = repo.find_remote(remote).and_then(|mut r| {
+        let connection = r.connect_auth(Direction::Fetch, Some(callbacks), None)?;
+        let head = connection.default_branch()?;
+        Ok(head.as_str().map(str::to_string))
+    });
+    if let Some(name) = asked.ok().flatten() {
+        return Some(
+            name.strip_prefix("refs/heads/")
+                .unwrap_or(&name)
+                .to_string(),
+        );
+    }
+    let tracked = repo
+        .find_reference(&format!("refs/remotes/{remote}/HEAD"))
+        .ok()?;
+    let target = tracked.symbolic_target()?;
+    Some(
+        target
+            .strip_prefix(&format!("refs/remotes/{remote}/"))?
+            .to_string(),
+    )
+}
+
+/// Names of the branches on the server of the remote, like `git ls-remote --heads` tells.
+pub fn list_server_branches(
+    repo: &Repository,
+    remote: &str,
+    callbacks: RemoteCallbacks,
+) -> Result<Vec<String>, git2::Error> {
+    let mut remote = repo.find_remote(remote)?;
+    let connection = remote.connect_auth(Direction::Fetch, Some(callbacks), None)?;
+    let names = connection
+        .list()?
+        .iter()
+        .filter_map(|head| head.name().strip_prefix("refs/heads/").map(str::to_string))
+        .collect();
+    Ok(names)
+}
+
+/// The refs which the server refused to update in a push, with its reason for each. libgit2
+/// tells them only to the `push_update_reference` callback, which is to record them here.
+#[derive(Debug, Clone, Default)]
+pub struct Rejections(Rc<RefCell<Vec<(String, String)>>>);
+
+impl Rejections {
+    /// Record the status of the ref given to `push_update_reference`, `None` if it was updated.
+    pub fn record(&self, refname: &str, status: Option<&str>) {
+        if let Some(reason) = status {
+            let rejection = (refname.to_string(), reason.to_string());
+            self.0.borrow_mut().push(rejection);
+        }
+    }
+
+    fn take(&self) -> Vec<(String, String)> {
+        self.0.take()
+    }
+}
+
+/// Error of a push which the server refused for some of the branches, which the others were
+/// deleted with.
+#[derive(Debug)]
+pub struct PushRejected(Vec<(String, String)>);
+
+impl PushRejected {
+    /// Why the server refused to delete the branch, if it did.
+    pub fn find_reason(&self, name: &str) -> Option<&str> {
+        let refname = format!("refs/heads/{name}");
+        self.0
+            .iter()
+            .find(|(r, _)| *r == refname)
+            .map(|(_, reason)| reason.as_str())
+    }
+}
+
+impl fmt::Display for PushRejected {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let refs: Vec<String> = self
+            .0
+            .iter()
+            .map(|(refname, reason)| format!("{refname} ({reason})"))
+            .collect();
+        write!(f, "The server refused to delete {}", refs.join(", "))
+    }
+}
+
+impl std::error::Error for PushRejected {}
+
+/// Delete the branch on the remote server, then our remote-tracking branch of it.
+pub fn delete_upstream_branch(
+    repo: &Repository,
+    remote: &mut Remote,
+    upstream: &Upstream,
+    opts: &mut PushOptions,
+    rejections: &Rejections,
+) -> Result<()> {
+    delete_upstream_branches(repo, remote, &[upstream], opts, rejections)
+}
+
+/// Delete the branches of the remote with a single push, then our remote-tracking branches.
+/// The callbacks of `opts` are to record the refs which the server refuses in `rejections`:
+/// the push succeeds without them, and they make it fail with `PushRejected`.
+pub fn delete_upstream_branches(
+    repo: &Repository,
+    remote: &mut Remote,
+    upstreams: &[&Upstream],
+    opts: &mut PushOptions,
+    rejections: &Rejections,
+) -> Result<()> {
+    let refspecs: Vec<String> = upstreams
+        .iter()
+        .map(|u| get_deletion_refspec(&u.name))
+        .collect();
+    // Those of an earlier push
+    rejections.take();
+    match remote.pushurl() {
+        // Pushing over the local transport of libgit2 goes to the fetch URL, so it is done
+        // with a remote of the push URL only
+        Some(url) if Some(url) != remote.url() => {
+            repo.remote_anonymous(url)?.push(&refspecs, Some(opts))?
+        }
+        _ => remote.push(&refspecs, Some(opts))?,
+    }
+    let rejected = PushRejected(rejections.take());
+    // The push normally updates the remote-tracking branches, but let's be sure.
+    for upstream in upstreams {
+        if rejected.find_reason(&upstream.name).is_none() {
+            delete_tracking_branch(repo, upstream)?;
+        }
+    }
+    match rejected.0.is_empty() {
+        true => Ok(()),
+        false => Err(rejected.into()),
+    }
+}
+
+/// Delete our remote-tracking branch of the upstream, if we have it.
+pub fn delete_tracking_branch(repo: &Repository, upstream: &Upstream) -> Result<(), git2::Error> {
+    if let Ok(mut tracking) = repo.find_branch(&upstream.to_string(), BranchType::Remote) {
+        tracking.delete()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gitenv::apply_config_overrides;
+    use crate::testutil::{commit, init_bare_repo, init_repo, serve_git};
+
+    #[test]
+    fn deletes_local_branch() {
+        let (_dir, repo) = init_repo();
+        commit(&repo, "refs/heads/feature", None);
+        delete_local_branch(&repo, "feature").unwrap();
+        assert!(repo.find_branch("feature", BranchType::Local).is_err());
+        assert!(delete_local_branch(&repo, "feature").is_err());
+    }
+
+    #[test]
+    fn finds_mirrors() {
+        let (_dir, repo) = init_repo();
+        repo.remote("origin", "https://example.com/app.git")
+            .unwrap();
+        repo.remote("backup", "https://example.com/backup.git")
+            .unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_bool("remote.backup.mirror", true).unwrap();
+        assert!(is_mirror(&repo, "backup"));
+        assert!(!is_mirror(&repo, "origin"));
+    }
+
+    #[test]
+    fn finds_remote_default_branch() {
+        let (_server_dir, server) = init_bare_repo();
+        commit(&server, "refs/heads/trunk", None);
+        server.set_head("refs/heads/trunk").unwrap();
+        let (_dir, repo) = init_repo();
+        repo.remote("origin", server.path().to_str().unwrap())
+            .unwrap();
+        let found = find_remote_default_branch(&repo, "origin", RemoteCallbacks::new());
+        assert_eq!(found.as_deref(), Some("trunk"));
+        let names = list_server_branches(&repo, "origin", RemoteCallbacks::new()).unwrap();
+        assert_eq!(names, ["trunk"]);
+        // The server cannot be reached, so our symref tells
+        commit(&repo, "refs/remotes/gone/main", None);
+        repo.remote("gone", "/nonexistent/repo.git").unwrap();
+        repo.reference_symbolic("refs/remotes/gone/HEAD", "refs/remotes/gone/main", true, "")
+            .unwrap();
+        let found = find_remote_default_branch(&repo, "gone", RemoteCallbacks::new());
+        assert_eq!(found.as_deref(), Some("main"));
+    }
+
+    #[test]
+    fn deletes_branch_config_with_overrides() {
+        let (_dir, repo) = init_repo();
+        commit(&repo, "refs/heads/fix/v1.2", None);
+        commit(&repo, "refs/heads/fix/v1", None);
+        let mut config = repo.config().unwrap();
+        config.set_str("branch.fix/v1.2.remote", "origin").unwrap();
+        config.set_str("branch.fix/v1.remote", "origin").unwrap();
+        let overrides = [("foo.bar".to_string(), "1".to_string())];
+        let _file = apply_config_overrides(&repo, &overrides).unwrap();
+        delete_local_branch(&repo, "fix/v1.2").unwrap();
+        let config = repo.config().unwrap().snapshot().unwrap();
+        assert!(config.get_string("branch.fix/v1.2.remote").is_err());
+        assert_eq!(config.get_string("branch.fix/v1.remote").unwrap(), "origin");
+    }
+
+    #[test]
+    fn deletes_upstream_branch() {
+        let (_server_dir, server) = init_bare_repo();
+        let tip = commit(&server, "refs/heads/feature", None);
+        commit(&server, "refs/heads/main", None);
+        server.set_head("refs/heads/main").unwrap();
+        let (_dir, repo) = init_repo();
+        let url = server.path().to_str().unwrap();
+        let mut remote = repo.remote("origin", url).unwrap();
+        remote
+            .fetch(&["refs/heads/*:refs/remotes/origin/*"], None, None)
+            .unwrap();
+        assert_eq!(
+            repo.refname_to_id("refs/remotes/origin/feature").unwrap(),
+            tip
+        );
+        let upstream = Upstream {
+            remote: "origin".into(),
+            name: "feature".into(),
+        };
+        delete_upstream_branch(
+            &repo,
+            &mut remote,
+            &upstream,
+            &mut PushOptions::new(),
+            &Rejections::default(),
+        )
+        .unwrap();
+        assert!(server.find_branch("feature", BranchType::Local).is_err());
+        assert!(repo
+            .find_branch("origin/feature", BranchType::Remote)
+            .is_err());
+    }
+
+    #[test]
+    fn pushes_to_rewritten_url() {
+        let (_server_dir, server) = init_bare_repo();
+        commit(&server, "refs/heads/feature", None);
+        let (_dir, repo) = init_repo();
+        let base = server.path().to_str().unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("remote.origin.url", "corp:").unwrap();
+        config.set_str("remote.mirror.url", "mirror:").unwrap();
+        config
+            .set_str(&format!("url.{base}.insteadOf"), "corp:")
+            .unwrap();
+        config
+            .set_str(&format!("url.{base}.pushInsteadOf"), "mirror:")
+            .unwrap();
+        let mirror = repo.find_remote("mirror").unwrap();
+        assert_eq!(get_push_url(&mirror), Some(base));
+        let mut remote = repo.find_remote("origin").unwrap();
+        assert_eq!(get_push_url(&remote), Some(base));
+        let upstream = Upstream {
+            remote: "origin".into(),
+            name: "feature".into(),
+        };
+        delete_upstream_branch(
+            &repo,
+            &mut remote,
+            &upstream,
+            &mut PushOptions::new(),
+            &Rejections::default(),
+        )
+        .unwrap();
+        assert!(server.find_branch("feature", BranchType::Local).is_err());
+    }
+
+    #[test]
+    fn pushes_to_push_url() {
+        let (_server_dir, server) = init_bare_repo();
+        commit(&server, "refs/heads/feature", None);
+        let (_fork_dir, fork) = init_bare_repo();
+        commit(&fork, "refs/heads/feature", None);
+        let (_dir, repo) = init_repo();
+        repo.remote("origin", server.path().to_str().unwrap())
+            .unwrap();
+        repo.remote_set_pushurl("origin", fork.path().to_str())
+            .unwrap();
+        let mut remote = repo.find_remote("origin").unwrap();
+        let upstream = Upstream {
+            remote: "origin".into(),
+            name: "feature".into(),
+        };
+        delete_upstream_branch(
+            &repo,
+            &mut remote,
+            &upstream,
+            &mut PushOptions::new(),
+            &Rejections::default(),
+        )
+        .unwrap();
+        assert!(fork.find_branch("feature", BranchType::Local).is_err());
+        assert!(server.find_branch("feature", BranchType::Local).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn fails_on_refused_deletions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let server_dir = tempfile::TempDir::new().unwrap();
+        let server = Repository::init_bare(server_dir.path().join("app.git")).unwrap();
+        commit(&server, "refs/heads/kept", None);
+        commit(&server, "refs/heads/feature", None);
+        let hook = server.path().join("hooks/pre-receive");
+        let script = "#!/bin/sh\nif grep -q refs/heads/kept; then echo kept >&2; exit 1; fi\n";
+        std::fs::write(&hook, script).unwrap();
+        std::fs::set_permissions(&hook, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let daemon = serve_git(server_dir.path());
+        let (_dir, repo) = init_repo();
+        let url = format!("{}/app.git", daemon.url);
+        let mut remote = repo.remote("origin", &url).unwrap();
+        remote
+            .fetch(&["refs/heads/*:refs/remotes/origin/*"], None, None)
+            .unwrap();
+        let rejections = Rejections::default();
+        let recorder = rejections.clone();
+        let mut callbacks = RemoteCallbacks::new();
+        callbacks.push_update_reference(move |refname, status| {
+            recorder.record(refname, status);
+            Ok(())
+        });
+        let mut opts = PushOptions::new();
+        opts.remote_callbacks(callbacks);
+        let upstream = |name: &str| Upstream {
+            remote: "origin".into(),
+            name: name.into(),
+        };
+        let (kept, feature) = (upstream("kept"), upstream("feature"));
+        let e = delete_upstream_branches(&repo, &mut remote, &[&kept], &mut opts, &rejections)
+            .unwrap_err();
+        let rejected = e.downcast_ref::<PushRejected>().unwrap();
+        assert!(rejected.find_reason("kept").is_some(), "{e}");
+        assert!(server.find_branch("kept", BranchType::Local).is_ok());
+        assert!(repo.find_branch("origin/kept", BranchType::Remote).is_ok());
+        delete_upstream_branches(&repo, &mut remote, &[&feature], &mut opts, &rejections).unwrap();
+        assert!(server.find_branch("feature", BranchType::Local).is_err());
+        assert!(repo
+            .find_branch("origin/feature", BranchType::Remote)
+            .is_err());
+    }
+}
@@ -0,0 +1,216 @@
+//! Local branches which depend on another local branch: they track it, or were branched from
+//! it. Deleting the latter leaves them without their upstream or their base.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use git2::{Oid, Repository};
+
+use crate::branch::BranchInfo;
+use crate::merged::is_contained_in;
+
+/// How a branch depends on another one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dependence {
+    /// Its upstream is the other branch, set with `git branch --track <name> <other>`.
+    Tracks,
+    /// Its tip descends from the tip of the other branch.
+    BranchedFrom,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dependent {
+    pub name: String,
+    pub dependence: Dependence,
+}
+
+/// Whether the branch `name` tracks the local branch `target`. Its remote is then ".".
+fn tracks(repo: &Repository, name: &str, target: &str) -> bool {
+    let Ok(config) = repo.config() else {
+        return false;
+    };
+    let remote = config.get_string(&format!("branch.{name}.remote"));
+    let merge = config.get_string(&format!("branch.{name}.merge"));
+    remote.is_ok_and(|r| r == ".")
+        && merge.is_ok_and(|m| m.strip_prefix("refs/heads/") == Some(target))
+}
+
+/// Find the branches of `locals` which depend on `target`. Once `target` is merged into the
+/// base, the branches made from the base afterwards descend from it too, so only tracking
+/// is checked then.
+pub fn find_dependents(
+    repo: &Repository,
+    target: &BranchInfo,
+    locals: &[BranchInfo],
+    base: Option<Oid>,
+) -> Result<Vec<Dependent>, git2::Error> {
+    let merged = match base {
+        Some(base) => is_contained_in(repo, target.tip, base)?,
+        None => false,
+    };
+    let mut dependents = Vec::new();
+    for branch in locals.iter().filter(|b| b.name != target.name) {
+        let dependence = if tracks(repo, &branch.name, &target.name) {
+            Dependence::Tracks
+        } else if !merged && repo.graph_descendant_of(branch.tip, target.tip)? {
+            Dependence::BranchedFrom
+        } else {
+            continue;
+        };
+        dependents.push(Dependent {
+            name: branch.name.clone(),
+            dependence,
+        });
+    }
+    Ok(dependents)
+}
+
+/// Branches each made from the one before, like in stacked workflows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stack {
+    /// From the bottom up, each branch after the one it was made from.
+    pub branches: Vec<String>,
+}
+
+impl Stack {
+    pub fn contains(&self, name: &str) -> bool {
+        self.branches.iter().any(|b| b == name)
+    }
+}
+
+/// Like "feat ← feat-2 ← feat-3".
+impl fmt::Display for Stack {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.branches.join(" ← "))
+    }
+}
+
+/// Find the stacks among the branches: a branch is on top of the nearest other one which its
+/// tip descends from. Branches merged into the base are left out, since the ones made from
+/// the base afterwards descend from them too. Stacks are sorted by their bottom branch.
+pub fn find_stacks(
+    repo: &Repository,
+    branches: &[BranchInfo],
+    base: Option<Oid>,
+) -> Result<Vec<Stack>, git2::Error> {
+    let mut unmerged = Vec::new();
+    for branch in branches {
+        if !base.map_or(Ok(false), |b| is_contained_in(repo, branch.tip, b))? {
+            unmerged.push(branch);
+        }
+    }
+    // The branch which each one is on top of, by index in `unmerged`
+    let mut parents: Vec<Option<usize>> = Vec::with_capacity(unmerged.len());
+    for branch in &unmerged {
+        let mut ancestors = Vec::new();
+        for (i, other) in unmerged.iter().enumerate() {
+            if other.tip != branch.tip && repo.graph_descendant_of(branch.tip, other.tip)? {
+                ancestors.push(i);
+            }
+        }
+        // The nearest is the one which descends from the others
+        let mut nearest: Option<usize> = None;
+        for i in ancestors {
+            match nearest {
+                Some(n) if !repo.graph_descendant_of(unmerged[i].tip, unmerged[n].tip)? => {}
+                _ => nearest = Some(i),
+            }
+        }
+        parents.push(nearest);
+    }
+    // The bottom of the stack of a branch, and how far it is from it
+    let find_bottom = |mut i: usize| {
+        let mut depth = 0;
+        while let Some(parent) = parents[i] {
+            (i, depth) = (parent, depth + 1);
+        }
+        (i, depth)
+    };
+    let mut by_bottom: HashMap<usize, Vec<(usize, usize)>> = HashMap::new();
+    for i in 0..unmerged.len() {
+        let (bottom, depth) = find_bottom(i);
+        by_bottom.entry(bottom).or_default().push((depth, i));
+    }
+    let mut stacks: Vec<Stack> = by_bottom
+        .into_values()
+        .filter(|members| members.len() > 1)
+        .map(|mut members| {
+            members.sort_by(|a, b| {
+                a.0.cmp(&b.0)
+                    .then_with(|| unmerged[a.1].name.cmp(&unmerged[b.1].name))
+            });
+            Stack {
+                branches: members
+                    .into_iter()
+                    .map(|(_, i)| unmerged[i].name.clone())
+                    .collect(),
+            }
+        })
+        .collect();
+    stacks.sort_by(|a, b| a.branches[0].cmp(&b.branches[0]));
+    Ok(stacks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::branch::list_local_branches;
+    use crate::testutil::{commit, init_repo};
+
+    #[test]
+    fn finds_dependents() {
+        let (_dir, repo) = init_repo();
+        let main = commit(&repo, "refs/heads/main", None);
+        let feat = commit(&repo, "refs/heads/feat", Some(main));
+        commit(&repo, "refs/heads/feat-next", Some(feat));
+        commit(&repo, "refs/heads/other", Some(main));
+        let mut config = repo.config().unwrap();
+        config.set_str("branch.other.remote", ".").unwrap();
+        config
+            .set_str("branch.other.merge", "refs/heads/feat")
+            .unwrap();
+        let branches = list_local_branches(&repo).unwrap();
+        let find = |name| branches.iter().find(|b| b.name == name).unwrap();
+        let dependents = find_dependents(&repo, find("feat"), &branches, Some(main)).unwrap();
+        assert_eq!(
+            dependents,
+            [
+                Dependent {
+                    name: "feat-next".into(),
+                    dependence: Dependence::BranchedFrom,
+                },
+                Dependent {
+                    name: "other".into(),
+                    dependence: Dependence::Tracks,
+                },
+            ]
+        );
+        // Everything made from main descends from it
+        let dependents = find_dependents(&repo, find("main"), &branches, Some(main)).unwrap();
+        assert!(dependents.is_empty());
+        let dependents = find_dependents(&repo, find("main"), &branches, None).unwrap();
+        assert_eq!(dependents.len(), 3);
+    }
+
+    #[test]
+    fn finds_stacks() {
+        let (_dir, repo) = init_repo();
+        let main = commit(&repo, "refs/heads/main", None);
+        let merged = commit(&repo, "refs/heads/merged", Some(main));
+        let main = commit(&repo, "refs/heads/main", Some(merged));
+        let a = commit(&repo, "refs/heads/a", Some(main));
+        let b = commit(&repo, "refs/heads/b", Some(a));
+        let b = commit(&repo, "refs/heads/b", Some(b));
+        commit(&repo, "refs/heads/c", Some(b));
+        commit(&repo, "refs/heads/lone", Some(main));
+        let branches = list_local_branches(&repo).unwrap();
+        let stacks = find_stacks(&repo, &branches, Some(main)).unwrap();
+        assert_eq!(
+            stacks,
+            [Stack {
+                branches: vec!["a".into(), "b".into(), "c".into()]
+            }]
+        );
+        assert_eq!(stacks[0].to_string(), "a ← b ← c");
+    }
+}
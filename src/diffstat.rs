@@ -0,0 +1,173 @@
+//! The work on a branch which is not in the base: its size, and its patches for review.
+
+use std::fmt;
+
+use git2::{DiffFormat, ErrorCode, Oid, Repository, Time};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiffStat {
+    pub insertions: usize,
+    pub deletions: usize,
+    pub files: usize,
+}
+
+impl fmt::Display for DiffStat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.files == 0 {
+            return f.write_str("no unique changes");
+        }
+        let unit = if self.files == 1 { "file" } else { "files" };
+        write!(
+            f,
+            "+{} −{} across {} {unit}",
+            self.insertions, self.deletions, self.files
+        )
+    }
+}
+
+/// Count the changes made on the branch since it forked from the base, like
+/// `git diff --shortstat base...tip`.
+pub fn get_diff_stat(repo: &Repository, tip: Oid, base: Oid) -> Result<DiffStat, git2::Error> {
+    let old_tree = match repo.merge_base(base, tip) {
+        Ok(id) => Some(repo.find_commit(id)?.tree()?),
+        // Unrelated histories, all the work is unique
+        Err(e) if e.code() == ErrorCode::NotFound => None,
+        Err(e) => return Err(e),
+    };
+    let new_tree = repo.find_commit(tip)?.tree()?;
+    let diff = repo.diff_tree_to_tree(old_tree.as_ref(), Some(&new_tree), None)?;
+    let stats = diff.stats()?;
+    Ok(DiffStat {
+        insertions: stats.insertions(),
+        deletions: stats.deletions(),
+        files: stats.files_changed(),
+    })
+}
+
+/// Format the time like `git log --date=iso`, in the time zone of the commit.
+pub fn format_time(time: Time) -> String {
+    let offset = time.offset_minutes();
+    let local = time.seconds() + i64::from(offset) * 60;
+    let (days, secs) = (local.div_euclid(86400), local.rem_euclid(86400));
+    // Civil date from days since epoch, by Howard Hinnant's algorithm
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    let sign = if offset < 0 { '-' } else { '+' };
+    format!(
+        "{year}-{month:02}-{day:02} {:02}:{:02}:{:02} {sign}{:02}{:02}",
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60,
+        offset.abs() / 60,
+        offset.abs() % 60
+    )
+}
+
+/// Print the commits of the branch which are not in the base, with their patches, like
+/// `git log -p base..tip`. Without a base, only the tip commit is shown.
+pub fn get_log_patch(
+    repo: &Repository,
+    tip: Oid,
+    base: Option<Oid>,
+) -> Result<Vec<String>, git2::Error> {
+    let mut walk = repo.revwalk()?;
+    walk.push(tip)?;
+    if let Some(base) = base {
+        walk.hide(base)?;
+    }
+    let limit = if base.is_some() { usize::MAX } else { 1 };
+    let mut out = String::new();
+    for id in walk.take(limit) {
+        let commit = repo.find_commit(id?)?;
+        let author = commit.author();
+        out.push_str(&format!("commit {}\n", commit.id()));
+        out.push_str(&format!(
+            "Author: {} <{}>\n",
+            author.name().unwrap_or_default(),
+            author.email().unwrap_or_default()
+        ));
+        out.push_str(&format!("Date:   {}\n\n", format_time(author.when())));
+        for line in commit.message().unwrap_or_default().lines() {
+            out.push_str(&format!("    {line}\n"));
+        }
+        out.push('\n');
+        // Like `git log -p`, merge commits are shown without patch
+        if commit.parent_count() > 1 {
+            continue;
+        }
+        let parent_tree = match commit.parent_count() {
+            0 => None,
+            _ => Some(commit.parent(0)?.tree()?),
+        };
+        let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?;
+        diff.print(DiffFormat::Patch, |_delta, _hunk, line| {
+            if matches!(line.origin(), '+' | '-' | ' ') {
+                out.push(line.origin());
+            }
+            out.push_str(&String::from_utf8_lossy(line.content()));
+            true
+        })?;
+        out.push('\n');
+    }
+    Ok(out.lines().map(|l| l.replace('\t', "    ")).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{commit_files, init_repo};
+
+    #[test]
+    fn formats_time() {
+        assert_eq!(
+            format_time(Time::new(1_700_000_000, 60)),
+            "2023-11-14 23:13:20 +0100"
+        );
+        assert_eq!(format_time(Time::new(0, -330)), "1969-12-31 18:30:00 -0530");
+    }
+
+    #[test]
+    fn prints_log_patch() {
+        let (_dir, repo) = init_repo();
+        let base = commit_files(&repo, "refs/heads/main", None, &[("a", "1\n")]);
+        let tip = commit_files(&repo, "refs/heads/feature", Some(base), &[("a", "2\n")]);
+        let lines = get_log_patch(&repo, tip, Some(base)).unwrap();
+        assert_eq!(lines[0], format!("commit {tip}"));
+        assert_eq!(lines[1], "Author: Tester <tester@example.com>");
+        assert!(lines.iter().any(|l| l == "-1"));
+        assert!(lines.iter().any(|l| l == "+2"));
+        assert!(!lines.iter().any(|l| l == &format!("commit {base}")));
+        let lines = get_log_patch(&repo, tip, None).unwrap();
+        assert_eq!(lines.iter().filter(|l| l.starts_with("commit ")).count(), 1);
+    }
+
+    #[test]
+    fn counts_unique_work() {
+        let (_dir, repo) = init_repo();
+        let base = commit_files(&repo, "refs/heads/main", None, &[("a", "1\n2\n")]);
+        let first = commit_files(&repo, "refs/heads/feature", Some(base), &[("a", "1\n3\n")]);
+        let tip = commit_files(&repo, "refs/heads/feature", Some(first), &[("b", "x\n")]);
+        let main = commit_files(&repo, "refs/heads/main", Some(base), &[("c", "y\n")]);
+        let stat = get_diff_stat(&repo, tip, main).unwrap();
+        assert_eq!(
+            stat,
+            DiffStat {
+                insertions: 2,
+                deletions: 1,
+                files: 2
+            }
+        );
+        assert_eq!(stat.to_string(), "+2 −1 across 2 files");
+        assert_eq!(
+            get_diff_stat(&repo, base, main).unwrap(),
+            DiffStat::default()
+        );
+    }
+}
@@ -0,0 +1,36 @@
+//! Events of a cleanup, printed on stdout with `--events` as they happen, one JSON object per
+//! line, for the GUIs and scripts wrapping the tool. The `event` field names each:
+//! `branch_listed`, `selected`, `deleting`, `deleted`, `gone`, `failed` and `done`.
+
+use serde_json::{json, Value};
+
+pub struct Events {
+    enabled: bool,
+}
+
+impl Events {
+    pub fn new(enabled: bool) -> Self {
+        Self { enabled }
+    }
+
+    /// Print the event with the fields of the object, if events are asked for.
+    pub fn emit(&self, event: &str, fields: Value) {
+        if !self.enabled {
+            return;
+        }
+        let mut object = json!({ "event": event });
+        if let (Some(object), Value::Object(fields)) = (object.as_object_mut(), fields) {
+            object.extend(fields);
+        }
+        // Stdout is line-buffered, so each event is out as soon as it is printed
+        println!("{object}");
+    }
+
+    /// Print the line for people: on stdout, unless the events take it.
+    pub fn print(&self, line: &str) {
+        match self.enabled {
+            true => eprintln!("{line}"),
+            false => println!("{line}"),
+        }
+    }
+}
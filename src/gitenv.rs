@@ -0,0 +1,314 @@
+//! Integration with the `git` command, for when we are run as `git del-branches`.
+
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use eyre::{Context, Result};
+use git2::{ConfigLevel, Repository, RepositoryOpenFlags};
+use tempfile::NamedTempFile;
+
+/// Name of the folder, inside the Git folder, where we keep our files.
+const DATA_DIR_NAME: &str = "del-branches";
+
+/// Repository opened the way `git` would do, with the `-c` overrides applied.
+pub struct GitRepo {
+    repo: Repository,
+    _overrides_file: Option<NamedTempFile>,
+}
+
+impl Deref for GitRepo {
+    type Target = Repository;
+
+    fn deref(&self) -> &Repository {
+        &self.repo
+    }
+}
+
+/// Open the repository like `git` does: the one in GIT_DIR if set, else the one found from
+/// the current folder. GIT_WORK_TREE and GIT_CEILING_DIRECTORIES are honored too.
+pub fn open_repository() -> Result<GitRepo> {
+    let repo = if env::var_os("GIT_DIR").is_some_and(|d| !d.is_empty()) {
+        Repository::open_from_env().wrap_err("GIT_DIR is not a Git folder")?
+    } else {
+        Repository::open_ext(
+            get_start_dir(),
+            RepositoryOpenFlags::FROM_ENV,
+            &[] as &[&OsStr],
+        )
+        .wrap_err("Not a Git working folder")?
+    };
+    let overrides_file = apply_config_overrides(&repo, &get_config_overrides())?;
+    Ok(GitRepo {
+        repo,
+        _overrides_file: overrides_file,
+    })
+}
+
+/// Open `repo` again, with the `-c` overrides applied, to see what `git` commands changed
+/// behind the back of libgit2's caches.
+pub fn reopen(repo: &Repository) -> Result<GitRepo> {
+    let new_repo = Repository::open(repo.path())?;
+    if let Some(workdir) = repo.workdir() {
+        // It may come from GIT_WORK_TREE
+        new_repo.set_workdir(workdir, false)?;
+    }
+    let overrides_file = apply_config_overrides(&new_repo, &get_config_overrides())?;
+    Ok(GitRepo {
+        repo: new_repo,
+        _overrides_file: overrides_file,
+    })
+}
+
+/// Open the initialized submodules, and theirs, with the `-c` overrides applied. They are
+/// returned with their paths from the top of the working tree.
+pub fn open_submodules(repo: &Repository) -> Result<Vec<(PathBuf, GitRepo)>> {
+    let mut found = Vec::new();
+    for submodule in repo.submodules()? {
+        // Not initialized, or not checked out
+        let Ok(sub_repo) = submodule.open() else {
+            continue;
+        };
+        let path = submodule.path().to_path_buf();
+        let nested = open_submodules(&sub_repo)?;
+        let overrides_file = apply_config_overrides(&sub_repo, &get_config_overrides())?;
+        let sub_repo = GitRepo {
+            repo: sub_repo,
+            _overrides_file: overrides_file,
+        };
+        found.push((path.clone(), sub_repo));
+        found.extend(nested.into_iter().map(|(p, r)| (path.join(p), r)));
+    }
+    Ok(found)
+}
+
+/// Make a `git` command, or a hook, work on `repo`. Set GIT_DIR and GIT_WORK_TREE explicitly,
+/// because the ones we were given may be relative, or belong to the superproject.
+pub fn set_git_env(cmd: &mut Command, repo: &Repository) {
+    set_git_dirs(cmd, repo.path(), repo.workdir());
+}
+
+/// Like `set_git_env`, for when the repository is not at hand anymore.
+pub fn set_git_dirs(cmd: &mut Command, git_dir: &Path, work_tree: Option<&Path>) {
+    cmd.env("GIT_DIR", git_dir);
+    match work_tree {
+        Some(workdir) => cmd.env("GIT_WORK_TREE", workdir),
+        None => cmd.env_remove("GIT_WORK_TREE"),
+    };
+}
+
+/// Get the Git folder shared by all worktrees. For linked worktrees, `Repository::path`
+/// is ".git/worktrees/<name>", which has a "commondir" file pointing back to ".git".
+pub fn get_common_dir(repo: &Repository) -> PathBuf {
+    let path = repo.path();
+    match fs::read_to_string(path.join("commondir")) {
+        Ok(content) => path.join(content.trim()),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+/// Folder to store our own files, like ".git/del-branches".
+pub fn get_data_dir(repo: &Repository) -> PathBuf {
+    get_common_dir(repo).join(DATA_DIR_NAME)
+}
+
+/// Folder to start looking for the repository from.
+/// When run via a Git alias, Git moves us to the top of the working tree and
+/// tells the original sub-folder in GIT_PREFIX.
+pub fn get_start_dir() -> PathBuf {
+    let cwd = PathBuf::from(".");
+    match env::var_os("GIT_PREFIX") {
+        Some(prefix) if !prefix.is_empty() => {
+            let dir = cwd.join(prefix);
+            if dir.is_dir() {
+                dir
+            } else {
+                cwd
+            }
+        }
+        _ => cwd,
+    }
+}
+
+/// Read one single-quoted word, as written by Git's `sq_quote_buf`, from the head of `input`.
+fn take_sq_word(input: &str) -> Option<(String, &str)> {
+    let mut rest = input.strip_prefix('\'')?;
+    let mut word = String::new();
+    loop {
+        let end = rest.find('\'')?;
+        word.push_str(&rest[..end]);
+        rest = &rest[end + 1..];
+        // Quotes and exclamation marks inside the word are written as '\'' and '\!'
+        let mut chars = rest.chars();
+        match (chars.next(), chars.next(), chars.next()) {
+            (Some('\\'), Some(c), Some('\'')) => {
+                word.push(c);
+                rest = &rest[2 + c.len_utf8()..];
+            }
+            _ => return Some((word, rest)),
+        }
+    }
+}
+
+/// Parse the `-c key=value` overrides which Git passes to sub-commands via GIT_CONFIG_PARAMETERS.
+pub fn parse_config_parameters(input: &str) -> Vec<(String, String)> {
+    let mut entries = Vec::new();
+    let mut rest = input.trim_start();
+    while let Some((word, r)) = take_sq_word(rest) {
+        rest = r;
+        let entry = if let Some(r) = rest.strip_prefix('=') {
+            // New format: 'key'='value'
+            match take_sq_word(r) {
+                Some((value, r)) => {
+                    rest = r;
+                    (word, value)
+                }
+                // A key without value means boolean true
+                None => {
+                    rest = r;
+                    (word, "true".to_string())
+                }
+            }
+        } else {
+            // Old format: 'key=value'
+            match word.split_once('=') {
+                Some((k, v)) => (k.to_string(), v.to_string()),
+                None => (word, "true".to_string()),
+            }
+        };
+        entries.push(entry);
+        rest = rest.trim_start();
+    }
+    entries
+}
+
+/// Collect config overrides given to `git` command line, via `-c` or `--config-env`.
+pub fn get_config_overrides() -> Vec<(String, String)> {
+    let mut entries = env::var("GIT_CONFIG_PARAMETERS")
+        .map(|s| parse_config_parameters(&s))
+        .unwrap_or_default();
+    let count: usize = env::var("GIT_CONFIG_COUNT")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(0);
+    for i in 0..count {
+        if let (Ok(k), Ok(v)) = (
+            env::var(format!("GIT_CONFIG_KEY_{i}")),
+            env::var(format!("GIT_CONFIG_VALUE_{i}")),
+        ) {
+            entries.push((k, v));
+        }
+    }
+    entries
+}
+
+/// Layer the overrides on top of the repository config, so that both we and libgit2 see them.
+/// libgit2 has no in-memory config backend, so the values are kept in a temporary file,
+/// which must live as long as the repository is used.
+pub fn apply_config_overrides(
+    repo: &Repository,
+    entries: &[(String, String)],
+) -> Result<Option<NamedTempFile>> {
+    if entries.is_empty() {
+        return Ok(None);
+    }
+    let file = NamedTempFile::new()?;
+    let mut config = repo.config()?;
+    config.add_file(file.path(), ConfigLevel::App, true)?;
+    let mut app_level = config.open_level(ConfigLevel::App)?;
+    for (key, value) in entries {
+        app_level
+            .set_str(key, value)
+            .wrap_err_with(|| format!("Invalid config override \"{key}\""))?;
+    }
+    Ok(Some(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{commit, init_repo};
+
+    #[test]
+    fn parses_new_format() {
+        let entries = parse_config_parameters(
+            r"'user.name'='x' 'a.b'='it'\''s' 'core.flag'= 'alias.se'=''\!'git-showenv'",
+        );
+        assert_eq!(
+            entries,
+            [
+                ("user.name".into(), "x".into()),
+                ("a.b".into(), "it's".into()),
+                ("core.flag".into(), "true".into()),
+                ("alias.se".into(), "!git-showenv".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_old_format() {
+        let entries = parse_config_parameters("'user.name=x y' 'core.flag'");
+        assert_eq!(
+            entries,
+            [
+                ("user.name".into(), "x y".into()),
+                ("core.flag".into(), "true".into())
+            ]
+        );
+    }
+
+    #[test]
+    fn applies_overrides() {
+        let (_dir, repo) = init_repo();
+        let entries = [("user.name".to_string(), "Override".to_string())];
+        let _file = apply_config_overrides(&repo, &entries).unwrap();
+        assert_eq!(
+            repo.config().unwrap().get_string("user.name").unwrap(),
+            "Override"
+        );
+    }
+
+    #[test]
+    fn opens_submodules() {
+        let (_lib_dir, lib) = init_repo();
+        let id = commit(&lib, "refs/heads/main", None);
+        lib.set_head("refs/heads/main").unwrap();
+        lib.reset(
+            &lib.find_object(id, None).unwrap(),
+            git2::ResetType::Hard,
+            None,
+        )
+        .unwrap();
+        let (_dir, repo) = init_repo();
+        assert!(open_submodules(&repo).unwrap().is_empty());
+        let url = lib.path().to_str().unwrap();
+        let mut submodule = repo.submodule(url, Path::new("vendor/lib"), true).unwrap();
+        submodule.clone(None).unwrap();
+        submodule.add_finalize().unwrap();
+        let found = open_submodules(&repo).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, Path::new("vendor/lib"));
+        assert!(found[0].1.find_reference("refs/heads/main").is_ok());
+    }
+
+    #[test]
+    fn sets_git_env() {
+        let (_dir, repo) = init_repo();
+        let mut cmd = Command::new("git");
+        set_git_env(&mut cmd, &repo);
+        let envs: Vec<_> = cmd.get_envs().collect();
+        assert_eq!(
+            envs,
+            [
+                (OsStr::new("GIT_DIR"), Some(repo.path().as_os_str())),
+                (
+                    OsStr::new("GIT_WORK_TREE"),
+                    Some(repo.workdir().unwrap().as_os_str())
+                ),
+            ]
+        );
+    }
+}
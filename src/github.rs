@@ -0,0 +1,136 @@
+//! Login to GitHub with the OAuth device flow: the user enters a code on the GitHub website,
+//! while we wait for the token.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use color_eyre::Result;
+use eyre::{eyre, Context};
+use serde::Deserialize;
+
+use git_del_branches::hosting::USER_AGENT;
+
+/// Scope for looking up the pull requests and deleting the branches of private repositories.
+pub const SCOPE: &str = "repo";
+/// Scope for doing so only on public repositories.
+pub const PUBLIC_SCOPE: &str = "public_repo";
+const GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+/// Added to the polling interval when GitHub tells us to slow down.
+const SLOW_DOWN_STEP: u64 = 5;
+
+#[derive(Debug, Deserialize)]
+pub struct DeviceCode {
+    pub device_code: String,
+    /// Code for the user to enter on the website.
+    pub user_code: String,
+    pub verification_uri: String,
+    pub expires_in: u64,
+    pub interval: u64,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct TokenResponse {
+    access_token: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+    interval: Option<u64>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Poll {
+    Token(String),
+    /// The user has not entered the code yet.
+    Pending,
+    /// We polled too often, and must now wait that many seconds between polls.
+    SlowDown(u64),
+    Failed(String),
+}
+
+impl TokenResponse {
+    fn into_poll(self, interval: u64) -> Poll {
+        if let Some(token) = self.access_token {
+            return Poll::Token(token);
+        }
+        match self.error.as_deref() {
+            Some("authorization_pending") => Poll::Pending,
+            Some("slow_down") => Poll::SlowDown(self.interval.unwrap_or(interval + SLOW_DOWN_STEP)),
+            Some("expired_token") => Poll::Failed("The code expired, run the login again".into()),
+            Some("access_denied") => Poll::Failed("The authorization was cancelled".into()),
+            Some(e) => Poll::Failed(self.error_description.unwrap_or_else(|| e.to_string())),
+            None => Poll::Failed("GitHub gave no token".into()),
+        }
+    }
+}
+
+fn post(url: &str, form: &[(&str, &str)]) -> Result<ureq::Response> {
+    ureq::post(url)
+        .set("User-Agent", USER_AGENT)
+        .set("Accept", "application/json")
+        .send_form(form)
+        .wrap_err_with(|| format!("Failed to reach {url}"))
+}
+
+/// Ask the GitHub at `host` for a code which the user enters to grant us `scope`.
+pub fn request_device_code(host: &str, client_id: &str, scope: &str) -> Result<DeviceCode> {
+    let url = format!("https://{host}/login/device/code");
+    let code = post(&url, &[("client_id", client_id), ("scope", scope)])?
+        .into_json()
+        .wrap_err("Unexpected answer to the device code request")?;
+    Ok(code)
+}
+
+/// Wait for the user to enter the code, and return the token then.
+pub fn wait_for_token(host: &str, client_id: &str, code: &DeviceCode) -> Result<String> {
+    let url = format!("https://{host}/login/oauth/access_token");
+    let deadline = Instant::now() + Duration::from_secs(code.expires_in);
+    let mut interval = code.interval;
+    while Instant::now() < deadline {
+        thread::sleep(Duration::from_secs(interval));
+        let form = [
+            ("client_id", client_id),
+            ("device_code", code.device_code.as_str()),
+            ("grant_type", GRANT_TYPE),
+        ];
+        let response: TokenResponse = post(&url, &form)?.into_json()?;
+        match response.into_poll(interval) {
+            Poll::Token(token) => return Ok(token),
+            Poll::Pending => {}
+            Poll::SlowDown(seconds) => interval = seconds,
+            Poll::Failed(msg) => return Err(eyre!(msg)),
+        }
+    }
+    Err(eyre!("The code expired, run the login again"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn poll(access_token: Option<&str>, error: Option<&str>, interval: Option<u64>) -> Poll {
+        let response = TokenResponse {
+            access_token: access_token.map(String::from),
+            error: error.map(String::from),
+            interval,
+            ..Default::default()
+        };
+        response.into_poll(5)
+    }
+
+    #[test]
+    fn reads_poll_answers() {
+        assert_eq!(
+            poll(Some("gho_abc"), None, None),
+            Poll::Token("gho_abc".into())
+        );
+        assert_eq!(
+            poll(None, Some("authorization_pending"), None),
+            Poll::Pending
+        );
+        assert_eq!(poll(None, Some("slow_down"), None), Poll::SlowDown(10));
+        assert_eq!(poll(None, Some("slow_down"), Some(15)), Poll::SlowDown(15));
+        assert_eq!(
+            poll(None, Some("device_flow_disabled"), None),
+            Poll::Failed("device_flow_disabled".into())
+        );
+    }
+}
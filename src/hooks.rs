@@ -0,0 +1,214 @@
+//! User commands run before and after deleting each branch.
+//!
+//! The command is run by the shell, in the top folder of the working tree, with these
+//! environment variables:
+//!
+//! - `DEL_BRANCHES_NAME`: Name of the local branch.
+//! - `DEL_BRANCHES_SHA`: Full SHA of the branch tip.
+//! - `DEL_BRANCHES_REMOTE`, `DEL_BRANCHES_UPSTREAM`: Remote name and branch name of the upstream,
+//!   empty if there is none.
+//! - `DEL_BRANCHES_DELETE_UPSTREAM`: "1" if the upstream is to be deleted too, else "0".
+//! - `DEL_BRANCHES_UPSTREAM_STATE`: Only for the post-delete hook, one of "kept", "deleted", "failed".
+//!
+//! If the pre-delete hook exits with non-zero status, the branch is not deleted.
+//!
+//! The protection command in `del-branches.protectCommand` is run the same way for each
+//! branch which could be offered, but without the variables about the deletion: the branch is
+//! only offered if it exits with zero status, so that it can tell which branches the
+//! organization protects, like those of a release calendar.
+//!
+//! Git's own pre-push hook is also run before deleting upstream branches, because libgit2
+//! pushes would bypass it.
+
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+
+use git2::{Oid, Repository};
+
+use crate::branch::{BranchInfo, Upstream};
+use crate::gitenv::{get_common_dir, set_git_env};
+use crate::journal::UpstreamState;
+
+pub fn get_shell_command(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", command]);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    }
+}
+
+/// The shell command, with the variables about the branch.
+fn get_branch_command(repo: &Repository, command: &str, branch: &BranchInfo) -> Command {
+    let (remote, upstream) = match &branch.upstream {
+        Some(u) => (u.remote.as_str(), u.name.as_str()),
+        None => ("", ""),
+    };
+    let mut cmd = get_shell_command(command);
+    set_git_env(&mut cmd, repo);
+    cmd.current_dir(repo.workdir().unwrap_or_else(|| repo.path()))
+        .env("DEL_BRANCHES_NAME", &branch.name)
+        .env("DEL_BRANCHES_SHA", branch.tip.to_string())
+        .env("DEL_BRANCHES_REMOTE", remote)
+        .env("DEL_BRANCHES_UPSTREAM", upstream);
+    cmd
+}
+
+pub fn run_hook(
+    repo: &Repository,
+    command: &str,
+    branch: &BranchInfo,
+    delete_upstream: bool,
+    upstream_state: Option<UpstreamState>,
+) -> io::Result<ExitStatus> {
+    let mut cmd = get_branch_command(repo, command, branch);
+    cmd.env(
+        "DEL_BRANCHES_DELETE_UPSTREAM",
+        if delete_upstream { "1" } else { "0" },
+    );
+    if let Some(state) = upstream_state {
+        cmd.env("DEL_BRANCHES_UPSTREAM_STATE", state.to_string());
+    }
+    cmd.status()
+}
+
+/// Run the protection command for the branch. Its output is dropped, not to mix with that
+/// of the events, but its errors are shown.
+pub fn run_protect_command(
+    repo: &Repository,
+    command: &str,
+    branch: &BranchInfo,
+) -> io::Result<ExitStatus> {
+    get_branch_command(repo, command, branch)
+        .stdout(Stdio::null())
+        .status()
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Find the Git hook of the given name, in core.hooksPath or ".git/hooks".
+/// Like Git, a hook which is not executable is ignored.
+pub fn find_git_hook(repo: &Repository, name: &str) -> Option<PathBuf> {
+    let folder = match repo.config().ok()?.get_path("core.hooksPath") {
+        // A relative hooksPath is relative to where hooks are run, the top of the working tree
+        Ok(p) => repo.workdir().unwrap_or_else(|| repo.path()).join(p),
+        Err(_) => get_common_dir(repo).join("hooks"),
+    };
+    Some(folder.join(name)).filter(|p| is_executable(p))
+}
+
+/// Run the pre-push hook, if any, for the push deleting the upstream branches, all of the
+/// remote. The hook gets the same arguments and input as when run by
+/// `git push <remote> --delete`, one line per branch.
+pub fn run_pre_push_hook(
+    repo: &Repository,
+    remote_name: &str,
+    url: &str,
+    upstreams: &[&Upstream],
+) -> io::Result<Option<ExitStatus>> {
+    let Some(hook) = find_git_hook(repo, "pre-push") else {
+        return Ok(None);
+    };
+    let mut input = String::new();
+    for upstream in upstreams {
+        // Our last known value of the remote branch, or zeros if unknown
+        let remote_sha = repo
+            .refname_to_id(&format!("refs/remotes/{upstream}"))
+            .unwrap_or_else(|_| Oid::zero());
+        input.push_str(&format!(
+            "(delete) {} refs/heads/{} {remote_sha}\n",
+            Oid::zero(),
+            upstream.name
+        ));
+    }
+    let mut cmd = Command::new(hook);
+    set_git_env(&mut cmd, repo);
+    let mut child = cmd
+        .args([remote_name, url])
+        .current_dir(repo.workdir().unwrap_or_else(|| repo.path()))
+        .stdin(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // The hook may exit without reading its input
+        let _ = stdin.write_all(input.as_bytes());
+    }
+    child.wait().map(Some)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::branch::list_local_branches;
+    use crate::testutil::{commit, init_repo};
+
+    #[test]
+    fn passes_branch_info() {
+        let (dir, repo) = init_repo();
+        let tip = commit(&repo, "refs/heads/feature", None);
+        let branch = list_local_branches(&repo).unwrap().remove(0);
+        let out = dir.path().join("out");
+        let command = format!(
+            "echo \"$DEL_BRANCHES_NAME $DEL_BRANCHES_SHA $DEL_BRANCHES_UPSTREAM_STATE\" > {}",
+            out.display()
+        );
+        let status = run_hook(&repo, &command, &branch, false, Some(UpstreamState::Kept)).unwrap();
+        assert!(status.success());
+        let content = std::fs::read_to_string(out).unwrap();
+        assert_eq!(content.trim(), format!("feature {tip} kept"));
+        let status = run_hook(&repo, "exit 3", &branch, false, None).unwrap();
+        assert_eq!(status.code(), Some(3));
+        let command = "echo yes; test \"$DEL_BRANCHES_NAME\" != feature";
+        let status = run_protect_command(&repo, command, &branch).unwrap();
+        assert_eq!(status.code(), Some(1));
+    }
+
+    #[test]
+    fn runs_pre_push_hook() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let (dir, repo) = init_repo();
+        let tip = commit(&repo, "refs/remotes/origin/feature", None);
+        let upstream = Upstream {
+            remote: "origin".into(),
+            name: "feature".into(),
+        };
+        let run = || run_pre_push_hook(&repo, "origin", "/srv/repo.git", &[&upstream]).unwrap();
+        assert!(run().is_none());
+        let hooks = dir.path().join("my-hooks");
+        std::fs::create_dir(&hooks).unwrap();
+        let out = dir.path().join("out");
+        let hook = hooks.join("pre-push");
+        let script = format!(
+            "#!/bin/sh\necho \"$1 $2\" > {0}\ncat >> {0}\nexit 1\n",
+            out.display()
+        );
+        std::fs::write(&hook, script).unwrap();
+        std::fs::set_permissions(&hook, std::fs::Permissions::from_mode(0o755)).unwrap();
+        repo.config()
+            .unwrap()
+            .set_str("core.hooksPath", "my-hooks")
+            .unwrap();
+        assert_eq!(run().unwrap().code(), Some(1));
+        let content = std::fs::read_to_string(out).unwrap();
+        let zero = Oid::zero();
+        assert_eq!(
+            content,
+            format!("origin /srv/repo.git\n(delete) {zero} refs/heads/feature {tip}\n")
+        );
+    }
+}
@@ -0,0 +1,540 @@
+//! Azure Repos, of Azure DevOps, reached with a personal access token: pull requests,
+//! branch policies, and deleting branches, which is updating their refs to zero.
+
+use std::collections::HashMap;
+
+use eyre::{eyre, Result};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::Deserialize;
+use serde_json::json;
+
+use super::{
+    basic_auth, call, describe_error, send, CiStatus, Hosting, HostingKind, ProtectedRef,
+    PullRequest, RemoteUrl, ReviewState, Site, USER_AGENT,
+};
+
+pub const API_URL: &str = "https://dev.azure.com";
+const API_VERSION: &str = "7.0";
+/// Pull requests per page of the list of those of the repository.
+const PAGE_SIZE: usize = 100;
+/// Pages of pull requests looked up for several branches, the latest first.
+const MAX_PULL_PAGES: usize = 10;
+/// Object ID which a ref is updated to for deleting it.
+const ZERO_ID: &str = "0000000000000000000000000000000000000000";
+
+/// Whether the remote is on Azure DevOps Services, not on a self-hosted Azure DevOps Server.
+pub fn is_cloud(url: &RemoteUrl) -> bool {
+    url.host.ends_with("dev.azure.com") || url.host.ends_with(".visualstudio.com")
+}
+
+/// Number of segments of the path of a remote on Azure DevOps Server before its collection,
+/// which is the organization there: those before "collection/project/_git/repo".
+fn get_collection_start(segments: &[&str]) -> usize {
+    segments
+        .iter()
+        .position(|s| *s == "_git")
+        .map_or(0, |i| i.saturating_sub(2))
+}
+
+/// URL of the API: the one of Azure DevOps Services, else the URL of the server before the
+/// collection, like "https://tfs.example.com/tfs".
+pub fn get_api_url(url: &RemoteUrl) -> String {
+    if is_cloud(url) {
+        return API_URL.to_string();
+    }
+    let segments: Vec<&str> = url.path.split('/').collect();
+    match get_collection_start(&segments) {
+        0 => url.get_web_url(),
+        start => format!("{}/{}", url.get_web_url(), segments[..start].join("/")),
+    }
+}
+
+/// Project of the remote, as "organization/project/repository", from URLs like
+/// "https://dev.azure.com/org/project/_git/repo", "git@ssh.dev.azure.com:v3/org/project/repo"
+/// or "https://org.visualstudio.com/DefaultCollection/project/_git/repo", and on Azure DevOps
+/// Server, like "https://tfs.example.com/tfs/collection/project/_git/repo".
+pub fn get_project(url: &RemoteUrl) -> String {
+    let path = percent_decode_str(&url.path).decode_utf8_lossy();
+    let segments: Vec<&str> = path.split('/').collect();
+    if let Some(("v3", rest)) = segments.split_first().map(|(first, rest)| (*first, rest)) {
+        return rest.join("/");
+    }
+    let has_git = segments.contains(&"_git");
+    let start = match is_cloud(url) {
+        true => 0,
+        false => get_collection_start(&segments),
+    };
+    let mut parts: Vec<&str> = segments[start..]
+        .iter()
+        .copied()
+        .filter(|s| *s != "_git" && !(is_cloud(url) && *s == "DefaultCollection"))
+        .collect();
+    // The organization is in the host of the old URLs
+    if let Some(org) = url.host.strip_suffix(".visualstudio.com") {
+        parts.insert(0, org);
+    }
+    // A repository named like its project may leave the project out
+    if has_git && parts.len() == 2 {
+        parts.push(parts[1]);
+    }
+    parts.join("/")
+}
+
+fn encode(s: &str) -> String {
+    utf8_percent_encode(s, NON_ALPHANUMERIC).to_string()
+}
+
+#[derive(Debug, Deserialize)]
+struct List<T> {
+    value: Vec<T>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GitRef {
+    name: String,
+    object_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RefUpdateResult {
+    success: bool,
+    update_status: String,
+    custom_message: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PullRequestInfo {
+    pull_request_id: u64,
+    title: String,
+    status: String,
+    source_ref_name: Option<String>,
+    /// Present for the pull requests from a fork.
+    fork_source: Option<serde_json::Value>,
+}
+
+impl PullRequestInfo {
+    fn into_pull_request(self) -> PullRequest {
+        let state = match self.status.as_str() {
+            "completed" => ReviewState::Merged,
+            "abandoned" => ReviewState::Closed,
+            _ => ReviewState::Open,
+        };
+        PullRequest {
+            number: self.pull_request_id,
+            title: self.title,
+            state,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CommitStatus {
+    state: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Repository {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PolicyConfiguration {
+    is_enabled: bool,
+    #[serde(default)]
+    settings: PolicySettings,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PolicySettings {
+    #[serde(default)]
+    scope: Vec<PolicyScope>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PolicyScope {
+    /// Absent for the policies of all the repositories of the project.
+    repository_id: Option<String>,
+    ref_name: Option<String>,
+    match_kind: Option<String>,
+}
+
+/// Names of the branches, from the answer listing the refs under "heads/".
+fn parse_branches(body: &str) -> Result<Vec<String>> {
+    let refs: List<GitRef> = serde_json::from_str(body)?;
+    Ok(refs
+        .value
+        .into_iter()
+        .filter_map(|r| r.name.strip_prefix("refs/heads/").map(String::from))
+        .collect())
+}
+
+/// The first pull request of the answer, which lists the latest first.
+fn parse_pull_request(body: &str) -> Result<Option<PullRequest>> {
+    let prs: List<PullRequestInfo> = serde_json::from_str(body)?;
+    Ok(prs
+        .value
+        .into_iter()
+        .next()
+        .map(PullRequestInfo::into_pull_request))
+}
+
+/// The pull requests of a page of those of the repository, with their branches, but not
+/// those from forks, and how many the page had.
+fn parse_pull_requests(body: &str) -> Result<(Vec<(String, PullRequest)>, usize)> {
+    let prs: List<PullRequestInfo> = serde_json::from_str(body)?;
+    let count = prs.value.len();
+    let own = prs
+        .value
+        .into_iter()
+        .filter(|pr| pr.fork_source.is_none())
+        .filter_map(|pr| {
+            let branch = pr.source_ref_name.as_deref()?.strip_prefix("refs/heads/")?;
+            Some((branch.to_string(), pr.into_pull_request()))
+        })
+        .collect();
+    Ok((own, count))
+}
+
+/// Status of the checks of the commit, from the latest statuses of each of them.
+fn parse_ci_status(body: &str) -> Result<Option<CiStatus>> {
+    let statuses: List<CommitStatus> = serde_json::from_str(body)?;
+    Ok(CiStatus::combine(statuses.value.iter().filter_map(|s| {
+        match s.state.as_str() {
+            "succeeded" => Some(CiStatus::Passed),
+            "pending" => Some(CiStatus::Pending),
+            "failed" | "error" => Some(CiStatus::Failed),
+            // "notSet" and "notApplicable"
+            _ => None,
+        }
+    })))
+}
+
+/// Branches which the enabled policies of the project apply to, in the repository.
+fn parse_protected(body: &str, repository_id: &str) -> Result<Vec<ProtectedRef>> {
+    let policies: List<PolicyConfiguration> = serde_json::from_str(body)?;
+    let mut protected = Vec::new();
+    for scope in policies
+        .value
+        .into_iter()
+        .filter(|p| p.is_enabled)
+        .flat_map(|p| p.settings.scope)
+    {
+        if scope.repository_id.is_some_and(|id| id != repository_id) {
+            continue;
+        }
+        let Some(name) = scope.ref_name else {
+            continue;
+        };
+        // Policies may apply to other refs, and "refs/heads/" to all the branches
+        let Some(name) = name.strip_prefix("refs/heads/") else {
+            continue;
+        };
+        let protected_ref = match scope.match_kind.as_deref() {
+            Some("Prefix") | Some("prefix") => ProtectedRef::Prefix(name.to_string()),
+            _ => ProtectedRef::Exact(name.to_string()),
+        };
+        if !protected.contains(&protected_ref) {
+            protected.push(protected_ref);
+        }
+    }
+    Ok(protected)
+}
+
+pub struct Azure {
+    api_url: String,
+    /// Organization, project and repository.
+    project: String,
+    token: String,
+}
+
+impl Azure {
+    pub fn new(site: &Site, token: &str) -> Self {
+        Self {
+            api_url: site.api_url.clone(),
+            project: site.project.clone(),
+            token: token.to_string(),
+        }
+    }
+
+    fn get_parts(&self) -> Result<(&str, &str, &str)> {
+        let mut parts = self.project.splitn(3, '/');
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(org), Some(project), Some(repo)) => Ok((org, project, repo)),
+            _ => Err(eyre!(
+                "Cannot tell the organization, project and repository of \"{}\" on Azure Repos",
+                self.project
+            )),
+        }
+    }
+
+    /// URL of the path under the project.
+    fn get_project_url(&self, path: &str) -> Result<String> {
+        let (org, project, _) = self.get_parts()?;
+        Ok(format!(
+            "{}/{}/{}/_apis/{path}",
+            self.api_url,
+            encode(org),
+            encode(project)
+        ))
+    }
+
+    /// URL of the path under the repository, which is the last one when the path is empty.
+    fn get_repo_url(&self, path: &str) -> Result<String> {
+        let (_, _, repo) = self.get_parts()?;
+        let url = self.get_project_url(&format!("git/repositories/{}", encode(repo)))?;
+        Ok(match path.is_empty() {
+            true => url,
+            false => format!("{url}/{path}"),
+        })
+    }
+
+    /// GET the URL with the query, which the API version is added to, and return the body.
+    fn get(&self, url: &str, query: &[(&str, &str)]) -> Result<String> {
+        let mut request = self.request("GET", url);
+        for (name, value) in query {
+            request = request.query(name, value);
+        }
+        Ok(call(request)
+            .map_err(|e| describe_error(*e, HostingKind::Azure.title()))?
+            .into_string()?)
+    }
+
+    fn request(&self, method: &str, url: &str) -> ureq::Request {
+        ureq::request(method, url)
+            .query("api-version", API_VERSION)
+            .set("User-Agent", USER_AGENT)
+            // A personal access token goes without a username
+            .set("Authorization", &basic_auth("", &self.token))
+    }
+
+    fn find_ref(&self, name: &str) -> Result<Option<GitRef>> {
+        let filter = format!("heads/{name}");
+        let body = self.get(&self.get_repo_url("refs")?, &[("filter", &filter)])?;
+        let refs: List<GitRef> = serde_json::from_str(&body)?;
+        // The filter matches the refs which start with it
+        let full_name = format!("refs/heads/{name}");
+        Ok(refs.value.into_iter().find(|r| r.name == full_name))
+    }
+}
+
+impl Hosting for Azure {
+    fn kind(&self) -> HostingKind {
+        HostingKind::Azure
+    }
+
+    fn list_branches(&self) -> Result<Vec<String>> {
+        let body = self.get(&self.get_repo_url("refs")?, &[("filter", "heads/")])?;
+        parse_branches(&body)
+    }
+
+    fn delete_branch(&self, name: &str) -> Result<()> {
+        let Some(git_ref) = self.find_ref(name)? else {
+            return Ok(());
+        };
+        let update = json!([{
+            "name": git_ref.name,
+            "oldObjectId": git_ref.object_id,
+            "newObjectId": ZERO_ID,
+        }]);
+        let request = self.request("POST", &self.get_repo_url("refs")?);
+        let results: List<RefUpdateResult> = send(request, Some(&update))
+            .map_err(|e| describe_error(*e, HostingKind::Azure.title()))?
+            .into_json()?;
+        match results.value.into_iter().find(|r| !r.success) {
+            Some(r) => Err(eyre!(
+                "Azure Repos refused to delete the branch: {}",
+                r.custom_message.unwrap_or(r.update_status)
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn find_pull_request(&self, branch: &str) -> Result<Option<PullRequest>> {
+        let source = format!("refs/heads/{branch}");
+        let query = [
+            ("searchCriteria.sourceRefName", source.as_str()),
+            ("searchCriteria.status", "all"),
+            ("$top", "1"),
+        ];
+        let body = self.get(&self.get_repo_url("pullrequests")?, &query)?;
+        parse_pull_request(&body)
+    }
+
+    /// Listed for the whole repository, page by page, instead of asked for each branch.
+    fn find_pull_requests(&self, branches: &[&str]) -> Result<HashMap<String, PullRequest>> {
+        let url = self.get_repo_url("pullrequests")?;
+        let top = PAGE_SIZE.to_string();
+        let mut found = HashMap::new();
+        for page in 0..MAX_PULL_PAGES {
+            let skip = (page * PAGE_SIZE).to_string();
+            let query = [
+                ("searchCriteria.status", "all"),
+                ("$top", top.as_str()),
+                ("$skip", skip.as_str()),
+            ];
+            let (prs, count) = parse_pull_requests(&self.get(&url, &query)?)?;
+            // The latest first, which is kept
+            for (branch, pr) in prs {
+                if branches.contains(&branch.as_str()) && !found.contains_key(&branch) {
+                    found.insert(branch, pr);
+                }
+            }
+            if count < PAGE_SIZE || found.len() == branches.len() {
+                break;
+            }
+        }
+        Ok(found)
+    }
+
+    fn list_protected(&self) -> Result<Vec<ProtectedRef>> {
+        let repository: Repository =
+            serde_json::from_str(&self.get(&self.get_repo_url("")?, &[])?)?;
+        let body = self.get(&self.get_project_url("policy/configurations")?, &[])?;
+        parse_protected(&body, &repository.id)
+    }
+
+    fn find_ci_status(&self, commit: &str) -> Result<Option<CiStatus>> {
+        let url = self.get_repo_url(&format!("commits/{commit}/statuses"))?;
+        let body = self.get(&url, &[("latestOnly", "true")])?;
+        parse_ci_status(&body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hosting::parse_remote_url;
+    use crate::testutil::serve_json;
+
+    fn project(url: &str) -> String {
+        get_project(&parse_remote_url(url).unwrap())
+    }
+
+    #[test]
+    fn gets_projects() {
+        assert_eq!(
+            project("https://me@dev.azure.com/corp/Web%20Site/_git/app"),
+            "corp/Web Site/app"
+        );
+        assert_eq!(
+            project("git@ssh.dev.azure.com:v3/corp/site/app"),
+            "corp/site/app"
+        );
+        assert_eq!(
+            project("https://corp.visualstudio.com/DefaultCollection/site/_git/app"),
+            "corp/site/app"
+        );
+        assert_eq!(
+            project("https://dev.azure.com/corp/_git/app"),
+            "corp/app/app"
+        );
+        assert_eq!(
+            project("https://tfs.example.com/tfs/DefaultCollection/site/_git/app"),
+            "DefaultCollection/site/app"
+        );
+    }
+
+    #[test]
+    fn gets_api_urls() {
+        let api_url = |url| get_api_url(&parse_remote_url(url).unwrap());
+        assert_eq!(api_url("git@ssh.dev.azure.com:v3/corp/site/app"), API_URL);
+        assert_eq!(
+            api_url("https://tfs.example.com/tfs/DefaultCollection/site/_git/app"),
+            "https://tfs.example.com/tfs"
+        );
+        assert_eq!(
+            api_url("ssh://tfs.example.com:22/tfs/DefaultCollection/site/_git/app"),
+            "https://tfs.example.com/tfs"
+        );
+        assert_eq!(
+            api_url("http://devops.local:8080/corp/site/_git/app"),
+            "http://devops.local:8080"
+        );
+    }
+
+    #[test]
+    fn parses_answers() {
+        let body = r#"{"value": [
+            {"name": "refs/heads/main", "objectId": "67ebf73496383c6777035e374d2d664009e2aa5c"},
+            {"name": "refs/heads/fix/login", "objectId": "1c7a1dd5e770730e98e4b7a9d2d4c5bf3a1c850e"}
+        ], "count": 2}"#;
+        assert_eq!(parse_branches(body).unwrap(), ["main", "fix/login"]);
+        let body = r#"{"value": [
+            {"pullRequestId": 42, "title": "Fix login", "status": "completed"}
+        ], "count": 1}"#;
+        let pr = parse_pull_request(body).unwrap().unwrap();
+        assert_eq!((pr.number, pr.state), (42, ReviewState::Merged));
+        assert_eq!(parse_pull_request(r#"{"value": []}"#).unwrap(), None);
+        let body = r#"{"value": [
+            {"state": "succeeded", "context": {"name": "build"}},
+            {"state": "notApplicable", "context": {"name": "deploy"}},
+            {"state": "pending", "context": {"name": "tests"}}
+        ]}"#;
+        assert_eq!(parse_ci_status(body).unwrap(), Some(CiStatus::Pending));
+    }
+
+    #[test]
+    fn parses_policies() {
+        let body = r#"{"value": [
+            {"isEnabled": true, "settings": {"scope": [
+                {"repositoryId": "r1", "refName": "refs/heads/main", "matchKind": "Exact"},
+                {"repositoryId": null, "refName": "refs/heads/release/", "matchKind": "Prefix"},
+                {"repositoryId": "r2", "refName": "refs/heads/dev", "matchKind": "Exact"}
+            ]}},
+            {"isEnabled": false, "settings": {"scope": [
+                {"repositoryId": "r1", "refName": "refs/heads/old", "matchKind": "Exact"}
+            ]}},
+            {"isEnabled": true, "settings": {"minimumApproverCount": 2}}
+        ]}"#;
+        let protected = parse_protected(body, "r1").unwrap();
+        assert_eq!(
+            protected,
+            [
+                ProtectedRef::Exact("main".into()),
+                ProtectedRef::Prefix("release/".into())
+            ]
+        );
+        assert!(protected[1].matches("release/1.0"));
+        assert!(!protected[0].matches("main2"));
+    }
+
+    #[test]
+    fn finds_pull_requests_at_once() {
+        let body = r#"{"value": [
+            {"pullRequestId": 12, "title": "Pay", "status": "active",
+             "sourceRefName": "refs/heads/pay"},
+            {"pullRequestId": 11, "title": "Theirs", "status": "active",
+             "sourceRefName": "refs/heads/login", "forkSource": {"name": "refs/heads/login"}},
+            {"pullRequestId": 10, "title": "Login", "status": "abandoned",
+             "sourceRefName": "refs/heads/login"}
+        ], "count": 3}"#;
+        let (url, requests) = serve_json(&[body]);
+        let site = Site {
+            kind: HostingKind::Azure,
+            api_url: url,
+            host: "127.0.0.1".into(),
+            project: "corp/site/app".into(),
+            user: None,
+            token_env: "AZURE_DEVOPS_EXT_PAT".into(),
+            guessed: false,
+        };
+        let azure = Azure::new(&site, "secret");
+        let prs = azure
+            .find_pull_requests(&["login", "pay", "docs", "old"])
+            .unwrap();
+        assert_eq!(prs.len(), 2);
+        assert_eq!(
+            (prs["login"].number, prs["login"].state),
+            (10, ReviewState::Closed)
+        );
+        assert_eq!(prs["pay"].state, ReviewState::Open);
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].starts_with("GET /corp/site/_apis/git/repositories/app/pullrequests?"));
+    }
+}
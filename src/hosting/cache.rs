@@ -0,0 +1,243 @@
+//! What the hosting services told on the last runs, so that the next ones do not ask again,
+//! and stay fast without using up the API quota.
+//!
+//! The cache is a JSON file in ".git/del-branches/hosting-cache.json". Pull requests and CI
+//! statuses are kept by the tip of their branch, so a new commit is always looked up: the
+//! merged and closed pull requests, and the finished checks, are then kept until the tip
+//! changes, the rest for a few minutes, as it may change without a commit. The states of
+//! issues are kept by tracker, the resolved ones for an hour, as they are seldom reopened.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use eyre::Result;
+use git2::{Oid, Repository};
+use serde::{Deserialize, Serialize};
+
+use super::tracker::IssueState;
+use super::{CiStatus, ProtectedRef, PullRequest, ReviewState};
+use crate::gitenv::get_data_dir;
+
+const FILE_NAME: &str = "hosting-cache.json";
+/// Seconds for which what may change without a commit is kept, like an open or closed pull
+/// request.
+const SHORT_TTL: i64 = 10 * 60;
+/// Seconds for which the protected branches, and the resolved issues, are kept.
+const PROTECTED_TTL: i64 = 60 * 60;
+/// Seconds after which any entry is dropped, for the cache not to grow forever.
+const MAX_AGE: i64 = 30 * 24 * 60 * 60;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry<T> {
+    value: T,
+    /// When it was looked up, in seconds since the epoch.
+    time: i64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Entries {
+    /// By site, branch on the server and tip.
+    #[serde(default)]
+    pull_requests: HashMap<String, Entry<Option<PullRequest>>>,
+    /// By site and tip.
+    #[serde(default)]
+    ci_statuses: HashMap<String, Entry<Option<CiStatus>>>,
+    /// By site.
+    #[serde(default)]
+    protected: HashMap<String, Entry<Vec<ProtectedRef>>>,
+    /// By tracker and issue.
+    #[serde(default)]
+    issues: HashMap<String, Entry<Option<IssueState>>>,
+}
+
+pub struct Cache {
+    path: PathBuf,
+    entries: Entries,
+    now: i64,
+    changed: bool,
+}
+
+fn get_fresh<T: Clone>(
+    map: &HashMap<String, Entry<T>>,
+    key: &str,
+    now: i64,
+    ttl: impl Fn(&T) -> i64,
+) -> Option<T> {
+    let entry = map.get(key)?;
+    (now - entry.time < ttl(&entry.value)).then(|| entry.value.clone())
+}
+
+impl Cache {
+    /// Load the cache of the repository. A missing or broken file makes an empty cache.
+    pub fn load(repo: &Repository, now: i64) -> Self {
+        let path = get_data_dir(repo).join(FILE_NAME);
+        let entries = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            entries,
+            now,
+            changed: false,
+        }
+    }
+
+    /// Write the cache, if anything was added, without the entries which are too old.
+    pub fn save(&mut self) -> Result<()> {
+        if !self.changed {
+            return Ok(());
+        }
+        let now = self.now;
+        let entries = &mut self.entries;
+        entries.pull_requests.retain(|_, e| now - e.time < MAX_AGE);
+        entries.ci_statuses.retain(|_, e| now - e.time < MAX_AGE);
+        entries.protected.retain(|_, e| now - e.time < MAX_AGE);
+        entries.issues.retain(|_, e| now - e.time < MAX_AGE);
+        if let Some(folder) = self.path.parent() {
+            fs::create_dir_all(folder)?;
+        }
+        fs::write(&self.path, serde_json::to_string(&self.entries)?)?;
+        Ok(())
+    }
+
+    /// The pull request from the branch, `Some(None)` if it was known to have none.
+    pub fn get_pull_request(
+        &self,
+        site: &str,
+        branch: &str,
+        tip: Oid,
+    ) -> Option<Option<PullRequest>> {
+        // A closed pull request can be reopened without a new commit
+        let ttl = |pr: &Option<PullRequest>| match pr {
+            Some(pr) if pr.state == ReviewState::Merged => MAX_AGE,
+            _ => SHORT_TTL,
+        };
+        let key = format!("{site}:{branch}:{tip}");
+        get_fresh(&self.entries.pull_requests, &key, self.now, ttl)
+    }
+
+    pub fn put_pull_request(
+        &mut self,
+        site: &str,
+        branch: &str,
+        tip: Oid,
+        pr: Option<PullRequest>,
+    ) {
+        let key = format!("{site}:{branch}:{tip}");
+        let entry = Entry {
+            value: pr,
+            time: self.now,
+        };
+        self.entries.pull_requests.insert(key, entry);
+        self.changed = true;
+    }
+
+    /// The CI status of the commit, `Some(None)` if it was known to have no checks.
+    pub fn get_ci_status(&self, site: &str, commit: Oid) -> Option<Option<CiStatus>> {
+        let ttl = |status: &Option<CiStatus>| match status {
+            Some(CiStatus::Passed | CiStatus::Failed) => MAX_AGE,
+            _ => SHORT_TTL,
+        };
+        let key = format!("{site}:{commit}");
+        get_fresh(&self.entries.ci_statuses, &key, self.now, ttl)
+    }
+
+    pub fn put_ci_status(&mut self, site: &str, commit: Oid, status: Option<CiStatus>) {
+        let entry = Entry {
+            value: status,
+            time: self.now,
+        };
+        self.entries
+            .ci_statuses
+            .insert(format!("{site}:{commit}"), entry);
+        self.changed = true;
+    }
+
+    pub fn get_protected(&self, site: &str) -> Option<Vec<ProtectedRef>> {
+        get_fresh(&self.entries.protected, site, self.now, |_| PROTECTED_TTL)
+    }
+
+    pub fn put_protected(&mut self, site: &str, protected: Vec<ProtectedRef>) {
+        let entry = Entry {
+            value: protected,
+            time: self.now,
+        };
+        self.entries.protected.insert(site.to_string(), entry);
+        self.changed = true;
+    }
+
+    /// The state of the issue, `Some(None)` if the tracker had no such issue.
+    pub fn get_issue_state(&self, tracker: &str, issue: &str) -> Option<Option<IssueState>> {
+        let ttl = |state: &Option<IssueState>| match state {
+            Some(IssueState::Resolved) => PROTECTED_TTL,
+            _ => SHORT_TTL,
+        };
+        let key = format!("{tracker}:{issue}");
+        get_fresh(&self.entries.issues, &key, self.now, ttl)
+    }
+
+    pub fn put_issue_state(&mut self, tracker: &str, issue: &str, state: Option<IssueState>) {
+        let entry = Entry {
+            value: state,
+            time: self.now,
+        };
+        self.entries
+            .issues
+            .insert(format!("{tracker}:{issue}"), entry);
+        self.changed = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::init_repo;
+
+    #[test]
+    fn keeps_entries() {
+        let (_dir, repo) = init_repo();
+        let tip = Oid::from_str("67ebf73496383c6777035e374d2d664009e2aa5c").unwrap();
+        let merged = PullRequest {
+            number: 3,
+            title: "Add feat".into(),
+            state: ReviewState::Merged,
+        };
+        let open = PullRequest {
+            number: 4,
+            state: ReviewState::Open,
+            ..merged.clone()
+        };
+        let closed = PullRequest {
+            number: 5,
+            state: ReviewState::Closed,
+            ..merged.clone()
+        };
+        let mut cache = Cache::load(&repo, 1000);
+        assert_eq!(cache.get_pull_request("site", "feat", tip), None);
+        cache.put_pull_request("site", "feat", tip, Some(merged.clone()));
+        cache.put_pull_request("site", "wip", tip, Some(open));
+        cache.put_pull_request("site", "dropped", tip, Some(closed));
+        cache.put_ci_status("site", tip, None);
+        cache.put_protected("site", vec![ProtectedRef::Exact("main".into())]);
+        cache.put_issue_state("jira", "PROJ-1", Some(IssueState::Resolved));
+        cache.put_issue_state("jira", "PROJ-2", Some(IssueState::Open));
+        cache.save().unwrap();
+        let cache = Cache::load(&repo, 1000 + SHORT_TTL);
+        assert_eq!(
+            cache.get_pull_request("site", "feat", tip),
+            Some(Some(merged))
+        );
+        assert_eq!(cache.get_pull_request("site", "wip", tip), None);
+        assert_eq!(cache.get_pull_request("site", "dropped", tip), None);
+        assert_eq!(cache.get_pull_request("other", "feat", tip), None);
+        assert_eq!(cache.get_ci_status("site", tip), None);
+        assert_eq!(cache.get_protected("site").unwrap().len(), 1);
+        assert_eq!(
+            cache.get_issue_state("jira", "PROJ-1"),
+            Some(Some(IssueState::Resolved))
+        );
+        assert_eq!(cache.get_issue_state("jira", "PROJ-2"), None);
+    }
+}
@@ -0,0 +1,129 @@
+//! Gerrit, whose REST API deletes branches for the users with the "Delete Reference"
+//! permission, while pushes deleting them are often refused.
+
+use eyre::{eyre, Result};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::Deserialize;
+
+use super::{basic_auth, call, describe_error, Hosting, HostingKind, Site, USER_AGENT};
+
+/// Gerrit starts its JSON answers with this, against cross-site script inclusion.
+const XSSI_PREFIX: &str = ")]}'";
+
+pub struct Gerrit {
+    api_url: String,
+    project: String,
+    user: Option<String>,
+    /// The HTTP password, generated in the settings of the user.
+    password: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct BranchInfo {
+    #[serde(rename = "ref")]
+    reference: String,
+}
+
+fn encode(s: &str) -> String {
+    utf8_percent_encode(s, NON_ALPHANUMERIC).to_string()
+}
+
+/// Names of the branches, from the answer listing them.
+fn parse_branches(body: &str) -> Result<Vec<String>> {
+    let json = body.strip_prefix(XSSI_PREFIX).unwrap_or(body);
+    let branches: Vec<BranchInfo> = serde_json::from_str(json)?;
+    Ok(branches
+        .into_iter()
+        .filter_map(|b| b.reference.strip_prefix("refs/heads/").map(String::from))
+        .collect())
+}
+
+impl Gerrit {
+    pub fn new(site: &Site, password: &str) -> Self {
+        Self {
+            api_url: site.api_url.clone(),
+            project: site.project.clone(),
+            user: site.user.clone(),
+            password: password.to_string(),
+        }
+    }
+
+    /// URL of the path under the project, on the authenticated API, whose paths start with "/a/".
+    fn get_url(&self, path: &str) -> String {
+        format!(
+            "{}/a/projects/{}/{path}",
+            self.api_url,
+            encode(&self.project)
+        )
+    }
+
+    fn request(&self, method: &str, url: &str) -> Result<ureq::Request> {
+        let user = self.user.as_deref().ok_or_else(|| {
+            eyre!("Gerrit needs a username, put it in the URL of the remote or in del-branches.hostingUser")
+        })?;
+        Ok(ureq::request(method, url)
+            .set("User-Agent", USER_AGENT)
+            .set("Authorization", &basic_auth(user, &self.password)))
+    }
+}
+
+impl Hosting for Gerrit {
+    fn kind(&self) -> HostingKind {
+        HostingKind::Gerrit
+    }
+
+    fn list_branches(&self) -> Result<Vec<String>> {
+        let url = self.get_url("branches/");
+        let body = call(self.request("GET", &url)?)
+            .map_err(|e| describe_error(*e, "Gerrit"))?
+            .into_string()?;
+        parse_branches(&body)
+    }
+
+    fn delete_branch(&self, name: &str) -> Result<()> {
+        let url = self.get_url(&format!("branches/{}", encode(name)));
+        let Err(e) = call(self.request("DELETE", &url)?) else {
+            return Ok(());
+        };
+        match *e {
+            ureq::Error::Status(404, _) => Ok(()),
+            e => Err(describe_error(e, "Gerrit")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_branches() {
+        let body = r#")]}'
+[
+  {"ref": "HEAD", "revision": "master"},
+  {"ref": "refs/meta/config", "revision": "76016386a0d8ecc7b6be212424978bb45959d668"},
+  {"ref": "refs/heads/master", "revision": "67ebf73496383c6777035e374d2d664009e2aa5c"},
+  {"ref": "refs/heads/fix/login", "revision": "1c7a1dd5e770730e98e4b7a9d2d4c5bf3a1c850e"}
+]"#;
+        assert_eq!(parse_branches(body).unwrap(), ["master", "fix/login"]);
+    }
+
+    #[test]
+    fn builds_urls() {
+        let site = Site {
+            kind: HostingKind::Gerrit,
+            api_url: "https://review.example.com".into(),
+            host: "review.example.com".into(),
+            project: "tools/app".into(),
+            user: None,
+            token_env: "GERRIT_HTTP_PASSWORD".into(),
+            guessed: false,
+        };
+        let gerrit = Gerrit::new(&site, "secret");
+        assert_eq!(
+            gerrit.get_url(&format!("branches/{}", encode("fix/login"))),
+            "https://review.example.com/a/projects/tools%2Fapp/branches/fix%2Flogin"
+        );
+        assert!(gerrit.request("GET", "https://review.example.com").is_err());
+    }
+}
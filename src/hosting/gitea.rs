@@ -0,0 +1,352 @@
+//! Gitea, and Forgejo which has its API, on self-hosted instances and Codeberg.
+
+use std::cell::OnceCell;
+use std::collections::HashMap;
+
+use eyre::{eyre, Result};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::Deserialize;
+
+use super::{
+    call, describe_error, CiStatus, Hosting, HostingKind, ProtectedRef, PullRequest, RemoteUrl,
+    ReviewState, Site, USER_AGENT,
+};
+
+/// Items per page, the most which Gitea gives by default.
+const PAGE_SIZE: usize = 50;
+/// Pages of pull requests looked up, the most recently updated first.
+const MAX_PULL_PAGES: usize = 10;
+
+/// Project of the remote, as "owner/repository", from the end of the path, which may start
+/// with the subpath of the instance, like "gitea/owner/repo".
+pub fn get_project(url: &RemoteUrl) -> String {
+    let segments: Vec<&str> = url.path.split('/').collect();
+    segments[segments.len().saturating_sub(2)..].join("/")
+}
+
+/// URL of the instance, from the web server of the remote and the subpath before the project,
+/// like "https://example.com/gitea".
+pub fn get_api_url(url: &RemoteUrl) -> String {
+    let segments: Vec<&str> = url.path.split('/').collect();
+    let subpath = &segments[..segments.len().saturating_sub(2)];
+    // SSH paths start at the project, whatever the subpath of the web server
+    if subpath.is_empty() || !url.scheme.starts_with("http") {
+        return url.get_web_url();
+    }
+    format!("{}/{}", url.get_web_url(), subpath.join("/"))
+}
+
+fn encode(s: &str) -> String {
+    utf8_percent_encode(s, NON_ALPHANUMERIC).to_string()
+}
+
+#[derive(Debug, Deserialize)]
+struct Branch {
+    name: String,
+    #[serde(default)]
+    protected: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct PullRequestInfo {
+    number: u64,
+    title: String,
+    state: String,
+    #[serde(default)]
+    merged: bool,
+    head: PullHead,
+}
+
+#[derive(Debug, Deserialize)]
+struct PullHead {
+    #[serde(rename = "ref")]
+    reference: String,
+    /// Absent when the repository of the head was deleted.
+    repo: Option<RepositoryInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RepositoryInfo {
+    full_name: String,
+}
+
+/// Status of a commit, combined from all its statuses.
+#[derive(Debug, Deserialize)]
+struct CombinedStatus {
+    state: String,
+    total_count: usize,
+}
+
+impl CombinedStatus {
+    fn get_ci_status(&self) -> Option<CiStatus> {
+        if self.total_count == 0 {
+            return None;
+        }
+        match self.state.as_str() {
+            "success" | "warning" => Some(CiStatus::Passed),
+            "pending" => Some(CiStatus::Pending),
+            "failure" | "error" => Some(CiStatus::Failed),
+            _ => None,
+        }
+    }
+}
+
+impl PullRequestInfo {
+    /// Whether it is from a branch of the repository, not of a fork.
+    fn is_from(&self, project: &str) -> bool {
+        self.head
+            .repo
+            .as_ref()
+            .is_some_and(|r| r.full_name.eq_ignore_ascii_case(project))
+    }
+
+    fn into_pull_request(self) -> PullRequest {
+        let state = match (self.state.as_str(), self.merged) {
+            (_, true) => ReviewState::Merged,
+            ("closed", false) => ReviewState::Closed,
+            _ => ReviewState::Open,
+        };
+        PullRequest {
+            number: self.number,
+            title: self.title,
+            state,
+        }
+    }
+}
+
+/// The pull requests from the branches of the project, in the same order, with their branches.
+fn get_own_pulls(pulls: Vec<PullRequestInfo>, project: &str) -> Vec<(String, PullRequest)> {
+    pulls
+        .into_iter()
+        .filter(|p| p.is_from(project))
+        .map(|p| (p.head.reference.clone(), p.into_pull_request()))
+        .collect()
+}
+
+pub struct Gitea {
+    api_url: String,
+    project: String,
+    token: String,
+    /// The latest pull requests from the branches of the repository, with their branches, as
+    /// the API cannot filter them by branch.
+    pulls: OnceCell<Vec<(String, PullRequest)>>,
+}
+
+impl Gitea {
+    pub fn new(site: &Site, token: &str) -> Self {
+        Self {
+            api_url: site.api_url.clone(),
+            project: site.project.clone(),
+            token: token.to_string(),
+            pulls: OnceCell::new(),
+        }
+    }
+
+    /// URL of the path under the repository.
+    fn get_url(&self, path: &str) -> String {
+        format!("{}/api/v1/repos/{}/{path}", self.api_url, self.project)
+    }
+
+    fn request(&self, method: &str, url: &str) -> ureq::Request {
+        ureq::request(method, url)
+            .set("User-Agent", USER_AGENT)
+            .set("Authorization", &format!("token {}", self.token))
+    }
+
+    /// Get the items of the pages of the path, up to `max_pages` of them.
+    fn get_pages<T: for<'de> Deserialize<'de>>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+        max_pages: usize,
+    ) -> Result<Vec<T>> {
+        let url = self.get_url(path);
+        let limit = PAGE_SIZE.to_string();
+        let mut items = Vec::new();
+        for page in 1..=max_pages {
+            let mut request = self
+                .request("GET", &url)
+                .query("limit", &limit)
+                .query("page", &page.to_string());
+            for (name, value) in query {
+                request = request.query(name, value);
+            }
+            let page_items: Vec<T> = call(request)
+                .map_err(|e| describe_error(*e, HostingKind::Gitea.title()))?
+                .into_json()?;
+            let last = page_items.len() < PAGE_SIZE;
+            items.extend(page_items);
+            if last {
+                break;
+            }
+        }
+        Ok(items)
+    }
+
+    fn list_all_branches(&self) -> Result<Vec<Branch>> {
+        self.get_pages("branches", &[], usize::MAX)
+    }
+
+    /// The latest pull requests from the branches of the repository, listed once for all the
+    /// branches.
+    fn get_pulls(&self) -> Result<&[(String, PullRequest)]> {
+        if let Some(pulls) = self.pulls.get() {
+            return Ok(pulls);
+        }
+        let query = [("state", "all"), ("sort", "recentupdate")];
+        let pulls: Vec<PullRequestInfo> = self.get_pages("pulls", &query, MAX_PULL_PAGES)?;
+        Ok(self
+            .pulls
+            .get_or_init(|| get_own_pulls(pulls, &self.project)))
+    }
+}
+
+impl Hosting for Gitea {
+    fn kind(&self) -> HostingKind {
+        HostingKind::Gitea
+    }
+
+    fn list_branches(&self) -> Result<Vec<String>> {
+        Ok(self
+            .list_all_branches()?
+            .into_iter()
+            .map(|b| b.name)
+            .collect())
+    }
+
+    fn delete_branch(&self, name: &str) -> Result<()> {
+        let url = self.get_url(&format!("branches/{}", encode(name)));
+        let Err(e) = call(self.request("DELETE", &url)) else {
+            return Ok(());
+        };
+        match *e {
+            ureq::Error::Status(404, _) => Ok(()),
+            ureq::Error::Status(403, _) => Err(eyre!(
+                "Gitea refused to delete the branch, it may be protected or the token lacks the write:repository scope"
+            )),
+            e => Err(describe_error(e, HostingKind::Gitea.title())),
+        }
+    }
+
+    fn find_pull_request(&self, branch: &str) -> Result<Option<PullRequest>> {
+        Ok(self
+            .get_pulls()?
+            .iter()
+            .find(|(head, _)| head == branch)
+            .map(|(_, pr)| pr.clone()))
+    }
+
+    fn find_pull_requests(&self, branches: &[&str]) -> Result<HashMap<String, PullRequest>> {
+        let mut found = HashMap::new();
+        // The most recently updated first, which is kept
+        for (head, pr) in self.get_pulls()? {
+            if branches.contains(&head.as_str()) && !found.contains_key(head) {
+                found.insert(head.clone(), pr.clone());
+            }
+        }
+        Ok(found)
+    }
+
+    fn list_protected(&self) -> Result<Vec<ProtectedRef>> {
+        Ok(self
+            .list_all_branches()?
+            .into_iter()
+            .filter(|b| b.protected)
+            .map(|b| ProtectedRef::Exact(b.name))
+            .collect())
+    }
+
+    fn find_ci_status(&self, commit: &str) -> Result<Option<CiStatus>> {
+        let url = self.get_url(&format!("commits/{commit}/status"));
+        let status: CombinedStatus = call(self.request("GET", &url))
+            .map_err(|e| describe_error(*e, HostingKind::Gitea.title()))?
+            .into_json()?;
+        Ok(status.get_ci_status())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hosting::parse_remote_url;
+    use crate::testutil::serve_json;
+
+    #[test]
+    fn gets_projects() {
+        let url = parse_remote_url("https://example.com/gitea/me/app.git").unwrap();
+        assert_eq!(get_project(&url), "me/app");
+        let url = parse_remote_url("git@codeberg.org:me/app.git").unwrap();
+        assert_eq!(get_project(&url), "me/app");
+        assert_eq!(get_api_url(&url), "https://codeberg.org");
+    }
+
+    #[test]
+    fn gets_api_urls() {
+        let url = parse_remote_url("https://example.com/gitea/me/app.git").unwrap();
+        assert_eq!(get_api_url(&url), "https://example.com/gitea");
+        let url = parse_remote_url("http://git.local:3000/me/app").unwrap();
+        assert_eq!(get_api_url(&url), "http://git.local:3000");
+        let url = parse_remote_url("ssh://git@git.local:2222/me/app").unwrap();
+        assert_eq!(get_api_url(&url), "https://git.local");
+    }
+
+    #[test]
+    fn reads_ci_status() {
+        let status: CombinedStatus =
+            serde_json::from_str(r#"{"state": "failure", "total_count": 2}"#).unwrap();
+        assert_eq!(status.get_ci_status(), Some(CiStatus::Failed));
+        let status: CombinedStatus =
+            serde_json::from_str(r#"{"state": "", "total_count": 0}"#).unwrap();
+        assert_eq!(status.get_ci_status(), None);
+    }
+
+    #[test]
+    fn reads_pull_requests() {
+        let body = r#"[
+            {"number": 7, "title": "Fix login", "state": "closed", "merged": true,
+             "head": {"ref": "fix/login", "repo": {"full_name": "someone/app"}}},
+            {"number": 5, "title": "Fix login", "state": "closed", "merged": false,
+             "head": {"ref": "fix/login", "repo": {"full_name": "Me/App"}}},
+            {"number": 3, "title": "Old", "state": "closed", "merged": false,
+             "head": {"ref": "old", "repo": null}}
+        ]"#;
+        let pulls = get_own_pulls(serde_json::from_str(body).unwrap(), "me/app");
+        assert_eq!(pulls.len(), 1);
+        let (head, pr) = &pulls[0];
+        assert_eq!(head, "fix/login");
+        assert_eq!((pr.number, pr.state), (5, ReviewState::Closed));
+    }
+
+    #[test]
+    fn finds_pull_requests_at_once() {
+        let body = r#"[
+            {"number": 9, "title": "Pay again", "state": "open", "merged": false,
+             "head": {"ref": "pay", "repo": {"full_name": "me/app"}}},
+            {"number": 8, "title": "Login", "state": "closed", "merged": true,
+             "head": {"ref": "login", "repo": {"full_name": "me/app"}}},
+            {"number": 4, "title": "Pay", "state": "closed", "merged": true,
+             "head": {"ref": "pay", "repo": {"full_name": "me/app"}}}
+        ]"#;
+        let (url, requests) = serve_json(&[body]);
+        let site = Site {
+            kind: HostingKind::Gitea,
+            api_url: url,
+            host: "127.0.0.1".into(),
+            project: "me/app".into(),
+            user: None,
+            token_env: "GITEA_TOKEN".into(),
+            guessed: false,
+        };
+        let gitea = Gitea::new(&site, "secret");
+        let prs = gitea
+            .find_pull_requests(&["login", "pay", "docs", "old"])
+            .unwrap();
+        assert_eq!(prs.len(), 2);
+        assert_eq!(prs["pay"].number, 9);
+        assert_eq!(prs["login"].state, ReviewState::Merged);
+        assert_eq!(gitea.find_pull_request("login").unwrap().unwrap().number, 8);
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].starts_with("GET /api/v1/repos/me/app/pulls?"));
+    }
+}
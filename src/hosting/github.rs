@@ -0,0 +1,444 @@
+//! GitHub, on github.com, reached with a token: pull requests, asked for many branches in one
+//! GraphQL query, protected branches, CI checks, and deleting branches.
+
+use std::collections::HashMap;
+
+use eyre::{eyre, Result};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::Deserialize;
+use serde_json::json;
+
+use super::{
+    call, describe_error, gitea, send, CiStatus, Hosting, HostingKind, ProtectedRef, PullRequest,
+    RemoteUrl, ReviewState, Site, USER_AGENT,
+};
+
+pub const API_URL: &str = "https://api.github.com";
+pub const WEB_URL: &str = "https://github.com";
+/// Path of the REST API on the servers of GitHub Enterprise.
+const ENTERPRISE_API_PATH: &str = "/api/v3";
+/// Items per page, the most which GitHub gives.
+const PAGE_SIZE: usize = 100;
+/// Branches asked about in one GraphQL query, well below the limits of its cost.
+const BATCH_SIZE: usize = 50;
+/// Pull requests looked up for each branch, as some may be from forks.
+const PULLS_PER_BRANCH: usize = 5;
+
+/// Project of the remote, as "owner/repository".
+pub fn get_project(url: &RemoteUrl) -> String {
+    gitea::get_project(url)
+}
+
+/// URL of the API: the one of github.com, else the one of a GitHub Enterprise server, like
+/// "https://github.example.com/api/v3".
+pub fn get_api_url(url: &RemoteUrl) -> String {
+    match url.host == "github.com" {
+        true => API_URL.to_string(),
+        false => format!("{}{ENTERPRISE_API_PATH}", url.get_web_url()),
+    }
+}
+
+/// URL of the website of the API, which the pages of the pull requests are on.
+pub fn get_web_url(api_url: &str) -> String {
+    match api_url == API_URL {
+        true => WEB_URL.to_string(),
+        false => api_url
+            .strip_suffix(ENTERPRISE_API_PATH)
+            .unwrap_or(api_url)
+            .to_string(),
+    }
+}
+
+/// URL of the GraphQL API, which GitHub Enterprise serves beside its REST API.
+fn get_graphql_url(api_url: &str) -> String {
+    match api_url.strip_suffix(ENTERPRISE_API_PATH) {
+        Some(web_url) => format!("{web_url}/api/graphql"),
+        None => format!("{api_url}/graphql"),
+    }
+}
+
+fn encode(s: &str) -> String {
+    utf8_percent_encode(s, NON_ALPHANUMERIC).to_string()
+}
+
+#[derive(Debug, Deserialize)]
+struct Branch {
+    name: String,
+}
+
+/// Status of a commit, combined from the statuses which integrations set on it.
+#[derive(Debug, Deserialize)]
+struct CombinedStatus {
+    state: String,
+    total_count: usize,
+}
+
+/// The check runs of a commit, like those of GitHub Actions, which are not statuses.
+#[derive(Debug, Deserialize)]
+struct CheckRuns {
+    check_runs: Vec<CheckRun>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CheckRun {
+    status: String,
+    conclusion: Option<String>,
+}
+
+impl CombinedStatus {
+    fn get_ci_status(&self) -> Option<CiStatus> {
+        if self.total_count == 0 {
+            return None;
+        }
+        match self.state.as_str() {
+            "success" => Some(CiStatus::Passed),
+            "pending" => Some(CiStatus::Pending),
+            "failure" | "error" => Some(CiStatus::Failed),
+            _ => None,
+        }
+    }
+}
+
+impl CheckRun {
+    fn get_ci_status(&self) -> Option<CiStatus> {
+        if self.status != "completed" {
+            return Some(CiStatus::Pending);
+        }
+        match self.conclusion.as_deref() {
+            Some("success") => Some(CiStatus::Passed),
+            Some("failure" | "timed_out" | "action_required") => Some(CiStatus::Failed),
+            // "neutral", "cancelled" and "skipped"
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphQlAnswer {
+    data: Option<GraphQlData>,
+    #[serde(default)]
+    errors: Vec<GraphQlError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphQlData {
+    /// The pull requests of each branch, by the aliases of the query.
+    repository: Option<HashMap<String, PullRequestNodes>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphQlError {
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PullRequestNodes {
+    nodes: Vec<PullRequestNode>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PullRequestNode {
+    number: u64,
+    title: String,
+    state: String,
+    /// Absent when the repository of the head was deleted.
+    head_repository: Option<HeadRepository>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HeadRepository {
+    name_with_owner: String,
+}
+
+/// Query of the latest pull requests of the branches, each under the alias "b<index>".
+fn build_pull_request_query(branches: &[&str]) -> String {
+    let fields: String = branches
+        .iter()
+        .enumerate()
+        .map(|(i, branch)| {
+            // A JSON string is a GraphQL string too
+            let name = serde_json::Value::from(*branch);
+            format!(
+                "b{i}: pullRequests(headRefName: {name}, first: {PULLS_PER_BRANCH}, \
+                 orderBy: {{field: CREATED_AT, direction: DESC}}) \
+                 {{ nodes {{ number title state headRepository {{ nameWithOwner }} }} }}\n"
+            )
+        })
+        .collect();
+    format!(
+        "query($owner: String!, $name: String!) {{\nrepository(owner: $owner, name: $name) {{\n{fields}}}\n}}"
+    )
+}
+
+/// The latest pull requests from the branches of the project, not from forks, by branch, from
+/// the answer to the query of `build_pull_request_query`.
+fn parse_pull_requests(
+    body: &str,
+    branches: &[&str],
+    project: &str,
+) -> Result<HashMap<String, PullRequest>> {
+    let answer: GraphQlAnswer = serde_json::from_str(body)?;
+    if let Some(error) = answer.errors.first() {
+        return Err(eyre!("GitHub refused the query: {}", error.message));
+    }
+    let mut by_alias = answer
+        .data
+        .and_then(|d| d.repository)
+        .ok_or_else(|| eyre!("GitHub has no repository {project}"))?;
+    let mut found = HashMap::new();
+    for (i, branch) in branches.iter().enumerate() {
+        let Some(prs) = by_alias.remove(&format!("b{i}")) else {
+            continue;
+        };
+        let own = prs.nodes.into_iter().find(|pr| {
+            pr.head_repository
+                .as_ref()
+                .is_some_and(|r| r.name_with_owner.eq_ignore_ascii_case(project))
+        });
+        if let Some(pr) = own {
+            let state = match pr.state.as_str() {
+                "MERGED" => ReviewState::Merged,
+                "CLOSED" => ReviewState::Closed,
+                _ => ReviewState::Open,
+            };
+            let pr = PullRequest {
+                number: pr.number,
+                title: pr.title,
+                state,
+            };
+            found.insert(branch.to_string(), pr);
+        }
+    }
+    Ok(found)
+}
+
+pub struct GitHub {
+    api_url: String,
+    project: String,
+    token: String,
+}
+
+impl GitHub {
+    pub fn new(site: &Site, token: &str) -> Self {
+        Self {
+            api_url: site.api_url.clone(),
+            project: site.project.clone(),
+            token: token.to_string(),
+        }
+    }
+
+    /// URL of the path under the repository.
+    fn get_url(&self, path: &str) -> String {
+        format!("{}/repos/{}/{path}", self.api_url, self.project)
+    }
+
+    fn request(&self, method: &str, url: &str) -> ureq::Request {
+        ureq::request(method, url)
+            .set("User-Agent", USER_AGENT)
+            .set("Accept", "application/vnd.github+json")
+            .set("Authorization", &format!("Bearer {}", self.token))
+    }
+
+    fn get_json<T: for<'de> Deserialize<'de>>(&self, url: &str) -> Result<T> {
+        Ok(call(self.request("GET", url))
+            .map_err(|e| describe_error(*e, HostingKind::GitHub.title()))?
+            .into_json()?)
+    }
+
+    /// Names of the branches, only the protected ones if asked.
+    fn list_branch_names(&self, protected: bool) -> Result<Vec<String>> {
+        let url = self.get_url("branches");
+        let mut names = Vec::new();
+        for page in 1.. {
+            let mut request = self
+                .request("GET", &url)
+                .query("per_page", &PAGE_SIZE.to_string())
+                .query("page", &page.to_string());
+            if protected {
+                request = request.query("protected", "true");
+            }
+            let branches: Vec<Branch> = call(request)
+                .map_err(|e| describe_error(*e, HostingKind::GitHub.title()))?
+                .into_json()?;
+            let last = branches.len() < PAGE_SIZE;
+            names.extend(branches.into_iter().map(|b| b.name));
+            if last {
+                break;
+            }
+        }
+        Ok(names)
+    }
+}
+
+impl Hosting for GitHub {
+    fn kind(&self) -> HostingKind {
+        HostingKind::GitHub
+    }
+
+    fn list_branches(&self) -> Result<Vec<String>> {
+        self.list_branch_names(false)
+    }
+
+    fn delete_branch(&self, name: &str) -> Result<()> {
+        let path: Vec<String> = name.split('/').map(encode).collect();
+        let url = self.get_url(&format!("git/refs/heads/{}", path.join("/")));
+        let Err(e) = call(self.request("DELETE", &url)) else {
+            return Ok(());
+        };
+        match *e {
+            ureq::Error::Status(404, _) => Ok(()),
+            ureq::Error::Status(422, response) => {
+                let body = response.into_string().unwrap_or_default();
+                match body.contains("Reference does not exist") {
+                    true => Ok(()),
+                    false => Err(eyre!(
+                        "GitHub refused to delete the branch, it may be protected: {}",
+                        body.trim()
+                    )),
+                }
+            }
+            ureq::Error::Status(403, _) => Err(eyre!(
+                "GitHub refused to delete the branch, the token may lack the repo scope"
+            )),
+            e => Err(describe_error(e, HostingKind::GitHub.title())),
+        }
+    }
+
+    fn find_pull_request(&self, branch: &str) -> Result<Option<PullRequest>> {
+        Ok(self.find_pull_requests(&[branch])?.remove(branch))
+    }
+
+    /// In one GraphQL query for up to `BATCH_SIZE` branches.
+    fn find_pull_requests(&self, branches: &[&str]) -> Result<HashMap<String, PullRequest>> {
+        let (owner, name) = self
+            .project
+            .split_once('/')
+            .ok_or_else(|| eyre!("Cannot tell the owner of \"{}\" on GitHub", self.project))?;
+        let mut found = HashMap::new();
+        for batch in branches.chunks(BATCH_SIZE) {
+            let query = json!({
+                "query": build_pull_request_query(batch),
+                "variables": { "owner": owner, "name": name },
+            });
+            let request = self.request("POST", &get_graphql_url(&self.api_url));
+            let body = send(request, Some(&query))
+                .map_err(|e| describe_error(*e, HostingKind::GitHub.title()))?
+                .into_string()?;
+            found.extend(parse_pull_requests(&body, batch, &self.project)?);
+        }
+        Ok(found)
+    }
+
+    fn list_protected(&self) -> Result<Vec<ProtectedRef>> {
+        Ok(self
+            .list_branch_names(true)?
+            .into_iter()
+            .map(ProtectedRef::Exact)
+            .collect())
+    }
+
+    fn find_ci_status(&self, commit: &str) -> Result<Option<CiStatus>> {
+        let status: CombinedStatus =
+            self.get_json(&self.get_url(&format!("commits/{commit}/status")))?;
+        let runs: CheckRuns =
+            self.get_json(&self.get_url(&format!("commits/{commit}/check-runs")))?;
+        let statuses = status.get_ci_status().into_iter();
+        Ok(CiStatus::combine(statuses.chain(
+            runs.check_runs.iter().filter_map(CheckRun::get_ci_status),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hosting::parse_remote_url;
+    use crate::testutil::serve_json;
+
+    #[test]
+    fn finds_api_urls() {
+        let url = parse_remote_url("git@github.com:me/app.git").unwrap();
+        assert_eq!(get_api_url(&url), API_URL);
+        assert_eq!(get_web_url(API_URL), WEB_URL);
+        assert_eq!(get_graphql_url(API_URL), "https://api.github.com/graphql");
+        let url = parse_remote_url("https://github.example.com:8443/corp/app.git").unwrap();
+        let api_url = get_api_url(&url);
+        assert_eq!(api_url, "https://github.example.com:8443/api/v3");
+        assert_eq!(get_web_url(&api_url), "https://github.example.com:8443");
+        assert_eq!(
+            get_graphql_url(&api_url),
+            "https://github.example.com:8443/api/graphql"
+        );
+    }
+
+    #[test]
+    fn builds_queries() {
+        let query = build_pull_request_query(&["fix/login", "say-\"hi\""]);
+        assert!(query.contains("b0: pullRequests(headRefName: \"fix/login\""));
+        assert!(query.contains("b1: pullRequests(headRefName: \"say-\\\"hi\\\"\""));
+    }
+
+    #[test]
+    fn reads_ci_status() {
+        let status: CombinedStatus =
+            serde_json::from_str(r#"{"state": "pending", "total_count": 0}"#).unwrap();
+        assert_eq!(status.get_ci_status(), None);
+        let runs: CheckRuns = serde_json::from_str(
+            r#"{"total_count": 2, "check_runs": [
+                {"status": "completed", "conclusion": "success"},
+                {"status": "completed", "conclusion": "failure"}
+            ]}"#,
+        )
+        .unwrap();
+        let statuses = runs.check_runs.iter().filter_map(CheckRun::get_ci_status);
+        assert_eq!(CiStatus::combine(statuses), Some(CiStatus::Failed));
+    }
+
+    #[test]
+    fn finds_pull_requests_at_once() {
+        let body = r#"{"data": {"repository": {
+            "b0": {"nodes": [
+                {"number": 7, "title": "Theirs", "state": "OPEN",
+                 "headRepository": {"nameWithOwner": "someone/app"}},
+                {"number": 5, "title": "Fix login", "state": "MERGED",
+                 "headRepository": {"nameWithOwner": "Me/App"}}
+            ]},
+            "b1": {"nodes": []},
+            "b2": {"nodes": [
+                {"number": 3, "title": "Pay", "state": "CLOSED",
+                 "headRepository": {"nameWithOwner": "me/app"}}
+            ]}
+        }}}"#;
+        let (url, requests) = serve_json(&[body]);
+        let site = Site {
+            kind: HostingKind::GitHub,
+            api_url: url,
+            host: "127.0.0.1".into(),
+            project: "me/app".into(),
+            user: None,
+            token_env: "GITHUB_TOKEN".into(),
+            guessed: false,
+        };
+        let github = GitHub::new(&site, "secret");
+        let prs = github
+            .find_pull_requests(&["login", "docs", "pay"])
+            .unwrap();
+        assert_eq!(prs.len(), 2);
+        assert_eq!(
+            (prs["login"].number, prs["login"].state),
+            (5, ReviewState::Merged)
+        );
+        assert_eq!(prs["pay"].state, ReviewState::Closed);
+        assert_eq!(*requests.lock().unwrap(), ["POST /graphql"]);
+
+        let e = parse_pull_requests(
+            r#"{"data": null, "errors": [{"message": "Bad"}]}"#,
+            &[],
+            "me/app",
+        )
+        .unwrap_err();
+        assert_eq!(e.to_string(), "GitHub refused the query: Bad");
+    }
+}
@@ -0,0 +1,314 @@
+//! GitLab, on gitlab.com and self-hosted instances: merge requests, protected branches,
+//! pipelines, and deleting branches.
+
+use std::cell::OnceCell;
+use std::collections::HashMap;
+
+use eyre::{eyre, Result};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::Deserialize;
+
+use super::{
+    call, describe_error, CiStatus, Hosting, HostingKind, ProtectedRef, PullRequest, RemoteUrl,
+    ReviewState, Site, USER_AGENT,
+};
+
+/// Items per page, the most which GitLab gives.
+const PAGE_SIZE: usize = 100;
+/// Pages of merge requests looked up, the most recently updated first.
+const MAX_MERGE_PAGES: usize = 10;
+
+/// Project of the remote, with all its groups, like "group/subgroup/app".
+pub fn get_project(url: &RemoteUrl) -> String {
+    url.path.clone()
+}
+
+/// URL of the instance, from the web server of the remote, which serves the API under
+/// "/api/v4".
+pub fn get_api_url(url: &RemoteUrl) -> String {
+    url.get_web_url()
+}
+
+fn encode(s: &str) -> String {
+    utf8_percent_encode(s, NON_ALPHANUMERIC).to_string()
+}
+
+#[derive(Debug, Deserialize)]
+struct Branch {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct MergeRequestInfo {
+    /// Number of the merge request in the project.
+    iid: u64,
+    title: String,
+    state: String,
+    source_branch: String,
+    source_project_id: u64,
+    target_project_id: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProtectedBranch {
+    /// Name of the branch, or a wildcard like "release/*".
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Commit {
+    last_pipeline: Option<Pipeline>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Pipeline {
+    status: String,
+}
+
+impl MergeRequestInfo {
+    fn into_pull_request(self) -> PullRequest {
+        let state = match self.state.as_str() {
+            "merged" => ReviewState::Merged,
+            "closed" => ReviewState::Closed,
+            // "opened" and "locked"
+            _ => ReviewState::Open,
+        };
+        PullRequest {
+            number: self.iid,
+            title: self.title,
+            state,
+        }
+    }
+}
+
+impl ProtectedBranch {
+    fn into_protected_ref(self) -> ProtectedRef {
+        match self.name.split_once('*') {
+            Some((prefix, _)) => ProtectedRef::Prefix(prefix.to_string()),
+            None => ProtectedRef::Exact(self.name),
+        }
+    }
+}
+
+impl Pipeline {
+    fn get_ci_status(&self) -> Option<CiStatus> {
+        match self.status.as_str() {
+            "success" => Some(CiStatus::Passed),
+            "failed" => Some(CiStatus::Failed),
+            "created"
+            | "waiting_for_resource"
+            | "preparing"
+            | "pending"
+            | "running"
+            | "scheduled" => Some(CiStatus::Pending),
+            // "canceled", "skipped" and "manual"
+            _ => None,
+        }
+    }
+}
+
+/// The merge requests from the branches of the project, not from forks, in the same order,
+/// with their branches.
+fn get_own_merge_requests(merges: Vec<MergeRequestInfo>) -> Vec<(String, PullRequest)> {
+    merges
+        .into_iter()
+        .filter(|m| m.source_project_id == m.target_project_id)
+        .map(|m| (m.source_branch.clone(), m.into_pull_request()))
+        .collect()
+}
+
+pub struct GitLab {
+    api_url: String,
+    project: String,
+    token: String,
+    /// The latest merge requests from the branches of the project, with their branches,
+    /// listed once for all the branches.
+    merges: OnceCell<Vec<(String, PullRequest)>>,
+}
+
+impl GitLab {
+    pub fn new(site: &Site, token: &str) -> Self {
+        Self {
+            api_url: site.api_url.clone(),
+            project: site.project.clone(),
+            token: token.to_string(),
+            merges: OnceCell::new(),
+        }
+    }
+
+    /// URL of the path under the project.
+    fn get_url(&self, path: &str) -> String {
+        format!(
+            "{}/api/v4/projects/{}/{path}",
+            self.api_url,
+            encode(&self.project)
+        )
+    }
+
+    fn request(&self, method: &str, url: &str) -> ureq::Request {
+        ureq::request(method, url)
+            .set("User-Agent", USER_AGENT)
+            .set("Authorization", &format!("Bearer {}", self.token))
+    }
+
+    /// Get the items of the pages of the path, up to `max_pages` of them.
+    fn get_pages<T: for<'de> Deserialize<'de>>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+        max_pages: usize,
+    ) -> Result<Vec<T>> {
+        let url = self.get_url(path);
+        let per_page = PAGE_SIZE.to_string();
+        let mut items = Vec::new();
+        for page in 1..=max_pages {
+            let mut request = self
+                .request("GET", &url)
+                .query("per_page", &per_page)
+                .query("page", &page.to_string());
+            for (name, value) in query {
+                request = request.query(name, value);
+            }
+            let page_items: Vec<T> = call(request)
+                .map_err(|e| describe_error(*e, HostingKind::GitLab.title()))?
+                .into_json()?;
+            let last = page_items.len() < PAGE_SIZE;
+            items.extend(page_items);
+            if last {
+                break;
+            }
+        }
+        Ok(items)
+    }
+
+    /// The latest merge requests from the branches of the project, listed once for all the
+    /// branches.
+    fn get_merge_requests(&self) -> Result<&[(String, PullRequest)]> {
+        if let Some(merges) = self.merges.get() {
+            return Ok(merges);
+        }
+        let query = [("state", "all"), ("order_by", "updated_at")];
+        let merges = self.get_pages("merge_requests", &query, MAX_MERGE_PAGES)?;
+        Ok(self.merges.get_or_init(|| get_own_merge_requests(merges)))
+    }
+}
+
+impl Hosting for GitLab {
+    fn kind(&self) -> HostingKind {
+        HostingKind::GitLab
+    }
+
+    fn list_branches(&self) -> Result<Vec<String>> {
+        let branches: Vec<Branch> = self.get_pages("repository/branches", &[], usize::MAX)?;
+        Ok(branches.into_iter().map(|b| b.name).collect())
+    }
+
+    fn delete_branch(&self, name: &str) -> Result<()> {
+        let url = self.get_url(&format!("repository/branches/{}", encode(name)));
+        let Err(e) = call(self.request("DELETE", &url)) else {
+            return Ok(());
+        };
+        match *e {
+            ureq::Error::Status(404, _) => Ok(()),
+            ureq::Error::Status(403, _) => Err(eyre!(
+                "GitLab refused to delete the branch, it may be protected or the token lacks the api scope"
+            )),
+            e => Err(describe_error(e, HostingKind::GitLab.title())),
+        }
+    }
+
+    fn find_pull_request(&self, branch: &str) -> Result<Option<PullRequest>> {
+        Ok(self
+            .get_merge_requests()?
+            .iter()
+            .find(|(source, _)| source == branch)
+            .map(|(_, pr)| pr.clone()))
+    }
+
+    fn find_pull_requests(&self, branches: &[&str]) -> Result<HashMap<String, PullRequest>> {
+        let mut found = HashMap::new();
+        // The most recently updated first, which is kept
+        for (source, pr) in self.get_merge_requests()? {
+            if branches.contains(&source.as_str()) && !found.contains_key(source) {
+                found.insert(source.clone(), pr.clone());
+            }
+        }
+        Ok(found)
+    }
+
+    fn list_protected(&self) -> Result<Vec<ProtectedRef>> {
+        let branches: Vec<ProtectedBranch> =
+            self.get_pages("protected_branches", &[], usize::MAX)?;
+        Ok(branches
+            .into_iter()
+            .map(ProtectedBranch::into_protected_ref)
+            .collect())
+    }
+
+    fn find_ci_status(&self, commit: &str) -> Result<Option<CiStatus>> {
+        let url = self.get_url(&format!("repository/commits/{commit}"));
+        let commit: Commit = call(self.request("GET", &url))
+            .map_err(|e| describe_error(*e, HostingKind::GitLab.title()))?
+            .into_json()?;
+        Ok(commit.last_pipeline.and_then(|p| p.get_ci_status()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::serve_json;
+
+    #[test]
+    fn reads_protected_branches() {
+        let branches: Vec<ProtectedBranch> =
+            serde_json::from_str(r#"[{"name": "main"}, {"name": "release/*"}]"#).unwrap();
+        let protected: Vec<ProtectedRef> = branches
+            .into_iter()
+            .map(ProtectedBranch::into_protected_ref)
+            .collect();
+        assert!(protected[0].matches("main"));
+        assert!(!protected[0].matches("main-2"));
+        assert!(protected[1].matches("release/1.0"));
+        assert!(!protected[1].matches("releases"));
+    }
+
+    #[test]
+    fn finds_merge_requests_at_once() {
+        let body = r#"[
+            {"iid": 9, "title": "Theirs", "state": "opened", "source_branch": "login",
+             "source_project_id": 2, "target_project_id": 1},
+            {"iid": 8, "title": "Fix login again", "state": "opened", "source_branch": "login",
+             "source_project_id": 1, "target_project_id": 1},
+            {"iid": 5, "title": "Fix login", "state": "merged", "source_branch": "login",
+             "source_project_id": 1, "target_project_id": 1},
+            {"iid": 3, "title": "Pay", "state": "closed", "source_branch": "pay",
+             "source_project_id": 1, "target_project_id": 1}
+        ]"#;
+        let (url, requests) = serve_json(&[body]);
+        let site = Site {
+            kind: HostingKind::GitLab,
+            api_url: url,
+            host: "127.0.0.1".into(),
+            project: "group/me/app".into(),
+            user: None,
+            token_env: "GITLAB_TOKEN".into(),
+            guessed: false,
+        };
+        let gitlab = GitLab::new(&site, "secret");
+        let prs = gitlab
+            .find_pull_requests(&["login", "docs", "pay"])
+            .unwrap();
+        assert_eq!(prs.len(), 2);
+        assert_eq!(
+            (prs["login"].number, prs["login"].state),
+            (8, ReviewState::Open)
+        );
+        assert_eq!(prs["pay"].state, ReviewState::Closed);
+        let pr = gitlab.find_pull_request("pay").unwrap().unwrap();
+        assert_eq!(pr.number, 3);
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].starts_with("GET /api/v4/projects/group%2Fme%2Fapp/merge_requests?"));
+    }
+}
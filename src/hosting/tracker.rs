@@ -0,0 +1,341 @@
+//! Issue trackers, which tell whether the issue a branch is named after is resolved, like
+//! "feature/PROJ-123-login" for Jira, or "567-fix-login" and "fix/#567" for the issues of
+//! GitHub and Gitea.
+
+use std::fmt;
+
+use eyre::Result;
+use git2::Config;
+use serde::{Deserialize, Serialize};
+
+use super::{basic_auth, call, describe_error, parse_remote_url, HostingKind, Site, USER_AGENT};
+use crate::config::{JIRA_URL, JIRA_USER};
+
+pub const JIRA_TOKEN_ENV: &str = "JIRA_API_TOKEN";
+
+/// Issue which a branch is named after.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IssueKey {
+    /// Like "PROJ-123".
+    Jira(String),
+    /// Number of an issue of the repository, like 567 for "#567".
+    Number(u64),
+}
+
+impl fmt::Display for IssueKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Jira(key) => f.write_str(key),
+            Self::Number(number) => write!(f, "#{number}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IssueState {
+    Open,
+    /// Done, or closed for any reason.
+    Resolved,
+}
+
+impl fmt::Display for IssueState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Open => "open",
+            Self::Resolved => "resolved",
+        })
+    }
+}
+
+/// The word is a Jira key, like "PROJ-123": a project key of uppercase letters and digits,
+/// starting with a letter, then a dash and digits.
+fn parse_jira_key(word: &str) -> Option<IssueKey> {
+    let (project, number) = word.split_once('-')?;
+    let valid = project.len() >= 2
+        && project.starts_with(|c: char| c.is_ascii_uppercase())
+        && project
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+        && !number.is_empty()
+        && number.chars().all(|c| c.is_ascii_digit());
+    valid.then(|| IssueKey::Jira(word.to_string()))
+}
+
+/// Find the issue which the branch is named after, the first one if there are several: a
+/// Jira key, "#567", "gh-567" or "issue-567", or the number starting the last part of the
+/// name, like "567-fix-login", as GitHub and GitLab name the branches of their issues.
+pub fn find_issue_key(branch: &str) -> Option<IssueKey> {
+    let is_separator = |c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '#');
+    for word in branch.split(is_separator) {
+        // Words like "PROJ-123-login" start with the key
+        let mut parts = word.splitn(3, '-');
+        let (first, second) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+        if let Some(key) = parse_jira_key(&format!("{first}-{second}")) {
+            return Some(key);
+        }
+        if let Some(number) = first.strip_prefix('#').and_then(|n| n.parse().ok()) {
+            return Some(IssueKey::Number(number));
+        }
+        let prefixed = ["gh", "issue", "issues"].contains(&first.to_ascii_lowercase().as_str());
+        if let (true, Ok(number)) = (prefixed, second.parse()) {
+            return Some(IssueKey::Number(number));
+        }
+    }
+    let last = branch.rsplit('/').next().unwrap_or(branch);
+    let number = last.split(['-', '_']).next()?;
+    number.parse().ok().map(IssueKey::Number)
+}
+
+/// Where the issues of a kind of key are tracked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrackerSite {
+    Jira {
+        /// Like "https://example.atlassian.net", without the slash at the end.
+        api_url: String,
+        user: Option<String>,
+    },
+    /// The issues of the repository of a GitHub site.
+    GitHub(Site),
+    /// The issues of the repository of a Gitea site.
+    Gitea(Site),
+}
+
+impl TrackerSite {
+    /// The Jira set in `del-branches.jiraUrl`, if any.
+    pub fn find_jira(config: &Config) -> Option<Self> {
+        let api_url = config.get_string(JIRA_URL).ok()?;
+        Some(Self::Jira {
+            api_url: api_url.trim_end_matches('/').to_string(),
+            user: config.get_string(JIRA_USER).ok(),
+        })
+    }
+
+    /// The issues of the repository of the site, if its service has issues we read.
+    pub fn from_site(site: &Site) -> Option<Self> {
+        match site.kind {
+            HostingKind::GitHub => Some(Self::GitHub(site.clone())),
+            HostingKind::Gitea => Some(Self::Gitea(site.clone())),
+            HostingKind::Gerrit | HostingKind::Azure | HostingKind::GitLab => None,
+        }
+    }
+
+    /// What tells the tracker apart from the others, for caching.
+    pub fn get_key(&self) -> String {
+        match self {
+            Self::Jira { api_url, .. } => api_url.clone(),
+            Self::GitHub(site) | Self::Gitea(site) => site.get_key(),
+        }
+    }
+
+    /// Host which its token is stored under.
+    pub fn get_host(&self) -> String {
+        match self {
+            Self::Jira { api_url, .. } => {
+                parse_remote_url(api_url).map_or(api_url.clone(), |u| u.host)
+            }
+            Self::GitHub(site) | Self::Gitea(site) => site.host.clone(),
+        }
+    }
+
+    pub fn token_env(&self) -> &str {
+        match self {
+            Self::Jira { .. } => JIRA_TOKEN_ENV,
+            Self::GitHub(site) | Self::Gitea(site) => &site.token_env,
+        }
+    }
+}
+
+/// Connect to the tracker with the token.
+pub fn connect(site: &TrackerSite, token: &str) -> Box<dyn Tracker> {
+    match site {
+        TrackerSite::Jira { api_url, user } => Box::new(Jira::new(api_url, user.as_deref(), token)),
+        TrackerSite::GitHub(site) => Box::new(RepositoryIssues {
+            title: "GitHub",
+            repo_url: format!("{}/repos/{}", site.api_url, site.project),
+            token: token.to_string(),
+        }),
+        TrackerSite::Gitea(site) => Box::new(RepositoryIssues {
+            title: "Gitea",
+            repo_url: format!("{}/api/v1/repos/{}", site.api_url, site.project),
+            token: token.to_string(),
+        }),
+    }
+}
+
+pub trait Tracker {
+    fn title(&self) -> &str;
+
+    /// State of the issue, `None` if the tracker has no such issue, or does not track this
+    /// kind of key.
+    fn find_issue_state(&self, key: &IssueKey) -> Result<Option<IssueState>>;
+}
+
+#[derive(Debug, Deserialize)]
+struct JiraIssue {
+    fields: JiraFields,
+}
+
+#[derive(Debug, Deserialize)]
+struct JiraFields {
+    status: JiraStatus,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JiraStatus {
+    status_category: JiraStatusCategory,
+}
+
+#[derive(Debug, Deserialize)]
+struct JiraStatusCategory {
+    key: String,
+}
+
+impl JiraIssue {
+    fn get_state(&self) -> IssueState {
+        match self.fields.status.status_category.key.as_str() {
+            "done" => IssueState::Resolved,
+            _ => IssueState::Open,
+        }
+    }
+}
+
+/// Jira, on Atlassian Cloud with the email of the user and an API token, or on a server with
+/// a personal access token.
+struct Jira {
+    api_url: String,
+    authorization: String,
+}
+
+impl Jira {
+    fn new(api_url: &str, user: Option<&str>, token: &str) -> Self {
+        let authorization = match user {
+            Some(user) => basic_auth(user, token),
+            None => format!("Bearer {token}"),
+        };
+        Self {
+            api_url: api_url.to_string(),
+            authorization,
+        }
+    }
+}
+
+impl Tracker for Jira {
+    fn title(&self) -> &str {
+        "Jira"
+    }
+
+    fn find_issue_state(&self, key: &IssueKey) -> Result<Option<IssueState>> {
+        let IssueKey::Jira(key) = key else {
+            return Ok(None);
+        };
+        let url = format!("{}/rest/api/2/issue/{key}", self.api_url);
+        let request = ureq::get(&url)
+            .set("User-Agent", USER_AGENT)
+            .set("Authorization", &self.authorization)
+            .query("fields", "status");
+        match call(request) {
+            Ok(response) => Ok(Some(response.into_json::<JiraIssue>()?.get_state())),
+            Err(e) => match *e {
+                ureq::Error::Status(404, _) => Ok(None),
+                e => Err(describe_error(e, "Jira")),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RepositoryIssue {
+    state: String,
+}
+
+/// The issues of a repository on GitHub or Gitea, whose APIs tell them alike.
+struct RepositoryIssues {
+    title: &'static str,
+    /// Like "https://api.github.com/repos/owner/repo".
+    repo_url: String,
+    token: String,
+}
+
+impl Tracker for RepositoryIssues {
+    fn title(&self) -> &str {
+        self.title
+    }
+
+    fn find_issue_state(&self, key: &IssueKey) -> Result<Option<IssueState>> {
+        let IssueKey::Number(number) = key else {
+            return Ok(None);
+        };
+        let url = format!("{}/issues/{number}", self.repo_url);
+        let request = ureq::get(&url)
+            .set("User-Agent", USER_AGENT)
+            .set("Authorization", &format!("token {}", self.token));
+        match call(request) {
+            Ok(response) => {
+                let issue: RepositoryIssue = response.into_json()?;
+                Ok(Some(match issue.state.as_str() {
+                    "closed" => IssueState::Resolved,
+                    _ => IssueState::Open,
+                }))
+            }
+            Err(e) => match *e {
+                ureq::Error::Status(404 | 410, _) => Ok(None),
+                e => Err(describe_error(e, self.title)),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hosting::find_site;
+    use crate::testutil::init_repo;
+
+    #[test]
+    fn finds_issue_keys() {
+        let jira = |key: &str| Some(IssueKey::Jira(key.into()));
+        assert_eq!(find_issue_key("feature/PROJ-123-login"), jira("PROJ-123"));
+        assert_eq!(find_issue_key("AB2-7"), jira("AB2-7"));
+        assert_eq!(find_issue_key("fix/#567"), Some(IssueKey::Number(567)));
+        assert_eq!(
+            find_issue_key("fix/gh-42-crash"),
+            Some(IssueKey::Number(42))
+        );
+        assert_eq!(
+            find_issue_key("me/567-fix-login"),
+            Some(IssueKey::Number(567))
+        );
+        assert_eq!(find_issue_key("release-2"), None);
+        assert_eq!(find_issue_key("fix/login-v2"), None);
+        assert_eq!(find_issue_key("X-1"), None);
+    }
+
+    #[test]
+    fn finds_trackers() {
+        let (_dir, repo) = init_repo();
+        let config = repo.config().unwrap();
+        let find = |url| TrackerSite::from_site(&find_site(&config, url).unwrap().unwrap());
+        let site = find("git@github.com:me/app.git").unwrap();
+        assert_eq!(site.get_key(), "https://api.github.com/me/app");
+        assert_eq!(site.get_host(), "github.com");
+        assert_eq!(site.token_env(), "GITHUB_TOKEN");
+        assert_eq!(find("git@gitlab.com:me/app.git"), None);
+        let site = TrackerSite::Jira {
+            api_url: "https://corp.atlassian.net".into(),
+            user: None,
+        };
+        assert_eq!(site.get_host(), "corp.atlassian.net");
+    }
+
+    #[test]
+    fn reads_jira_issues() {
+        let body = r#"{"key": "PROJ-1", "fields": {"status": {"name": "Won't Do",
+            "statusCategory": {"id": 3, "key": "done"}}}}"#;
+        let issue: JiraIssue = serde_json::from_str(body).unwrap();
+        assert_eq!(issue.get_state(), IssueState::Resolved);
+        let body = r#"{"fields": {"status": {"statusCategory": {"key": "indeterminate"}}}}"#;
+        let issue: JiraIssue = serde_json::from_str(body).unwrap();
+        assert_eq!(issue.get_state(), IssueState::Open);
+    }
+}
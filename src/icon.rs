@@ -0,0 +1,97 @@
+//! Icons of the branch categories, by the prefixes of their names, so that they stand out in
+//! the picker and the reports.
+//!
+//! They are the values of `del-branches.icon`, one per category: the prefix, the glyph and
+//! maybe the color of the names, like "feature/ ✨" or "hotfix/ 🔥 red".
+
+use eyre::{eyre, Result};
+use git2::Config;
+
+use crate::config::ICON;
+
+/// Colors which the names can be shown in.
+pub const COLORS: &[&str] = &[
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Icon {
+    pub prefix: String,
+    pub glyph: String,
+    /// One of `COLORS`.
+    pub color: Option<String>,
+}
+
+impl Icon {
+    fn parse(value: &str) -> Result<Self> {
+        let invalid = || {
+            eyre!(
+                "Invalid {ICON} \"{value}\", expected a prefix, a glyph and maybe a color, like \
+                 \"feature/ ✨\" or \"hotfix/ 🔥 red\""
+            )
+        };
+        let mut words = value.split_whitespace();
+        let (Some(prefix), Some(glyph)) = (words.next(), words.next()) else {
+            return Err(invalid());
+        };
+        let color = words.next();
+        if words.next().is_some() {
+            return Err(invalid());
+        }
+        if let Some(color) = color.filter(|c| !COLORS.contains(c)) {
+            return Err(eyre!(
+                "Unknown color \"{color}\" in {ICON} \"{value}\", expected one of {}",
+                COLORS.join(", ")
+            ));
+        }
+        Ok(Self {
+            prefix: prefix.to_string(),
+            glyph: glyph.to_string(),
+            color: color.map(str::to_string),
+        })
+    }
+}
+
+/// Read the icons of the config, in its order.
+pub fn load_icons(config: &Config) -> Result<Vec<Icon>> {
+    let mut icons = Vec::new();
+    let mut entries = config.multivar(ICON, None)?;
+    while let Some(entry) = entries.next() {
+        if let Some(value) = entry?.value() {
+            icons.push(Icon::parse(value)?);
+        }
+    }
+    Ok(icons)
+}
+
+/// The icon of the branch: the one with the longest prefix of its name.
+pub fn find_icon<'i>(icons: &'i [Icon], name: &str) -> Option<&'i Icon> {
+    icons
+        .iter()
+        .filter(|i| name.starts_with(&i.prefix))
+        .max_by_key(|i| i.prefix.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::init_repo;
+
+    #[test]
+    fn finds_icons() {
+        let (_dir, repo) = init_repo();
+        let mut config = repo.config().unwrap();
+        for value in ["feature/ ✨", "hotfix/ 🔥 red", "feature/ui/ 🎨 magenta"] {
+            config.set_multivar(ICON, "^$", value).unwrap();
+        }
+        let icons = load_icons(&repo.config().unwrap()).unwrap();
+        assert_eq!(icons.len(), 3);
+        assert_eq!(icons[1].color.as_deref(), Some("red"));
+        assert_eq!(find_icon(&icons, "feature/login").unwrap().glyph, "✨");
+        assert_eq!(find_icon(&icons, "feature/ui/menu").unwrap().glyph, "🎨");
+        assert!(find_icon(&icons, "fix/typo").is_none());
+        assert!(Icon::parse("wip/").is_err());
+        let e = Icon::parse("wip/ 🚧 orange").unwrap_err();
+        assert!(e.to_string().starts_with("Unknown color \"orange\""));
+    }
+}
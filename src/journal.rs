@@ -0,0 +1,403 @@
+//! Record of deleted branches, so that they can be restored.
+//!
+//! The journal is a text file in ".git/del-branches/journal", one deletion per line,
+//! with tab-separated fields:
+//!
+//! ```text
+//! <time> <branch> <tip SHA> <remote> <remote branch> <upstream state>
+//! ```
+//!
+//! The remote fields are empty if the branch had no upstream.
+//!
+//! Entries older than the retention period of `del-branches.retention` are purged after each
+//! run, or by the `purge` command, so that the journal does not grow forever.
+
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use eyre::{eyre, Result};
+use git2::{ConfigLevel, Oid, Repository};
+
+use crate::age::{is_older_than, HumanDuration};
+use crate::branch::{BranchInfo, Upstream};
+use crate::gitenv::get_data_dir;
+
+const FILE_NAME: &str = "journal";
+
+/// What happened to the upstream of a deleted branch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpstreamState {
+    /// User chose not to delete it, or there was no upstream.
+    Kept,
+    Deleted,
+    /// We tried to delete it but failed.
+    Failed,
+}
+
+impl fmt::Display for UpstreamState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Self::Kept => "kept",
+            Self::Deleted => "deleted",
+            Self::Failed => "failed",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for UpstreamState {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "kept" => Ok(Self::Kept),
+            "deleted" => Ok(Self::Deleted),
+            "failed" => Ok(Self::Failed),
+            _ => Err(eyre!("Unknown upstream state \"{s}\"")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// Time of deletion, in seconds since Unix epoch.
+    pub time: i64,
+    pub name: String,
+    pub tip: Oid,
+    pub upstream: Option<Upstream>,
+    pub upstream_state: UpstreamState,
+}
+
+impl Entry {
+    pub fn new(branch: &BranchInfo, time: i64, upstream_state: UpstreamState) -> Self {
+        Self {
+            time,
+            name: branch.name.clone(),
+            tip: branch.tip,
+            upstream: branch.upstream.clone(),
+            upstream_state,
+        }
+    }
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (remote, remote_branch) = match &self.upstream {
+            Some(u) => (u.remote.as_str(), u.name.as_str()),
+            None => ("", ""),
+        };
+        write!(
+            f,
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            self.time, self.name, self.tip, remote, remote_branch, self.upstream_state
+        )
+    }
+}
+
+impl FromStr for Entry {
+    type Err = eyre::Report;
+
+    fn from_str(line: &str) -> Result<Self> {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let [time, name, tip, remote, remote_branch, state] = fields[..] else {
+            return Err(eyre!("Malformed journal line: {line}"));
+        };
+        let upstream = (!remote.is_empty()).then(|| Upstream {
+            remote: remote.to_string(),
+            name: remote_branch.to_string(),
+        });
+        Ok(Self {
+            time: time.parse()?,
+            name: name.to_string(),
+            tip: Oid::from_str(tip)?,
+            upstream,
+            upstream_state: state.parse()?,
+        })
+    }
+}
+
+pub fn get_journal_path(repo: &Repository) -> PathBuf {
+    get_data_dir(repo).join(FILE_NAME)
+}
+
+pub fn append(repo: &Repository, entry: &Entry) -> Result<()> {
+    let path = get_journal_path(repo);
+    if let Some(folder) = path.parent() {
+        fs::create_dir_all(folder)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{entry}")?;
+    Ok(())
+}
+
+/// Read all entries, oldest first. Malformed lines are skipped.
+pub fn read(repo: &Repository) -> Result<Vec<Entry>> {
+    let content = match fs::read_to_string(get_journal_path(repo)) {
+        Ok(c) => c,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(content.lines().filter_map(|l| l.parse().ok()).collect())
+}
+
+/// Remove the entries of the deletions made before `older_than`, returning how many were
+/// removed. Lines which are not entries are kept as they are.
+pub fn purge(repo: &Repository, older_than: HumanDuration, now: i64) -> Result<usize> {
+    let path = get_journal_path(repo);
+    let content = match fs::read_to_string(&path) {
+        Ok(c) => c,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e.into()),
+    };
+    let mut kept = String::new();
+    let mut removed = 0;
+    for line in content.lines() {
+        match line.parse::<Entry>() {
+            Ok(entry) if is_older_than(entry.time, older_than, now) => removed += 1,
+            _ => {
+                kept.push_str(line);
+                kept.push('\n');
+            }
+        }
+    }
+    if removed > 0 {
+        // Replaced at once, so that no entry is lost if writing fails
+        let temp = path.with_extension("tmp");
+        fs::write(&temp, kept)?;
+        fs::rename(&temp, &path)?;
+    }
+    Ok(removed)
+}
+
+/// Find entries of branches which can be restored: they don't exist now and their commits
+/// are still in the repository. Only the latest deletion of each name is returned, latest first.
+pub fn list_restorable(repo: &Repository) -> Result<Vec<Entry>> {
+    let mut entries = read(repo)?;
+    entries.reverse();
+    let mut seen = Vec::new();
+    entries.retain(|e| {
+        if seen.contains(&e.name) {
+            return false;
+        }
+        seen.push(e.name.clone());
+        repo.find_branch(&e.name, git2::BranchType::Local).is_err()
+            && repo.find_commit(e.tip).is_ok()
+    });
+    Ok(entries)
+}
+
+/// Find entries of deletions whose upstream branch failed to be deleted, and was not retried
+/// with success since, oldest first. Those whose branch was restored are left out.
+pub fn list_failed_upstreams(repo: &Repository) -> Result<Vec<Entry>> {
+    let mut failed: Vec<Entry> = Vec::new();
+    for entry in read(repo)? {
+        // A later entry of the same deletion tells the result of the retry
+        failed.retain(|e| (&e.name, e.tip) != (&entry.name, entry.tip));
+        if entry.upstream.is_some() && entry.upstream_state == UpstreamState::Failed {
+            failed.push(entry);
+        }
+    }
+    failed.retain(|e| repo.find_branch(&e.name, git2::BranchType::Local).is_err());
+    Ok(failed)
+}
+
+/// Re-create the local branch, with its upstream config if the upstream was kept.
+pub fn restore(repo: &Repository, entry: &Entry) -> Result<(), git2::Error> {
+    let commit = repo.find_commit(entry.tip)?;
+    repo.branch(&entry.name, &commit, false)?;
+    if let (Some(upstream), UpstreamState::Kept) = (&entry.upstream, entry.upstream_state) {
+        // Not the top level, which may be the file of `git -c` overrides
+        let mut config = repo.config()?.open_level(ConfigLevel::Local)?;
+        config.set_str(&format!("branch.{}.remote", entry.name), &upstream.remote)?;
+        config.set_str(
+            &format!("branch.{}.merge", entry.name),
+            &format!("refs/heads/{}", upstream.name),
+        )?;
+    }
+    Ok(())
+}
+
+/// Quote for the shell, like 'it'\''s'.
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// Shell script re-creating the deleted branches with plain Git, and pushing back the
+/// upstream branches which were deleted, for as long as `git gc` keeps their commits.
+pub fn build_undo_script(entries: &[Entry]) -> String {
+    let mut script = String::from(
+        "#!/bin/sh\n# Restore the branches deleted by git-del-branches, from the top of the repository\nset -e\n",
+    );
+    for entry in entries {
+        let name = quote(&entry.name);
+        script.push_str(&format!("git branch {name} {}\n", entry.tip));
+        let Some(upstream) = &entry.upstream else {
+            continue;
+        };
+        match entry.upstream_state {
+            UpstreamState::Kept => {
+                let key = |field| quote(&format!("branch.{}.{field}", entry.name));
+                script.push_str(&format!(
+                    "git config {} {}\n",
+                    key("remote"),
+                    quote(&upstream.remote)
+                ));
+                script.push_str(&format!(
+                    "git config {} {}\n",
+                    key("merge"),
+                    quote(&format!("refs/heads/{}", upstream.name))
+                ));
+            }
+            UpstreamState::Deleted => script.push_str(&format!(
+                "git push {} {}\n",
+                quote(&upstream.remote),
+                quote(&format!("{}:refs/heads/{}", entry.tip, upstream.name))
+            )),
+            UpstreamState::Failed => {}
+        }
+    }
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::branch::list_local_branches;
+    use crate::delete::delete_local_branch;
+    use crate::testutil::{commit, init_repo};
+
+    #[test]
+    fn formats_and_parses() {
+        let entry = Entry {
+            time: 1_700_000_000,
+            name: "feature/x".into(),
+            tip: Oid::from_str("a1b2c3d4e5f60718293a4b5c6d7e8f9012345678").unwrap(),
+            upstream: Some(Upstream {
+                remote: "origin".into(),
+                name: "feature/x".into(),
+            }),
+            upstream_state: UpstreamState::Deleted,
+        };
+        let line = entry.to_string();
+        assert_eq!(
+            line,
+            "1700000000\tfeature/x\ta1b2c3d4e5f60718293a4b5c6d7e8f9012345678\torigin\tfeature/x\tdeleted"
+        );
+        assert_eq!(line.parse::<Entry>().unwrap(), entry);
+        assert!("1700000000\tfeature".parse::<Entry>().is_err());
+    }
+
+    #[test]
+    fn builds_undo_script() {
+        let tip = Oid::from_str("a1b2c3d4e5f60718293a4b5c6d7e8f9012345678").unwrap();
+        let upstream = Upstream {
+            remote: "origin".into(),
+            name: "it's".into(),
+        };
+        let entries = [
+            Entry {
+                time: 0,
+                name: "it's".into(),
+                tip,
+                upstream: Some(upstream.clone()),
+                upstream_state: UpstreamState::Deleted,
+            },
+            Entry {
+                time: 0,
+                name: "kept".into(),
+                tip,
+                upstream: Some(upstream),
+                upstream_state: UpstreamState::Kept,
+            },
+        ];
+        let script = build_undo_script(&entries);
+        let lines: Vec<&str> = script.lines().skip(3).collect();
+        assert_eq!(
+            lines,
+            [
+                "git branch 'it'\\''s' a1b2c3d4e5f60718293a4b5c6d7e8f9012345678",
+                "git push 'origin' 'a1b2c3d4e5f60718293a4b5c6d7e8f9012345678:refs/heads/it'\\''s'",
+                "git branch 'kept' a1b2c3d4e5f60718293a4b5c6d7e8f9012345678",
+                "git config 'branch.kept.remote' 'origin'",
+                "git config 'branch.kept.merge' 'refs/heads/it'\\''s'",
+            ]
+        );
+    }
+
+    #[test]
+    fn lists_failed_upstreams() {
+        let (_dir, repo) = init_repo();
+        commit(&repo, "refs/heads/feature", None);
+        commit(&repo, "refs/heads/fix", None);
+        let mut branches = list_local_branches(&repo).unwrap();
+        for branch in &mut branches {
+            branch.upstream = Some(Upstream {
+                remote: "origin".into(),
+                name: branch.name.clone(),
+            });
+            append(&repo, &Entry::new(branch, 1, UpstreamState::Failed)).unwrap();
+        }
+        // Still there, as if restored
+        assert!(list_failed_upstreams(&repo).unwrap().is_empty());
+        repo.find_reference("refs/heads/feature")
+            .unwrap()
+            .delete()
+            .unwrap();
+        repo.find_reference("refs/heads/fix")
+            .unwrap()
+            .delete()
+            .unwrap();
+        append(&repo, &Entry::new(&branches[0], 2, UpstreamState::Deleted)).unwrap();
+        let failed = list_failed_upstreams(&repo).unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].name, branches[1].name);
+    }
+
+    #[test]
+    fn purges_old_entries() {
+        let (_dir, repo) = init_repo();
+        commit(&repo, "refs/heads/feature", None);
+        let branch = list_local_branches(&repo).unwrap().remove(0);
+        let now = 1_700_000_000;
+        append(
+            &repo,
+            &Entry::new(&branch, now - 100 * 86400, UpstreamState::Kept),
+        )
+        .unwrap();
+        append(
+            &repo,
+            &Entry::new(&branch, now - 86400, UpstreamState::Kept),
+        )
+        .unwrap();
+        let path = get_journal_path(&repo);
+        let mut content = fs::read_to_string(&path).unwrap();
+        content.push_str("not an entry\n");
+        fs::write(&path, content).unwrap();
+        let older_than = "3m".parse().unwrap();
+        assert_eq!(purge(&repo, older_than, now).unwrap(), 1);
+        assert_eq!(purge(&repo, older_than, now).unwrap(), 0);
+        let entries = read(&repo).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].time, now - 86400);
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .ends_with("not an entry\n"));
+    }
+
+    #[test]
+    fn restores_deleted_branch() {
+        let (_dir, repo) = init_repo();
+        let tip = commit(&repo, "refs/heads/feature", None);
+        let branch = list_local_branches(&repo).unwrap().remove(0);
+        delete_local_branch(&repo, "feature").unwrap();
+        append(&repo, &Entry::new(&branch, 1, UpstreamState::Kept)).unwrap();
+        let entries = list_restorable(&repo).unwrap();
+        assert_eq!(entries.len(), 1);
+        restore(&repo, &entries[0]).unwrap();
+        assert_eq!(repo.refname_to_id("refs/heads/feature").unwrap(), tip);
+        assert!(list_restorable(&repo).unwrap().is_empty());
+    }
+}
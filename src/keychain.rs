@@ -0,0 +1,200 @@
+//! Secrets, like the tokens of hosting APIs, kept in the keychain of the OS: the macOS
+//! Keychain, the Secret Service of Linux desktops (GNOME Keyring, KWallet), or the Windows
+//! Credential Locker. They are reached with the tools which come with the OS: `security`,
+//! `secret-tool` and PowerShell.
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+use eyre::{eyre, Result};
+
+/// Service which our secrets are filed under, with the account telling them apart, like the
+/// host of an API.
+pub const SERVICE: &str = "git-del-branches";
+
+/// Run the command, writing `input` to its stdin.
+fn run(cmd: &mut Command, input: &str) -> Result<Output> {
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| eyre!("Cannot reach the keychain with {program}: {e}"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input.as_bytes())?;
+    }
+    Ok(child.wait_with_output()?)
+}
+
+/// Fail with the error output of the tool, if it failed.
+fn check(output: Output, action: &str) -> Result<Output> {
+    if output.status.success() {
+        return Ok(output);
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Err(eyre!("Cannot {action} the keychain: {}", stderr.trim()))
+}
+
+/// Quote a word for the command line of `security -i`.
+#[cfg(any(target_os = "macos", test))]
+fn quote(word: &str) -> String {
+    format!("\"{}\"", word.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(target_os = "macos")]
+fn store(account: &str, secret: &str) -> Result<Output> {
+    // Given on stdin, to not show in the process list
+    let line = format!(
+        "add-generic-password -U -s {} -a {} -w {}\n",
+        quote(SERVICE),
+        quote(account),
+        quote(secret)
+    );
+    run(Command::new("security").arg("-i"), &line)
+}
+
+#[cfg(target_os = "macos")]
+fn load(account: &str) -> Result<Output> {
+    run(
+        Command::new("security").args([
+            "find-generic-password",
+            "-s",
+            SERVICE,
+            "-a",
+            account,
+            "-w",
+        ]),
+        "",
+    )
+}
+
+#[cfg(target_os = "macos")]
+fn delete(account: &str) -> Result<Output> {
+    run(
+        Command::new("security").args(["delete-generic-password", "-s", SERVICE, "-a", account]),
+        "",
+    )
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn store(account: &str, secret: &str) -> Result<Output> {
+    let label = format!("{SERVICE} token for {account}");
+    let mut cmd = Command::new("secret-tool");
+    cmd.args([
+        "store", "--label", &label, "service", SERVICE, "account", account,
+    ]);
+    run(&mut cmd, secret)
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn load(account: &str) -> Result<Output> {
+    run(
+        Command::new("secret-tool").args(["lookup", "service", SERVICE, "account", account]),
+        "",
+    )
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn delete(account: &str) -> Result<Output> {
+    run(
+        Command::new("secret-tool").args(["clear", "service", SERVICE, "account", account]),
+        "",
+    )
+}
+
+/// Run a PowerShell script on the Credential Locker (`PasswordVault`), which reads the
+/// secret from stdin.
+#[cfg(windows)]
+fn run_vault_script(script: &str, account: &str, secret: &str) -> Result<Output> {
+    let script = format!(
+        "$ErrorActionPreference = 'Stop'; \
+         [void][Windows.Security.Credentials.PasswordVault, Windows.Security.Credentials, ContentType = WindowsRuntime]; \
+         $vault = New-Object Windows.Security.Credentials.PasswordVault; {script}"
+    );
+    let mut cmd = Command::new("powershell");
+    cmd.args(["-NoProfile", "-NonInteractive", "-Command", &script])
+        .env("DEL_BRANCHES_SERVICE", SERVICE)
+        .env("DEL_BRANCHES_ACCOUNT", account);
+    run(&mut cmd, secret)
+}
+
+#[cfg(windows)]
+fn store(account: &str, secret: &str) -> Result<Output> {
+    run_vault_script(
+        "$secret = [Console]::In.ReadToEnd(); \
+         $vault.Add((New-Object Windows.Security.Credentials.PasswordCredential($env:DEL_BRANCHES_SERVICE, $env:DEL_BRANCHES_ACCOUNT, $secret)))",
+        account,
+        secret,
+    )
+}
+
+#[cfg(windows)]
+fn load(account: &str) -> Result<Output> {
+    run_vault_script(
+        "$cred = $vault.Retrieve($env:DEL_BRANCHES_SERVICE, $env:DEL_BRANCHES_ACCOUNT); \
+         $cred.RetrievePassword(); [Console]::Out.Write($cred.Password)",
+        account,
+        "",
+    )
+}
+
+#[cfg(windows)]
+fn delete(account: &str) -> Result<Output> {
+    run_vault_script(
+        "$vault.Remove($vault.Retrieve($env:DEL_BRANCHES_SERVICE, $env:DEL_BRANCHES_ACCOUNT))",
+        account,
+        "",
+    )
+}
+
+/// Store the secret of the account, replacing the one it had.
+pub fn store_secret(account: &str, secret: &str) -> Result<()> {
+    check(store(account, secret)?, "store the secret in")?;
+    Ok(())
+}
+
+/// Get the secret of the account. Returns `None` if it has none, or the keychain cannot be
+/// reached.
+pub fn load_secret(account: &str) -> Option<String> {
+    let output = load(account).ok().filter(|o| o.status.success())?;
+    let secret = String::from_utf8(output.stdout).ok()?;
+    let secret = secret.trim_end_matches(['\r', '\n']);
+    (!secret.is_empty()).then(|| secret.to_string())
+}
+
+/// Remove the secret of the account.
+pub fn delete_secret(account: &str) -> Result<()> {
+    check(delete(account)?, "remove the secret from")?;
+    Ok(())
+}
+
+/// Get the token for the API of the host: from the environment variable if it is set, as
+/// on CI, else from the keychain.
+pub fn find_token(host: &str, env_var: &str) -> Option<String> {
+    std::env::var(env_var)
+        .ok()
+        .filter(|t| !t.trim().is_empty())
+        .or_else(|| load_secret(host))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_token_in_env() {
+        std::env::set_var("DEL_BRANCHES_TEST_TOKEN", "ghp_env");
+        assert_eq!(
+            find_token("example.invalid", "DEL_BRANCHES_TEST_TOKEN").as_deref(),
+            Some("ghp_env")
+        );
+        std::env::remove_var("DEL_BRANCHES_TEST_TOKEN");
+    }
+
+    #[test]
+    fn quotes_words() {
+        assert_eq!(quote("ghp_abc"), "\"ghp_abc\"");
+        assert_eq!(quote(r#"a"b\c"#), r#""a\"b\\c""#);
+    }
+}
@@ -0,0 +1,43 @@
+//! Core logic of git-del-branches: finding local branches which can be deleted,
+//! gathering their metadata and deleting them, locally and on the remote.
+//!
+//! The `git-del-branches` binary is a thin interactive layer on top of this crate.
+
+pub mod age;
+pub mod backend;
+pub mod branch;
+pub mod checkout;
+pub mod config;
+pub mod credential;
+pub mod delete;
+pub mod depend;
+pub mod diffstat;
+pub mod gitenv;
+pub mod hooks;
+pub mod hosting;
+pub mod icon;
+pub mod journal;
+pub mod keychain;
+pub mod lock;
+pub mod merged;
+pub mod notify;
+pub mod partial;
+pub mod pattern;
+pub mod policy;
+pub mod profile;
+pub mod report;
+pub mod selection;
+pub mod session;
+pub mod shallow;
+pub mod sharedlog;
+pub mod space;
+pub mod spec;
+pub mod ssh;
+pub mod stash;
+pub mod stats;
+pub mod tag;
+pub mod tls;
+pub mod urlremote;
+
+#[cfg(test)]
+mod testutil;
@@ -0,0 +1,228 @@
+//! Lock against concurrent cleanups of a repository, like by someone and a cron job at the same
+//! time, which would race on the same branches.
+//!
+//! The lock is a file in ".git/del-branches/lock", made only if there is none, telling the
+//! process which holds it, on which host, and since when. It is removed when the cleanup is
+//! done. Where the running processes can be told, on Linux, the lock of one which is no longer
+//! running, like after the terminal was closed, is taken over, but only if it ran on this host:
+//! a repository on a shared folder may be locked by a process of another machine.
+
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use eyre::Result;
+use git2::Repository;
+
+use crate::age::now;
+use crate::gitenv::get_data_dir;
+
+const FILE_NAME: &str = "lock";
+
+/// Name of the machine we run on, if it can be told.
+fn get_host_name() -> Option<String> {
+    let name = fs::read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .or_else(|| env::var("HOSTNAME").ok())
+        .or_else(|| env::var("COMPUTERNAME").ok())?;
+    let name = name.trim();
+    (!name.is_empty() && !name.contains(char::is_whitespace)).then(|| name.to_string())
+}
+
+/// The process which holds the lock.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Holder {
+    pub pid: u32,
+    /// When it took the lock, in seconds since the Unix epoch.
+    pub time: i64,
+    /// Machine which the process runs on, `None` if it could not be told.
+    pub host: Option<String>,
+}
+
+impl Holder {
+    fn current() -> Self {
+        Self {
+            pid: process::id(),
+            time: now(),
+            host: get_host_name(),
+        }
+    }
+
+    /// Whether the process is still running, if that can be told: only on this host.
+    fn is_running(&self) -> Option<bool> {
+        let proc = Path::new("/proc");
+        let here = self.host.is_some() && self.host == get_host_name();
+        match here && proc.join("self").exists() {
+            true => Some(proc.join(self.pid.to_string()).exists()),
+            false => None,
+        }
+    }
+
+    fn parse(content: &str) -> Option<Self> {
+        let mut fields = content.split_whitespace();
+        let pid = fields.next()?.parse().ok()?;
+        let time = fields.next()?.parse().ok()?;
+        // The nanoseconds, then the host, which older locks do not have
+        let host = fields.nth(1).map(String::from);
+        Some(Self { pid, time, host })
+    }
+
+    /// The content of the lock file. The nanoseconds tell apart two locks taken by the same
+    /// process in the same second.
+    fn to_content(&self) -> String {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.subsec_nanos());
+        let host = self
+            .host
+            .as_deref()
+            .map(|h| format!(" {h}"))
+            .unwrap_or_default();
+        format!("{} {} {nanos}{host}\n", self.pid, self.time)
+    }
+}
+
+/// The lock, held until dropped.
+#[derive(Debug)]
+pub struct Lock {
+    path: PathBuf,
+    content: String,
+}
+
+fn get_lock_path(repo: &Repository) -> PathBuf {
+    get_data_dir(repo).join(FILE_NAME)
+}
+
+impl Lock {
+    /// Make the lock file, if there is none. `None` if another process made it first.
+    fn create(path: &Path) -> Result<Option<Self>> {
+        if let Some(folder) = path.parent() {
+            fs::create_dir_all(folder)?;
+        }
+        let mut file = match OpenOptions::new().write(true).create_new(true).open(path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let content = Holder::current().to_content();
+        file.write_all(content.as_bytes())?;
+        Ok(Some(Self {
+            path: path.to_path_buf(),
+            content,
+        }))
+    }
+
+    /// Remove the lock file, if it still has the content, then make ours. `None` if another
+    /// process took the lock in between, like one taking it over too.
+    fn take_over(path: &Path, content: &str) -> Result<Option<Self>> {
+        if fs::read_to_string(path).is_ok_and(|c| c != content) {
+            return Ok(None);
+        }
+        match fs::remove_file(path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Self::create(path),
+        }
+    }
+
+    /// Take the lock. `None` if another process holds it.
+    pub fn acquire(repo: &Repository) -> Result<Option<Self>> {
+        let path = get_lock_path(repo);
+        if let Some(lock) = Self::create(&path)? {
+            return Ok(Some(lock));
+        }
+        let Ok(content) = fs::read_to_string(&path) else {
+            return Ok(None);
+        };
+        let stale = Holder::parse(&content).and_then(|h| h.is_running()) == Some(false);
+        match stale {
+            true => Self::take_over(&path, &content),
+            false => Ok(None),
+        }
+    }
+
+    /// Take the lock even if it is held, like by a process which crashed. `None` if another
+    /// process took it at the same time.
+    pub fn force(repo: &Repository) -> Result<Option<Self>> {
+        let path = get_lock_path(repo);
+        let content = fs::read_to_string(&path).unwrap_or_default();
+        Self::take_over(&path, &content)
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        // Unless another process forced it since, in which case it is theirs
+        if fs::read_to_string(&self.path).is_ok_and(|c| c == self.content) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// The process which holds the lock, if any. `None` too if the lock file cannot be read.
+pub fn get_holder(repo: &Repository) -> Option<Holder> {
+    let content = fs::read_to_string(get_lock_path(repo)).ok()?;
+    Holder::parse(&content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::init_repo;
+
+    #[test]
+    fn locks_once() {
+        let (_dir, repo) = init_repo();
+        let lock = Lock::acquire(&repo).unwrap().unwrap();
+        assert!(Lock::acquire(&repo).unwrap().is_none());
+        assert_eq!(get_holder(&repo).unwrap().pid, process::id());
+        drop(lock);
+        assert!(get_holder(&repo).is_none());
+
+        let stale = Lock::acquire(&repo).unwrap().unwrap();
+        let forced = Lock::force(&repo).unwrap().unwrap();
+        // The lock is left to whom forced it
+        drop(stale);
+        assert!(get_holder(&repo).is_some());
+        drop(forced);
+        assert!(Lock::acquire(&repo).unwrap().is_some());
+    }
+
+    #[test]
+    fn takes_over_stale_locks() {
+        let (_dir, repo) = init_repo();
+        let path = get_lock_path(&repo);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        // Not one of another host, even if no process here has its ID
+        fs::write(&path, format!("{} 1700000000 0 elsewhere\n", u32::MAX)).unwrap();
+        assert!(Lock::acquire(&repo).unwrap().is_none());
+        fs::write(&path, format!("{} 1700000000 0\n", u32::MAX)).unwrap();
+        assert!(Lock::acquire(&repo).unwrap().is_none());
+        // No process has the largest ID
+        let host = get_host_name().unwrap_or_default();
+        fs::write(&path, format!("{} 1700000000 0 {host}\n", u32::MAX)).unwrap();
+        // Only where the running processes and the host can be told
+        let lock = Lock::acquire(&repo).unwrap();
+        let here = Path::new("/proc/self").exists() && !host.is_empty();
+        assert_eq!(lock.is_some(), here);
+        if here {
+            assert_eq!(get_holder(&repo).unwrap().host, Some(host));
+        }
+    }
+
+    #[test]
+    fn loses_races_to_take_over() {
+        let (_dir, repo) = init_repo();
+        let path = get_lock_path(&repo);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "1 1700000000 0 elsewhere\n").unwrap();
+        // Another process took it over since we read it
+        fs::write(&path, "2 1700000001 0 elsewhere\n").unwrap();
+        assert!(Lock::take_over(&path, "1 1700000000 0 elsewhere\n")
+            .unwrap()
+            .is_none());
+        assert_eq!(get_holder(&repo).unwrap().pid, 2);
+    }
+}
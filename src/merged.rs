@@ -0,0 +1,376 @@
+//! Finding out whether a branch has been merged into a base branch.
+
+use std::collections::HashSet;
+
+use git2::{Commit, ErrorCode, Oid, Repository, Sort};
+
+/// Check if the commit `tip` is reachable from `base`, i.e. `git merge-base --is-ancestor`.
+pub fn is_contained_in(repo: &Repository, tip: Oid, base: Oid) -> Result<bool, git2::Error> {
+    Ok(tip == base || repo.graph_descendant_of(base, tip)?)
+}
+
+/// Like `is_contained_in`, but `None` when the clone is shallow and `tip` is not found in
+/// the part of the history of `base` which we have: it may be merged below the cut.
+pub fn check_contained_in(
+    repo: &Repository,
+    tip: Oid,
+    base: Oid,
+) -> Result<Option<bool>, git2::Error> {
+    if is_contained_in(repo, tip, base)? {
+        return Ok(Some(true));
+    }
+    Ok((!repo.is_shallow()).then_some(false))
+}
+
+/// Resolve a revision, like "main" or "origin/develop", to a commit.
+pub fn resolve_commit(repo: &Repository, rev: &str) -> Result<Oid, git2::Error> {
+    Ok(repo.revparse_single(rev)?.peel_to_commit()?.id())
+}
+
+/// Count the commits of `tip` which are not on `base`, like `git rev-list --count base..tip`:
+/// 0 for a branch with nothing of its own.
+pub fn count_unique_commits(repo: &Repository, tip: Oid, base: Oid) -> Result<usize, git2::Error> {
+    let mut walk = repo.revwalk()?;
+    walk.push(tip)?;
+    walk.hide(base)?;
+    walk.try_fold(0, |count, id| id.map(|_| count + 1))
+}
+
+/// Count the commits of a local branch which are not on its remote-tracking branch, the
+/// "ahead" of `git status`. `None` if the branch has no upstream which we have fetched.
+pub fn count_unpushed_commits(
+    repo: &Repository,
+    name: &str,
+    tip: Oid,
+) -> Result<Option<usize>, git2::Error> {
+    Ok(count_ahead_behind(repo, name, tip)?.map(|(ahead, _behind)| ahead))
+}
+
+/// Whether deleting a local branch may lose work: it is not merged into the base, it has
+/// commits which were not pushed to its upstream, or its pull request is still open.
+pub fn is_risky(
+    repo: &Repository,
+    name: &str,
+    tip: Oid,
+    unmerged: bool,
+    open_review: bool,
+) -> bool {
+    unmerged
+        || open_review
+        || count_unpushed_commits(repo, name, tip)
+            .ok()
+            .flatten()
+            .is_some_and(|n| n > 0)
+}
+
+/// Count the commits of a local branch which are not on its remote-tracking branch, and
+/// those of the latter which are not on the branch, like "ahead 2, behind 1" in `git status`.
+/// `None` if the branch has no upstream which we have fetched.
+pub fn count_ahead_behind(
+    repo: &Repository,
+    name: &str,
+    tip: Oid,
+) -> Result<Option<(usize, usize)>, git2::Error> {
+    let Ok(tracking) = repo.branch_upstream_name(&format!("refs/heads/{name}")) else {
+        return Ok(None);
+    };
+    let Some(tracking) = tracking.as_str() else {
+        return Ok(None);
+    };
+    let upstream = match repo.refname_to_id(tracking) {
+        Ok(id) => id,
+        Err(e) if e.code() == ErrorCode::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    Ok(Some(repo.graph_ahead_behind(tip, upstream)?))
+}
+
+/// Patch ID of the changes which the commit makes to its parent, like `git patch-id`.
+/// Merge commits have none.
+fn get_commit_patch_id(repo: &Repository, commit: &Commit) -> Result<Option<Oid>, git2::Error> {
+    if commit.parent_count() > 1 {
+        return Ok(None);
+    }
+    let parent_tree = match commit.parent_count() {
+        0 => None,
+        _ => Some(commit.parent(0)?.tree()?),
+    };
+    let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?;
+    Ok(Some(diff.patchid(None)?))
+}
+
+/// How the changes of a branch reached the base, through other commits than its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeKind {
+    /// All the changes are in a single commit.
+    Squashed,
+    /// Each commit was copied, as by `git rebase`.
+    Rebased,
+}
+
+/// Finds branches which were squash or rebase merged into the base, by comparing patch IDs
+/// as `git cherry` does.
+pub struct PatchIndex<'r> {
+    repo: &'r Repository,
+    base: Oid,
+    /// Patch IDs of the base commits, collected from the tip down to the oldest merge base
+    /// seen so far.
+    patch_ids: HashSet<Oid>,
+    /// Trees of these base commits.
+    trees: HashSet<Oid>,
+    /// Base commits whose patch IDs are collected.
+    scanned: HashSet<Oid>,
+}
+
+impl<'r> PatchIndex<'r> {
+    pub fn new(repo: &'r Repository, base: Oid) -> Self {
+        Self {
+            repo,
+            base,
+            patch_ids: HashSet::new(),
+            trees: HashSet::new(),
+            scanned: HashSet::new(),
+        }
+    }
+
+    /// Collect the patch IDs of the base commits which are not in `since`.
+    fn scan_base_since(&mut self, since: Oid) -> Result<(), git2::Error> {
+        let mut walk = self.repo.revwalk()?;
+        walk.set_sorting(Sort::TOPOLOGICAL)?;
+        walk.push(self.base)?;
+        walk.hide(since)?;
+        for id in walk {
+            let id = id?;
+            if !self.scanned.insert(id) {
+                continue;
+            }
+            let commit = self.repo.find_commit(id)?;
+            self.trees.insert(commit.tree_id());
+            if let Some(patch_id) = get_commit_patch_id(self.repo, &commit)? {
+                self.patch_ids.insert(patch_id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Find where the branch forked from the base, or `None` if it is contained in the base
+    /// or unrelated to it.
+    fn get_fork_point(&self, tip: Oid) -> Result<Option<Oid>, git2::Error> {
+        match self.repo.merge_base(self.base, tip) {
+            Ok(id) if id == tip => Ok(None),
+            Ok(id) => Ok(Some(id)),
+            Err(e) if e.code() == ErrorCode::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Check if all the changes of the branch, as a single patch, are in one base commit.
+    /// A branch contained in the base is not counted.
+    pub fn is_squash_merged(&mut self, tip: Oid) -> Result<bool, git2::Error> {
+        let Some(fork_point) = self.get_fork_point(tip)? else {
+            return Ok(false);
+        };
+        let old_tree = self.repo.find_commit(fork_point)?.tree()?;
+        let new_tree = self.repo.find_commit(tip)?.tree()?;
+        let diff = self
+            .repo
+            .diff_tree_to_tree(Some(&old_tree), Some(&new_tree), None)?;
+        if diff.deltas().len() == 0 {
+            return Ok(false);
+        }
+        let patch_id = diff.patchid(None)?;
+        self.scan_base_since(fork_point)?;
+        Ok(self.patch_ids.contains(&patch_id))
+    }
+
+    /// Check if every commit of the branch has a copy in the base, or the base has had the tree
+    /// of the branch tip. A branch contained in the base is not counted.
+    pub fn is_rebase_merged(&mut self, tip: Oid) -> Result<bool, git2::Error> {
+        let Some(fork_point) = self.get_fork_point(tip)? else {
+            return Ok(false);
+        };
+        self.scan_base_since(fork_point)?;
+        if self.trees.contains(&self.repo.find_commit(tip)?.tree_id()) {
+            return Ok(true);
+        }
+        let mut walk = self.repo.revwalk()?;
+        walk.push(tip)?;
+        walk.hide(fork_point)?;
+        let mut found = false;
+        for id in walk {
+            let commit = self.repo.find_commit(id?)?;
+            // Merge commits are skipped, like `git cherry` does
+            let Some(patch_id) = get_commit_patch_id(self.repo, &commit)? else {
+                continue;
+            };
+            if !self.patch_ids.contains(&patch_id) {
+                return Ok(false);
+            }
+            found = true;
+        }
+        Ok(found)
+    }
+
+    /// Tell if the branch was squash or rebase merged into the base.
+    pub fn find_merge_kind(&mut self, tip: Oid) -> Result<Option<MergeKind>, git2::Error> {
+        if self.is_squash_merged(tip)? {
+            return Ok(Some(MergeKind::Squashed));
+        }
+        if self.is_rebase_merged(tip)? {
+            return Ok(Some(MergeKind::Rebased));
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{commit, commit_files, init_repo};
+
+    #[test]
+    fn counts_unique_commits() {
+        let (_dir, repo) = init_repo();
+        let base = commit(&repo, "refs/heads/main", None);
+        let first = commit(&repo, "refs/heads/feat", Some(base));
+        let second = commit(&repo, "refs/heads/feat", Some(first));
+        let main = commit(&repo, "refs/heads/main", Some(base));
+        assert_eq!(count_unique_commits(&repo, second, main).unwrap(), 2);
+        assert_eq!(count_unique_commits(&repo, base, main).unwrap(), 0);
+    }
+
+    #[test]
+    fn checks_containment() {
+        let (_dir, repo) = init_repo();
+        let base = commit(&repo, "refs/heads/main", None);
+        let merged = commit(&repo, "refs/heads/merged", Some(base));
+        commit(&repo, "refs/heads/main", Some(merged));
+        let other = commit(&repo, "refs/heads/other", Some(base));
+        let main = resolve_commit(&repo, "main").unwrap();
+        assert!(is_contained_in(&repo, merged, main).unwrap());
+        assert!(is_contained_in(&repo, main, main).unwrap());
+        assert!(!is_contained_in(&repo, other, main).unwrap());
+    }
+
+    #[test]
+    fn tells_unknown_containment_in_shallow_clone() {
+        let (_dir, repo) = init_repo();
+        let old = commit(&repo, "refs/heads/old", None);
+        let cut = commit(&repo, "refs/heads/main", Some(old));
+        let main = commit(&repo, "refs/heads/main", Some(cut));
+        let other = commit(&repo, "refs/heads/other", Some(old));
+        assert_eq!(check_contained_in(&repo, old, main).unwrap(), Some(true));
+        assert_eq!(check_contained_in(&repo, other, main).unwrap(), Some(false));
+        // Like `git clone --depth 2`, which leaves out the parents of `cut`
+        std::fs::write(repo.path().join("shallow"), format!("{cut}\n")).unwrap();
+        let repo = Repository::open(repo.path()).unwrap();
+        assert!(repo.is_shallow());
+        assert_eq!(check_contained_in(&repo, cut, main).unwrap(), Some(true));
+        assert_eq!(check_contained_in(&repo, old, main).unwrap(), None);
+        assert_eq!(check_contained_in(&repo, other, main).unwrap(), None);
+    }
+
+    #[test]
+    fn counts_unpushed_commits() {
+        let (_dir, repo) = init_repo();
+        let pushed = commit(&repo, "refs/heads/feature", None);
+        let tip = commit(&repo, "refs/heads/feature", Some(pushed));
+        assert_eq!(count_unpushed_commits(&repo, "feature", tip).unwrap(), None);
+        repo.remote("origin", "https://example.com/repo.git")
+            .unwrap();
+        repo.reference("refs/remotes/origin/feature", pushed, false, "")
+            .unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("branch.feature.remote", "origin").unwrap();
+        config
+            .set_str("branch.feature.merge", "refs/heads/feature")
+            .unwrap();
+        assert_eq!(
+            count_unpushed_commits(&repo, "feature", tip).unwrap(),
+            Some(1)
+        );
+        assert_eq!(
+            count_unpushed_commits(&repo, "feature", pushed).unwrap(),
+            Some(0)
+        );
+        let other = commit(&repo, "refs/remotes/origin/feature", Some(pushed));
+        assert_eq!(
+            count_ahead_behind(&repo, "feature", tip).unwrap(),
+            Some((1, 1))
+        );
+        assert_eq!(
+            count_ahead_behind(&repo, "feature", other).unwrap(),
+            Some((0, 0))
+        );
+    }
+
+    #[test]
+    fn tells_risky_branches() {
+        let (_dir, repo) = init_repo();
+        let pushed = commit(&repo, "refs/heads/feature", None);
+        let tip = commit(&repo, "refs/heads/feature", Some(pushed));
+        repo.remote("origin", "https://example.com/repo.git")
+            .unwrap();
+        repo.reference("refs/remotes/origin/feature", pushed, false, "")
+            .unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("branch.feature.remote", "origin").unwrap();
+        config
+            .set_str("branch.feature.merge", "refs/heads/feature")
+            .unwrap();
+        assert!(!is_risky(&repo, "feature", pushed, false, false));
+        assert!(is_risky(&repo, "feature", pushed, true, false));
+        assert!(is_risky(&repo, "feature", pushed, false, true));
+        assert!(is_risky(&repo, "feature", tip, false, false));
+    }
+
+    #[test]
+    fn detects_squash_merge() {
+        let (_dir, repo) = init_repo();
+        let base = commit_files(&repo, "refs/heads/main", None, &[("a", "1")]);
+        let first = commit_files(&repo, "refs/heads/feature", Some(base), &[("b", "1")]);
+        let tip = commit_files(&repo, "refs/heads/feature", Some(first), &[("c", "1")]);
+        let other = commit_files(&repo, "refs/heads/other", Some(base), &[("d", "1")]);
+        let main = commit_files(&repo, "refs/heads/main", Some(base), &[("e", "1")]);
+        let squash = commit_files(
+            &repo,
+            "refs/heads/main",
+            Some(main),
+            &[("b", "1"), ("c", "1")],
+        );
+        let mut index = PatchIndex::new(&repo, squash);
+        assert!(index.is_squash_merged(tip).unwrap());
+        assert!(!index.is_squash_merged(first).unwrap());
+        assert!(!index.is_squash_merged(other).unwrap());
+        assert!(!index.is_squash_merged(base).unwrap());
+    }
+
+    #[test]
+    fn detects_rebase_merge() {
+        let (_dir, repo) = init_repo();
+        let base = commit_files(&repo, "refs/heads/main", None, &[("a", "1")]);
+        let first = commit_files(&repo, "refs/heads/feature", Some(base), &[("b", "1")]);
+        let tip = commit_files(&repo, "refs/heads/feature", Some(first), &[("c", "1")]);
+        let partial = commit_files(&repo, "refs/heads/partial", Some(first), &[("d", "1")]);
+        let main = commit_files(&repo, "refs/heads/main", Some(base), &[("e", "1")]);
+        let copy = commit_files(&repo, "refs/heads/main", Some(main), &[("b", "1")]);
+        let copy = commit_files(&repo, "refs/heads/main", Some(copy), &[("c", "1")]);
+        let mut index = PatchIndex::new(&repo, copy);
+        assert!(index.is_rebase_merged(tip).unwrap());
+        assert!(!index.is_squash_merged(tip).unwrap());
+        assert_eq!(
+            index.find_merge_kind(tip).unwrap(),
+            Some(MergeKind::Rebased)
+        );
+        assert_eq!(index.find_merge_kind(partial).unwrap(), None);
+        // Same tree as a base commit
+        let mut index = PatchIndex::new(&repo, tip);
+        let other = commit_files(
+            &repo,
+            "refs/heads/other",
+            Some(base),
+            &[("b", "1"), ("c", "1")],
+        );
+        assert!(index.is_rebase_merged(other).unwrap());
+    }
+}
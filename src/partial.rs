@@ -0,0 +1,92 @@
+//! Partial clones, made with `git clone --filter`, which lack some file contents or trees.
+//! Git fetches them when it needs them, but libgit2 cannot, so what reads them is either
+//! skipped or done after fetching them with `git`.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use eyre::{eyre, Result};
+use git2::{Oid, Repository};
+
+use crate::gitenv::set_git_env;
+
+/// Whether objects may be missing, to be fetched from a promisor remote.
+pub fn is_partial_clone(repo: &Repository) -> bool {
+    let Ok(config) = repo.config() else {
+        return false;
+    };
+    if config.get_string("extensions.partialClone").is_ok() {
+        return true;
+    }
+    let Ok(remotes) = repo.remotes() else {
+        return false;
+    };
+    let is_promisor = |r: &str| config.get_bool(&format!("remote.{r}.promisor"));
+    remotes
+        .iter()
+        .flatten()
+        .any(|r| is_promisor(r).unwrap_or(false))
+}
+
+/// Fetch the objects needed to compare the `tips` with `base`: those of their commits since
+/// the oldest fork point. `git log -p` reads them all, fetching the missing ones in batches.
+pub fn fetch_missing_objects(repo: &Repository, base: Oid, tips: &[Oid]) -> Result<()> {
+    let mut oldest: Option<(Oid, i64)> = None;
+    for tip in tips {
+        let Ok(fork_point) = repo.merge_base(base, *tip) else {
+            continue;
+        };
+        let time = repo.find_commit(fork_point)?.time().seconds();
+        if oldest.is_none_or(|(_, t)| time < t) {
+            oldest = Some((fork_point, time));
+        }
+    }
+    let mut revs: Vec<String> = tips.iter().map(Oid::to_string).collect();
+    revs.push(base.to_string());
+    if let Some((fork_point, _)) = oldest {
+        revs.push(format!("^{fork_point}"));
+    }
+    let mut cmd = Command::new("git");
+    set_git_env(&mut cmd, repo);
+    let mut child = cmd
+        .args([
+            "log",
+            "-p",
+            "--format=",
+            "--no-color",
+            "--no-ext-diff",
+            "--stdin",
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .map_err(|e| eyre!("Failed to run git: {e}"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(revs.join("\n").as_bytes())?;
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(eyre!("git log failed ({status})"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::init_repo;
+
+    #[test]
+    fn detects_partial_clone() {
+        let (_dir, repo) = init_repo();
+        repo.remote("origin", "https://example.com/repo.git")
+            .unwrap();
+        assert!(!is_partial_clone(&repo));
+        let mut config = repo.config().unwrap();
+        config.set_bool("remote.origin.promisor", true).unwrap();
+        assert!(is_partial_clone(&repo));
+        config.remove("remote.origin.promisor").unwrap();
+        config.set_str("extensions.partialClone", "origin").unwrap();
+        assert!(is_partial_clone(&repo));
+    }
+}
@@ -0,0 +1,119 @@
+//! Glob patterns for branch names, like "release/*".
+//!
+//! Like `git branch --list`, `*` matches any characters including "/", `?` matches one character
+//! and `[...]` matches one character in the set (`[!...]` for not in the set).
+
+use std::fs;
+use std::path::Path;
+
+use eyre::{Context, Result};
+
+/// Check if `name` matches the glob `pattern`.
+pub fn matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    match_from(&pattern, &name)
+}
+
+pub fn matches_any<S: AsRef<str>>(patterns: &[S], name: &str) -> bool {
+    patterns.iter().any(|p| matches(p.as_ref(), name))
+}
+
+/// The globs of a file, one per line. Blank lines and those starting with "#" are skipped.
+pub fn parse_patterns(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(String::from)
+        .collect()
+}
+
+/// Read the globs of the file, like a list of branches never to delete.
+pub fn read_patterns(path: &Path) -> Result<Vec<String>> {
+    let content =
+        fs::read_to_string(path).wrap_err_with(|| format!("Cannot read {}", path.display()))?;
+    Ok(parse_patterns(&content))
+}
+
+fn match_from(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => {
+            let rest = &pattern[1..];
+            (0..=name.len()).any(|i| match_from(rest, &name[i..]))
+        }
+        Some('?') => !name.is_empty() && match_from(&pattern[1..], &name[1..]),
+        Some('[') => {
+            let Some((&c, name_rest)) = name.split_first() else {
+                return false;
+            };
+            match match_class(&pattern[1..], c) {
+                Some((true, rest)) => match_from(rest, name_rest),
+                Some((false, _)) => false,
+                // No closing bracket, treat "[" literally
+                None => c == '[' && match_from(&pattern[1..], name_rest),
+            }
+        }
+        Some(&p) => name.first() == Some(&p) && match_from(&pattern[1..], &name[1..]),
+    }
+}
+
+/// Match `c` against a character class, whose "[" is already consumed.
+/// Return whether it matches, and the pattern after the closing "]".
+fn match_class(pattern: &[char], c: char) -> Option<(bool, &[char])> {
+    let (negated, mut pattern) = match pattern.first() {
+        Some('!') | Some('^') => (true, &pattern[1..]),
+        _ => (false, pattern),
+    };
+    let mut found = false;
+    let mut first = true;
+    loop {
+        match pattern {
+            [] => return None,
+            [']', rest @ ..] if !first => return Some((found != negated, rest)),
+            [lo, '-', hi, rest @ ..] if *hi != ']' => {
+                found |= (*lo..=*hi).contains(&c);
+                pattern = rest;
+            }
+            [x, rest @ ..] => {
+                found |= *x == c;
+                pattern = rest;
+            }
+        }
+        first = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_wildcards() {
+        assert!(matches("release/*", "release/1.0"));
+        assert!(matches("release/*", "release/1.0/hotfix"));
+        assert!(!matches("release/*", "releases"));
+        assert!(matches("*", ""));
+        assert!(matches("fix-?", "fix-1"));
+        assert!(!matches("fix-?", "fix-12"));
+        assert!(matches("main", "main"));
+        assert!(!matches("main", "maint"));
+    }
+
+    #[test]
+    fn matches_classes() {
+        assert!(matches("v[0-9]*", "v2.1"));
+        assert!(!matches("v[0-9]*", "vx"));
+        assert!(matches("[!w]ip", "tip"));
+        assert!(!matches("[!w]ip", "wip"));
+        assert!(matches("a[]]b", "a]b"));
+        assert!(matches("a[b", "a[b"));
+    }
+
+    #[test]
+    fn parses_patterns() {
+        let content = "# Ask the release team first\nrelease/*\n\n  hotfix  \n#old\n";
+        assert_eq!(parse_patterns(content), ["release/*", "hotfix"]);
+    }
+}
@@ -0,0 +1,1229 @@
+//! Multi-select list for picking branches, with a search mode and a preview of the
+//! highlighted item. Slash-separated names can be shown as a tree of their folders, which
+//! are selected and folded as a whole.
+
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fmt;
+use std::io::{self, Write};
+use std::path::Path;
+
+use console::{style, truncate_str};
+use crossterm::cursor::{Hide, MoveToColumn, MoveUp, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::terminal::{self, Clear, ClearType};
+use crossterm::{execute, queue};
+use git_del_branches::{pattern, selection};
+
+mod filter;
+mod keys;
+mod viewer;
+
+use self::filter::Filter;
+pub use self::filter::{Fields, Value};
+pub use self::keys::KeyMap;
+
+/// Most rows of items shown at once.
+const MAX_PAGE_SIZE: usize = 15;
+
+/// What to do after a key press.
+#[derive(Debug, PartialEq, Eq)]
+enum Action {
+    Continue,
+    Confirm,
+    Cancel,
+    Preview,
+    Details,
+    /// Write the names of the selected items to the file at this path.
+    Export(String),
+    Sort,
+    Help,
+}
+
+/// A line of the list: an item, or in the tree view a folder of items, like "feature/" or
+/// "feature/payments/", indented by its depth.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Row {
+    Item {
+        index: usize,
+        depth: usize,
+    },
+    Folder {
+        path: String,
+        depth: usize,
+        /// The shown items under it, at any depth.
+        items: Vec<usize>,
+    },
+}
+
+/// Rows of the tree of the items, by the folders of their names, which are in the order of
+/// their first item. The items of the collapsed folders are left out.
+fn build_tree(names: &[String], items: &[usize], collapsed: &HashSet<String>) -> Vec<Row> {
+    let mut rows = Vec::new();
+    add_subtree(names, items, "", 0, collapsed, &mut rows);
+    rows
+}
+
+fn add_subtree(
+    names: &[String],
+    items: &[usize],
+    prefix: &str,
+    depth: usize,
+    collapsed: &HashSet<String>,
+    rows: &mut Vec<Row>,
+) {
+    // The items, or the folders with their items
+    let mut children: Vec<(Option<String>, Vec<usize>)> = Vec::new();
+    for &i in items {
+        let rest = &names[i][prefix.len()..];
+        let Some(end) = rest.find('/') else {
+            children.push((None, vec![i]));
+            continue;
+        };
+        let path = format!("{prefix}{}", &rest[..=end]);
+        match children.iter_mut().find(|(p, _)| p.as_ref() == Some(&path)) {
+            Some((_, folder)) => folder.push(i),
+            None => children.push((Some(path), vec![i])),
+        }
+    }
+    for (path, folder) in children {
+        let Some(path) = path else {
+            rows.extend(folder.iter().map(|&index| Row::Item { index, depth }));
+            continue;
+        };
+        let open = !collapsed.contains(&path);
+        rows.push(Row::Folder {
+            path: path.clone(),
+            depth,
+            items: folder.clone(),
+        });
+        if open {
+            add_subtree(names, &folder, &path, depth + 1, collapsed, rows);
+        }
+    }
+}
+
+/// Selection state of the picker, apart from the terminal.
+struct State {
+    labels: Vec<String>,
+    /// Short names of the items, for selecting by pattern.
+    names: Vec<String>,
+    keys: KeyMap,
+    /// Whether the items have a preview.
+    can_preview: bool,
+    /// Whether the items have details.
+    has_details: bool,
+    /// Whether the selection can be written to a file.
+    can_export: bool,
+    /// Indices of the items, in the order they are shown.
+    order: Vec<usize>,
+    /// Name of the sort order, if not the original one.
+    sorted_by: Option<String>,
+    /// Group of each item, if the order clusters them. Groups are shown with headers.
+    groups: Option<Vec<String>>,
+    selected: Vec<bool>,
+    /// Fields of each item, for the filter expressions.
+    fields: Vec<Fields>,
+    filter: String,
+    /// Why a word of the filter cannot be applied, like an unknown field.
+    filter_error: Option<String>,
+    /// Whether keys are typed into the filter.
+    searching: bool,
+    /// Glob being typed, to select the items whose names match.
+    pattern: Option<String>,
+    /// Path being typed, to write the names of the selected items to.
+    export: Option<String>,
+    /// What the last action did, styled, shown until the next key.
+    notice: Option<String>,
+    /// Indices of the items matching the filter.
+    visible: Vec<usize>,
+    /// Folders collapsed in the tree view, if the items are shown as a tree.
+    tree: Option<HashSet<String>>,
+    /// Lines of the visible items, with their folders in the tree view.
+    rows: Vec<Row>,
+    /// Position of the highlighted row in `rows`.
+    cursor: usize,
+    /// Position in `rows` of the first row on screen.
+    offset: usize,
+    page_size: usize,
+}
+
+impl State {
+    fn new(labels: Vec<String>, names: Vec<String>, page_size: usize, keys: KeyMap) -> Self {
+        let mut state = Self {
+            selected: vec![false; labels.len()],
+            fields: vec![Vec::new(); labels.len()],
+            order: (0..labels.len()).collect(),
+            sorted_by: None,
+            groups: None,
+            keys,
+            can_preview: true,
+            has_details: true,
+            can_export: true,
+            labels,
+            names,
+            pattern: None,
+            export: None,
+            notice: None,
+            filter: String::new(),
+            filter_error: None,
+            searching: false,
+            visible: Vec::new(),
+            tree: None,
+            rows: Vec::new(),
+            cursor: 0,
+            offset: 0,
+            page_size,
+        };
+        state.update_visible();
+        state
+    }
+
+    fn update_visible(&mut self) {
+        let filter = Filter::parse(&self.filter);
+        let mut error = None;
+        self.visible = self
+            .order
+            .iter()
+            .copied()
+            .filter(|&i| {
+                let (matched, e) = filter.matches(&self.labels[i], &self.fields[i]);
+                error = error.take().or(e);
+                matched
+            })
+            .collect();
+        self.filter_error = error;
+        self.update_rows();
+    }
+
+    fn update_rows(&mut self) {
+        self.rows = match &self.tree {
+            Some(collapsed) => build_tree(&self.names, &self.visible, collapsed),
+            None => self
+                .visible
+                .iter()
+                .map(|&index| Row::Item { index, depth: 0 })
+                .collect(),
+        };
+        self.cursor = self.cursor.min(self.rows.len().saturating_sub(1));
+        self.scroll();
+    }
+
+    /// Highlight the row of the item, if it is shown.
+    fn highlight(&mut self, item: Option<usize>) {
+        let found = self
+            .rows
+            .iter()
+            .position(|r| matches!(r, Row::Item { index, .. } if Some(*index) == item));
+        if let Some(pos) = found {
+            self.cursor = pos;
+            self.scroll();
+        }
+    }
+
+    /// Show the items as a tree of their folders, or back as a list.
+    fn toggle_tree(&mut self) {
+        let highlighted = self.highlighted();
+        self.tree = match self.tree {
+            Some(_) => None,
+            None => Some(HashSet::new()),
+        };
+        self.update_rows();
+        self.highlight(highlighted);
+    }
+
+    /// Fold or unfold the highlighted folder. On an item, its folder is folded.
+    fn toggle_fold(&mut self) {
+        let Some(collapsed) = self.tree.as_mut() else {
+            return;
+        };
+        let path = match self.rows.get(self.cursor) {
+            Some(Row::Folder { path, .. }) => path.clone(),
+            Some(Row::Item { index, depth }) if *depth > 0 => {
+                let name = &self.names[*index];
+                name[..name.rfind('/').map_or(0, |p| p + 1)].to_string()
+            }
+            _ => return,
+        };
+        if !collapsed.remove(&path) {
+            collapsed.insert(path.clone());
+        }
+        self.update_rows();
+        let found = self
+            .rows
+            .iter()
+            .position(|r| matches!(r, Row::Folder { path: p, .. } if *p == path));
+        if let Some(pos) = found {
+            self.cursor = pos;
+            self.scroll();
+        }
+    }
+
+    /// Toggle the highlighted item, or all of the highlighted folder: they are all selected,
+    /// unless they already are.
+    fn toggle_highlighted(&mut self) {
+        match self.rows.get(self.cursor) {
+            Some(Row::Item { index, .. }) => self.selected[*index] = !self.selected[*index],
+            Some(Row::Folder { items, .. }) => {
+                let value = !items.iter().all(|&i| self.selected[i]);
+                for &i in items {
+                    self.selected[i] = value;
+                }
+            }
+            None => {}
+        }
+    }
+
+    /// Show the items in another order, keeping the same one highlighted.
+    fn set_order(
+        &mut self,
+        order: Vec<usize>,
+        sorted_by: Option<String>,
+        groups: Option<Vec<String>>,
+    ) {
+        let highlighted = self.highlighted();
+        self.order = order;
+        self.sorted_by = sorted_by;
+        self.groups = groups;
+        self.update_visible();
+        self.highlight(highlighted);
+    }
+
+    /// Index of the highlighted item. `None` on a folder.
+    fn highlighted(&self) -> Option<usize> {
+        match self.rows.get(self.cursor) {
+            Some(Row::Item { index, .. }) => Some(*index),
+            _ => None,
+        }
+    }
+
+    fn move_cursor(&mut self, delta: isize) {
+        let Some(last) = self.rows.len().checked_sub(1) else {
+            return;
+        };
+        self.cursor = self.cursor.saturating_add_signed(delta).min(last);
+        self.scroll();
+    }
+
+    /// Keep the cursor on screen.
+    fn scroll(&mut self) {
+        if self.cursor < self.offset {
+            self.offset = self.cursor;
+        } else if self.cursor >= self.offset + self.page_size {
+            self.offset = self.cursor + 1 - self.page_size;
+        }
+        let max_offset = self.rows.len().saturating_sub(self.page_size);
+        self.offset = self.offset.min(max_offset);
+        // Group headers take lines too
+        while self.offset < self.cursor
+            && self.count_lines(self.offset, self.cursor) > self.page_size
+        {
+            self.offset += 1;
+        }
+    }
+
+    /// Whether a group header is shown above the row, when the screen starts at `first`. The
+    /// tree view has its folders instead. Without it, the rows are the visible items.
+    fn starts_group(&self, row: usize, first: usize) -> bool {
+        let (Some(groups), None) = (&self.groups, &self.tree) else {
+            return false;
+        };
+        row == first || groups[self.visible[row]] != groups[self.visible[row - 1]]
+    }
+
+    /// Number of lines taken by the rows from `first` to `last`, with the group headers.
+    fn count_lines(&self, first: usize, last: usize) -> usize {
+        (first..=last)
+            .map(|row| 1 + usize::from(self.starts_group(row, first)))
+            .sum()
+    }
+
+    /// Header of the group which starts at the row, with the number of its items.
+    fn group_header(&self, row: usize) -> Option<String> {
+        let groups = self.groups.as_ref()?;
+        let group = &groups[self.visible[row]];
+        let (mut count, mut selected) = (0, 0);
+        for &i in self.visible.iter().filter(|&&i| &groups[i] == group) {
+            count += 1;
+            selected += usize::from(self.selected[i]);
+        }
+        let header = match selected {
+            0 => format!("{group} ({count})"),
+            _ => format!("{group} ({count}, {selected} selected)"),
+        };
+        Some(format!("  {}", style(header).bold()))
+    }
+
+    fn select_visible(&mut self, value: bool) {
+        for &i in &self.visible {
+            self.selected[i] = value;
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Action {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Action::Cancel;
+        }
+        self.notice = None;
+        let page = self.page_size as isize;
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Up => self.move_cursor(-1),
+            KeyCode::Down => self.move_cursor(1),
+            KeyCode::PageUp => self.move_cursor(-page),
+            KeyCode::PageDown => self.move_cursor(page),
+            _ if self.pattern.is_some() => self.handle_pattern_key(key),
+            _ if self.export.is_some() => return self.handle_export_key(key),
+            _ if self.searching => self.handle_search_key(key),
+            // Remappable keys take over the fixed ones
+            _ if self.keys.toggle.matches(&key) => self.toggle_highlighted(),
+            _ if self.keys.confirm.matches(&key) => return Action::Confirm,
+            _ if self.keys.search.matches(&key) => self.searching = true,
+            _ if self.keys.sort.matches(&key) => return Action::Sort,
+            _ if self.keys.preview.matches(&key)
+                && self.can_preview
+                && self.highlighted().is_some() =>
+            {
+                return Action::Preview
+            }
+            _ if self.keys.details.matches(&key)
+                && self.has_details
+                && self.highlighted().is_some() =>
+            {
+                return Action::Details
+            }
+            KeyCode::Home | KeyCode::Char('g') => self.move_cursor(isize::MIN),
+            KeyCode::End | KeyCode::Char('G') => self.move_cursor(isize::MAX),
+            // Like Vim, half a page
+            KeyCode::Char('d') if ctrl => self.move_cursor((page / 2).max(1)),
+            KeyCode::Char('u') if ctrl => self.move_cursor(-(page / 2).max(1)),
+            KeyCode::Char('k') => self.move_cursor(-1),
+            KeyCode::Char('j') => self.move_cursor(1),
+            KeyCode::Right | KeyCode::Char('a') => self.select_visible(true),
+            KeyCode::Left => self.select_visible(false),
+            KeyCode::Char('i') => {
+                for &i in &self.visible {
+                    self.selected[i] = !self.selected[i];
+                }
+            }
+            KeyCode::Char('+') => self.pattern = Some(String::new()),
+            KeyCode::Char('w') if self.can_export => self.export = Some(String::new()),
+            KeyCode::Char('t') => self.toggle_tree(),
+            KeyCode::Char('o') => self.toggle_fold(),
+            KeyCode::Char('?') => return Action::Help,
+            KeyCode::Esc if !self.filter.is_empty() => {
+                self.filter.clear();
+                self.update_visible();
+            }
+            KeyCode::Esc => return Action::Cancel,
+            _ => {}
+        }
+        Action::Continue
+    }
+
+    fn handle_pattern_key(&mut self, key: KeyEvent) {
+        let Some(glob) = self.pattern.as_mut() else {
+            return;
+        };
+        match key.code {
+            KeyCode::Char(c) => glob.push(c),
+            KeyCode::Backspace => {
+                glob.pop();
+            }
+            KeyCode::Enter => {
+                for &i in &self.visible {
+                    if pattern::matches(glob, &self.names[i]) {
+                        self.selected[i] = true;
+                    }
+                }
+                self.pattern = None;
+            }
+            KeyCode::Esc => self.pattern = None,
+            _ => {}
+        }
+    }
+
+    fn handle_export_key(&mut self, key: KeyEvent) -> Action {
+        let Some(path) = self.export.as_mut() else {
+            return Action::Continue;
+        };
+        match key.code {
+            KeyCode::Char(c) => path.push(c),
+            KeyCode::Backspace => {
+                path.pop();
+            }
+            KeyCode::Enter => {
+                let path = self.export.take().unwrap_or_default();
+                if !path.trim().is_empty() {
+                    return Action::Export(path.trim().to_string());
+                }
+            }
+            KeyCode::Esc => self.export = None,
+            _ => {}
+        }
+        Action::Continue
+    }
+
+    /// Names of the selected items, in their original order.
+    fn selected_names(&self) -> Vec<String> {
+        let selected = self.names.iter().zip(&self.selected);
+        selected
+            .filter(|(_, s)| **s)
+            .map(|(n, _)| n.clone())
+            .collect()
+    }
+
+    fn handle_search_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char(c) => {
+                self.filter.push(c);
+                self.update_visible();
+            }
+            KeyCode::Backspace => {
+                self.filter.pop();
+                self.update_visible();
+            }
+            KeyCode::Enter => self.searching = false,
+            KeyCode::Esc => {
+                self.searching = false;
+                self.filter.clear();
+                self.update_visible();
+            }
+            _ => {}
+        }
+    }
+
+    /// Lines of the help screen about the keys.
+    fn help_keys(&self) -> Vec<String> {
+        let KeyMap {
+            toggle,
+            confirm,
+            search,
+            sort,
+            preview,
+            details,
+        } = self.keys;
+        let keys = [
+            ("↑ ↓ j k".to_string(), "move"),
+            ("PgUp PgDn".into(), "move by a page"),
+            ("ctrl-u ctrl-d".into(), "move by half a page"),
+            ("Home End g G".into(), "go to the first or last"),
+            (
+                toggle.to_string(),
+                "select or unselect the highlighted one, or all of its folder",
+            ),
+            ("a →".into(), "select all the shown ones"),
+            ("←".into(), "unselect all the shown ones"),
+            ("i".into(), "invert the selection of the shown ones"),
+            (
+                "+".into(),
+                "select the shown ones matching a glob, like \"feature/*\"",
+            ),
+            (
+                search.to_string(),
+                "filter by text, or fields like \"age>6m\", enter keeps it",
+            ),
+            (sort.to_string(), "change the order"),
+            ("t".into(), "show the names as a tree of their folders"),
+            ("o".into(), "fold or unfold the highlighted folder"),
+            (
+                preview.to_string(),
+                "review the changes of the highlighted one",
+            ),
+            (
+                details.to_string(),
+                "show all that is known about the highlighted one",
+            ),
+            (
+                "w".into(),
+                "write the names of the selected ones to a file, for --select-from",
+            ),
+            (confirm.to_string(), "confirm the selection"),
+            ("esc".into(), "clear the filter, or cancel"),
+            ("?".into(), "show this help"),
+        ];
+        let mut lines = vec!["Keys".to_string()];
+        lines.extend(
+            keys.iter()
+                .filter(|(k, _)| self.can_preview || *k != preview.to_string())
+                .filter(|(k, _)| self.has_details || *k != details.to_string())
+                .filter(|(k, _)| self.can_export || k != "w")
+                .map(|(k, desc)| format!("  {k:<16}{desc}")),
+        );
+        lines
+    }
+
+    /// The row with its check box, without the cursor.
+    fn render_row(&self, row: &Row) -> String {
+        match row {
+            Row::Item { index, depth } => {
+                let check = if self.selected[*index] { "[x]" } else { "[ ]" };
+                format!("{check} {}{}", "  ".repeat(*depth), self.labels[*index])
+            }
+            Row::Folder { path, depth, items } => {
+                let selected = items.iter().filter(|&&i| self.selected[i]).count();
+                let check = match selected {
+                    0 => "[ ]",
+                    _ if selected == items.len() => "[x]",
+                    _ => "[-]",
+                };
+                let sign = match self.tree.as_ref().is_some_and(|c| c.contains(path)) {
+                    true => "▸",
+                    false => "▾",
+                };
+                let name = path[..path.len() - 1].rsplit('/').next().unwrap_or(path);
+                let count = match selected {
+                    0 => format!("({})", items.len()),
+                    _ => format!("({}, {selected} selected)", items.len()),
+                };
+                format!(
+                    "{check} {}{sign} {} {}",
+                    "  ".repeat(*depth),
+                    style(format!("{name}/")).bold(),
+                    style(count).dim()
+                )
+            }
+        }
+    }
+
+    fn render(&self, message: &str, status: Option<&str>, width: usize) -> Vec<String> {
+        let mut header = format!("{} {message}", style("?").green());
+        if let Some(name) = &self.sorted_by {
+            header.push_str(&format!(" {}", style(format!("({name})")).dim()));
+        }
+        if let Some(glob) = &self.pattern {
+            header.push_str(&format!(" {} {glob}", style("select matching:").cyan()));
+        } else if let Some(path) = &self.export {
+            header.push_str(&format!(" {} {path}", style("write selection to:").cyan()));
+        } else if let Some(notice) = &self.notice {
+            header.push_str(&format!(" {notice}"));
+        } else if self.searching || !self.filter.is_empty() {
+            header.push_str(&format!(" {}", style(format!("/{}", self.filter)).cyan()));
+            if let Some(error) = &self.filter_error {
+                header.push_str(&format!(" {}", style(format!("({error})")).red()));
+            }
+        }
+        let mut lines = vec![header];
+        let mut end = self.offset;
+        let mut used = 0;
+        while end < self.rows.len() {
+            used += 1 + usize::from(self.starts_group(end, self.offset));
+            if used > self.page_size {
+                break;
+            }
+            end += 1;
+        }
+        for row in self.offset..end {
+            if self.starts_group(row, self.offset) {
+                lines.extend(self.group_header(row));
+            }
+            let text = self.render_row(&self.rows[row]);
+            let line = if row == self.cursor {
+                format!("{} {}", style(">").cyan(), style(text).cyan())
+            } else {
+                let marker = match row {
+                    _ if row == self.offset && self.offset > 0 => "▲",
+                    _ if row == end - 1 && end < self.rows.len() => "▼",
+                    _ => " ",
+                };
+                format!("{marker} {text}")
+            };
+            lines.push(line);
+        }
+        if self.rows.is_empty() {
+            lines.push(format!("  {}", style("No matching items").dim()));
+        }
+        if let Some(status) = status {
+            lines.push(format!("  {}", style(status).dim()));
+        }
+        let KeyMap {
+            toggle,
+            confirm,
+            search,
+            preview,
+            ..
+        } = self.keys;
+        let preview = match self.can_preview {
+            true => format!(" {preview} preview,"),
+            false => String::new(),
+        };
+        let help = format!(
+            "[↑↓ move, {toggle} select, a all, {search} search,{preview} {confirm} confirm, ? help]"
+        );
+        lines.push(style(help).dim().to_string());
+        lines
+            .iter()
+            .map(|l| truncate_str(l, width, "…").into_owned())
+            .collect()
+    }
+}
+
+/// Puts the terminal back to normal when dropped.
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stderr(), Hide)?;
+        Ok(Self)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = execute!(io::stderr(), Show);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Erase the lines we drew before, the cursor being on the last one.
+fn clear_lines(out: &mut impl Write, count: usize) -> io::Result<()> {
+    queue!(out, MoveToColumn(0))?;
+    if count > 1 {
+        queue!(out, MoveUp((count - 1) as u16))?;
+    }
+    queue!(out, Clear(ClearType::FromCursorDown))
+}
+
+type ItemFn<'a, T, R> = Box<dyn FnMut(&T) -> R + 'a>;
+type CompareFn<'a, T> = Box<dyn Fn(&T, &T) -> Ordering + 'a>;
+type GroupFn<'a, T> = Box<dyn Fn(&T) -> String + 'a>;
+/// Name of an order, how it compares items, and what it clusters them by.
+type SortOrder<'a, T> = (&'a str, CompareFn<'a, T>, Option<GroupFn<'a, T>>);
+
+pub struct Picker<'a, T> {
+    message: &'a str,
+    items: Vec<T>,
+    /// How chosen items are shown in the answer line.
+    formatter: ItemFn<'a, T, String>,
+    /// Line about the highlighted item.
+    status: Option<ItemFn<'a, T, Option<String>>>,
+    /// Text to show in the viewer for the highlighted item.
+    preview: Option<ItemFn<'a, T, Vec<String>>>,
+    /// Everything known about the highlighted item, to show in the viewer.
+    details: Option<ItemFn<'a, T, Vec<String>>>,
+    /// Named values of an item, for filter expressions like `age>6m`.
+    fields: Option<ItemFn<'a, T, Fields>>,
+    /// Orders which the sort key cycles through, after the original one.
+    sorts: Vec<SortOrder<'a, T>>,
+    /// Meanings of the signs in the labels, for the help screen.
+    legend: Vec<(String, String)>,
+    /// Items selected from the start.
+    selected: Vec<bool>,
+    /// Whether the selection can be written to a file for `--select-from`.
+    export: bool,
+    /// Order to start with: 0 for the original one, else 1 + its index in `sorts`.
+    sort_index: usize,
+    keys: KeyMap,
+}
+
+impl<'a, T: fmt::Display> Picker<'a, T> {
+    pub fn new(message: &'a str, items: Vec<T>) -> Self {
+        Self {
+            selected: vec![false; items.len()],
+            export: false,
+            message,
+            items,
+            formatter: Box::new(|i| i.to_string()),
+            status: None,
+            preview: None,
+            details: None,
+            fields: None,
+            sorts: Vec::new(),
+            legend: Vec::new(),
+            sort_index: 0,
+            keys: KeyMap::default(),
+        }
+    }
+
+    /// Select from the start the items for which `f` returns true.
+    pub fn with_selected(mut self, f: impl Fn(&T) -> bool) -> Self {
+        self.selected = self.items.iter().map(f).collect();
+        self
+    }
+
+    /// Let user write the names of the selected items to a file, to select them again later.
+    pub fn with_export(mut self) -> Self {
+        self.export = true;
+        self
+    }
+
+    pub fn with_keys(mut self, keys: KeyMap) -> Self {
+        self.keys = keys;
+        self
+    }
+
+    /// Explain the signs used in the labels, on the help screen.
+    pub fn with_legend(mut self, legend: Vec<(String, String)>) -> Self {
+        self.legend = legend;
+        self
+    }
+
+    /// Add an order to sort the items by, with its name shown when it is used.
+    pub fn with_sort(mut self, name: &'a str, compare: impl Fn(&T, &T) -> Ordering + 'a) -> Self {
+        self.sorts.push((name, Box::new(compare), None));
+        self
+    }
+
+    /// Add an order which clusters the items by `group`, under headers telling how many
+    /// items each group has. Items are sorted by `compare` inside a group.
+    pub fn with_grouping(
+        mut self,
+        name: &'a str,
+        group: impl Fn(&T) -> String + 'a,
+        compare: impl Fn(&T, &T) -> Ordering + 'a,
+    ) -> Self {
+        self.sorts
+            .push((name, Box::new(compare), Some(Box::new(group))));
+        self
+    }
+
+    /// Start with the order of this name, added by `with_sort` or `with_grouping`.
+    pub fn with_initial_sort(mut self, name: &str) -> Self {
+        if let Some(i) = self.sorts.iter().position(|(n, _, _)| *n == name) {
+            self.sort_index = i + 1;
+        }
+        self
+    }
+
+    pub fn with_formatter(mut self, f: impl FnMut(&T) -> String + 'a) -> Self {
+        self.formatter = Box::new(f);
+        self
+    }
+
+    /// Let the search filter the items by these fields, like `author:alice` or `age>6m`.
+    pub fn with_fields(mut self, f: impl FnMut(&T) -> Fields + 'a) -> Self {
+        self.fields = Some(Box::new(f));
+        self
+    }
+
+    pub fn with_status(mut self, f: impl FnMut(&T) -> Option<String> + 'a) -> Self {
+        self.status = Some(Box::new(f));
+        self
+    }
+
+    pub fn with_preview(mut self, f: impl FnMut(&T) -> Vec<String> + 'a) -> Self {
+        self.preview = Some(Box::new(f));
+        self
+    }
+
+    pub fn with_details(mut self, f: impl FnMut(&T) -> Vec<String> + 'a) -> Self {
+        self.details = Some(Box::new(f));
+        self
+    }
+
+    /// Indices of the items in the order of this index, with its name and the groups of
+    /// the items, if it clusters them.
+    fn get_order(&self, sort_index: usize) -> (Vec<usize>, Option<String>, Option<Vec<String>>) {
+        let mut order: Vec<usize> = (0..self.items.len()).collect();
+        let Some((name, compare, group)) = sort_index.checked_sub(1).map(|i| &self.sorts[i]) else {
+            return (order, None, None);
+        };
+        let groups: Option<Vec<String>> =
+            group.as_ref().map(|g| self.items.iter().map(g).collect());
+        order.sort_by(|&a, &b| {
+            let by_group = match &groups {
+                Some(groups) => groups[a].cmp(&groups[b]),
+                None => Ordering::Equal,
+            };
+            by_group.then_with(|| compare(&self.items[a], &self.items[b]))
+        });
+        (order, Some(name.to_string()), groups)
+    }
+
+    /// Let user pick items. Returns `None` if cancelled.
+    pub fn prompt(mut self) -> io::Result<Option<Vec<T>>> {
+        let labels = self.items.iter().map(ToString::to_string).collect();
+        let names = self.items.iter().map(&mut self.formatter).collect();
+        let (_, rows) = terminal::size()?;
+        let page_size = usize::from(rows).saturating_sub(5).clamp(1, MAX_PAGE_SIZE);
+        let mut state = State::new(labels, names, page_size, self.keys);
+        state.selected = self.selected.clone();
+        state.can_preview = self.preview.is_some();
+        state.has_details = self.details.is_some();
+        state.can_export = self.export;
+        if let Some(f) = self.fields.as_mut() {
+            state.fields = self.items.iter().map(f).collect();
+            state.update_visible();
+        }
+        let mut sort_index = self.sort_index;
+        if sort_index > 0 {
+            let (order, sorted_by, groups) = self.get_order(sort_index);
+            state.set_order(order, sorted_by, groups);
+        }
+        let mut out = io::stderr();
+        let guard = RawMode::enable()?;
+        let mut drawn = 0;
+        let confirmed = loop {
+            let width = usize::from(terminal::size()?.0);
+            let status = match (state.highlighted(), self.status.as_mut()) {
+                (Some(i), Some(f)) => f(&self.items[i]),
+                _ => None,
+            };
+            let lines = state.render(self.message, status.as_deref(), width);
+            if drawn > 0 {
+                clear_lines(&mut out, drawn)?;
+            }
+            write!(out, "{}", lines.join("\r\n"))?;
+            out.flush()?;
+            drawn = lines.len();
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind == KeyEventKind::Release {
+                continue;
+            }
+            match state.handle_key(key) {
+                Action::Continue => {}
+                Action::Preview => {
+                    let (Some(i), Some(preview)) = (state.highlighted(), self.preview.as_mut())
+                    else {
+                        continue;
+                    };
+                    let title = (self.formatter)(&self.items[i]);
+                    viewer::show(&title, &preview(&self.items[i]))?;
+                }
+                Action::Details => {
+                    let (Some(i), Some(details)) = (state.highlighted(), self.details.as_mut())
+                    else {
+                        continue;
+                    };
+                    let title = (self.formatter)(&self.items[i]);
+                    viewer::show(&title, &details(&self.items[i]))?;
+                }
+                Action::Export(path) => {
+                    let names = state.selected_names();
+                    state.notice = Some(match selection::write(Path::new(&path), &names) {
+                        Ok(()) => {
+                            let msg = format!("(wrote {} names to {path})", names.len());
+                            style(msg).dim().to_string()
+                        }
+                        Err(e) => style(format!("({e})")).red().to_string(),
+                    });
+                }
+                Action::Sort if !self.sorts.is_empty() => {
+                    sort_index = (sort_index + 1) % (self.sorts.len() + 1);
+                    let (order, sorted_by, groups) = self.get_order(sort_index);
+                    state.set_order(order, sorted_by, groups);
+                }
+                Action::Sort => {}
+                Action::Help => {
+                    let mut lines = state.help_keys();
+                    if !self.legend.is_empty() {
+                        lines.push(String::new());
+                        lines.push("Signs".into());
+                        for (sign, meaning) in &self.legend {
+                            let pad = 16usize.saturating_sub(console::measure_text_width(sign));
+                            lines.push(format!("  {sign}{:pad$}{meaning}", ""));
+                        }
+                    }
+                    viewer::show("Help", &lines)?;
+                }
+                Action::Confirm => break true,
+                Action::Cancel => break false,
+            }
+        };
+        clear_lines(&mut out, drawn)?;
+        out.flush()?;
+        drop(guard);
+        let prefix = style(">").green();
+        if !confirmed {
+            eprintln!("{prefix} {} {}", self.message, style("<canceled>").yellow());
+            return Ok(None);
+        }
+        let mut picked = Vec::new();
+        for (item, selected) in self.items.into_iter().zip(state.selected) {
+            if selected {
+                picked.push(item);
+            }
+        }
+        let answer: Vec<String> = picked.iter().map(&mut self.formatter).collect();
+        eprintln!(
+            "{prefix} {} {}",
+            self.message,
+            style(answer.join(", ")).cyan()
+        );
+        Ok(Some(picked))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(state: &mut State, code: KeyCode) -> Action {
+        state.handle_key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    fn new_state() -> State {
+        let labels = ["feat1", "feat2", "fix/bug", "old"];
+        let labels: Vec<String> = labels.iter().map(|s| s.to_string()).collect();
+        State::new(labels.clone(), labels, 2, KeyMap::default())
+    }
+
+    #[test]
+    fn moves_and_scrolls() {
+        let mut state = new_state();
+        press(&mut state, KeyCode::Down);
+        press(&mut state, KeyCode::Down);
+        assert_eq!((state.cursor, state.offset), (2, 1));
+        press(&mut state, KeyCode::End);
+        assert_eq!((state.cursor, state.offset), (3, 2));
+        press(&mut state, KeyCode::Home);
+        assert_eq!((state.cursor, state.offset), (0, 0));
+        press(&mut state, KeyCode::Up);
+        assert_eq!(state.cursor, 0);
+    }
+
+    #[test]
+    fn moves_with_vim_keys() {
+        let mut state = new_state();
+        state.page_size = 4;
+        press(&mut state, KeyCode::Char('j'));
+        assert_eq!(state.cursor, 1);
+        press(&mut state, KeyCode::Char('G'));
+        assert_eq!(state.cursor, 3);
+        press(&mut state, KeyCode::Char('k'));
+        assert_eq!(state.cursor, 2);
+        state.handle_key(KeyEvent::new(KeyCode::Char('u'), KeyModifiers::CONTROL));
+        assert_eq!(state.cursor, 0);
+        state.handle_key(KeyEvent::new(KeyCode::Char('d'), KeyModifiers::CONTROL));
+        assert_eq!(state.cursor, 2);
+        press(&mut state, KeyCode::Char('g'));
+        assert_eq!(state.cursor, 0);
+        // While searching, they are typed into the filter
+        press(&mut state, KeyCode::Char('/'));
+        press(&mut state, KeyCode::Char('j'));
+        assert_eq!(state.filter, "j");
+    }
+
+    #[test]
+    fn searches_and_selects() {
+        let mut state = new_state();
+        press(&mut state, KeyCode::Char('/'));
+        for c in "FEAT".chars() {
+            press(&mut state, KeyCode::Char(c));
+        }
+        assert_eq!(state.visible, [0, 1]);
+        press(&mut state, KeyCode::Enter);
+        assert!(!state.searching);
+        press(&mut state, KeyCode::Right);
+        assert_eq!(state.selected, [true, true, false, false]);
+        press(&mut state, KeyCode::Char(' '));
+        assert_eq!(state.selected, [false, true, false, false]);
+        assert_eq!(press(&mut state, KeyCode::Char('p')), Action::Preview);
+        assert_eq!(press(&mut state, KeyCode::Tab), Action::Details);
+        press(&mut state, KeyCode::Char('w'));
+        for c in "r.txt".chars() {
+            press(&mut state, KeyCode::Char(c));
+        }
+        let export = press(&mut state, KeyCode::Enter);
+        assert_eq!(export, Action::Export("r.txt".into()));
+        assert_eq!(state.selected_names(), ["feat2"]);
+        // First Esc clears the filter, second one cancels
+        assert_eq!(press(&mut state, KeyCode::Esc), Action::Continue);
+        assert_eq!(state.visible.len(), 4);
+        assert_eq!(press(&mut state, KeyCode::Esc), Action::Cancel);
+        assert_eq!(press(&mut state, KeyCode::Enter), Action::Confirm);
+    }
+
+    #[test]
+    fn starts_with_chosen_order() {
+        let picker = Picker::new("Pick", vec![3, 1, 2])
+            .with_sort("ascending", |a: &i32, b: &i32| a.cmp(b))
+            .with_initial_sort("ascending");
+        assert_eq!(picker.sort_index, 1);
+        let (order, sorted_by, groups) = picker.get_order(1);
+        assert_eq!(order, [1, 2, 0]);
+        assert_eq!(sorted_by.as_deref(), Some("ascending"));
+        assert_eq!(groups, None);
+        assert_eq!(picker.get_order(0).0, [0, 1, 2]);
+    }
+
+    #[test]
+    fn filters_by_fields() {
+        let mut state = new_state();
+        state.fields = [10, 20, 30, 40]
+            .iter()
+            .map(|&age| vec![("age", Value::Age(age * 24 * 3600))])
+            .collect();
+        press(&mut state, KeyCode::Char('/'));
+        for c in "age>2w f".chars() {
+            press(&mut state, KeyCode::Char(c));
+        }
+        assert_eq!(state.visible, [1, 2]);
+        assert_eq!(state.filter_error, None);
+        for c in " by:me".chars() {
+            press(&mut state, KeyCode::Char(c));
+        }
+        assert_eq!(state.visible, [1, 2]);
+        assert_eq!(
+            state.filter_error.as_deref(),
+            Some("unknown field \"by\", expected age")
+        );
+    }
+
+    #[test]
+    fn selects_in_bulk() {
+        let mut state = new_state();
+        press(&mut state, KeyCode::Char('+'));
+        for c in "f*".chars() {
+            press(&mut state, KeyCode::Char(c));
+        }
+        // Keys are typed into the pattern, rather than running actions
+        assert_eq!(state.selected, [false; 4]);
+        press(&mut state, KeyCode::Enter);
+        assert_eq!(state.selected, [true, true, true, false]);
+        press(&mut state, KeyCode::Char('i'));
+        assert_eq!(state.selected, [false, false, false, true]);
+        press(&mut state, KeyCode::Char('/'));
+        press(&mut state, KeyCode::Char('t'));
+        press(&mut state, KeyCode::Enter);
+        // Only the visible ones
+        press(&mut state, KeyCode::Char('a'));
+        assert_eq!(state.selected, [true, true, false, true]);
+    }
+
+    #[test]
+    fn uses_remapped_keys() {
+        let mut state = new_state();
+        state.keys.toggle = "x".parse().unwrap();
+        state.keys.confirm = "ctrl-s".parse().unwrap();
+        assert_eq!(press(&mut state, KeyCode::Char(' ')), Action::Continue);
+        assert_eq!(state.selected, [false; 4]);
+        press(&mut state, KeyCode::Char('x'));
+        assert_eq!(state.selected, [true, false, false, false]);
+        assert_eq!(press(&mut state, KeyCode::Enter), Action::Continue);
+        let key = KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL);
+        assert_eq!(state.handle_key(key), Action::Confirm);
+        assert_eq!(press(&mut state, KeyCode::Char('s')), Action::Sort);
+    }
+
+    #[test]
+    fn keeps_highlight_when_sorting() {
+        let mut state = new_state();
+        press(&mut state, KeyCode::Down);
+        assert_eq!(press(&mut state, KeyCode::Char('s')), Action::Sort);
+        state.set_order(vec![3, 2, 1, 0], Some("reversed".into()), None);
+        assert_eq!(state.visible, [3, 2, 1, 0]);
+        assert_eq!(state.highlighted(), Some(1));
+        assert_eq!(state.cursor, 2);
+    }
+
+    #[test]
+    fn lists_remapped_keys_in_help() {
+        let mut state = new_state();
+        state.keys.sort = "ctrl-o".parse().unwrap();
+        assert_eq!(press(&mut state, KeyCode::Char('?')), Action::Help);
+        let help = state.help_keys();
+        assert!(help
+            .iter()
+            .any(|l| l == "  ctrl-o          change the order"));
+    }
+
+    #[test]
+    fn renders_rows() {
+        let mut state = new_state();
+        press(&mut state, KeyCode::Down);
+        press(&mut state, KeyCode::Char(' '));
+        let lines = state.render("Pick", Some("status"), 80);
+        let plain: Vec<String> = lines
+            .iter()
+            .map(|l| console::strip_ansi_codes(l).into_owned())
+            .collect();
+        assert_eq!(plain[0], "? Pick");
+        assert_eq!(plain[1], "  [ ] feat1");
+        assert_eq!(plain[2], "> [x] feat2");
+        assert_eq!(plain[3], "  status");
+    }
+
+    #[test]
+    fn selects_folders_in_tree() {
+        let names = [
+            "feature/pay/retry",
+            "fix",
+            "feature/login",
+            "feature/pay/undo",
+        ];
+        let names: Vec<String> = names.iter().map(|s| s.to_string()).collect();
+        let mut state = State::new(names.clone(), names, 10, KeyMap::default());
+        press(&mut state, KeyCode::Down);
+        press(&mut state, KeyCode::Char('t'));
+        // Still on "fix", after its folder and the whole subtree
+        assert_eq!(state.highlighted(), Some(1));
+        let lines = state.render("Pick", None, 80);
+        let plain: Vec<String> = lines
+            .iter()
+            .map(|l| console::strip_ansi_codes(l).into_owned())
+            .collect();
+        assert_eq!(
+            plain[1..7],
+            [
+                "  [ ] ▾ feature/ (3)",
+                "  [ ]   ▾ pay/ (2)",
+                "  [ ]     feature/pay/retry",
+                "  [ ]     feature/pay/undo",
+                "  [ ]   feature/login",
+                "> [ ] fix",
+            ]
+        );
+        press(&mut state, KeyCode::Home);
+        press(&mut state, KeyCode::Down);
+        press(&mut state, KeyCode::Char(' '));
+        assert_eq!(state.selected, [true, false, false, true]);
+        press(&mut state, KeyCode::Up);
+        assert_eq!(
+            state.render_row(&state.rows[0]),
+            format!(
+                "[-] ▾ {} {}",
+                style("feature/").bold(),
+                style("(3, 2 selected)").dim()
+            )
+        );
+        press(&mut state, KeyCode::Char(' '));
+        assert_eq!(state.selected, [true, false, true, true]);
+        press(&mut state, KeyCode::Char(' '));
+        assert_eq!(state.selected, [false; 4]);
+        // Folding the folder of an item highlights the folder
+        press(&mut state, KeyCode::Down);
+        press(&mut state, KeyCode::Down);
+        press(&mut state, KeyCode::Char('o'));
+        assert_eq!(state.rows.len(), 4);
+        assert_eq!(state.cursor, 1);
+        assert_eq!(press(&mut state, KeyCode::Char('p')), Action::Continue);
+        press(&mut state, KeyCode::Char('o'));
+        assert_eq!(state.rows.len(), 6);
+        press(&mut state, KeyCode::Char('t'));
+        assert_eq!(state.rows.len(), 4);
+    }
+
+    #[test]
+    fn renders_groups() {
+        let mut state = new_state();
+        state.page_size = 4;
+        let groups = ["a", "b", "b", "b"].map(String::from).to_vec();
+        state.set_order(vec![0, 1, 2, 3], Some("grouped".into()), Some(groups));
+        press(&mut state, KeyCode::Down);
+        press(&mut state, KeyCode::Char(' '));
+        let lines = state.render("Pick", None, 80);
+        let plain: Vec<String> = lines
+            .iter()
+            .map(|l| console::strip_ansi_codes(l).into_owned())
+            .collect();
+        assert_eq!(
+            plain[1..5],
+            [
+                "  a (1)",
+                "  [ ] feat1",
+                "  b (3, 1 selected)",
+                "> [x] feat2"
+            ]
+        );
+        // The headers take lines, so fewer rows fit
+        press(&mut state, KeyCode::Down);
+        assert_eq!(state.offset, 1);
+        let lines = state.render("Pick", None, 80);
+        let plain = console::strip_ansi_codes(&lines[1]).into_owned();
+        assert_eq!(plain, "  b (3, 1 selected)");
+    }
+}
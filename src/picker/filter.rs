@@ -0,0 +1,192 @@
+//! Filter typed in the picker's search line, like `feat age>6m author:alice`.
+//!
+//! Words are matched against the items' labels, and words of the form `field:text`,
+//! `field>duration` or `field<duration` against the fields the items provide. An item is
+//! shown if all words match it.
+
+use git_del_branches::age::HumanDuration;
+
+/// Value of an item's field, for the filter to match.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    /// Matched by `field:text`, if it contains the text.
+    Text(String),
+    /// Age in seconds, matched by `field>duration` and `field<duration`.
+    Age(i64),
+    /// Age which is not known, matched by no duration.
+    UnknownAge,
+    /// Matched by `field:flag`, if it has the flag, like `is:merged`.
+    Flags(Vec<&'static str>),
+}
+
+/// Named values of an item.
+pub type Fields = Vec<(&'static str, Value)>;
+
+#[derive(Debug, PartialEq, Eq)]
+enum Term {
+    Text(String),
+    Field {
+        name: String,
+        op: char,
+        value: String,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Filter {
+    terms: Vec<Term>,
+}
+
+impl Filter {
+    pub fn parse(input: &str) -> Self {
+        let terms = input
+            .split_whitespace()
+            .map(|word| {
+                let word = word.to_lowercase();
+                match word.find([':', '>', '<']) {
+                    Some(pos)
+                        if pos > 0
+                            && pos + 1 < word.len()
+                            && word[..pos].chars().all(|c| c.is_ascii_alphabetic()) =>
+                    {
+                        Term::Field {
+                            name: word[..pos].to_string(),
+                            op: word[pos..].chars().next().unwrap_or(':'),
+                            value: word[pos + 1..].to_string(),
+                        }
+                    }
+                    _ => Term::Text(word),
+                }
+            })
+            .collect();
+        Self { terms }
+    }
+
+    /// Tell whether an item matches all the terms. A term which cannot be applied, like one
+    /// with an unknown field, is reported in the error, and the item then only has to match
+    /// the other terms.
+    pub fn matches(&self, label: &str, fields: &Fields) -> (bool, Option<String>) {
+        let label = label.to_lowercase();
+        let mut error = None;
+        // Not stopping at the first mismatch, to find the errors of the later terms
+        let matched = self.terms.iter().fold(true, |matched, term| {
+            let term_matched = match term {
+                Term::Text(text) => label.contains(text.as_str()),
+                Term::Field { name, op, value } => match match_field(fields, name, *op, value) {
+                    Ok(m) => m,
+                    Err(e) => {
+                        error.get_or_insert(e);
+                        true
+                    }
+                },
+            };
+            matched && term_matched
+        });
+        (matched, error)
+    }
+}
+
+fn match_field(fields: &Fields, name: &str, op: char, value: &str) -> Result<bool, String> {
+    let Some((_, field)) = fields.iter().find(|(n, _)| *n == name) else {
+        let mut names: Vec<&str> = fields.iter().map(|(n, _)| *n).collect();
+        if names.is_empty() {
+            return Err(format!("unknown field \"{name}\""));
+        }
+        names.sort_unstable();
+        return Err(format!(
+            "unknown field \"{name}\", expected {}",
+            names.join(", ")
+        ));
+    };
+    match (field, op) {
+        (Value::Text(text), ':') => Ok(text.to_lowercase().contains(value)),
+        (Value::Flags(flags), ':') => Ok(flags.contains(&value)),
+        (Value::Age(_) | Value::UnknownAge, '>' | '<') => {
+            let duration: HumanDuration = value
+                .parse()
+                .map_err(|_| format!("invalid duration \"{value}\", like \"6m\""))?;
+            let Value::Age(age) = field else {
+                return Ok(false);
+            };
+            Ok(if op == '>' {
+                *age > duration.as_secs()
+            } else {
+                *age < duration.as_secs()
+            })
+        }
+        (Value::Age(_) | Value::UnknownAge, _) => {
+            Err(format!("use {name}>duration or {name}<duration"))
+        }
+        _ => Err(format!("use {name}:text")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: i64 = 24 * 3600;
+
+    fn fields() -> Fields {
+        vec![
+            (
+                "author",
+                Value::Text("Alice Doe <alice@example.com>".into()),
+            ),
+            ("age", Value::Age(200 * DAY)),
+            ("is", Value::Flags(vec!["merged", "gone"])),
+            ("created", Value::UnknownAge),
+        ]
+    }
+
+    #[test]
+    fn parses_terms() {
+        let filter = Filter::parse(" Feat  age>6m a:b: :x");
+        assert_eq!(
+            filter.terms,
+            [
+                Term::Text("feat".into()),
+                Term::Field {
+                    name: "age".into(),
+                    op: '>',
+                    value: "6m".into()
+                },
+                Term::Field {
+                    name: "a".into(),
+                    op: ':',
+                    value: "b:".into()
+                },
+                Term::Text(":x".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn matches_fields() {
+        let fields = fields();
+        let matches = |input: &str| Filter::parse(input).matches("feature/login", &fields);
+        assert_eq!(matches(""), (true, None));
+        assert_eq!(matches("login FEAT"), (true, None));
+        assert_eq!(matches("login fix"), (false, None));
+        assert_eq!(matches("author:alice"), (true, None));
+        assert_eq!(matches("author:bob"), (false, None));
+        assert_eq!(matches("age>6m is:gone"), (true, None));
+        assert_eq!(matches("age<6m"), (false, None));
+        assert_eq!(matches("is:stashed"), (false, None));
+        assert_eq!(matches("created>1d"), (false, None));
+        assert!(matches("created>x").1.is_some());
+        let (matched, error) = matches("team:x login");
+        assert!(matched);
+        assert_eq!(
+            error.as_deref(),
+            Some("unknown field \"team\", expected age, author, created, is")
+        );
+        let (_, error) = matches("age>soon");
+        assert_eq!(
+            error.as_deref(),
+            Some("invalid duration \"soon\", like \"6m\"")
+        );
+        let (_, error) = matches("age:6m");
+        assert_eq!(error.as_deref(), Some("use age>duration or age<duration"));
+    }
+}
@@ -0,0 +1,148 @@
+//! Keys of the picker actions, which user can remap in Git config.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use eyre::{eyre, Report};
+
+/// A key with its modifiers, written like "p", "space" or "ctrl-t".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl Key {
+    const fn new(code: KeyCode) -> Self {
+        Self {
+            code,
+            modifiers: KeyModifiers::NONE,
+        }
+    }
+
+    pub fn matches(&self, event: &KeyEvent) -> bool {
+        let mut modifiers = event.modifiers;
+        // Shift is already in the character, like "G"
+        if matches!(event.code, KeyCode::Char(_)) {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        event.code == self.code && modifiers == self.modifiers
+    }
+}
+
+const NAMED_KEYS: &[(&str, KeyCode)] = &[
+    ("space", KeyCode::Char(' ')),
+    ("enter", KeyCode::Enter),
+    ("tab", KeyCode::Tab),
+    ("esc", KeyCode::Esc),
+    ("backspace", KeyCode::Backspace),
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("pageup", KeyCode::PageUp),
+    ("pagedown", KeyCode::PageDown),
+];
+
+impl FromStr for Key {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Report> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = s.trim();
+        loop {
+            if let Some(r) = rest.strip_prefix("ctrl-") {
+                modifiers |= KeyModifiers::CONTROL;
+                rest = r;
+            } else if let Some(r) = rest.strip_prefix("alt-") {
+                modifiers |= KeyModifiers::ALT;
+                rest = r;
+            } else {
+                break;
+            }
+        }
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => {
+                let name = rest.to_lowercase();
+                let function = name
+                    .strip_prefix('f')
+                    .and_then(|n| n.parse::<u8>().ok())
+                    .filter(|n| (1..=12).contains(n));
+                match NAMED_KEYS.iter().find(|(n, _)| *n == name) {
+                    Some((_, code)) => *code,
+                    None => KeyCode::F(function.ok_or_else(|| {
+                        eyre!("Unknown key \"{s}\", expected a character or a name like \"space\" or \"ctrl-t\"")
+                    })?),
+                }
+            }
+        };
+        Ok(Self { code, modifiers })
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            f.write_str("ctrl-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            f.write_str("alt-")?;
+        }
+        if let Some((name, _)) = NAMED_KEYS.iter().find(|(_, c)| *c == self.code) {
+            return f.write_str(name);
+        }
+        match self.code {
+            KeyCode::Char(c) => write!(f, "{c}"),
+            KeyCode::F(n) => write!(f, "f{n}"),
+            code => write!(f, "{code:?}"),
+        }
+    }
+}
+
+/// Keys of the actions which can be remapped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyMap {
+    pub toggle: Key,
+    pub confirm: Key,
+    pub search: Key,
+    pub sort: Key,
+    pub preview: Key,
+    pub details: Key,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        Self {
+            toggle: Key::new(KeyCode::Char(' ')),
+            confirm: Key::new(KeyCode::Enter),
+            search: Key::new(KeyCode::Char('/')),
+            sort: Key::new(KeyCode::Char('s')),
+            preview: Key::new(KeyCode::Char('p')),
+            details: Key::new(KeyCode::Tab),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_keys() {
+        let key: Key = "ctrl-t".parse().unwrap();
+        assert!(key.matches(&KeyEvent::new(KeyCode::Char('t'), KeyModifiers::CONTROL)));
+        assert!(!key.matches(&KeyEvent::new(KeyCode::Char('t'), KeyModifiers::NONE)));
+        assert_eq!(key.to_string(), "ctrl-t");
+        let key: Key = "G".parse().unwrap();
+        assert!(key.matches(&KeyEvent::new(KeyCode::Char('G'), KeyModifiers::SHIFT)));
+        assert_eq!("Space".parse::<Key>().unwrap(), KeyMap::default().toggle);
+        assert_eq!("f2".parse::<Key>().unwrap().to_string(), "f2");
+        assert!("f13".parse::<Key>().is_err());
+        assert!("spacebar".parse::<Key>().is_err());
+    }
+}
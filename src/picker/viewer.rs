@@ -0,0 +1,75 @@
+//! Full-screen, scrollable view of a long text, like a pager.
+
+use std::io::{self, Write};
+
+use console::{style, truncate_str};
+use crossterm::cursor::MoveTo;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+
+/// Color a line of `git log -p` output.
+fn color_line(line: &str) -> String {
+    if line.starts_with("+++") || line.starts_with("---") || line.starts_with("diff ") {
+        style(line).bold().to_string()
+    } else if line.starts_with('+') {
+        style(line).green().to_string()
+    } else if line.starts_with('-') {
+        style(line).red().to_string()
+    } else if line.starts_with("@@") {
+        style(line).cyan().to_string()
+    } else if line.starts_with("commit ") {
+        style(line).yellow().to_string()
+    } else {
+        line.to_string()
+    }
+}
+
+/// Show the lines until user closes the view. The terminal must be in raw mode.
+pub fn show(title: &str, lines: &[String]) -> io::Result<()> {
+    let mut out = io::stderr();
+    execute!(out, EnterAlternateScreen)?;
+    let mut offset = 0;
+    loop {
+        let (width, height) = terminal::size()?;
+        let body = usize::from(height.saturating_sub(1)).max(1);
+        let max_offset = lines.len().saturating_sub(body);
+        offset = offset.min(max_offset);
+        queue!(out, Clear(ClearType::All))?;
+        for (row, line) in lines.iter().skip(offset).take(body).enumerate() {
+            queue!(out, MoveTo(0, row as u16))?;
+            write!(
+                out,
+                "{}",
+                truncate_str(&color_line(line), width.into(), "…")
+            )?;
+        }
+        let last = lines.len().min(offset + body);
+        let status = format!(
+            " {title}  {}-{last}/{}  [↑↓ to scroll, space/b for pages, q to close]",
+            offset + 1,
+            lines.len()
+        );
+        queue!(out, MoveTo(0, height.saturating_sub(1)))?;
+        let status = truncate_str(&status, width.into(), "…").into_owned();
+        write!(out, "{}", style(status).reverse())?;
+        out.flush()?;
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind == KeyEventKind::Release {
+            continue;
+        }
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => offset = offset.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') | KeyCode::Enter => offset += 1,
+            KeyCode::PageUp | KeyCode::Char('b') => offset = offset.saturating_sub(body),
+            KeyCode::PageDown | KeyCode::Char(' ') => offset += body,
+            KeyCode::Home | KeyCode::Char('g') => offset = 0,
+            KeyCode::End | KeyCode::Char('G') => offset = max_offset,
+            KeyCode::Esc | KeyCode::Char('q') => break,
+            _ => {}
+        }
+    }
+    execute!(out, LeaveAlternateScreen)
+}
@@ -0,0 +1,240 @@
+//! Declarative rules to pick the branches to clean up, read from a TOML file like:
+//!
+//! ```toml
+//! # Never touch these, whatever the rules say
+//! exclude = ["release/*"]
+//!
+//! # A branch is picked if it matches any rule. All conditions of a rule must hold.
+//! [[rule]]
+//! merged-into = "main"
+//! older-than = "60d"
+//!
+//! [[rule]]
+//! pattern = "tmp/*"
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use eyre::{Context, Result};
+use git2::Repository;
+use serde::Deserialize;
+
+use crate::age::{is_older_than, HumanDuration};
+use crate::branch::BranchInfo;
+use crate::merged::{is_contained_in, resolve_commit};
+use crate::pattern;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Rule {
+    /// Glob pattern the branch name must match.
+    pub pattern: Option<String>,
+    /// Revision which the branch must be fully merged into.
+    pub merged_into: Option<String>,
+    /// The branch tip must be older than this.
+    pub older_than: Option<HumanDuration>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Policy {
+    /// Glob patterns of branches which are never picked.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    #[serde(default, rename = "rule")]
+    pub rules: Vec<Rule>,
+    /// Whether to delete the upstream branches too.
+    #[serde(default)]
+    pub delete_upstream: bool,
+}
+
+/// What a policy decides for a branch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision<'a> {
+    /// It matches this pattern of `exclude`.
+    Excluded(&'a str),
+    /// It is picked by the rule of this index, the first one which it matches.
+    Picked(usize),
+    /// It matches no rule.
+    Unmatched,
+}
+
+impl Rule {
+    pub fn matches(&self, repo: &Repository, branch: &BranchInfo, now: i64) -> Result<bool> {
+        if let Some(p) = &self.pattern {
+            if !pattern::matches(p, &branch.name) {
+                return Ok(false);
+            }
+        }
+        if let Some(d) = self.older_than {
+            if !is_older_than(branch.time, d, now) {
+                return Ok(false);
+            }
+        }
+        if let Some(base) = &self.merged_into {
+            let base_id = resolve_commit(repo, base)
+                .wrap_err_with(|| format!("Cannot find \"{base}\" of merged-into rule"))?;
+            if !is_contained_in(repo, branch.tip, base_id)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Describe the conditions, like "tmp/*, merged into main, older than 60d".
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(p) = &self.pattern {
+            parts.push(p.clone());
+        }
+        if let Some(base) = &self.merged_into {
+            parts.push(format!("merged into {base}"));
+        }
+        if let Some(d) = self.older_than {
+            parts.push(format!("older than {d}"));
+        }
+        if parts.is_empty() {
+            write!(f, "all branches")
+        } else {
+            write!(f, "{}", parts.join(", "))
+        }
+    }
+}
+
+impl Policy {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .wrap_err_with(|| format!("Cannot read policy file {}", path.display()))?;
+        toml::from_str(&content).wrap_err_with(|| format!("Invalid policy file {}", path.display()))
+    }
+
+    pub fn matches(&self, repo: &Repository, branch: &BranchInfo, now: i64) -> Result<bool> {
+        Ok(matches!(
+            self.decide(repo, branch, now)?,
+            Decision::Picked(_)
+        ))
+    }
+
+    /// Tell whether the branch is picked, and by what.
+    pub fn decide(&self, repo: &Repository, branch: &BranchInfo, now: i64) -> Result<Decision<'_>> {
+        if let Some(p) = self
+            .exclude
+            .iter()
+            .find(|p| pattern::matches(p, &branch.name))
+        {
+            return Ok(Decision::Excluded(p));
+        }
+        for (i, rule) in self.rules.iter().enumerate() {
+            if rule.matches(repo, branch, now)? {
+                return Ok(Decision::Picked(i));
+            }
+        }
+        Ok(Decision::Unmatched)
+    }
+
+    /// Keep only the branches picked by the policy.
+    pub fn select(
+        &self,
+        repo: &Repository,
+        branches: Vec<BranchInfo>,
+        now: i64,
+    ) -> Result<Vec<BranchInfo>> {
+        let mut selected = Vec::new();
+        for b in branches {
+            if self.matches(repo, &b, now)? {
+                selected.push(b);
+            }
+        }
+        Ok(selected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::branch::list_local_branches;
+    use crate::testutil::{commit, commit_at, init_repo};
+
+    const DAY: i64 = 86400;
+
+    #[test]
+    fn parses_policy() {
+        let policy: Policy = toml::from_str(
+            r#"
+            exclude = ["release/*"]
+            delete-upstream = true
+            [[rule]]
+            merged-into = "main"
+            older-than = "60d"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(policy.exclude, ["release/*"]);
+        assert!(policy.delete_upstream);
+        assert_eq!(policy.rules[0].merged_into.as_deref(), Some("main"));
+        assert_eq!(policy.rules[0].older_than.unwrap().as_secs(), 60 * DAY);
+        assert!(toml::from_str::<Policy>("older = 1").is_err());
+        assert!(toml::from_str::<Policy>("[[rule]]\nolder-than = \"soon\"").is_err());
+    }
+
+    #[test]
+    fn selects_branches() {
+        let (_dir, repo) = init_repo();
+        let now = 1_000 * DAY;
+        let base = commit_at(&repo, "refs/heads/main", None, now - 200 * DAY);
+        let old_merged = commit_at(&repo, "refs/heads/old-merged", Some(base), now - 100 * DAY);
+        commit(&repo, "refs/heads/main", Some(old_merged));
+        commit_at(&repo, "refs/heads/release/1", Some(base), now - 90 * DAY);
+        commit_at(&repo, "refs/heads/new-merged", Some(old_merged), now - DAY);
+        commit(
+            &repo,
+            "refs/heads/main",
+            Some(repo.refname_to_id("refs/heads/new-merged").unwrap()),
+        );
+        commit_at(
+            &repo,
+            "refs/heads/old-unmerged",
+            Some(base),
+            now - 100 * DAY,
+        );
+        commit_at(&repo, "refs/heads/tmp/x", Some(base), now);
+        let policy = Policy {
+            exclude: vec!["release/*".into()],
+            rules: vec![
+                Rule {
+                    merged_into: Some("main".into()),
+                    older_than: Some("60d".parse().unwrap()),
+                    ..Rule::default()
+                },
+                Rule {
+                    pattern: Some("*/*".into()),
+                    ..Rule::default()
+                },
+            ],
+            delete_upstream: false,
+        };
+        let branches = list_local_branches(&repo).unwrap();
+        let names: Vec<_> = policy
+            .select(&repo, branches, now)
+            .unwrap()
+            .into_iter()
+            .map(|b| b.name)
+            .collect();
+        assert_eq!(names, ["old-merged", "tmp/x"]);
+        let release = list_local_branches(&repo)
+            .unwrap()
+            .into_iter()
+            .find(|b| b.name == "release/1")
+            .unwrap();
+        let decision = policy.decide(&repo, &release, now).unwrap();
+        assert_eq!(decision, Decision::Excluded("release/*"));
+        assert_eq!(
+            policy.rules[0].to_string(),
+            "merged into main, older than 2m"
+        );
+    }
+}
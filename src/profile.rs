@@ -0,0 +1,283 @@
+//! Saved filter profiles: named criteria for the branches to offer, and how to sort them,
+//! defined in Git config like:
+//!
+//! ```text
+//! [del-branches "profile.mine-stale"]
+//!     mine = true
+//!     olderThan = 3m
+//!     sort = oldest
+//! [del-branches "profile.weekly"]
+//!     pattern = feature/*
+//!     pattern = fix/*
+//!     merged = true
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+use eyre::{eyre, Report, Result, WrapErr};
+use git2::Config;
+
+use crate::age::{is_older_than, HumanDuration};
+use crate::branch::{is_authored_by, BranchInfo};
+use crate::config::PROFILE_PREFIX;
+use crate::pattern;
+
+/// Order to show the branches in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    Oldest,
+    Newest,
+    /// Grouped by author, oldest first in each group.
+    Author,
+}
+
+impl fmt::Display for SortOrder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Self::Oldest => "oldest",
+            Self::Newest => "newest",
+            Self::Author => "author",
+        };
+        write!(f, "{s}")
+    }
+}
+
+impl FromStr for SortOrder {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Report> {
+        match s {
+            "oldest" => Ok(Self::Oldest),
+            "newest" => Ok(Self::Newest),
+            "author" => Ok(Self::Author),
+            _ => Err(eyre!(
+                "Unknown sort order \"{s}\", expected oldest, newest or author"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+    pub name: String,
+    /// Globs which the branch name must match one of. Any name matches if empty.
+    pub patterns: Vec<String>,
+    /// The branch tip must be older than this.
+    pub older_than: Option<HumanDuration>,
+    /// Only the branches merged into the base.
+    pub merged: bool,
+    /// Only the branches whose last commit is by user.email.
+    pub mine: bool,
+    /// Text which the name or the email of the tip's author must contain, regardless of case.
+    pub author: Option<String>,
+    pub sort: Option<SortOrder>,
+}
+
+impl Profile {
+    /// Whether the branch meets the criteria, except `merged`, which needs the base to be
+    /// checked against. `email` is the user's one, required if `mine` is set.
+    pub fn matches(&self, branch: &BranchInfo, email: Option<&str>, now: i64) -> bool {
+        if !self.patterns.is_empty() && !pattern::matches_any(&self.patterns, &branch.name) {
+            return false;
+        }
+        if self
+            .older_than
+            .is_some_and(|d| !is_older_than(branch.time, d, now))
+        {
+            return false;
+        }
+        if self.mine && !email.is_some_and(|e| is_authored_by(branch, e)) {
+            return false;
+        }
+        if let Some(author) = &self.author {
+            let author = author.to_lowercase();
+            let by = format!("{} <{}>", branch.author, branch.author_email).to_lowercase();
+            if !by.contains(&author) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Describe the criteria, like "feature/* or fix/*, older than 1w, merged, sorted newest".
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts = Vec::new();
+        if !self.patterns.is_empty() {
+            parts.push(self.patterns.join(" or "));
+        }
+        if let Some(d) = self.older_than {
+            parts.push(format!("older than {d}"));
+        }
+        if self.merged {
+            parts.push("merged".into());
+        }
+        if self.mine {
+            parts.push("mine".into());
+        }
+        if let Some(author) = &self.author {
+            parts.push(format!("by {author}"));
+        }
+        if let Some(sort) = self.sort {
+            parts.push(format!("sorted {sort}"));
+        }
+        if parts.is_empty() {
+            write!(f, "all branches")
+        } else {
+            write!(f, "{}", parts.join(", "))
+        }
+    }
+}
+
+/// Split a config key like "del-branches.profile.weekly.olderthan" into the profile name
+/// and the setting.
+fn split_key(key: &str) -> Option<(&str, &str)> {
+    key.strip_prefix(PROFILE_PREFIX)?.rsplit_once('.')
+}
+
+/// Names of the profiles defined in Git config, sorted.
+pub fn list_profiles(config: &Config) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    let mut entries = config.entries(Some("^del-branches\\.profile\\."))?;
+    while let Some(entry) = entries.next() {
+        if let Some((name, _)) = entry?.name().and_then(split_key) {
+            names.push(name.to_string());
+        }
+    }
+    names.sort();
+    names.dedup();
+    Ok(names)
+}
+
+/// Read a profile from Git config. Fails if it is not defined, or has an unknown or
+/// invalid setting.
+pub fn load_profile(config: &Config, name: &str) -> Result<Profile> {
+    let mut profile = Profile {
+        name: name.to_string(),
+        ..Profile::default()
+    };
+    let mut found = false;
+    let mut entries = config.entries(Some("^del-branches\\.profile\\."))?;
+    while let Some(entry) = entries.next() {
+        let entry = entry?;
+        let (Some(key), Some(value)) = (entry.name(), entry.value()) else {
+            continue;
+        };
+        let Some((_, setting)) = split_key(key).filter(|(n, _)| *n == name) else {
+            continue;
+        };
+        found = true;
+        let invalid = || format!("Invalid {key} \"{value}\"");
+        // Git lowercases the names of the settings
+        match setting {
+            "pattern" => profile.patterns.push(value.to_string()),
+            "olderthan" => profile.older_than = Some(value.parse().wrap_err_with(invalid)?),
+            "merged" => profile.merged = Config::parse_bool(value).wrap_err_with(invalid)?,
+            "mine" => profile.mine = Config::parse_bool(value).wrap_err_with(invalid)?,
+            "author" => profile.author = Some(value.to_string()),
+            "sort" => profile.sort = Some(value.parse().wrap_err_with(invalid)?),
+            _ => {
+                return Err(eyre!(
+                    "Unknown setting {key}, expected pattern, olderThan, merged, mine, author \
+                     or sort"
+                ))
+            }
+        }
+    }
+    if !found {
+        return Err(eyre!(
+            "No profile \"{name}\", define it with settings like {PROFILE_PREFIX}{name}.olderThan"
+        ));
+    }
+    Ok(profile)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::branch::list_local_branches;
+    use crate::testutil::{commit_at, init_repo};
+
+    const DAY: i64 = 24 * 3600;
+
+    #[test]
+    fn loads_profiles() {
+        let (_dir, repo) = init_repo();
+        let mut config = repo.config().unwrap();
+        config
+            .set_multivar("del-branches.profile.weekly.pattern", "^$", "feature/*")
+            .unwrap();
+        config
+            .set_multivar("del-branches.profile.weekly.pattern", "^$", "fix/*")
+            .unwrap();
+        config
+            .set_str("del-branches.profile.weekly.olderThan", "1w")
+            .unwrap();
+        config
+            .set_str("del-branches.profile.weekly.merged", "yes")
+            .unwrap();
+        config
+            .set_str("del-branches.profile.weekly.sort", "newest")
+            .unwrap();
+        config
+            .set_bool("del-branches.profile.mine.mine", true)
+            .unwrap();
+        config
+            .set_str("del-branches.profile.bad.sort", "size")
+            .unwrap();
+        let config = config.snapshot().unwrap();
+        assert_eq!(list_profiles(&config).unwrap(), ["bad", "mine", "weekly"]);
+        let profile = load_profile(&config, "weekly").unwrap();
+        assert_eq!(
+            profile,
+            Profile {
+                name: "weekly".into(),
+                patterns: vec!["feature/*".into(), "fix/*".into()],
+                older_than: Some("1w".parse().unwrap()),
+                merged: true,
+                sort: Some(SortOrder::Newest),
+                ..Profile::default()
+            }
+        );
+        assert_eq!(
+            profile.to_string(),
+            "feature/* or fix/*, older than 1w, merged, sorted newest"
+        );
+        assert!(load_profile(&config, "mine").unwrap().mine);
+        assert!(load_profile(&config, "bad").is_err());
+        assert!(load_profile(&config, "nope").is_err());
+    }
+
+    #[test]
+    fn matches_branches() {
+        let (_dir, repo) = init_repo();
+        let now = 1_700_000_000;
+        let base = commit_at(&repo, "refs/heads/main", None, now - 30 * DAY);
+        commit_at(&repo, "refs/heads/feature/new", Some(base), now - DAY);
+        let branches = list_local_branches(&repo).unwrap();
+        let find = |name| branches.iter().find(|b| b.name == name).unwrap();
+        let (old, new) = (find("main"), find("feature/new"));
+        let profile = Profile {
+            patterns: vec!["feature/*".into()],
+            ..Profile::default()
+        };
+        assert!(profile.matches(new, None, now));
+        assert!(!profile.matches(old, None, now));
+        let profile = Profile {
+            older_than: Some("1w".parse().unwrap()),
+            author: Some("TESTER@".into()),
+            ..Profile::default()
+        };
+        assert!(profile.matches(old, None, now));
+        assert!(!profile.matches(new, None, now));
+        let profile = Profile {
+            mine: true,
+            ..Profile::default()
+        };
+        assert!(profile.matches(old, Some("Tester@Example.com"), now));
+        assert!(!profile.matches(old, Some("other@example.com"), now));
+        assert!(!profile.matches(old, None, now));
+    }
+}
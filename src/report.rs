@@ -0,0 +1,419 @@
+//! Reports of the stale branches, to share with the team: in Markdown for wikis and issues,
+//! or as an HTML page. The branches are grouped by author, the one with the most first.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use eyre::eyre;
+use git2::{Oid, Repository};
+
+use crate::age::{format_age, is_older_than, HumanDuration};
+use crate::branch::{AuthorFormat, BranchInfo};
+use crate::icon::{find_icon, Icon};
+use crate::merged::{check_contained_in, PatchIndex};
+use crate::partial::is_partial_clone;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    #[default]
+    Markdown,
+    Html,
+}
+
+impl fmt::Display for ReportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Markdown => "markdown",
+            Self::Html => "html",
+        })
+    }
+}
+
+impl ReportFormat {
+    /// Extension of the files in this format.
+    pub fn extension(&self) -> &str {
+        match self {
+            Self::Markdown => "md",
+            Self::Html => "html",
+        }
+    }
+}
+
+impl FromStr for ReportFormat {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> eyre::Result<Self> {
+        match s {
+            "markdown" | "md" => Ok(Self::Markdown),
+            "html" => Ok(Self::Html),
+            _ => Err(eyre!(
+                "Unknown report format \"{s}\", expected markdown or html"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaleBranch {
+    pub name: String,
+    /// Seconds since the tip was committed.
+    pub age: i64,
+    /// Whether it is merged into the base, by any of the ways we detect. `None` if there is no
+    /// base, or if the shallow clone lacks the history to tell.
+    pub merged: Option<bool>,
+    pub upstream_gone: bool,
+    /// Glyph of its category, by the prefix of its name.
+    pub icon: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    /// Name of the repository, like "github.com/owner/repo".
+    pub repository: String,
+    pub older_than: HumanDuration,
+    /// Name of the branch which merge status is told against.
+    pub base: Option<String>,
+    /// Stale branches by author, the most first, each oldest first.
+    pub by_author: Vec<(String, Vec<StaleBranch>)>,
+}
+
+impl Report {
+    /// Gather the branches whose last commit is older than `older_than`.
+    pub fn collect(
+        repo: &Repository,
+        repository: String,
+        branches: &[BranchInfo],
+        base: Option<(&str, Oid)>,
+        older_than: HumanDuration,
+        author_format: AuthorFormat,
+        now: i64,
+    ) -> Result<Self, git2::Error> {
+        // Patches cannot be compared without the file contents, which a partial clone lacks
+        let mut index = base
+            .filter(|_| !is_partial_clone(repo))
+            .map(|(_, id)| PatchIndex::new(repo, id));
+        let mut authors: HashMap<String, Vec<StaleBranch>> = HashMap::new();
+        for branch in branches {
+            if !is_older_than(branch.time, older_than, now) {
+                continue;
+            }
+            let merged = match base {
+                Some((_, id)) => {
+                    let contained = check_contained_in(repo, branch.tip, id)?;
+                    let patched = match (contained, index.as_mut()) {
+                        (Some(true), _) | (_, None) => false,
+                        (_, Some(index)) => index.find_merge_kind(branch.tip)?.is_some(),
+                    };
+                    if patched {
+                        Some(true)
+                    } else {
+                        contained
+                    }
+                }
+                None => None,
+            };
+            authors
+                .entry(branch.format_author(author_format))
+                .or_default()
+                .push(StaleBranch {
+                    name: branch.name.clone(),
+                    age: now - branch.time,
+                    merged,
+                    upstream_gone: branch.upstream_gone,
+                    icon: None,
+                });
+        }
+        let mut by_author: Vec<(String, Vec<StaleBranch>)> = authors.into_iter().collect();
+        for (_, branches) in &mut by_author {
+            branches.sort_by(|a, b| b.age.cmp(&a.age).then_with(|| a.name.cmp(&b.name)));
+        }
+        by_author.sort_by(|a, b| b.1.len().cmp(&a.1.len()).then_with(|| a.0.cmp(&b.0)));
+        Ok(Self {
+            repository,
+            older_than,
+            base: base.map(|(name, _)| name.to_string()),
+            by_author,
+        })
+    }
+
+    /// Show the branches with the glyphs of their categories.
+    pub fn with_icons(mut self, icons: &[Icon]) -> Self {
+        for (_, branches) in &mut self.by_author {
+            for b in branches {
+                b.icon = find_icon(icons, &b.name).map(|i| i.glyph.clone());
+            }
+        }
+        self
+    }
+
+    pub fn count(&self) -> usize {
+        self.by_author.iter().map(|(_, b)| b.len()).sum()
+    }
+
+    /// One line per author, like "Alice: 12 stale branches, oldest 1y".
+    pub fn summarize(&self) -> Vec<String> {
+        self.by_author
+            .iter()
+            .map(|(author, branches)| {
+                // Sorted oldest first
+                let oldest = branches.first().map_or(0, |b| b.age);
+                format!(
+                    "{author}: {} stale branches, oldest {}",
+                    branches.len(),
+                    format_age(oldest)
+                )
+            })
+            .collect()
+    }
+
+    /// The report of each author, to send to them.
+    pub fn split(&self) -> Vec<(String, Self)> {
+        self.by_author
+            .iter()
+            .map(|(author, branches)| {
+                let report = Self {
+                    by_author: vec![(author.clone(), branches.clone())],
+                    ..self.clone()
+                };
+                (author.clone(), report)
+            })
+            .collect()
+    }
+
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Markdown => self.render_markdown(),
+            ReportFormat::Html => self.render_html(),
+        }
+    }
+
+    fn get_title(&self) -> String {
+        format!("Stale branches of {}", self.repository)
+    }
+
+    /// Sentence telling what the report has.
+    fn get_summary(&self) -> String {
+        let compared = match &self.base {
+            Some(base) => format!(", compared with {base}"),
+            None => String::new(),
+        };
+        let authors = match self.by_author.as_slice() {
+            [(author, _)] => author.clone(),
+            by_author => format!("{} authors", by_author.len()),
+        };
+        format!(
+            "{} branches of {authors} have no commits in the last {}{compared}.",
+            self.count(),
+            self.older_than
+        )
+    }
+
+    /// Headers of the columns of the tables.
+    fn get_headers(&self) -> Vec<String> {
+        let merged = match &self.base {
+            Some(base) => format!("Merged into {base}"),
+            None => "Merged".into(),
+        };
+        vec![
+            "Branch".into(),
+            "Last commit".into(),
+            merged,
+            "Upstream".into(),
+        ]
+    }
+
+    fn render_markdown(&self) -> String {
+        let escape = |s: &str| s.replace('|', "\\|");
+        let mut doc = format!("# {}\n\n{}\n", self.get_title(), self.get_summary());
+        let headers = self.get_headers();
+        for (author, branches) in &self.by_author {
+            doc.push_str(&format!("\n## {} ({})\n\n", escape(author), branches.len()));
+            doc.push_str(&format!("| {} |\n", headers.join(" | ")));
+            doc.push_str(&format!("|{}\n", "---|".repeat(headers.len())));
+            for b in branches {
+                let icon = b.icon.as_ref().map_or(String::new(), |i| format!("{i} "));
+                doc.push_str(&format!(
+                    "| {icon}`{}` | {} ago | {} | {} |\n",
+                    escape(&b.name),
+                    format_age(b.age),
+                    describe_merged(b.merged, self.base.is_some()),
+                    describe_upstream(b.upstream_gone),
+                ));
+            }
+        }
+        doc
+    }
+
+    fn render_html(&self) -> String {
+        let title = escape_html(&self.get_title());
+        let mut doc = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+             <style>body {{ font-family: sans-serif; }} table {{ border-collapse: collapse; }} \
+             th, td {{ border: 1px solid #ccc; padding: 4px 8px; text-align: left; }}</style>\n\
+             </head>\n<body>\n<h1>{title}</h1>\n<p>{}</p>\n",
+            escape_html(&self.get_summary())
+        );
+        let headers: Vec<String> = self
+            .get_headers()
+            .iter()
+            .map(|h| format!("<th>{}</th>", escape_html(h)))
+            .collect();
+        for (author, branches) in &self.by_author {
+            doc.push_str(&format!(
+                "<h2>{} ({})</h2>\n<table>\n<tr>{}</tr>\n",
+                escape_html(author),
+                branches.len(),
+                headers.concat()
+            ));
+            for b in branches {
+                let icon = b
+                    .icon
+                    .as_ref()
+                    .map_or(String::new(), |i| format!("{} ", escape_html(i)));
+                doc.push_str(&format!(
+                    "<tr><td>{icon}<code>{}</code></td><td>{} ago</td><td>{}</td><td>{}</td></tr>\n",
+                    escape_html(&b.name),
+                    format_age(b.age),
+                    describe_merged(b.merged, self.base.is_some()),
+                    describe_upstream(b.upstream_gone),
+                ));
+            }
+            doc.push_str("</table>\n");
+        }
+        doc.push_str("</body>\n</html>\n");
+        doc
+    }
+}
+
+/// Name of the file of the report of an author, like "alice-example-com.md" for
+/// "alice@example.com".
+pub fn get_file_name(author: &str, format: ReportFormat) -> String {
+    let mut name = String::new();
+    for c in author.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            name.push(c);
+        } else if !name.is_empty() && !name.ends_with('-') {
+            name.push('-');
+        }
+    }
+    let name = name.trim_end_matches('-');
+    let name = if name.is_empty() { "unknown" } else { name };
+    format!("{name}.{}", format.extension())
+}
+
+fn describe_merged(merged: Option<bool>, has_base: bool) -> &'static str {
+    match (merged, has_base) {
+        (Some(true), _) => "yes",
+        (Some(false), _) => "no",
+        (None, true) => "unknown",
+        (None, false) => "",
+    }
+}
+
+fn describe_upstream(gone: bool) -> &'static str {
+    match gone {
+        true => "gone",
+        false => "",
+    }
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::branch::list_local_branches;
+    use crate::testutil::{commit_at, init_repo};
+
+    const DAY: i64 = 24 * 3600;
+
+    #[test]
+    fn renders_reports() {
+        let (_dir, repo) = init_repo();
+        let now = 1_700_000_000;
+        let base = commit_at(&repo, "refs/heads/main", None, now - 400 * DAY);
+        commit_at(&repo, "refs/heads/fresh", Some(base), now - DAY);
+        commit_at(&repo, "refs/heads/old<x>", Some(base), now - 100 * DAY);
+        let branches: Vec<BranchInfo> = list_local_branches(&repo)
+            .unwrap()
+            .into_iter()
+            .filter(|b| b.name != "main")
+            .collect();
+        let older_than = "3m".parse().unwrap();
+        let report = Report::collect(
+            &repo,
+            "example.com/me/app".into(),
+            &branches,
+            Some(("main", base)),
+            older_than,
+            AuthorFormat::Name,
+            now,
+        )
+        .unwrap();
+        assert_eq!(report.count(), 1);
+        assert_eq!(report.by_author[0].1[0].merged, Some(false));
+        let markdown = report.render(ReportFormat::Markdown);
+        assert!(markdown.starts_with("# Stale branches of example.com/me/app\n"));
+        assert!(markdown.contains("\n## Tester (1)\n"));
+        assert!(markdown.contains("| `old<x>` | 3mo ago | no |  |\n"));
+        let icons = [Icon {
+            prefix: "old".into(),
+            glyph: "🕸".into(),
+            color: None,
+        }];
+        let report = report.with_icons(&icons);
+        assert!(report
+            .render(ReportFormat::Markdown)
+            .contains("| 🕸 `old<x>` | 3mo ago |"));
+        let html = report.render(ReportFormat::Html);
+        assert!(html.contains("<td>🕸 <code>old&lt;x&gt;</code></td><td>3mo ago</td>"));
+        assert!("pdf".parse::<ReportFormat>().is_err());
+    }
+
+    #[test]
+    fn splits_by_author() {
+        let branch = |name: &str, age: i64| StaleBranch {
+            name: name.into(),
+            age,
+            merged: None,
+            upstream_gone: false,
+            icon: None,
+        };
+        let report = Report {
+            repository: "app".into(),
+            older_than: "3m".parse().unwrap(),
+            base: None,
+            by_author: vec![
+                (
+                    "Alice".into(),
+                    vec![branch("a", 420 * DAY), branch("b", DAY)],
+                ),
+                ("Bob".into(), vec![branch("c", 100 * DAY)]),
+            ],
+        };
+        assert_eq!(
+            report.summarize(),
+            [
+                "Alice: 2 stale branches, oldest 1y",
+                "Bob: 1 stale branches, oldest 3mo"
+            ]
+        );
+        let reports = report.split();
+        assert_eq!(reports[1].1.count(), 1);
+        assert!(reports[1]
+            .1
+            .render(ReportFormat::Markdown)
+            .contains("1 branches of Bob have"));
+        assert_eq!(
+            get_file_name("Alice <alice@example.com>", ReportFormat::Html),
+            "alice-alice-example-com.html"
+        );
+        assert_eq!(get_file_name("?", ReportFormat::Markdown), "unknown.md");
+    }
+}
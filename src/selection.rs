@@ -0,0 +1,50 @@
+//! Branches picked for review, written to a file which a colleague passes to `--select-from`,
+//! to start with the same branches selected before anything is deleted.
+//!
+//! The file has one branch name per line. Blank lines and those starting with "#" are skipped,
+//! so that it can be annotated.
+
+use std::fs;
+use std::path::Path;
+
+use eyre::{Context, Result};
+
+const HEADER: &str = "# Branches to delete, for git del-branches --select-from\n";
+
+/// Write the names, replacing the file.
+pub fn write(path: &Path, names: &[String]) -> Result<()> {
+    let content: String = names.iter().map(|n| format!("{n}\n")).collect();
+    fs::write(path, format!("{HEADER}{content}"))
+        .wrap_err_with(|| format!("Cannot write {}", path.display()))
+}
+
+/// Read the names, in the order of the file.
+pub fn read(path: &Path) -> Result<Vec<String>> {
+    let content =
+        fs::read_to_string(path).wrap_err_with(|| format!("Cannot read {}", path.display()))?;
+    let names = content
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(String::from)
+        .collect();
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_and_reads() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("review.txt");
+        let names = ["feature/login", "fix"].map(String::from);
+        write(&path, &names).unwrap();
+        assert_eq!(read(&path).unwrap(), names);
+        fs::write(&path, "# Checked with Bob\n\nfix\n  old  \n").unwrap();
+        assert_eq!(read(&path).unwrap(), ["fix", "old"]);
+        let e = read(&dir.path().join("missing")).unwrap_err();
+        assert!(e.to_string().starts_with("Cannot read "));
+    }
+}
@@ -0,0 +1,75 @@
+//! Branches picked in a run which did not finish, so that the next run can offer to pick
+//! them again.
+//!
+//! The session is a text file in ".git/del-branches/session", one branch name per line.
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use eyre::Result;
+use git2::{BranchType, Repository};
+
+use crate::gitenv::get_data_dir;
+
+const FILE_NAME: &str = "session";
+
+pub fn get_session_path(repo: &Repository) -> PathBuf {
+    get_data_dir(repo).join(FILE_NAME)
+}
+
+/// Save the picked branches, replacing the previous session.
+pub fn save(repo: &Repository, names: &[String]) -> Result<()> {
+    if names.is_empty() {
+        return clear(repo);
+    }
+    let path = get_session_path(repo);
+    if let Some(folder) = path.parent() {
+        fs::create_dir_all(folder)?;
+    }
+    let content: String = names.iter().map(|n| format!("{n}\n")).collect();
+    fs::write(path, content)?;
+    Ok(())
+}
+
+/// Read the saved branches which still exist. Empty if there is no session.
+pub fn load(repo: &Repository) -> Result<Vec<String>> {
+    let content = match fs::read_to_string(get_session_path(repo)) {
+        Ok(c) => c,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let names = content
+        .lines()
+        .filter(|n| repo.find_branch(n, BranchType::Local).is_ok())
+        .map(String::from)
+        .collect();
+    Ok(names)
+}
+
+pub fn clear(repo: &Repository) -> Result<()> {
+    match fs::remove_file(get_session_path(repo)) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{commit, init_repo};
+
+    #[test]
+    fn saves_and_loads() {
+        let (_dir, repo) = init_repo();
+        assert!(load(&repo).unwrap().is_empty());
+        let base = commit(&repo, "refs/heads/feat1", None);
+        commit(&repo, "refs/heads/feat2", Some(base));
+        let names = ["feat1", "gone", "feat2"].map(String::from);
+        save(&repo, &names).unwrap();
+        assert_eq!(load(&repo).unwrap(), ["feat1", "feat2"]);
+        clear(&repo).unwrap();
+        assert!(!get_session_path(&repo).exists());
+        clear(&repo).unwrap();
+    }
+}
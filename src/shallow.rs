@@ -0,0 +1,72 @@
+//! Deepening shallow clones, to tell whether branches are merged.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use eyre::{eyre, Result};
+use git2::{Oid, Repository};
+
+use crate::gitenv::{get_common_dir, set_git_env};
+use crate::merged::{check_contained_in, resolve_commit};
+
+/// Number of commits to deepen by at first. It is doubled at each round.
+const FIRST_DEPTH: u32 = 64;
+
+/// Fetch more history into a shallow clone, until it tells whether each of the `tips` is
+/// contained in `base`, or the clone is complete. Before each fetch, `on_fetch` is given
+/// the number of commits to deepen by and the number of tips still unknown.
+///
+/// The repository is opened again at each round, because libgit2 keeps the commits it
+/// has read, with the parents they had, in its cache.
+pub fn deepen_until_known(
+    path: &Path,
+    tips: &[Oid],
+    base: &str,
+    mut on_fetch: impl FnMut(u32, usize),
+) -> Result<()> {
+    let mut depth = FIRST_DEPTH;
+    loop {
+        let repo = Repository::open(path)?;
+        if !repo.is_shallow() {
+            return Ok(());
+        }
+        let base_id = resolve_commit(&repo, base)?;
+        let mut unknown = 0;
+        for tip in tips {
+            if check_contained_in(&repo, *tip, base_id)?.is_none() {
+                unknown += 1;
+            }
+        }
+        if unknown == 0 {
+            return Ok(());
+        }
+        on_fetch(depth, unknown);
+        let cut = read_shallow_file(&repo);
+        deepen(&repo, depth)?;
+        // The remote has nothing more to give
+        if read_shallow_file(&repo) == cut {
+            return Ok(());
+        }
+        depth = depth.saturating_mul(2);
+    }
+}
+
+/// The commits where the history of a shallow clone is cut.
+fn read_shallow_file(repo: &Repository) -> String {
+    fs::read_to_string(get_common_dir(repo).join("shallow")).unwrap_or_default()
+}
+
+/// Fetch `depth` more commits of history, from the default remote.
+pub fn deepen(repo: &Repository, depth: u32) -> Result<()> {
+    let mut cmd = Command::new("git");
+    set_git_env(&mut cmd, repo);
+    let status = cmd
+        .args(["fetch", "--quiet", &format!("--deepen={depth}")])
+        .status()
+        .map_err(|e| eyre!("Failed to run git: {e}"))?;
+    if !status.success() {
+        return Err(eyre!("git fetch --deepen failed ({status})"));
+    }
+    Ok(())
+}
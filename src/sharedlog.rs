@@ -0,0 +1,229 @@
+//! Log of the deletions shared with the team, in the ref `refs/del-branches/log` pushed to the
+//! remote of `del-branches.sharedLog`, so that anyone can see who deleted which branches, and
+//! restore them.
+//!
+//! The ref points to a commit whose tree has a "log" file: the lines of the journal, with who
+//! deleted the branch in a last field. Its parents are the previous logs and the tips of the
+//! deleted branches, which the remote therefore keeps for as long as the log.
+//!
+//! The log of the remote is fetched before recording, and the lines of the local log which it
+//! lacks are added to it, so that deletions whose push failed are pushed by the next run.
+
+use std::fmt;
+use std::str::FromStr;
+
+use eyre::{eyre, Result};
+use git2::{BranchType, Commit, FetchOptions, Oid, PushOptions, Repository};
+
+use crate::journal::Entry;
+use crate::notify::get_user;
+
+pub const LOG_REF: &str = "refs/del-branches/log";
+/// Where the log of the remote is fetched, to be merged.
+const FETCHED_REF: &str = "refs/del-branches/fetched-log";
+const FILE_NAME: &str = "log";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SharedEntry {
+    pub entry: Entry,
+    /// Who deleted the branch, like "Alice <alice@example.com>".
+    pub user: String,
+}
+
+impl fmt::Display for SharedEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}\t{}", self.entry, self.user)
+    }
+}
+
+impl FromStr for SharedEntry {
+    type Err = eyre::Report;
+
+    fn from_str(line: &str) -> Result<Self> {
+        let (entry, user) = line
+            .rsplit_once('\t')
+            .ok_or_else(|| eyre!("Malformed shared log line: {line}"))?;
+        Ok(Self {
+            entry: entry.parse()?,
+            user: user.to_string(),
+        })
+    }
+}
+
+/// Fetch the log of the remote, if it has one.
+pub fn fetch(repo: &Repository, remote: &str, opts: &mut FetchOptions) -> Result<(), git2::Error> {
+    let mut remote = repo.find_remote(remote)?;
+    remote.fetch(&[&format!("+{LOG_REF}:{FETCHED_REF}")], Some(opts), None)
+}
+
+/// Push the log to the remote. It is not forced: it is refused if someone pushed theirs since
+/// it was fetched.
+pub fn push(repo: &Repository, remote: &str, opts: &mut PushOptions) -> Result<(), git2::Error> {
+    let mut remote = repo.find_remote(remote)?;
+    remote.push(&[&format!("{LOG_REF}:{LOG_REF}")], Some(opts))
+}
+
+/// The commit of the log at the ref, and its lines. Both are empty if there is no such ref.
+fn read_log<'r>(
+    repo: &'r Repository,
+    refname: &str,
+) -> Result<(Option<Commit<'r>>, Vec<String>), git2::Error> {
+    let commit = match repo.find_reference(refname) {
+        Ok(reference) => reference.peel_to_commit()?,
+        Err(e) if e.code() == git2::ErrorCode::NotFound => return Ok((None, Vec::new())),
+        Err(e) => return Err(e),
+    };
+    let lines = match commit.tree()?.get_name(FILE_NAME) {
+        Some(file) => {
+            let blob = file.to_object(repo)?.peel_to_blob()?;
+            let content = String::from_utf8_lossy(blob.content());
+            content.lines().map(str::to_string).collect()
+        }
+        None => Vec::new(),
+    };
+    Ok((Some(commit), lines))
+}
+
+/// Lines of the logs of the remote and ours: those of the remote first, then ours which it
+/// lacks.
+fn read_lines(repo: &Repository) -> Result<(Vec<Commit<'_>>, Vec<String>), git2::Error> {
+    let (fetched, mut lines) = read_log(repo, FETCHED_REF)?;
+    let (local, local_lines) = read_log(repo, LOG_REF)?;
+    for line in local_lines {
+        if !lines.contains(&line) {
+            lines.push(line);
+        }
+    }
+    let mut heads: Vec<Commit> = fetched.into_iter().collect();
+    if let Some(local) = local {
+        let known = match heads.first() {
+            Some(fetched) => {
+                fetched.id() == local.id() || repo.graph_descendant_of(fetched.id(), local.id())?
+            }
+            None => false,
+        };
+        if !known {
+            heads.push(local);
+        }
+    }
+    Ok((heads, lines))
+}
+
+/// Read all entries of the logs of the remote and ours, oldest first. Malformed lines are
+/// skipped.
+pub fn read(repo: &Repository) -> Result<Vec<SharedEntry>, git2::Error> {
+    let (_, lines) = read_lines(repo)?;
+    Ok(lines.iter().filter_map(|l| l.parse().ok()).collect())
+}
+
+/// Add the deletions to the local log, with the Git identity of the user, returning the new
+/// commit of the log.
+pub fn record(repo: &Repository, entries: &[Entry]) -> Result<Oid, git2::Error> {
+    let (heads, mut lines) = read_lines(repo)?;
+    let user = get_user(repo);
+    for entry in entries {
+        let shared = SharedEntry {
+            entry: entry.clone(),
+            user: user.clone(),
+        };
+        lines.push(shared.to_string());
+    }
+    let mut content = lines.join("\n");
+    content.push('\n');
+    let blob = repo.blob(content.as_bytes())?;
+    let mut builder = repo.treebuilder(None)?;
+    builder.insert(FILE_NAME, blob, 0o100644)?;
+    let tree = repo.find_tree(builder.write()?)?;
+    // The tips too, so that they are pushed and kept with the log. Some may have been pruned.
+    let tips: Vec<Commit> = entries
+        .iter()
+        .filter_map(|e| repo.find_commit(e.tip).ok())
+        .collect();
+    let parents: Vec<&Commit> = heads.iter().chain(&tips).collect();
+    let signature = repo.signature()?;
+    let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+    let message = format!("Delete {}", names.join(", "));
+    let id = repo.commit(None, &signature, &signature, &message, &tree, &parents)?;
+    repo.reference(LOG_REF, id, true, "del-branches: record deletions")?;
+    Ok(id)
+}
+
+/// Find entries of branches which can be restored, like `journal::list_restorable`, from the
+/// shared log.
+pub fn list_restorable(repo: &Repository) -> Result<Vec<SharedEntry>, git2::Error> {
+    let mut entries = read(repo)?;
+    entries.reverse();
+    let mut seen = Vec::new();
+    entries.retain(|e| {
+        let name = &e.entry.name;
+        if seen.contains(name) {
+            return false;
+        }
+        seen.push(name.clone());
+        repo.find_branch(name, BranchType::Local).is_err() && repo.find_commit(e.entry.tip).is_ok()
+    });
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::journal::UpstreamState;
+    use crate::testutil::{commit, init_bare_repo, init_repo};
+
+    fn entry(name: &str, tip: Oid) -> Entry {
+        Entry {
+            time: 1_700_000_000,
+            name: name.into(),
+            tip,
+            upstream: None,
+            upstream_state: UpstreamState::Kept,
+        }
+    }
+
+    #[test]
+    fn parses_lines() {
+        let line = format!("1\tfeat\t{}\t\t\tkept\tAlice <a@example.com>", Oid::zero());
+        let shared: SharedEntry = line.parse().unwrap();
+        assert_eq!(shared.entry.name, "feat");
+        assert_eq!(shared.user, "Alice <a@example.com>");
+        assert_eq!(shared.to_string(), line);
+        assert!("1\tfeat".parse::<SharedEntry>().is_err());
+    }
+
+    #[test]
+    fn shares_deletions() {
+        let (_server_dir, server) = init_bare_repo();
+        let url = server.path().to_str().unwrap();
+        let (_dir, repo) = init_repo();
+        repo.remote("origin", url).unwrap();
+        let tip = commit(&repo, "refs/heads/feat", None);
+        repo.find_reference("refs/heads/feat")
+            .unwrap()
+            .delete()
+            .unwrap();
+        record(&repo, &[entry("feat", tip)]).unwrap();
+        push(&repo, "origin", &mut PushOptions::new()).unwrap();
+        assert!(server.find_commit(tip).is_ok());
+
+        // A teammate records theirs on top, and sees ours
+        let (_other_dir, other) = init_repo();
+        other.remote("origin", url).unwrap();
+        let other_tip = commit(&other, "refs/heads/wip", None);
+        fetch(&other, "origin", &mut FetchOptions::new()).unwrap();
+        let restorable = list_restorable(&other).unwrap();
+        assert_eq!(restorable.len(), 1);
+        assert_eq!(restorable[0].user, "Tester <tester@example.com>");
+        record(&other, &[entry("wip", other_tip)]).unwrap();
+        push(&other, "origin", &mut PushOptions::new()).unwrap();
+
+        // Ours was not pushed since, but is merged on the next fetch
+        fetch(&repo, "origin", &mut FetchOptions::new()).unwrap();
+        let names: Vec<String> = read(&repo)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.entry.name)
+            .collect();
+        assert_eq!(names, ["feat", "wip"]);
+    }
+}
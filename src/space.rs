@@ -0,0 +1,287 @@
+//! Disk space which deleting branches makes reclaimable.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use git2::{Delta, ErrorCode, Odb, Oid, Repository, Tree, TreeWalkMode, TreeWalkResult};
+
+/// Objects which no ref reaches anymore, that `git gc` can remove.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Reclaimable {
+    pub commits: usize,
+    /// Commits and the blobs they added.
+    pub objects: usize,
+    pub bytes: u64,
+}
+
+impl fmt::Display for Reclaimable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} in {} objects ({} commits)",
+            format_size(self.bytes),
+            self.objects,
+            self.commits
+        )
+    }
+}
+
+/// Size in bytes, in binary units like "1.5 MiB".
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+/// Find the commits, from these tips down, which no ref reaches.
+pub fn find_unreachable_commits(repo: &Repository, tips: &[Oid]) -> Result<Vec<Oid>, git2::Error> {
+    let mut walk = repo.revwalk()?;
+    let mut any = false;
+    for &tip in tips {
+        if repo.find_commit(tip).is_ok() {
+            walk.push(tip)?;
+            any = true;
+        }
+    }
+    if !any {
+        return Ok(Vec::new());
+    }
+    for reference in repo.references()?.flatten() {
+        if let Ok(commit) = reference.peel_to_commit() {
+            walk.hide(commit.id())?;
+        }
+    }
+    walk.collect()
+}
+
+/// Remove the reflogs which keep the deleted branches' commits from `git gc`: those of the
+/// branches themselves, and the entries of HEAD's reflog which moved to these commits.
+/// Returns the number of HEAD entries removed.
+pub fn expire_reflogs(
+    repo: &Repository,
+    names: &[String],
+    commits: &[Oid],
+) -> Result<usize, git2::Error> {
+    for name in names {
+        match repo.reflog_delete(&format!("refs/heads/{name}")) {
+            Err(e) if e.code() != ErrorCode::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    let commits: HashSet<&Oid> = commits.iter().collect();
+    let mut reflog = repo.reflog("HEAD")?;
+    let mut removed = 0;
+    // From the end, so that the indices of the next ones don't change
+    for i in (0..reflog.len()).rev() {
+        let hit = reflog.get(i).is_some_and(|e| commits.contains(&e.id_new()));
+        if hit {
+            // Rewrite the next entry to move from where this one came
+            reflog.remove(i, true)?;
+            removed += 1;
+        }
+    }
+    if removed > 0 {
+        reflog.write()?;
+    }
+    Ok(removed)
+}
+
+/// Estimate what became unreachable from the refs by deleting branches with these tips:
+/// their commits which no ref reaches, and the blobs which these commits added. Sizes are
+/// as stored uncompressed. Trees are not counted, and blobs which are still in other history
+/// than the tree of HEAD are, so it is an estimate.
+pub fn estimate_unreachable(repo: &Repository, tips: &[Oid]) -> Result<Reclaimable, git2::Error> {
+    let commits = find_unreachable_commits(repo, tips)?;
+    let head_tree = repo.head().and_then(|h| h.peel_to_tree()).ok();
+    let odb = repo.odb()?;
+    let mut result = Reclaimable::default();
+    let mut blobs = HashSet::new();
+    for id in commits {
+        result.commits += 1;
+        result.objects += 1;
+        result.bytes += add_commit(repo, &odb, id, &mut blobs)?;
+    }
+    if let Some(tree) = head_tree {
+        // Blobs of the checked out files are kept
+        remove_tree_blobs(&tree, &mut blobs)?;
+    }
+    for blob in blobs {
+        // Submodule commits are not in our database
+        if let Ok((size, _)) = odb.read_header(blob) {
+            result.objects += 1;
+            result.bytes += size as u64;
+        }
+    }
+    Ok(result)
+}
+
+/// Add the blobs which the commit added or changed, from its first parent, and return its
+/// own size.
+fn add_commit(
+    repo: &Repository,
+    odb: &Odb,
+    id: Oid,
+    blobs: &mut HashSet<Oid>,
+) -> Result<u64, git2::Error> {
+    let commit = repo.find_commit(id)?;
+    let parent_tree = match commit.parent_count() {
+        0 => None,
+        _ => Some(commit.parent(0)?.tree()?),
+    };
+    let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?;
+    for delta in diff.deltas() {
+        if matches!(delta.status(), Delta::Added | Delta::Modified) {
+            blobs.insert(delta.new_file().id());
+        }
+    }
+    Ok(odb.read_header(id)?.0 as u64)
+}
+
+fn remove_tree_blobs(tree: &Tree, blobs: &mut HashSet<Oid>) -> Result<(), git2::Error> {
+    tree.walk(TreeWalkMode::PreOrder, |_, entry| {
+        blobs.remove(&entry.id());
+        TreeWalkResult::Ok
+    })
+}
+
+/// Estimates of the space which each branch takes on its own, which deleting it would
+/// eventually free: its commits which no protected branch reaches, and the blobs they added
+/// which are not in the trees of the protected branches. Sizes are as stored uncompressed,
+/// and what the branch shares with other unprotected branches is counted for each of them.
+pub struct BranchSizes<'r> {
+    repo: &'r Repository,
+    protected: Vec<Oid>,
+    /// Blobs of the trees of the protected branches.
+    kept: HashSet<Oid>,
+}
+
+impl<'r> BranchSizes<'r> {
+    pub fn new(repo: &'r Repository, protected: &[Oid]) -> Result<Self, git2::Error> {
+        let mut kept = HashSet::new();
+        for &id in protected {
+            repo.find_commit(id)?
+                .tree()?
+                .walk(TreeWalkMode::PreOrder, |_, entry| {
+                    kept.insert(entry.id());
+                    TreeWalkResult::Ok
+                })?;
+        }
+        Ok(Self {
+            repo,
+            protected: protected.to_vec(),
+            kept,
+        })
+    }
+
+    /// Estimate the size, in bytes, of the branch with this tip.
+    pub fn estimate(&self, tip: Oid) -> Result<u64, git2::Error> {
+        let mut walk = self.repo.revwalk()?;
+        walk.push(tip)?;
+        for &id in &self.protected {
+            walk.hide(id)?;
+        }
+        let odb = self.repo.odb()?;
+        let mut bytes = 0;
+        let mut blobs = HashSet::new();
+        for id in walk {
+            bytes += add_commit(self.repo, &odb, id?, &mut blobs)?;
+        }
+        for blob in blobs.difference(&self.kept) {
+            if let Ok((size, _)) = odb.read_header(*blob) {
+                bytes += size as u64;
+            }
+        }
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{commit_files, init_repo};
+
+    #[test]
+    fn expires_reflogs() {
+        let (_dir, repo) = init_repo();
+        let sig = repo.signature().unwrap();
+        let base = commit_files(&repo, "refs/heads/main", None, &[("a", "1")]);
+        let tip = commit_files(&repo, "refs/heads/feature", Some(base), &[("b", "1")]);
+        let mut reflog = repo.reflog("HEAD").unwrap();
+        reflog.append(base, &sig, Some("checkout: main")).unwrap();
+        reflog.append(tip, &sig, Some("commit: b")).unwrap();
+        reflog.append(base, &sig, Some("checkout: main")).unwrap();
+        reflog.write().unwrap();
+        repo.find_reference("refs/heads/feature")
+            .unwrap()
+            .delete()
+            .unwrap();
+        let commits = find_unreachable_commits(&repo, &[tip]).unwrap();
+        assert_eq!(commits, [tip]);
+        let names = ["feature".to_string()];
+        assert_eq!(expire_reflogs(&repo, &names, &commits).unwrap(), 1);
+        let reflog = repo.reflog("HEAD").unwrap();
+        assert_eq!(reflog.len(), 2);
+        // The next entry now moves from where the removed one came
+        assert!(reflog
+            .iter()
+            .all(|e| e.id_new() == base && e.id_old() != tip));
+    }
+
+    #[test]
+    fn formats_sizes() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(3 * 1024 * 1024), "3.0 MiB");
+    }
+
+    #[test]
+    fn estimates_branch_sizes() {
+        let (_dir, repo) = init_repo();
+        let base = commit_files(&repo, "refs/heads/main", None, &[("a", "shared")]);
+        let tip = commit_files(&repo, "refs/heads/feature", Some(base), &[("b", "1234")]);
+        let tip = commit_files(&repo, "refs/heads/feature", Some(tip), &[("a", "shared")]);
+        let sizes = BranchSizes::new(&repo, &[base]).unwrap();
+        let odb = repo.odb().unwrap();
+        let first = repo.find_commit(tip).unwrap().parent_id(0).unwrap();
+        let commit_sizes: u64 = [first, tip]
+            .iter()
+            .map(|id| odb.read_header(*id).unwrap().0 as u64)
+            .sum();
+        // The blob of "a" is on main
+        assert_eq!(sizes.estimate(tip).unwrap(), commit_sizes + 4);
+        assert_eq!(sizes.estimate(base).unwrap(), 0);
+    }
+
+    #[test]
+    fn estimates_unreachable() {
+        let (_dir, repo) = init_repo();
+        let base = commit_files(&repo, "refs/heads/main", None, &[("a", "shared")]);
+        repo.set_head("refs/heads/main").unwrap();
+        let merged = commit_files(&repo, "refs/heads/merged", Some(base), &[("b", "1")]);
+        let tip = commit_files(&repo, "refs/heads/feature", Some(base), &[("c", "12345")]);
+        let tip = commit_files(&repo, "refs/heads/feature", Some(tip), &[("a", "shared")]);
+        repo.find_reference("refs/heads/feature")
+            .unwrap()
+            .delete()
+            .unwrap();
+        let result = estimate_unreachable(&repo, &[tip, merged]).unwrap();
+        // The blob of "a" is in HEAD, and "merged" still exists
+        assert_eq!(result.commits, 2);
+        assert_eq!(result.objects, 3);
+        let first = repo.find_commit(tip).unwrap().parent_id(0).unwrap();
+        let odb = repo.odb().unwrap();
+        let commit_sizes: u64 = [first, tip]
+            .iter()
+            .map(|id| odb.read_header(*id).unwrap().0 as u64)
+            .sum();
+        assert_eq!(result.bytes, commit_sizes + 5);
+    }
+}
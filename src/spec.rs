@@ -0,0 +1,168 @@
+//! Exactly which branches to delete, for `--apply`, read from a TOML file like the one below,
+//! or the same in JSON if its name ends with ".json":
+//!
+//! ```toml
+//! # For the branches which do not say
+//! delete-upstream = true
+//!
+//! [[branch]]
+//! name = "feature/login"
+//! # Refused if the branch moved since the spec was written
+//! tip = "4f2a9c1"
+//!
+//! [[branch]]
+//! name = "release/1.2"
+//! delete-upstream = false
+//! # Protected branches are only deleted with this
+//! force = true
+//! ```
+//!
+//! The spec is applied as a whole or not at all: if any branch cannot be deleted as it says,
+//! none is.
+
+use std::fs;
+use std::path::Path;
+
+use eyre::{Context, Result};
+use serde::Deserialize;
+
+use crate::branch::{is_protected, BranchInfo};
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct BranchSpec {
+    pub name: String,
+    /// Start of the commit ID which the branch must point to.
+    pub tip: Option<String>,
+    /// Whether to delete its upstream branch, else as the spec says for all.
+    pub delete_upstream: Option<bool>,
+    /// Whether to delete it even if it is protected.
+    #[serde(default)]
+    pub force: bool,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Spec {
+    /// Whether to delete the upstream branches too.
+    #[serde(default)]
+    pub delete_upstream: bool,
+    #[serde(default, rename = "branch", alias = "branches")]
+    pub branches: Vec<BranchSpec>,
+}
+
+/// What applying the spec does.
+#[derive(Debug, Default)]
+pub struct Plan {
+    /// The branches to delete, with whether to delete their upstream branches.
+    pub deletions: Vec<(BranchInfo, bool)>,
+    /// The branches of the spec which do not exist.
+    pub gone: Vec<String>,
+}
+
+impl Spec {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .wrap_err_with(|| format!("Cannot read spec file {}", path.display()))?;
+        let invalid = || format!("Invalid spec file {}", path.display());
+        match path.extension().is_some_and(|e| e == "json") {
+            true => serde_json::from_str(&content).wrap_err_with(invalid),
+            false => toml::from_str(&content).wrap_err_with(invalid),
+        }
+    }
+
+    /// What the spec does to these local branches, or why it cannot be applied, one problem
+    /// per string.
+    pub fn plan(&self, branches: &[BranchInfo], protected: &[String]) -> Result<Plan, Vec<String>> {
+        let mut plan = Plan::default();
+        let mut problems = Vec::new();
+        for spec in &self.branches {
+            if plan.deletions.iter().any(|(b, _)| b.name == spec.name) {
+                continue;
+            }
+            let Some(branch) = branches.iter().find(|b| b.name == spec.name) else {
+                plan.gone.push(spec.name.clone());
+                continue;
+            };
+            let tip = branch.tip.to_string();
+            match &spec.tip {
+                Some(expected) if !tip.starts_with(&expected.to_lowercase()) => {
+                    problems.push(format!(
+                        "{} is at {}, not at {expected} as the spec says",
+                        spec.name,
+                        &tip[..7]
+                    ));
+                }
+                _ if branch.is_head => {
+                    problems.push(format!("{} is the current branch", spec.name));
+                }
+                _ if branch.worktree.is_some() => {
+                    problems.push(format!("{} is checked out in a worktree", spec.name));
+                }
+                _ if is_protected(protected, &spec.name) && !spec.force => {
+                    problems.push(format!(
+                        "{} is protected, which needs force = true",
+                        spec.name
+                    ));
+                }
+                _ => {
+                    let upstream = spec.delete_upstream.unwrap_or(self.delete_upstream);
+                    plan.deletions.push((branch.clone(), upstream));
+                }
+            }
+        }
+        match problems.is_empty() {
+            true => Ok(plan),
+            false => Err(problems),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::branch::list_local_branches;
+    use crate::testutil::{commit, init_repo};
+
+    #[test]
+    fn plans_deletions() {
+        let (_dir, repo) = init_repo();
+        let base = commit(&repo, "refs/heads/main", None);
+        let tip = commit(&repo, "refs/heads/feat", Some(base));
+        commit(&repo, "refs/heads/release/1", Some(base));
+        let branches = list_local_branches(&repo).unwrap();
+        let protected = vec!["main".to_string(), "release/1".to_string()];
+        let spec: Spec = toml::from_str(&format!(
+            r#"
+            delete-upstream = true
+            [[branch]]
+            name = "feat"
+            tip = "{}"
+            [[branch]]
+            name = "release/1"
+            delete-upstream = false
+            force = true
+            [[branch]]
+            name = "old"
+            "#,
+            &tip.to_string()[..7]
+        ))
+        .unwrap();
+        let plan = spec.plan(&branches, &protected).unwrap();
+        let deletions: Vec<(&str, bool)> = plan
+            .deletions
+            .iter()
+            .map(|(b, up)| (b.name.as_str(), *up))
+            .collect();
+        assert_eq!(deletions, [("feat", true), ("release/1", false)]);
+        assert_eq!(plan.gone, ["old"]);
+
+        let json = r#"{"branches": [{"name": "feat", "tip": "0000000"}, {"name": "main"}]}"#;
+        let spec: Spec = serde_json::from_str(json).unwrap();
+        let problems = spec.plan(&branches, &protected).unwrap_err();
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].ends_with("not at 0000000 as the spec says"));
+        assert_eq!(problems[1], "main is protected, which needs force = true");
+        assert!(toml::from_str::<Spec>("[[branch]]\nname = \"x\"\nupstream = true").is_err());
+    }
+}
@@ -0,0 +1,191 @@
+//! Explaining why authenticating to a remote over SSH failed: what was tried, and what could
+//! fix it, instead of the last error of libgit2.
+
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Key files in `~/.ssh` which are tried after the agent, in this order.
+pub const DEFAULT_KEY_FILES: &[&str] = &[
+    "id_dsa",
+    "id_ecdsa",
+    "id_ecdsa_sk",
+    "id_ed25519",
+    "id_ed25519_sk",
+    "id_rsa",
+];
+
+/// Username used when neither the URL nor the SSH config gives one.
+const DEFAULT_USERNAME: &str = "git";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AgentState {
+    /// `SSH_AUTH_SOCK` is not set.
+    NotRunning,
+    Unreachable,
+    NoKeys,
+    /// It has keys, this many.
+    Keys(usize),
+}
+
+/// Ask the SSH agent, with `ssh-add -l`, whether it runs and has keys.
+pub fn probe_agent() -> AgentState {
+    if cfg!(unix) && env::var_os("SSH_AUTH_SOCK").is_none() {
+        return AgentState::NotRunning;
+    }
+    let output = Command::new("ssh-add")
+        .arg("-l")
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output();
+    match output {
+        Ok(o) if o.status.success() => {
+            let keys = String::from_utf8_lossy(&o.stdout).lines().count();
+            AgentState::Keys(keys)
+        }
+        // ssh-add exits with 1 when the agent has no keys, 2 when it cannot reach it
+        Ok(o) if o.status.code() == Some(1) => AgentState::NoKeys,
+        _ => AgentState::Unreachable,
+    }
+}
+
+/// The default key files which exist in the SSH directory.
+pub fn find_key_files(ssh_dir: &Path) -> Vec<PathBuf> {
+    DEFAULT_KEY_FILES
+        .iter()
+        .map(|name| ssh_dir.join(name))
+        .filter(|p| p.is_file())
+        .collect()
+}
+
+/// Host of an SSH URL, like "ssh://git@host:22/path" or "git@host:path".
+pub fn get_host(url: &str) -> Option<&str> {
+    let rest = match url.split_once("://") {
+        Some((_, rest)) => rest,
+        None => url,
+    };
+    let authority = rest.split(['/', ':']).next()?;
+    let host = authority.rsplit('@').next()?;
+    (!host.is_empty()).then_some(host)
+}
+
+/// What was offered to the server when authenticating over SSH.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SshAttempts {
+    pub url: String,
+    /// In the order they were tried.
+    pub usernames: Vec<String>,
+    /// Credentials offered: from the agent first, then from the key files.
+    pub offers: usize,
+}
+
+impl SshAttempts {
+    /// Record that libgit2 asked for an SSH key for the username.
+    pub fn record(&mut self, url: &str, username: Option<&str>) {
+        let username = username.unwrap_or(DEFAULT_USERNAME);
+        if !self.usernames.iter().any(|u| u == username) {
+            self.usernames.push(username.to_string());
+        }
+        self.url = url.to_string();
+        self.offers += 1;
+    }
+}
+
+/// What was tried, and hints for fixing it, one line each.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Diagnosis {
+    pub facts: Vec<String>,
+    pub hints: Vec<String>,
+}
+
+/// Explain the failure, from the attempts, the state of the agent and the key files found.
+pub fn diagnose(attempts: &SshAttempts, agent: AgentState, key_files: &[PathBuf]) -> Diagnosis {
+    let mut facts = vec![format!("Remote: {}", attempts.url)];
+    let mut hints = Vec::new();
+    facts.push(format!(
+        "Usernames tried: {}",
+        attempts.usernames.join(", ")
+    ));
+    facts.push(match agent {
+        AgentState::NotRunning => "SSH agent: not running (SSH_AUTH_SOCK is not set)".into(),
+        AgentState::Unreachable => "SSH agent: cannot be reached".into(),
+        AgentState::NoKeys => "SSH agent: running, without keys".into(),
+        AgentState::Keys(1) => "SSH agent: running, its key was refused".into(),
+        AgentState::Keys(n) => format!("SSH agent: running, its {n} keys were refused"),
+    });
+    if key_files.is_empty() {
+        facts.push("Key files: none of the default ones exist".into());
+    } else {
+        let names: Vec<_> = key_files.iter().map(|p| p.display().to_string()).collect();
+        facts.push(format!("Key files tried: {}", names.join(", ")));
+    }
+    match agent {
+        AgentState::NotRunning | AgentState::Unreachable => {
+            hints.push(
+                "Start the agent with `eval $(ssh-agent)`, then add your key with `ssh-add`".into(),
+            );
+        }
+        AgentState::NoKeys => hints.push("Add your key to the agent with `ssh-add`".into()),
+        AgentState::Keys(_) => {}
+    }
+    let username = attempts
+        .usernames
+        .first()
+        .map_or(DEFAULT_USERNAME, String::as_str);
+    if let Some(host) = get_host(&attempts.url) {
+        hints.push(format!(
+            "Check that your public key is registered on the server, with `ssh -T {username}@{host}`"
+        ));
+    }
+    if attempts.usernames == [DEFAULT_USERNAME] {
+        hints.push(
+            "If the server expects another user, put it in the URL, or set User in ~/.ssh/config"
+                .into(),
+        );
+    }
+    Diagnosis { facts, hints }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gets_host() {
+        assert_eq!(get_host("git@github.com:me/repo.git"), Some("github.com"));
+        assert_eq!(get_host("ssh://me@host:2222/repo.git"), Some("host"));
+        assert_eq!(get_host("ssh://host/repo.git"), Some("host"));
+        assert_eq!(get_host(""), None);
+    }
+
+    #[test]
+    fn diagnoses_failure() {
+        let mut attempts = SshAttempts::default();
+        attempts.record("git@github.com:me/repo.git", None);
+        attempts.record("git@github.com:me/repo.git", Some("git"));
+        assert_eq!(attempts.usernames, ["git"]);
+        assert_eq!(attempts.offers, 2);
+        let diagnosis = diagnose(&attempts, AgentState::NoKeys, &[]);
+        assert_eq!(
+            diagnosis.facts,
+            [
+                "Remote: git@github.com:me/repo.git",
+                "Usernames tried: git",
+                "SSH agent: running, without keys",
+                "Key files: none of the default ones exist",
+            ]
+        );
+        assert_eq!(diagnosis.hints.len(), 3);
+        assert!(diagnosis.hints[1].contains("ssh -T git@github.com"));
+        let diagnosis = diagnose(&attempts, AgentState::Keys(2), &[]);
+        assert!(diagnosis.hints[0].starts_with("Check that"));
+    }
+
+    #[test]
+    fn finds_key_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("id_ed25519"), "").unwrap();
+        std::fs::write(dir.path().join("id_ed25519.pub"), "").unwrap();
+        assert_eq!(find_key_files(dir.path()), [dir.path().join("id_ed25519")]);
+    }
+}
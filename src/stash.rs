@@ -0,0 +1,67 @@
+//! Stashes, and the branches they were created on.
+
+use std::collections::HashMap;
+
+use git2::{ErrorCode, Repository};
+
+/// Name of the branch a stash was created on, from its message like "WIP on feature: 1234abc
+/// Message" or "On feature: message". Ref names cannot have ":", so this is not ambiguous.
+fn parse_stash_branch(message: &str) -> Option<&str> {
+    let rest = message
+        .strip_prefix("WIP on ")
+        .or_else(|| message.strip_prefix("On "))?;
+    let (name, _) = rest.split_once(':')?;
+    // Stashed at a detached HEAD
+    (name != "(no branch)").then_some(name)
+}
+
+/// Count the stashes by the branch they were created on.
+pub fn count_stashes_by_branch(repo: &Repository) -> Result<HashMap<String, usize>, git2::Error> {
+    let reflog = match repo.reflog("refs/stash") {
+        Ok(r) => r,
+        Err(e) if e.code() == ErrorCode::NotFound => return Ok(HashMap::new()),
+        Err(e) => return Err(e),
+    };
+    let mut counts = HashMap::new();
+    for entry in reflog.iter() {
+        if let Some(name) = entry.message().and_then(parse_stash_branch) {
+            *counts.entry(name.to_string()).or_default() += 1;
+        }
+    }
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{commit, init_repo};
+
+    #[test]
+    fn parses_messages() {
+        assert_eq!(
+            parse_stash_branch("WIP on feat/a: 1234abc Add a"),
+            Some("feat/a")
+        );
+        assert_eq!(parse_stash_branch("On main: before rebase"), Some("main"));
+        assert_eq!(parse_stash_branch("WIP on (no branch): 1234abc x"), None);
+        assert_eq!(parse_stash_branch("autostash"), None);
+    }
+
+    #[test]
+    fn counts_stashes() {
+        let (_dir, repo) = init_repo();
+        assert!(count_stashes_by_branch(&repo).unwrap().is_empty());
+        let id = commit(&repo, "refs/stash", None);
+        let sig = repo.signature().unwrap();
+        let mut reflog = repo.reflog("refs/stash").unwrap();
+        reflog
+            .append(id, &sig, Some("WIP on feature: 1234abc x"))
+            .unwrap();
+        reflog.append(id, &sig, Some("On feature: y")).unwrap();
+        reflog.append(id, &sig, Some("On main: z")).unwrap();
+        reflog.write().unwrap();
+        let counts = count_stashes_by_branch(&repo).unwrap();
+        assert_eq!(counts["feature"], 2);
+        assert_eq!(counts["main"], 1);
+    }
+}
@@ -0,0 +1,122 @@
+//! Statistics of the local branches, for telling whether a cleanup is due.
+
+use std::collections::HashMap;
+
+use git2::{Oid, Repository};
+
+use crate::branch::{AuthorFormat, BranchInfo};
+use crate::merged::{check_contained_in, PatchIndex};
+use crate::partial::is_partial_clone;
+
+const DAY: i64 = 24 * 3600;
+
+/// Upper bounds of the age buckets, in seconds, with their names. Older branches go into
+/// the last bucket.
+pub const AGE_BUCKETS: &[(i64, &str)] = &[
+    (7 * DAY, "under a week"),
+    (30 * DAY, "under a month"),
+    (90 * DAY, "under 3 months"),
+    (365 * DAY, "under a year"),
+    (i64::MAX, "older"),
+];
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub total: usize,
+    /// Branches by author of the tip commit, most first.
+    pub by_author: Vec<(String, usize)>,
+    /// Number of branches in each of `AGE_BUCKETS`.
+    pub by_age: Vec<usize>,
+    /// Branches merged into the base, by any of the ways we detect.
+    pub merged: usize,
+    pub unmerged: usize,
+    /// Branches which the shallow clone lacks the history to tell about.
+    pub merge_unknown: usize,
+    pub with_upstream: usize,
+    /// Branches whose upstream is gone from the remote.
+    pub upstream_gone: usize,
+    pub without_upstream: usize,
+}
+
+/// Gather the statistics. Merge status is only counted if a base is given, and squash or
+/// rebase merges are not detected in partial clones.
+pub fn collect(
+    repo: &Repository,
+    branches: &[BranchInfo],
+    base: Option<Oid>,
+    author_format: AuthorFormat,
+    now: i64,
+) -> Result<Stats, git2::Error> {
+    let mut stats = Stats {
+        total: branches.len(),
+        by_age: vec![0; AGE_BUCKETS.len()],
+        ..Stats::default()
+    };
+    let mut authors: HashMap<String, usize> = HashMap::new();
+    // Patches cannot be compared without the file contents, which a partial clone lacks
+    let mut index = base
+        .filter(|_| !is_partial_clone(repo))
+        .map(|id| PatchIndex::new(repo, id));
+    for branch in branches {
+        *authors
+            .entry(branch.format_author(author_format))
+            .or_default() += 1;
+        let age = now - branch.time;
+        let bucket = AGE_BUCKETS
+            .iter()
+            .position(|(bound, _)| age < *bound)
+            .unwrap_or(AGE_BUCKETS.len() - 1);
+        stats.by_age[bucket] += 1;
+        if branch.upstream.is_some() {
+            stats.with_upstream += 1;
+        } else if branch.upstream_gone {
+            stats.upstream_gone += 1;
+        } else {
+            stats.without_upstream += 1;
+        }
+        if let Some(base) = base {
+            let contained = check_contained_in(repo, branch.tip, base)?;
+            let patched = match index.as_mut() {
+                Some(index) => index.find_merge_kind(branch.tip)?.is_some(),
+                None => false,
+            };
+            if contained == Some(true) || patched {
+                stats.merged += 1;
+            } else if contained.is_none() {
+                stats.merge_unknown += 1;
+            } else {
+                stats.unmerged += 1;
+            }
+        }
+    }
+    let mut by_author: Vec<(String, usize)> = authors.into_iter().collect();
+    by_author.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    stats.by_author = by_author;
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::branch::list_local_branches;
+    use crate::testutil::{commit_at, init_repo};
+
+    #[test]
+    fn counts_branches() {
+        let (_dir, repo) = init_repo();
+        let now = 1_700_000_000;
+        let base = commit_at(&repo, "refs/heads/main", None, now - 400 * DAY);
+        commit_at(&repo, "refs/heads/fresh", Some(base), now - DAY);
+        commit_at(&repo, "refs/heads/aging", Some(base), now - 40 * DAY);
+        let branches = list_local_branches(&repo).unwrap();
+        let stats = collect(&repo, &branches, Some(base), AuthorFormat::Name, now).unwrap();
+        assert_eq!(stats.total, 3);
+        assert_eq!(stats.by_author, [("Tester".to_string(), 3)]);
+        assert_eq!(stats.by_age, [1, 0, 1, 0, 1]);
+        assert_eq!((stats.merged, stats.unmerged), (1, 2));
+        assert_eq!(stats.without_upstream, 3);
+        let stats = collect(&repo, &branches, None, AuthorFormat::Email, now).unwrap();
+        assert_eq!((stats.merged, stats.unmerged), (0, 0));
+        assert_eq!(stats.by_author, [("tester@example.com".to_string(), 3)]);
+    }
+}
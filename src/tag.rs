@@ -0,0 +1,98 @@
+//! Tags, gathered and deleted like branches are.
+
+use git2::{Oid, PushOptions, Remote, Repository};
+
+#[derive(Debug, Clone)]
+pub struct TagInfo {
+    /// Name without "refs/tags/".
+    pub name: String,
+    /// The object the tag ref points to: the tag object if it is annotated.
+    pub id: Oid,
+    pub annotated: bool,
+    /// Time of tagging for annotated tags, else time of the tagged commit, in seconds since
+    /// Unix epoch. 0 for tags of trees and blobs.
+    pub time: i64,
+}
+
+pub fn list_tags(repo: &Repository) -> Result<Vec<TagInfo>, git2::Error> {
+    let names = repo.tag_names(None)?;
+    let mut tags = Vec::new();
+    for name in names.iter().flatten() {
+        let reference = repo.find_reference(&format!("refs/tags/{name}"))?;
+        let Some(id) = reference.target() else {
+            continue;
+        };
+        let commit_time = reference
+            .peel_to_commit()
+            .map(|c| c.time().seconds())
+            .unwrap_or(0);
+        let (annotated, time) = match repo.find_tag(id) {
+            Ok(tag) => (
+                true,
+                tag.tagger().map_or(commit_time, |s| s.when().seconds()),
+            ),
+            Err(_) => (false, commit_time),
+        };
+        tags.push(TagInfo {
+            name: name.to_string(),
+            id,
+            annotated,
+            time,
+        });
+    }
+    Ok(tags)
+}
+
+pub fn delete_local_tag(repo: &Repository, name: &str) -> Result<(), git2::Error> {
+    repo.tag_delete(name)
+}
+
+/// Delete the tag on the remote server.
+pub fn delete_remote_tag(
+    remote: &mut Remote,
+    name: &str,
+    opts: &mut PushOptions,
+) -> Result<(), git2::Error> {
+    remote.push(&[&format!(":refs/tags/{name}")], Some(opts))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{commit_at, init_bare_repo, init_repo};
+
+    #[test]
+    fn lists_and_deletes_tags() {
+        let (_server_dir, server) = init_bare_repo();
+        let (_dir, repo) = init_repo();
+        let id = commit_at(&repo, "refs/heads/main", None, 1_600_000_000);
+        let commit = repo.find_object(id, None).unwrap();
+        repo.tag_lightweight("light", &commit, false).unwrap();
+        let sig = git2::Signature::new(
+            "Tester",
+            "t@example.com",
+            &git2::Time::new(1_700_000_000, 0),
+        )
+        .unwrap();
+        repo.tag("v1.0", &commit, &sig, "Release", false).unwrap();
+        let tags = list_tags(&repo).unwrap();
+        let light = tags.iter().find(|t| t.name == "light").unwrap();
+        assert!(!light.annotated);
+        assert_eq!(light.time, 1_600_000_000);
+        let annotated = tags.iter().find(|t| t.name == "v1.0").unwrap();
+        assert!(annotated.annotated);
+        assert_eq!(annotated.time, 1_700_000_000);
+
+        let mut remote = repo
+            .remote("origin", server.path().to_str().unwrap())
+            .unwrap();
+        remote
+            .push(&["refs/tags/v1.0:refs/tags/v1.0"], None)
+            .unwrap();
+        assert!(server.find_reference("refs/tags/v1.0").is_ok());
+        delete_remote_tag(&mut remote, "v1.0", &mut PushOptions::new()).unwrap();
+        assert!(server.find_reference("refs/tags/v1.0").is_err());
+        delete_local_tag(&repo, "v1.0").unwrap();
+        assert_eq!(list_tags(&repo).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,175 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use git2::{Oid, Repository, Signature, Time};
+use tempfile::TempDir;
+
+/// Create an empty repository in a temporary folder, which is removed when the `TempDir` is dropped.
+pub fn init_repo() -> (TempDir, Repository) {
+    let dir = TempDir::new().unwrap();
+    let repo = Repository::init(dir.path()).unwrap();
+    {
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "Tester").unwrap();
+        config.set_str("user.email", "tester@example.com").unwrap();
+    }
+    (dir, repo)
+}
+
+/// Create a bare repository, to act as a remote server.
+pub fn init_bare_repo() -> (TempDir, Repository) {
+    let dir = TempDir::new().unwrap();
+    let repo = Repository::init_bare(dir.path()).unwrap();
+    (dir, repo)
+}
+
+static COMMIT_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Add a commit, with an empty tree and a unique message, on top of `parent` and point `refname` to it.
+pub fn commit_at(repo: &Repository, refname: &str, parent: Option<Oid>, time: i64) -> Oid {
+    let sig = Signature::new("Tester", "tester@example.com", &Time::new(time, 0)).unwrap();
+    let tree_id = repo.treebuilder(None).unwrap().write().unwrap();
+    let tree = repo.find_tree(tree_id).unwrap();
+    let parents: Vec<_> = parent
+        .map(|p| repo.find_commit(p).unwrap())
+        .into_iter()
+        .collect();
+    let parents: Vec<_> = parents.iter().collect();
+    let message = format!("commit {}", COMMIT_COUNT.fetch_add(1, Ordering::Relaxed));
+    let id = repo
+        .commit(None, &sig, &sig, &message, &tree, &parents)
+        .unwrap();
+    // Not via `commit(Some(refname))`, which requires the parent to be the current tip
+    repo.reference(refname, id, true, "").unwrap();
+    id
+}
+
+/// Add a commit which writes the files, in the top folder, on top of the tree of `parent`.
+pub fn commit_files(
+    repo: &Repository,
+    refname: &str,
+    parent: Option<Oid>,
+    files: &[(&str, &str)],
+) -> Oid {
+    let parent_commit = parent.map(|p| repo.find_commit(p).unwrap());
+    let parent_tree = parent_commit.as_ref().map(|c| c.tree().unwrap());
+    let mut builder = repo.treebuilder(parent_tree.as_ref()).unwrap();
+    for (name, content) in files {
+        let blob = repo.blob(content.as_bytes()).unwrap();
+        builder.insert(name, blob, 0o100644).unwrap();
+    }
+    let tree = repo.find_tree(builder.write().unwrap()).unwrap();
+    let sig = Signature::new("Tester", "tester@example.com", &Time::new(1_600_000_000, 0)).unwrap();
+    let parents: Vec<_> = parent_commit.iter().collect();
+    let message = format!("commit {}", COMMIT_COUNT.fetch_add(1, Ordering::Relaxed));
+    let id = repo
+        .commit(None, &sig, &sig, &message, &tree, &parents)
+        .unwrap();
+    repo.reference(refname, id, true, "").unwrap();
+    id
+}
+
+pub fn commit(repo: &Repository, refname: &str, parent: Option<Oid>) -> Oid {
+    commit_at(repo, refname, parent, 1_600_000_000)
+}
+
+/// Serve HTTP on a local port, answering the requests with the bodies in turn, as JSON, the
+/// last one over and over. Returns the URL of the server, and the requests it got, as their
+/// method, path and query, like "GET /repos/me/app/pulls?state=all".
+pub fn serve_json(bodies: &[&str]) -> (String, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&requests);
+    let bodies: Vec<String> = bodies.iter().map(|b| b.to_string()).collect();
+    thread::spawn(move || {
+        for (i, stream) in listener.incoming().enumerate() {
+            let Ok(mut stream) = stream else {
+                continue;
+            };
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let mut length = 0;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = header.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        length = value.trim().parse().unwrap();
+                    }
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            let request: Vec<&str> = line.split_whitespace().take(2).collect();
+            seen.lock().unwrap().push(request.join(" "));
+            let answer = &bodies[i.min(bodies.len() - 1)];
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{answer}",
+                answer.len()
+            );
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+    (url, requests)
+}
+
+/// `git daemon` serving the repositories of a folder, and taking pushes, until it is dropped.
+pub struct GitDaemon {
+    child: Child,
+    /// URL of the folder, which the names of the repositories are to be added to.
+    pub url: String,
+}
+
+impl Drop for GitDaemon {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Serve the repositories of the folder over the git protocol, on a local port, for pushing
+/// to a server which runs its hooks, unlike the local transport of libgit2.
+pub fn serve_git(dir: &Path) -> GitDaemon {
+    // A free port for the daemon
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    // Not `git daemon`, which would leave the daemon running when killed
+    let exec_path = Command::new("git").arg("--exec-path").output().unwrap();
+    let exec_path = String::from_utf8(exec_path.stdout).unwrap();
+    let child = Command::new(Path::new(exec_path.trim()).join("git-daemon"))
+        .args([
+            "--listen=127.0.0.1",
+            "--export-all",
+            "--enable=receive-pack",
+        ])
+        .arg(format!("--port={port}"))
+        .arg(format!("--base-path={}", dir.display()))
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let daemon = GitDaemon {
+        child,
+        url: format!("git://127.0.0.1:{port}"),
+    };
+    for _ in 0..100 {
+        if TcpStream::connect(("127.0.0.1", port)).is_ok() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    daemon
+}
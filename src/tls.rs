@@ -0,0 +1,105 @@
+//! TLS settings of Git for HTTPS remotes, which libgit2 does not read itself: `http.sslCAInfo`
+//! for servers signed by a private CA, and `http.sslVerify` for turning the checks off.
+
+use std::env;
+use std::ffi::{CString, OsString};
+use std::os::raw::{c_char, c_int};
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+use git2::Config;
+
+pub const SSL_CA_INFO: &str = "http.sslCAInfo";
+pub const SSL_VERIFY: &str = "http.sslVerify";
+/// Environment variables which override the settings, like for Git.
+const CA_INFO_ENV: &str = "GIT_SSL_CAINFO";
+const NO_VERIFY_ENV: &str = "GIT_SSL_NO_VERIFY";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsSettings {
+    /// File of the certificates of the CAs to trust, instead of those of the system.
+    pub ca_info: Option<PathBuf>,
+    /// Setting which turns off the checks of certificates, if any, to name it in warnings.
+    pub no_verify: Option<&'static str>,
+}
+
+impl TlsSettings {
+    /// Read the settings from the Git config and the environment.
+    pub fn read(config: &Config) -> Self {
+        Self::read_with(config, |name| env::var_os(name))
+    }
+
+    fn read_with(config: &Config, get_env: impl Fn(&str) -> Option<OsString>) -> Self {
+        let ca_info = get_env(CA_INFO_ENV)
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| config.get_path(SSL_CA_INFO).ok());
+        let no_verify = if get_env(NO_VERIFY_ENV).is_some_and(|v| !v.is_empty()) {
+            Some(NO_VERIFY_ENV)
+        } else if config.get_bool(SSL_VERIFY).is_ok_and(|v| !v) {
+            Some(SSL_VERIFY)
+        } else {
+            None
+        };
+        Self { ca_info, no_verify }
+    }
+}
+
+/// Make libgit2 trust the CAs of the file, for all connections. Only libgit2 built with
+/// OpenSSL, or mbedTLS, can do it: not the builds for Windows and macOS, which use the TLS
+/// of the OS.
+pub fn use_ca_file(path: &Path) -> Result<(), git2::Error> {
+    let file = CString::new(path.to_string_lossy().into_owned())
+        .map_err(|_| git2::Error::from_str("the path of the CA file contains a nul byte"))?;
+    // Initializes libgit2, like every call of git2
+    Config::new()?;
+    // SAFETY: the option takes a file and a folder, as C strings which may be null, and the
+    // file outlives the call.
+    let code = unsafe {
+        libgit2_sys::git_libgit2_opts(
+            libgit2_sys::GIT_OPT_SET_SSL_CERT_LOCATIONS as c_int,
+            file.as_ptr(),
+            ptr::null::<c_char>(),
+        )
+    };
+    if code < 0 {
+        return Err(git2::Error::last_error(code)
+            .unwrap_or_else(|| git2::Error::from_str("cannot use the CA file")));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::init_repo;
+
+    #[test]
+    fn reads_settings() {
+        let (_dir, repo) = init_repo();
+        let mut config = repo.config().unwrap();
+        let no_env = |_: &str| None;
+        let settings = TlsSettings::read_with(&config, no_env);
+        assert_eq!(settings.ca_info, None);
+        assert_eq!(settings.no_verify, None);
+        config.set_str(SSL_CA_INFO, "/etc/corp-ca.pem").unwrap();
+        config.set_bool(SSL_VERIFY, false).unwrap();
+        let settings = TlsSettings::read_with(&config, no_env);
+        assert_eq!(settings.ca_info, Some(PathBuf::from("/etc/corp-ca.pem")));
+        assert_eq!(settings.no_verify, Some(SSL_VERIFY));
+        let env = |name: &str| match name {
+            CA_INFO_ENV => Some("/tmp/ca.pem".into()),
+            NO_VERIFY_ENV => Some("1".into()),
+            _ => None,
+        };
+        let settings = TlsSettings::read_with(&config, env);
+        assert_eq!(settings.ca_info, Some(PathBuf::from("/tmp/ca.pem")));
+        assert_eq!(settings.no_verify, Some(NO_VERIFY_ENV));
+    }
+
+    #[test]
+    fn uses_ca_file() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(use_ca_file(&dir.path().join("missing.pem")).is_err());
+    }
+}
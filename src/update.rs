@@ -0,0 +1,371 @@
+//! Updates from the releases on GitHub: replacing the running binary with the latest one,
+//! checked against its published SHA-256, and the opt-in hint about a newer version.
+
+use std::env;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use color_eyre::Result;
+use console::{style, Emoji};
+use eyre::{eyre, Context};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tempfile::NamedTempFile;
+
+use git_del_branches::age::now;
+use git_del_branches::config::UPDATE_CHECK;
+use git_del_branches::hosting::USER_AGENT;
+
+const LATEST_RELEASE_URL: &str =
+    "https://api.github.com/repos/hongquan/git-del-branches/releases/latest";
+const CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    pub html_url: String,
+    #[serde(default)]
+    pub assets: Vec<Asset>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Asset {
+    pub name: String,
+    pub browser_download_url: String,
+}
+
+impl Release {
+    pub fn version(&self) -> &str {
+        self.tag_name.trim_start_matches('v')
+    }
+
+    fn find_asset(&self, name: &str) -> Option<&Asset> {
+        self.assets.iter().find(|a| a.name == name)
+    }
+}
+
+/// Name of the release file built for the platform we are running on,
+/// like "git-del-branches-x86_64-linux".
+fn get_asset_name() -> String {
+    format!(
+        "{}-{}-{}{}",
+        env!("CARGO_PKG_NAME"),
+        env::consts::ARCH,
+        env::consts::OS,
+        env::consts::EXE_SUFFIX
+    )
+}
+
+/// An identifier of a pre-release, like "rc" or "1" in "1.2.0-rc.1". Numbers come before
+/// words, which are ordered by their ASCII characters, like in Semantic Versioning.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum PreRelease {
+    Number(u64),
+    Word(String),
+}
+
+/// What versions are ordered by: their numbers, then whether they are a release, which comes
+/// after its pre-releases like "1.2.0-rc.1", then the identifiers of the pre-release. Build
+/// metadata, after a "+", is left out.
+fn parse_version(version: &str) -> (Vec<u64>, bool, Vec<PreRelease>) {
+    let version = version.trim_start_matches('v');
+    let version = version.split('+').next().unwrap_or(version);
+    let (numbers, pre) = match version.split_once('-') {
+        Some((numbers, pre)) => (numbers, Some(pre)),
+        None => (version, None),
+    };
+    let numbers = numbers.split('.').map_while(|p| p.parse().ok()).collect();
+    let pre_release = pre
+        .into_iter()
+        .flat_map(|p| p.split('.'))
+        .map(|p| match p.parse() {
+            Ok(n) => PreRelease::Number(n),
+            Err(_) => PreRelease::Word(p.to_string()),
+        })
+        .collect();
+    (numbers, pre.is_none(), pre_release)
+}
+
+pub fn is_newer(version: &str, than: &str) -> bool {
+    parse_version(version) > parse_version(than)
+}
+
+pub fn fetch_latest_release(timeout: Option<Duration>) -> Result<Release> {
+    let mut request = ureq::get(LATEST_RELEASE_URL);
+    if let Some(t) = timeout {
+        request = request.timeout(t);
+    }
+    let release = request
+        .set("User-Agent", USER_AGENT)
+        .set("Accept", "application/vnd.github+json")
+        .call()
+        .wrap_err("Failed to query GitHub releases")?
+        .into_json()?;
+    Ok(release)
+}
+
+fn download(url: &str) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    ureq::get(url)
+        .set("User-Agent", USER_AGENT)
+        .call()
+        .wrap_err_with(|| format!("Failed to download {url}"))?
+        .into_reader()
+        .read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// Find the checksum of the asset in the lines "<hash>  <file>" of a checksum file, "*"
+/// marking binary files. Only the line of the asset matches, except in a file of the asset
+/// alone, which may have only its hash.
+fn find_checksum(content: &str, asset_name: &str, alone: bool) -> Option<String> {
+    let lines: Vec<&str> = content.lines().filter(|l| !l.trim().is_empty()).collect();
+    if let ([line], true) = (lines.as_slice(), alone) {
+        let mut parts = line.split_whitespace();
+        if let (Some(hash), None) = (parts.next(), parts.next()) {
+            return Some(hash.to_lowercase());
+        }
+    }
+    lines.iter().find_map(|line| {
+        let mut parts = line.split_whitespace();
+        let hash = parts.next()?;
+        let name = parts.next()?.trim_start_matches('*');
+        (name == asset_name && parts.next().is_none()).then(|| hash.to_lowercase())
+    })
+}
+
+/// Get the expected SHA-256 of the asset, published either as "<asset>.sha256"
+/// or as a line in "SHA256SUMS".
+fn get_expected_checksum(release: &Release, asset_name: &str) -> Result<String> {
+    let (sum_file, alone) = match release.find_asset(&format!("{asset_name}.sha256")) {
+        Some(a) => (a, true),
+        None => match release.find_asset("SHA256SUMS") {
+            Some(a) => (a, false),
+            None => {
+                return Err(eyre!(
+                    "Release {} publishes no checksum for {asset_name}",
+                    release.tag_name
+                ))
+            }
+        },
+    };
+    let content = download(&sum_file.browser_download_url)?;
+    find_checksum(&String::from_utf8_lossy(&content), asset_name, alone)
+        .ok_or_else(|| eyre!("No checksum for {asset_name} in {}", sum_file.name))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Put the new binary in place of the running one.
+/// The file is written next to the executable first, so that the final rename is atomic.
+fn replace_current_exe(data: &[u8]) -> Result<()> {
+    let exe = env::current_exe()?.canonicalize()?;
+    let folder = exe
+        .parent()
+        .ok_or_else(|| eyre!("Cannot locate the executable folder"))?;
+    let mut file = NamedTempFile::new_in(folder)
+        .wrap_err_with(|| format!("Cannot write to {}", folder.display()))?;
+    file.write_all(data)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(file.path(), fs::Permissions::from_mode(0o755))?;
+    }
+    // Windows doesn't let us overwrite a running executable, but let us rename it.
+    #[cfg(windows)]
+    {
+        let old = exe.with_extension("old.exe");
+        fs::remove_file(&old).ok();
+        fs::rename(&exe, &old)?;
+        if let Err(e) = file.persist(&exe) {
+            // Put the running one back, so that the executable is not left missing
+            fs::rename(&old, &exe).ok();
+            return Err(e.into());
+        }
+    }
+    #[cfg(not(windows))]
+    file.persist(&exe)?;
+    Ok(())
+}
+
+pub fn self_update(check_only: bool) -> Result<()> {
+    let current = env!("CARGO_PKG_VERSION");
+    let release = fetch_latest_release(None)?;
+    let latest = release.version();
+    if !is_newer(latest, current) {
+        eprintln!("You are using the latest version ({current}).");
+        return Ok(());
+    }
+    eprintln!(
+        "New version available: {} → {}",
+        current,
+        style(latest).green()
+    );
+    if check_only {
+        eprintln!("  {}", style(&release.html_url).dim());
+        return Ok(());
+    }
+    let asset_name = get_asset_name();
+    let asset = release.find_asset(&asset_name).ok_or_else(|| {
+        eyre!(
+            "Release {} has no binary for this platform ({asset_name})",
+            release.tag_name
+        )
+    })?;
+    let expected = get_expected_checksum(&release, &asset_name)?;
+    eprintln!(
+        "  {}",
+        style(format!("Downloading {}...", asset.browser_download_url)).dim()
+    );
+    let data = download(&asset.browser_download_url)?;
+    let actual = to_hex(&Sha256::digest(&data));
+    if actual != expected {
+        return Err(eyre!(
+            "Checksum mismatch for {asset_name}: expected {expected}, got {actual}"
+        ));
+    }
+    replace_current_exe(&data)?;
+    eprintln!(
+        "{} {}",
+        Emoji("🎉", "v"),
+        style(format!("Updated to {latest}")).bright().green()
+    );
+    Ok(())
+}
+
+fn get_cache_path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|d| d.join(env!("CARGO_PKG_NAME")).join("latest-version"))
+}
+
+/// The version in the content of the cache file, if it was checked recently, at `now`.
+/// The cache file has the check time (Unix timestamp) and the version, on two lines.
+/// The version is empty if the last check failed, so that we don't retry on every run.
+fn read_cache(content: &str, now: i64) -> Option<String> {
+    let mut lines = content.lines();
+    let checked_at: i64 = lines.next()?.parse().ok()?;
+    let version = lines.next().unwrap_or_default().to_string();
+    (now.saturating_sub(checked_at) < CHECK_INTERVAL.as_secs() as i64).then_some(version)
+}
+
+fn write_cache(version: &str, now: i64) -> String {
+    format!("{now}\n{version}\n")
+}
+
+/// Get the latest version, from cache if we checked recently.
+fn get_latest_version() -> Option<String> {
+    let path = get_cache_path()?;
+    let cached = fs::read_to_string(&path)
+        .ok()
+        .and_then(|content| read_cache(&content, now()));
+    let version = match cached {
+        Some(version) => version,
+        None => {
+            let version = fetch_latest_release(Some(CHECK_TIMEOUT))
+                .map(|r| r.version().to_string())
+                .unwrap_or_default();
+            if let Some(folder) = path.parent() {
+                fs::create_dir_all(folder).ok();
+            }
+            fs::write(&path, write_cache(&version, now())).ok();
+            version
+        }
+    };
+    Some(version).filter(|v| !v.is_empty())
+}
+
+/// Print a hint if there is a newer release. Only done if user opted in via Git config.
+pub fn notify_new_version(config: &git2::Config) {
+    if !config.get_bool(UPDATE_CHECK).unwrap_or(false) {
+        return;
+    }
+    let current = env!("CARGO_PKG_VERSION");
+    match get_latest_version() {
+        Some(latest) if is_newer(&latest, current) => {
+            let msg = format!(
+                "git-del-branches {latest} is available (you have {current}). Run \"git-del-branches self-update\" to upgrade."
+            );
+            eprintln!("{}", style(msg).dim());
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_versions() {
+        assert_eq!(parse_version("v1.2.3"), (vec![1, 2, 3], true, vec![]));
+        assert_eq!(
+            parse_version("1.2.3+build.5"),
+            (vec![1, 2, 3], true, vec![])
+        );
+        let rc = vec![PreRelease::Word("rc".into()), PreRelease::Number(2)];
+        assert_eq!(parse_version("1.2.0-rc.2"), (vec![1, 2, 0], false, rc));
+    }
+
+    #[test]
+    fn compares_versions() {
+        assert!(is_newer("0.10.0", "0.9.1"));
+        assert!(is_newer("v0.4.1", "0.4.0"));
+        assert!(!is_newer("v0.4.0", "0.4.0"));
+        assert!(is_newer("1.0.0", "1.0.0-rc.1"));
+        assert!(!is_newer("1.0.0-rc.1", "1.0.0"));
+        assert!(is_newer("1.0.0-rc.2", "1.0.0-rc.1"));
+        assert!(is_newer("1.0.0-rc.1", "0.9.0"));
+        assert!(is_newer("1.0.0-rc.1", "1.0.0-beta.2"));
+        assert!(is_newer("1.0.0-beta", "1.0.0-alpha.5"));
+        assert!(is_newer("1.0.0-alpha.beta", "1.0.0-alpha.1"));
+        assert!(is_newer("1.0.0-alpha.1", "1.0.0-alpha"));
+        assert!(is_newer("1.0.0-rc.10", "1.0.0-rc.9"));
+    }
+
+    #[test]
+    fn caches_latest_version() {
+        let content = write_cache("0.5.0", 1000);
+        assert_eq!(read_cache(&content, 1000).as_deref(), Some("0.5.0"));
+        let later = 1000 + CHECK_INTERVAL.as_secs() as i64;
+        assert_eq!(read_cache(&content, later), None);
+        // A failed check is cached too
+        assert_eq!(
+            read_cache(&write_cache("", 1000), 1060).as_deref(),
+            Some("")
+        );
+        assert_eq!(read_cache("garbage", 1000), None);
+    }
+
+    #[test]
+    fn finds_checksums() {
+        let name = "git-del-branches-x86_64-linux";
+        let sums = format!("AB12  {name}.sha256\ncd34 *{name}\nef56  other\n");
+        assert_eq!(find_checksum(&sums, name, false).as_deref(), Some("cd34"));
+        assert_eq!(find_checksum("ef56  other\n", name, false), None);
+        assert_eq!(find_checksum("ab12\n", name, false), None);
+        assert_eq!(find_checksum("AB12\n", name, true).as_deref(), Some("ab12"));
+        assert_eq!(find_checksum("ab12\ncd34\n", name, true), None);
+        assert_eq!(find_checksum("ab12  other\n", name, true), None);
+    }
+
+    #[test]
+    fn needs_published_checksum() {
+        let release = Release {
+            tag_name: "v9.0.0".into(),
+            html_url: String::new(),
+            assets: vec![Asset {
+                name: "app".into(),
+                browser_download_url: String::new(),
+            }],
+        };
+        let e = get_expected_checksum(&release, "app").unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Release v9.0.0 publishes no checksum for app"
+        );
+    }
+}
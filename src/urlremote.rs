@@ -0,0 +1,98 @@
+//! Cleanup of a remote by its URL, without a clone: its branches are listed like
+//! `git ls-remote` does, and deleted by pushes. libgit2 needs a repository for both, so an
+//! empty bare one is made in a temporary folder, which reads the global Git config only.
+
+use eyre::{Context, Result};
+use git2::{Direction, Oid, PushOptions, RemoteCallbacks, Repository};
+use tempfile::TempDir;
+
+use crate::branch::get_protected_names;
+use crate::delete::get_deletion_refspec;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteBranch {
+    pub name: String,
+    pub tip: Oid,
+}
+
+pub struct UrlRemote {
+    pub url: String,
+    repo: Repository,
+    // Removed when dropped, after the repository
+    _dir: TempDir,
+}
+
+impl UrlRemote {
+    pub fn new(url: &str) -> Result<Self> {
+        let dir = TempDir::new().wrap_err("Cannot create a temporary folder")?;
+        let repo = Repository::init_bare(dir.path())?;
+        Ok(Self {
+            url: url.to_string(),
+            repo,
+            _dir: dir,
+        })
+    }
+
+    /// The empty repository which talks to the remote, for its config.
+    pub fn repo(&self) -> &Repository {
+        &self.repo
+    }
+
+    /// List the branches of the remote, by name, with the names which must not be deleted:
+    /// the one its HEAD points to, and the usual default branches.
+    pub fn list_branches(
+        &self,
+        callbacks: RemoteCallbacks,
+    ) -> Result<(Vec<RemoteBranch>, Vec<String>), git2::Error> {
+        let mut remote = self.repo.remote_anonymous(&self.url)?;
+        let connection = remote.connect_auth(Direction::Fetch, Some(callbacks), None)?;
+        let mut branches: Vec<RemoteBranch> = connection
+            .list()?
+            .iter()
+            .filter_map(|head| {
+                let name = head.name().strip_prefix("refs/heads/")?;
+                Some(RemoteBranch {
+                    name: name.to_string(),
+                    tip: head.oid(),
+                })
+            })
+            .collect();
+        branches.sort_by(|a, b| a.name.cmp(&b.name));
+        let mut protected = get_protected_names(&self.repo);
+        let default = connection.default_branch().ok();
+        if let Some(name) = default.as_ref().and_then(|b| b.as_str()) {
+            let name = name.strip_prefix("refs/heads/").unwrap_or(name);
+            protected.insert(0, name.to_string());
+        }
+        Ok((branches, protected))
+    }
+
+    pub fn delete_branch(&self, name: &str, opts: &mut PushOptions) -> Result<(), git2::Error> {
+        let mut remote = self.repo.remote_anonymous(&self.url)?;
+        remote.push(&[&get_deletion_refspec(name)], Some(opts))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{commit, init_bare_repo};
+
+    #[test]
+    fn cleans_up_by_url() {
+        let (_server_dir, server) = init_bare_repo();
+        let tip = commit(&server, "refs/heads/main", None);
+        commit(&server, "refs/heads/feature", Some(tip));
+        server.set_head("refs/heads/main").unwrap();
+        let remote = UrlRemote::new(server.path().to_str().unwrap()).unwrap();
+        let (branches, protected) = remote.list_branches(RemoteCallbacks::new()).unwrap();
+        let names: Vec<&str> = branches.iter().map(|b| b.name.as_str()).collect();
+        assert_eq!(names, ["feature", "main"]);
+        assert_eq!(branches[1].tip, tip);
+        assert_eq!(protected[0], "main");
+        remote
+            .delete_branch("feature", &mut PushOptions::new())
+            .unwrap();
+        assert!(server.find_reference("refs/heads/feature").is_err());
+    }
+}